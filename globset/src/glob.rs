@@ -82,6 +82,7 @@ pub struct Glob {
     re: String,
     opts: GlobOptions,
     tokens: Tokens,
+    is_dir_only: bool,
 }
 
 impl PartialEq for Glob {
@@ -120,10 +121,70 @@ impl GlobMatcher {
 
     /// Tests whether the given path matches this pattern or not.
     pub fn is_match_candidate(&self, path: &Candidate) -> bool {
+        if self.pat.is_dir_only && !path.is_dir {
+            return false;
+        }
         self.re.is_match(&path.path)
     }
 }
 
+/// A matcher for a single pattern that reports the substrings matched by
+/// each wildcard in the pattern.
+#[derive(Clone, Debug)]
+pub struct GlobCaptureMatcher {
+    /// The underlying pattern.
+    pat: Glob,
+    /// The pattern, as a compiled regex with a capture group around every
+    /// wildcard.
+    re: Regex,
+}
+
+impl GlobCaptureMatcher {
+    /// Returns the `Glob` used to build this matcher.
+    pub fn glob(&self) -> &Glob {
+        &self.pat
+    }
+
+    /// Tests whether the given path matches this pattern or not.
+    pub fn is_match<P: AsRef<Path>>(&self, path: P) -> bool {
+        if self.pat.is_dir_only {
+            return false;
+        }
+        self.re.is_match(&Candidate::new(path.as_ref()).path)
+    }
+
+    /// Returns the substrings matched by each `?`, `*` or `**` in this
+    /// pattern, in the order in which they appear in the pattern, or `None`
+    /// if the path doesn't match.
+    ///
+    /// A wildcard that did not participate in the match (e.g., because it
+    /// occurs in an alternation branch that wasn't taken, or because a `**`
+    /// matched zero components) yields `None` for its entry. The substrings
+    /// are byte strings, which may be converted to a string using standard
+    /// UTF-8 conversion routines.
+    pub fn captures<P: AsRef<Path>>(
+        &self,
+        path: P,
+    ) -> Option<Vec<Option<Vec<u8>>>> {
+        self.captures_candidate(&Candidate::new(path.as_ref()))
+    }
+
+    /// Like `captures`, but accepts a pre-computed candidate path.
+    pub fn captures_candidate(
+        &self,
+        path: &Candidate,
+    ) -> Option<Vec<Option<Vec<u8>>>> {
+        if self.pat.is_dir_only && !path.is_dir {
+            return None;
+        }
+        self.re.captures(&path.path).map(|caps| {
+            (1..caps.len())
+                .map(|i| caps.get(i).map(|m| m.as_bytes().to_vec()))
+                .collect()
+        })
+    }
+}
+
 /// A strategic matcher for a single pattern.
 #[cfg(test)]
 #[derive(Clone, Debug)]
@@ -145,6 +206,9 @@ impl GlobStrategic {
 
     /// Tests whether the given path matches this pattern or not.
     fn is_match_candidate(&self, candidate: &Candidate) -> bool {
+        if self.pat.is_dir_only && !candidate.is_dir {
+            return false;
+        }
         let byte_path = &*candidate.path;
 
         match self.strategy {
@@ -252,6 +316,17 @@ impl Glob {
         }
     }
 
+    /// Returns a matcher for this pattern that can additionally report the
+    /// substrings matched by each wildcard in the pattern.
+    pub fn compile_capture_matcher(&self) -> GlobCaptureMatcher {
+        let re = new_regex(&self.tokens.to_regex_capturing_with(&self.opts))
+            .expect("regex compilation shouldn't fail");
+        GlobCaptureMatcher {
+            pat: self.clone(),
+            re: re,
+        }
+    }
+
     /// Returns a strategic matcher.
     ///
     /// This isn't exposed because it's not clear whether it's actually
@@ -292,6 +367,33 @@ impl Glob {
         &self.re
     }
 
+    /// Returns the number of characters in this pattern that must match a
+    /// path character literally.
+    ///
+    /// This can be used as a crude measure of the specificity of a pattern.
+    /// For example, `foo/bar.rs` has 10 literal characters, `foo/*.rs` has
+    /// 7 and `**/*.rs` has only 3. Wildcards and character classes
+    /// contribute nothing, and an alternation contributes the count of its
+    /// most literal branch.
+    pub fn literal_len(&self) -> usize {
+        fn count(tokens: &[Token]) -> usize {
+            tokens.iter().map(|tok| match *tok {
+                Token::Literal(_) => 1,
+                Token::Alternates(ref alts) => {
+                    alts.iter().map(|ts| count(ts)).max().unwrap_or(0)
+                }
+                _ => 0,
+            }).sum()
+        }
+        count(&self.tokens)
+    }
+
+    /// Returns true if and only if this glob matches directories only,
+    /// i.e., the pattern was written with a trailing slash.
+    pub fn is_dir_only(&self) -> bool {
+        self.is_dir_only
+    }
+
     /// Returns the pattern as a literal if and only if the pattern must match
     /// an entire path exactly.
     ///
@@ -568,14 +670,43 @@ impl<'a> GlobBuilder<'a> {
     }
 
     /// Parses and builds the pattern.
+    ///
+    /// A pattern may start with one or more inline flag groups, which
+    /// override the corresponding builder options for this pattern only.
+    /// `(?i)` enables case insensitive matching and `(?s)` requires a
+    /// literal `/` to match a path separator. Flags may be combined and
+    /// prefixing them with `-` disables them, e.g., `(?i-s)`.
     pub fn build(&self) -> Result<Glob, Error> {
+        let mut opts = self.opts;
+        let start = match parse_inline_flags(self.glob, &mut opts) {
+            Ok(start) => start,
+            Err(kind) => {
+                return Err(Error {
+                    glob: Some(self.glob.to_string()),
+                    kind: kind,
+                });
+            }
+        };
+        let mut pat = &self.glob[start..];
+        // A trailing slash means the glob matches directories only. The
+        // slash is not part of the path match itself, so strip it before
+        // tokenizing. An escaped trailing slash remains a literal.
+        let mut is_dir_only = false;
+        if pat.len() > 1
+            && pat.ends_with('/')
+            && !(opts.backslash_escape && pat.ends_with("\\/"))
+        {
+            is_dir_only = true;
+            pat = &pat[..pat.len() - 1];
+        }
         let mut p = Parser {
             glob: &self.glob,
             stack: vec![Tokens::default()],
-            chars: self.glob.chars().peekable(),
+            alt_starts: vec![],
+            chars: pat.chars().peekable(),
             prev: None,
             cur: None,
-            opts: &self.opts,
+            opts: &opts,
         };
         p.parse()?;
         if p.stack.is_empty() {
@@ -592,9 +723,10 @@ impl<'a> GlobBuilder<'a> {
             let tokens = p.stack.pop().unwrap();
             Ok(Glob {
                 glob: self.glob.to_string(),
-                re: tokens.to_regex_with(&self.opts),
-                opts: self.opts,
+                re: tokens.to_regex_with(&opts),
+                opts: opts,
                 tokens: tokens,
+                is_dir_only: is_dir_only,
             })
         }
     }
@@ -632,6 +764,16 @@ impl Tokens {
     /// regular expression and will represent the matching semantics of this
     /// glob pattern and the options given.
     fn to_regex_with(&self, options: &GlobOptions) -> String {
+        self.to_regex_inner(options, false)
+    }
+
+    /// Like `to_regex_with`, but wraps every wildcard in a capture group so
+    /// that the substrings they match can be extracted.
+    fn to_regex_capturing_with(&self, options: &GlobOptions) -> String {
+        self.to_regex_inner(options, true)
+    }
+
+    fn to_regex_inner(&self, options: &GlobOptions, captures: bool) -> String {
         let mut re = String::new();
         re.push_str("(?-u)");
         if options.case_insensitive {
@@ -641,11 +783,11 @@ impl Tokens {
         // Special case. If the entire glob is just `**`, then it should match
         // everything.
         if self.len() == 1 && self[0] == Token::RecursivePrefix {
-            re.push_str(".*");
+            re.push_str(if captures { "(.*)" } else { ".*" });
             re.push('$');
             return re;
         }
-        self.tokens_to_regex(options, &self, &mut re);
+        self.tokens_to_regex(options, &self, captures, &mut re);
         re.push('$');
         re
     }
@@ -654,6 +796,7 @@ impl Tokens {
         &self,
         options: &GlobOptions,
         tokens: &[Token],
+        captures: bool,
         re: &mut String,
     ) {
         for tok in tokens {
@@ -662,27 +805,51 @@ impl Tokens {
                     re.push_str(&char_to_escaped_literal(c));
                 }
                 Token::Any => {
+                    if captures {
+                        re.push('(');
+                    }
                     if options.literal_separator {
                         re.push_str("[^/]");
                     } else {
                         re.push_str(".");
                     }
+                    if captures {
+                        re.push(')');
+                    }
                 }
                 Token::ZeroOrMore => {
+                    if captures {
+                        re.push('(');
+                    }
                     if options.literal_separator {
                         re.push_str("[^/]*");
                     } else {
                         re.push_str(".*");
                     }
+                    if captures {
+                        re.push(')');
+                    }
                 }
                 Token::RecursivePrefix => {
-                    re.push_str("(?:/?|.*/)");
+                    re.push_str(if captures {
+                        "(?:/?|(.*)/)"
+                    } else {
+                        "(?:/?|.*/)"
+                    });
                 }
                 Token::RecursiveSuffix => {
-                    re.push_str("(?:/?|/.*)");
+                    re.push_str(if captures {
+                        "(?:/?|/(.*))"
+                    } else {
+                        "(?:/?|/.*)"
+                    });
                 }
                 Token::RecursiveZeroOrMore => {
-                    re.push_str("(?:/|/.*/)");
+                    re.push_str(if captures {
+                        "(?:/|/(.*)/)"
+                    } else {
+                        "(?:/|/.*/)"
+                    });
                 }
                 Token::Class { negated, ref ranges } => {
                     re.push('[');
@@ -705,7 +872,7 @@ impl Tokens {
                     let mut parts = vec![];
                     for pat in patterns {
                         let mut altre = String::new();
-                        self.tokens_to_regex(options, &pat, &mut altre);
+                        self.tokens_to_regex(options, &pat, captures, &mut altre);
                         if !altre.is_empty() {
                             parts.push(altre);
                         }
@@ -714,7 +881,9 @@ impl Tokens {
                     // It is possible to have an empty set in which case the
                     // resulting alternation '()' would be an error.
                     if !parts.is_empty() {
-                        re.push('(');
+                        // Don't perturb the numbering of the wildcard capture
+                        // groups when extracting captures.
+                        re.push_str(if captures { "(?:" } else { "(" });
                         re.push_str(&parts.join("|"));
                         re.push(')');
                     }
@@ -744,9 +913,42 @@ fn bytes_to_escaped_literal(bs: &[u8]) -> String {
     s
 }
 
+/// Parses any inline flag groups (e.g., `(?i)`) at the beginning of a glob,
+/// updating the given options accordingly. Returns the byte offset at which
+/// the glob pattern itself begins.
+fn parse_inline_flags(
+    glob: &str,
+    opts: &mut GlobOptions,
+) -> Result<usize, ErrorKind> {
+    let mut start = 0;
+    while glob[start..].starts_with("(?") {
+        let flags = match glob[start + 2..].find(')') {
+            None => return Err(ErrorKind::InvalidInlineFlags),
+            Some(end) => &glob[start + 2..start + 2 + end],
+        };
+        if flags.is_empty() || flags.ends_with('-') {
+            return Err(ErrorKind::InvalidInlineFlags);
+        }
+        let mut enable = true;
+        for c in flags.chars() {
+            match c {
+                'i' => opts.case_insensitive = enable,
+                's' => opts.literal_separator = enable,
+                '-' if enable => enable = false,
+                _ => return Err(ErrorKind::InvalidInlineFlags),
+            }
+        }
+        start += 2 + flags.len() + 1;
+    }
+    Ok(start)
+}
+
 struct Parser<'a> {
     glob: &'a str,
     stack: Vec<Tokens>,
+    /// For every alternating group that is currently open, the index on the
+    /// stack at which its first branch lives. Groups may nest.
+    alt_starts: Vec<usize>,
     chars: iter::Peekable<str::Chars<'a>>,
     prev: Option<char>,
     cur: Option<char>,
@@ -775,17 +977,18 @@ impl<'a> Parser<'a> {
     }
 
     fn push_alternate(&mut self) -> Result<(), Error> {
-        if self.stack.len() > 1 {
-            return Err(self.error(ErrorKind::NestedAlternates));
-        }
+        self.alt_starts.push(self.stack.len());
         Ok(self.stack.push(Tokens::default()))
     }
 
     fn pop_alternate(&mut self) -> Result<(), Error> {
-        let mut alts = vec![];
-        while self.stack.len() >= 2 {
-            alts.push(self.stack.pop().unwrap());
-        }
+        let start = match self.alt_starts.pop() {
+            // An unmatched '}' is swallowed, for backward compatibility
+            // with globs written before alternation was supported.
+            None => return self.push_token(Token::Alternates(vec![])),
+            Some(start) => start,
+        };
+        let alts: Vec<Tokens> = self.stack.drain(start..).collect();
         self.push_token(Token::Alternates(alts))
     }
 
@@ -814,7 +1017,7 @@ impl<'a> Parser<'a> {
         // If we aren't inside a group alternation, then don't
         // treat commas specially. Otherwise, we need to start
         // a new alternate.
-        if self.stack.len() <= 1 {
+        if self.alt_starts.is_empty() {
             self.push_token(Token::Literal(','))
         } else {
             Ok(self.stack.push(Tokens::default()))
@@ -852,7 +1055,7 @@ impl<'a> Parser<'a> {
         }
         self.pop_token()?;
         if !prev.map(is_separator).unwrap_or(false) {
-            if self.stack.len() <= 1
+            if self.alt_starts.is_empty()
                 || (prev != Some(',') && prev != Some('{')) {
                 return Err(self.error(ErrorKind::InvalidRecursive));
             }
@@ -862,7 +1065,7 @@ impl<'a> Parser<'a> {
                 assert!(self.bump().is_none());
                 self.push_token(Token::RecursiveSuffix)
             }
-            Some(&',') | Some(&'}') if self.stack.len() >= 2 => {
+            Some(&',') | Some(&'}') if !self.alt_starts.is_empty() => {
                 self.push_token(Token::RecursiveSuffix)
             }
             Some(&c) if is_separator(c) => {
@@ -976,7 +1179,7 @@ fn ends_with(needle: &[u8], haystack: &[u8]) -> bool {
 
 #[cfg(test)]
 mod tests {
-    use {GlobSetBuilder, ErrorKind};
+    use {Candidate, GlobSetBuilder, ErrorKind};
     use super::{Glob, GlobBuilder, Token};
     use super::Token::*;
 
@@ -1116,7 +1319,9 @@ mod tests {
     syntax!(rseq1, "**", vec![RecursivePrefix]);
     syntax!(rseq2, "**/", vec![RecursivePrefix]);
     syntax!(rseq3, "/**", vec![RecursiveSuffix]);
-    syntax!(rseq4, "/**/", vec![RecursiveZeroOrMore]);
+    // A trailing slash makes the glob directory-only, so `/**/` parses the
+    // same as `/**`.
+    syntax!(rseq4, "/**/", vec![RecursiveSuffix]);
     syntax!(rseq5, "a/**/b", vec![
         Literal('a'), RecursiveZeroOrMore, Literal('b'),
     ]);
@@ -1151,6 +1356,10 @@ mod tests {
     syntaxerr!(err_rseq5, "/a**", ErrorKind::InvalidRecursive);
     syntaxerr!(err_rseq6, "/**a", ErrorKind::InvalidRecursive);
     syntaxerr!(err_rseq7, "/a**b", ErrorKind::InvalidRecursive);
+    syntaxerr!(err_flags1, "(?x)a", ErrorKind::InvalidInlineFlags);
+    syntaxerr!(err_flags2, "(?ia", ErrorKind::InvalidInlineFlags);
+    syntaxerr!(err_flags3, "(?)a", ErrorKind::InvalidInlineFlags);
+    syntaxerr!(err_flags4, "(?i-)a", ErrorKind::InvalidInlineFlags);
     syntaxerr!(err_unclosed1, "[", ErrorKind::UnclosedClass);
     syntaxerr!(err_unclosed2, "[]", ErrorKind::UnclosedClass);
     syntaxerr!(err_unclosed3, "[!", ErrorKind::UnclosedClass);
@@ -1276,6 +1485,13 @@ mod tests {
     matches!(matchalt11, "{*.foo,*.bar,*.wat}", "test.foo");
     matches!(matchalt12, "{*.foo,*.bar,*.wat}", "test.bar");
     matches!(matchalt13, "{*.foo,*.bar,*.wat}", "test.wat");
+    matches!(matchalt14, "{a,{b,c}}", "a");
+    matches!(matchalt15, "{a,{b,c}}", "b");
+    matches!(matchalt16, "{a,{b,c}}", "c");
+    matches!(matchalt17, "src/{foo,bar/{baz,quux}}/*.rs", "src/foo/a.rs");
+    matches!(matchalt18, "src/{foo,bar/{baz,quux}}/*.rs", "src/bar/baz/a.rs");
+    matches!(matchalt19, "src/{foo,bar/{baz,quux}}/*.rs", "src/bar/quux/a.rs");
+    matches!(matchalt20, "{{a,b},{c,d}}", "d");
 
     matches!(matchslash1, "abc/def", "abc/def", SLASHLIT);
     #[cfg(unix)]
@@ -1333,6 +1549,16 @@ mod tests {
     nmatches!(matchnot28, "a[^0-9]b", "a9b");
     nmatches!(matchnot29, "[^-]", "-");
     nmatches!(matchnot30, "some/*/needle.txt", "some/needle.txt");
+    nmatches!(matchnot31, "{a,{b,c}}", "d");
+    nmatches!(matchnot32, "src/{foo,bar/{baz,quux}}/*.rs", "src/bar/a.rs");
+
+    matches!(matchflags1, "(?i)*.html", "INDEX.HTML");
+    matches!(matchflags2, "(?i)src/*.rs", "SRC/MAIN.RS");
+    matches!(matchflags3, "(?i-s)*a", "B/A");
+    matches!(matchflags4, "(?-i)*.html", "index.html", CASEI);
+    nmatches!(matchflags5, "(?s)*a", "b/a");
+    nmatches!(matchflags6, "(?-i)*.html", "INDEX.HTML", CASEI);
+    nmatches!(matchflags7, "(?i)(?-i)*.html", "INDEX.HTML");
     nmatches!(
         matchrec31,
         "some/*/needle.txt", "some/one/two/needle.txt", SLASHLIT);
@@ -1394,7 +1620,8 @@ mod tests {
     literal!(extract_lit1, "foo", Some(s("foo")));
     literal!(extract_lit2, "foo", None, CASEI);
     literal!(extract_lit3, "/foo", Some(s("/foo")));
-    literal!(extract_lit4, "/foo/", Some(s("/foo/")));
+    // The trailing slash is not part of the matched path.
+    literal!(extract_lit4, "/foo/", Some(s("/foo")));
     literal!(extract_lit5, "/foo/bar", Some(s("/foo/bar")));
     literal!(extract_lit6, "*.foo", None);
     literal!(extract_lit7, "foo/bar", Some(s("foo/bar")));
@@ -1429,7 +1656,7 @@ mod tests {
     required_ext!(extract_req_ext5, ".rs", Some(s(".rs")));
     required_ext!(extract_req_ext6, "./rs", None);
     required_ext!(extract_req_ext7, "foo", None);
-    required_ext!(extract_req_ext8, ".foo/", None);
+    required_ext!(extract_req_ext8, ".foo/", Some(s(".foo")));
     required_ext!(extract_req_ext9, "foo/", None);
 
     prefix!(extract_prefix1, "/foo", Some(s("/foo")));
@@ -1449,4 +1676,57 @@ mod tests {
     baseliteral!(extract_baselit2, "foo", None);
     baseliteral!(extract_baselit3, "*foo", None);
     baseliteral!(extract_baselit4, "*/foo", None);
+
+    #[test]
+    fn dir_only() {
+        let pat = Glob::new("build/").unwrap();
+        assert!(pat.is_dir_only());
+        assert!(!Glob::new("build").unwrap().is_dir_only());
+
+        let m = pat.compile_matcher();
+        assert!(!m.is_match("build"));
+        assert!(!m.is_match_candidate(&Candidate::new("build")));
+        assert!(m.is_match_candidate(
+            &Candidate::new("build").with_is_dir(true)));
+        assert!(!m.is_match_candidate(
+            &Candidate::new("builds").with_is_dir(true)));
+    }
+
+    #[test]
+    fn captures() {
+        let pat = GlobBuilder::new("src/**/*.rs")
+            .literal_separator(true)
+            .build()
+            .unwrap();
+        let m = pat.compile_capture_matcher();
+        assert!(m.is_match("src/a/b/foo.rs"));
+        assert_eq!(
+            Some(vec![Some(b"a/b".to_vec()), Some(b"foo".to_vec())]),
+            m.captures("src/a/b/foo.rs"));
+        assert_eq!(
+            Some(vec![None, Some(b"foo".to_vec())]),
+            m.captures("src/foo.rs"));
+        assert_eq!(None, m.captures("src/foo.c"));
+    }
+
+    #[test]
+    fn captures_any() {
+        let pat = Glob::new("a?c-*").unwrap();
+        let m = pat.compile_capture_matcher();
+        assert_eq!(
+            Some(vec![Some(b"b".to_vec()), Some(b"xyz".to_vec())]),
+            m.captures("abc-xyz"));
+    }
+
+    #[test]
+    fn captures_alternates() {
+        let pat = Glob::new("{lib?,bin?}").unwrap();
+        let m = pat.compile_capture_matcher();
+        assert_eq!(
+            Some(vec![Some(b"a".to_vec()), None]),
+            m.captures("liba"));
+        assert_eq!(
+            Some(vec![None, Some(b"z".to_vec())]),
+            m.captures("binz"));
+    }
 }
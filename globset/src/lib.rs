@@ -116,6 +116,7 @@ use std::error::Error as StdError;
 use std::ffi::OsStr;
 use std::fmt;
 use std::hash;
+use std::io::{self, Read, Write};
 use std::path::Path;
 use std::str;
 
@@ -126,7 +127,7 @@ use pathutil::{
     file_name, file_name_ext, normalize_path, os_str_bytes, path_bytes,
 };
 use glob::MatchStrategy;
-pub use glob::{Glob, GlobBuilder, GlobMatcher};
+pub use glob::{Glob, GlobBuilder, GlobCaptureMatcher, GlobMatcher};
 
 mod glob;
 mod pathutil;
@@ -158,7 +159,13 @@ pub enum ErrorKind {
     UnclosedAlternates,
     /// Occurs when an alternating group is nested inside another alternating
     /// group, e.g., `{{a,b},{c,d}}`.
+    ///
+    /// Note that nested alternating groups are now supported, so this error
+    /// is no longer returned. The variant remains for compatibility.
     NestedAlternates,
+    /// Occurs when an inline flag group (e.g., `(?i)`) at the beginning of
+    /// a glob is malformed or contains an unrecognized flag.
+    InvalidInlineFlags,
     /// Occurs when an unescaped '\' is found at the end of a glob.
     DanglingEscape,
     /// An error associated with parsing or compiling a regex.
@@ -213,6 +220,10 @@ impl ErrorKind {
             ErrorKind::NestedAlternates => {
                 "nested alternate groups are not allowed"
             }
+            ErrorKind::InvalidInlineFlags => {
+                "invalid inline flags; expected '(?i)', '(?s)' or a \
+                combination such as '(?i-s)'"
+            }
             ErrorKind::DanglingEscape => {
                 "dangling '\\'"
             }
@@ -241,6 +252,7 @@ impl fmt::Display for ErrorKind {
             | ErrorKind::UnopenedAlternates
             | ErrorKind::UnclosedAlternates
             | ErrorKind::NestedAlternates
+            | ErrorKind::InvalidInlineFlags
             | ErrorKind::DanglingEscape
             | ErrorKind::Regex(_) => {
                 write!(f, "{}", self.description())
@@ -279,12 +291,39 @@ fn new_regex_set<I, S>(pats: I) -> Result<RegexSet, Error>
 
 type Fnv = hash::BuildHasherDefault<fnv::FnvHasher>;
 
+/// The rule used by `GlobSet::best_match` to choose a single winning glob
+/// when more than one glob in a set matches a path.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Precedence {
+    /// The matching glob added to the set last wins. This corresponds to the
+    /// rule used for the patterns in a gitignore file.
+    DefinitionOrder,
+    /// The matching glob with the longest pattern wins, measured in
+    /// characters. Ties are broken by definition order, with later globs
+    /// winning.
+    LongestPattern,
+    /// The most specific matching glob wins, where specificity is the number
+    /// of characters in the pattern that must match a path character
+    /// literally (see `Glob::literal_len`). Ties are broken by definition
+    /// order, with later globs winning.
+    Specificity,
+}
+
 /// GlobSet represents a group of globs that can be matched together in a
 /// single pass.
 #[derive(Clone, Debug)]
 pub struct GlobSet {
     len: usize,
     strats: Vec<GlobSetMatchStrategy>,
+    /// For every glob in this set, whether it is negated. This is empty
+    /// when the set contains no negated globs.
+    negated: Vec<bool>,
+    /// For every glob in this set, its pattern length in characters and its
+    /// literal length, used to rank matches in `best_match`.
+    ranks: Vec<(usize, usize)>,
+    /// For every glob in this set, whether it matches directories only.
+    /// This is empty when the set contains no such globs.
+    dir_only: Vec<bool>,
 }
 
 impl GlobSet {
@@ -293,6 +332,9 @@ impl GlobSet {
         GlobSet {
             len: 0,
             strats: vec![],
+            negated: vec![],
+            ranks: vec![],
+            dir_only: vec![],
         }
     }
 
@@ -307,6 +349,12 @@ impl GlobSet {
     }
 
     /// Returns true if any glob in this set matches the path given.
+    ///
+    /// If this set contains negated globs (see
+    /// `GlobSetBuilder::add_negated`), then the decision follows gitignore
+    /// semantics: the last glob in the set that matches the path determines
+    /// the outcome, and if that glob is negated, then the path is considered
+    /// not matched.
     pub fn is_match<P: AsRef<Path>>(&self, path: P) -> bool {
         self.is_match_candidate(&Candidate::new(path.as_ref()))
     }
@@ -319,6 +367,19 @@ impl GlobSet {
         if self.is_empty() {
             return false;
         }
+        if !self.negated.is_empty() {
+            // A negated glob can overrule an earlier match, so the decision
+            // belongs to the last glob in the set that matches.
+            return match self.matches_candidate(path).last() {
+                None => false,
+                Some(&i) => !self.negated[i],
+            };
+        }
+        if !self.dir_only.is_empty() {
+            // Directory-only globs are filtered out of the match set after
+            // the fact, so the fast path below can't be used.
+            return !self.matches_candidate(path).is_empty();
+        }
         for strat in &self.strats {
             if strat.is_match(path) {
                 return true;
@@ -327,6 +388,54 @@ impl GlobSet {
         false
     }
 
+    /// Returns the sequence number of the single winning glob for the given
+    /// path under the given precedence rule, or `None` if no glob in this
+    /// set matches.
+    ///
+    /// Negated globs participate like any other glob; callers that want
+    /// gitignore semantics should use `is_match` instead.
+    pub fn best_match<P: AsRef<Path>>(
+        &self,
+        path: P,
+        precedence: Precedence,
+    ) -> Option<usize> {
+        self.best_match_candidate(&Candidate::new(path.as_ref()), precedence)
+    }
+
+    /// Returns the sequence number of the single winning glob for the given
+    /// path under the given precedence rule, or `None` if no glob in this
+    /// set matches.
+    ///
+    /// This takes a Candidate as input, which can be used to amortize the
+    /// cost of preparing a path for matching.
+    pub fn best_match_candidate(
+        &self,
+        path: &Candidate,
+        precedence: Precedence,
+    ) -> Option<usize> {
+        let matches = self.matches_candidate(path);
+        match precedence {
+            Precedence::DefinitionOrder => matches.last().map(|&i| i),
+            Precedence::LongestPattern => {
+                // Ties go to the later glob, since sequence numbers are
+                // compared after lengths and `matches` is in ascending
+                // order.
+                matches
+                    .iter()
+                    .map(|&i| (self.ranks[i].0, i))
+                    .max()
+                    .map(|(_, i)| i)
+            }
+            Precedence::Specificity => {
+                matches
+                    .iter()
+                    .map(|&i| (self.ranks[i].1, i))
+                    .max()
+                    .map(|(_, i)| i)
+            }
+        }
+    }
+
     /// Returns the sequence number of every glob pattern that matches the
     /// given path.
     pub fn matches<P: AsRef<Path>>(&self, path: P) -> Vec<usize> {
@@ -384,11 +493,14 @@ impl GlobSet {
         }
         into.sort();
         into.dedup();
+        if !self.dir_only.is_empty() && !path.is_dir {
+            into.retain(|&i| !self.dir_only[i]);
+        }
     }
 
-    fn new(pats: &[Glob]) -> Result<GlobSet, Error> {
+    fn new(pats: &[Glob], negated: &[bool]) -> Result<GlobSet, Error> {
         if pats.is_empty() {
-            return Ok(GlobSet { len: 0, strats: vec![] });
+            return Ok(GlobSet::empty());
         }
         let mut lits = LiteralStrategy::new();
         let mut base_lits = BasenameLiteralStrategy::new();
@@ -433,6 +545,20 @@ impl GlobSet {
                 required_exts.0.len(), regexes.literals.len());
         Ok(GlobSet {
             len: pats.len(),
+            negated: if negated.iter().any(|&b| b) {
+                negated.to_vec()
+            } else {
+                vec![]
+            },
+            ranks: pats
+                .iter()
+                .map(|p| (p.glob().chars().count(), p.literal_len()))
+                .collect(),
+            dir_only: if pats.iter().any(|p| p.is_dir_only()) {
+                pats.iter().map(|p| p.is_dir_only()).collect()
+            } else {
+                vec![]
+            },
             strats: vec![
                 GlobSetMatchStrategy::Extension(exts),
                 GlobSetMatchStrategy::BasenameLiteral(base_lits),
@@ -452,6 +578,7 @@ impl GlobSet {
 #[derive(Clone, Debug)]
 pub struct GlobSetBuilder {
     pats: Vec<Glob>,
+    negated: Vec<bool>,
 }
 
 impl GlobSetBuilder {
@@ -459,22 +586,118 @@ impl GlobSetBuilder {
     /// patterns. Once all patterns have been added, `build` should be called
     /// to produce a `GlobSet`, which can then be used for matching.
     pub fn new() -> GlobSetBuilder {
-        GlobSetBuilder { pats: vec![] }
+        GlobSetBuilder { pats: vec![], negated: vec![] }
     }
 
     /// Builds a new matcher from all of the glob patterns added so far.
     ///
     /// Once a matcher is built, no new patterns can be added to it.
     pub fn build(&self) -> Result<GlobSet, Error> {
-        GlobSet::new(&self.pats)
+        GlobSet::new(&self.pats, &self.negated)
     }
 
     /// Add a new pattern to this set.
     #[allow(dead_code)]
     pub fn add(&mut self, pat: Glob) -> &mut GlobSetBuilder {
         self.pats.push(pat);
+        self.negated.push(false);
         self
     }
+
+    /// Add a new negated pattern to this set.
+    ///
+    /// A path that matches a negated pattern is considered not matched by
+    /// the set, unless a pattern added after the negated one matches it
+    /// again. That is, `GlobSet::is_match` gives the outcome of the last
+    /// pattern in the set that matches, just like the patterns in a
+    /// gitignore file.
+    pub fn add_negated(&mut self, pat: Glob) -> &mut GlobSetBuilder {
+        self.pats.push(pat);
+        self.negated.push(true);
+        self
+    }
+
+    /// Add a new pattern to this set, treating a leading `!` as negation.
+    ///
+    /// This is a convenience routine for config formats with gitignore-like
+    /// entries. If the pattern starts with `!`, then the remainder of it is
+    /// added with `add_negated`. Otherwise, the entire pattern is added with
+    /// `add`. A pattern that needs to match a literal leading `!` can escape
+    /// it with a backslash.
+    pub fn add_negatable(
+        &mut self,
+        pat: &str,
+    ) -> Result<&mut GlobSetBuilder, Error> {
+        if pat.starts_with('!') {
+            Ok(self.add_negated(Glob::new(&pat[1..])?))
+        } else {
+            Ok(self.add(Glob::new(pat)?))
+        }
+    }
+
+    /// Writes the patterns in this builder to the given writer, one pattern
+    /// per line, in a form that can be read back with `load`.
+    ///
+    /// This permits applications to persist a set of patterns across runs
+    /// instead of regenerating them. Note that it is the patterns themselves
+    /// that are stored and not the compiled matcher, so `build` must be
+    /// called again after loading and will recompile the set. Patterns added
+    /// with non-default options from `GlobBuilder` are written as their
+    /// original pattern strings and are read back with the default options.
+    ///
+    /// This returns an error if any pattern contains a line terminator.
+    pub fn dump<W: Write>(&self, mut wtr: W) -> io::Result<()> {
+        for (pat, &negated) in self.pats.iter().zip(&self.negated) {
+            let glob = pat.glob();
+            if glob.contains('\n') || glob.contains('\r') {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("glob {:?} contains a line terminator", glob),
+                ));
+            }
+            // Negated patterns get a `!` prefix, so protect patterns that
+            // happen to start with `!` (or with the escape itself) with a
+            // leading backslash.
+            if negated {
+                wtr.write_all(b"!")?;
+            } else if glob.starts_with('!') || glob.starts_with('\\') {
+                wtr.write_all(b"\\")?;
+            }
+            wtr.write_all(glob.as_bytes())?;
+            wtr.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+
+    /// Reads a set of patterns previously written by `dump` and returns a
+    /// builder containing them, in their original order and with their
+    /// negations intact.
+    ///
+    /// If any pattern could not be parsed, then an error with kind
+    /// `io::ErrorKind::InvalidData` is returned.
+    pub fn load<R: Read>(mut rdr: R) -> io::Result<GlobSetBuilder> {
+        let mut contents = String::new();
+        rdr.read_to_string(&mut contents)?;
+        let mut builder = GlobSetBuilder::new();
+        for line in contents.lines() {
+            let (negated, pat) = if line.starts_with('!') {
+                (true, &line[1..])
+            } else if line.starts_with('\\') {
+                (false, &line[1..])
+            } else {
+                (false, line)
+            };
+            let glob = Glob::new(pat).map_err(|err| {
+                io::Error::new(io::ErrorKind::InvalidData, err)
+            })?;
+            if negated {
+                builder.add_negated(glob);
+            } else {
+                builder.add(glob);
+            }
+        }
+        Ok(builder)
+    }
 }
 
 /// A candidate path for matching.
@@ -488,6 +711,7 @@ pub struct Candidate<'a> {
     path: Cow<'a, [u8]>,
     basename: Cow<'a, [u8]>,
     ext: Cow<'a, [u8]>,
+    is_dir: bool,
 }
 
 impl<'a> Candidate<'a> {
@@ -499,9 +723,21 @@ impl<'a> Candidate<'a> {
             path: normalize_path(path_bytes(path)),
             basename: os_str_bytes(basename),
             ext: file_name_ext(basename).unwrap_or(Cow::Borrowed(b"")),
+            is_dir: false,
         }
     }
 
+    /// Returns this candidate with its directory status set.
+    ///
+    /// Globs written with a trailing slash (e.g., `build/`) match
+    /// directories only. A candidate is assumed not to be a directory
+    /// unless this is called, so such globs never match candidates built
+    /// with `new` alone.
+    pub fn with_is_dir(mut self, yes: bool) -> Candidate<'a> {
+        self.is_dir = yes;
+        self
+    }
+
     fn path_prefix(&self, max: usize) -> &[u8] {
         if self.path.len() <= max {
             &*self.path
@@ -834,7 +1070,7 @@ impl RequiredExtensionStrategyBuilder {
 
 #[cfg(test)]
 mod tests {
-    use super::GlobSetBuilder;
+    use super::{Candidate, GlobSetBuilder, Precedence};
     use glob::Glob;
 
     #[test]
@@ -864,4 +1100,94 @@ mod tests {
         assert!(!set.is_match(""));
         assert!(!set.is_match("a"));
     }
+
+    #[test]
+    fn dir_only_set_works() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("build/").unwrap());
+        builder.add(Glob::new("*.o").unwrap());
+        let set = builder.build().unwrap();
+
+        assert!(!set.is_match("build"));
+        assert!(set.is_match("foo.o"));
+        assert!(set.is_match_candidate(
+            &Candidate::new("build").with_is_dir(true)));
+        assert!(set.matches_candidate(&Candidate::new("build")).is_empty());
+        assert_eq!(
+            vec![0],
+            set.matches_candidate(
+                &Candidate::new("build").with_is_dir(true)));
+    }
+
+    #[test]
+    fn best_match_works() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("**/*.rs").unwrap());
+        builder.add(Glob::new("src/*.rs").unwrap());
+        builder.add(Glob::new("src/lib.rs").unwrap());
+        builder.add(Glob::new("*.rs").unwrap());
+        let set = builder.build().unwrap();
+
+        assert_eq!(
+            Some(3),
+            set.best_match("src/lib.rs", Precedence::DefinitionOrder));
+        assert_eq!(
+            Some(2),
+            set.best_match("src/lib.rs", Precedence::LongestPattern));
+        assert_eq!(
+            Some(2),
+            set.best_match("src/lib.rs", Precedence::Specificity));
+        assert_eq!(
+            Some(1),
+            set.best_match("src/main.rs", Precedence::Specificity));
+        assert_eq!(
+            None,
+            set.best_match("foo.c", Precedence::DefinitionOrder));
+    }
+
+    #[test]
+    fn negated_set_works() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("*.rs").unwrap());
+        builder.add_negated(Glob::new("build.rs").unwrap());
+        builder.add(Glob::new("src/build.rs").unwrap());
+        let set = builder.build().unwrap();
+
+        assert!(set.is_match("lib.rs"));
+        assert!(!set.is_match("build.rs"));
+        assert!(set.is_match("src/build.rs"));
+        assert!(!set.is_match("foo.c"));
+    }
+
+    #[test]
+    fn dump_load_round_trip() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("src/**/*.rs").unwrap());
+        builder.add_negated(Glob::new("src/build.rs").unwrap());
+        builder.add(Glob::new("!bang").unwrap());
+        let mut buf = vec![];
+        builder.dump(&mut buf).unwrap();
+
+        let builder = GlobSetBuilder::load(&buf[..]).unwrap();
+        let set = builder.build().unwrap();
+        assert!(set.is_match("src/lib.rs"));
+        assert!(!set.is_match("src/build.rs"));
+        assert!(set.is_match("!bang"));
+
+        let mut buf2 = vec![];
+        builder.dump(&mut buf2).unwrap();
+        assert_eq!(buf, buf2);
+    }
+
+    #[test]
+    fn negatable_set_works() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add_negatable("*.log").unwrap();
+        builder.add_negatable("!important.log").unwrap();
+        let set = builder.build().unwrap();
+
+        assert!(set.is_match("debug.log"));
+        assert!(!set.is_match("important.log"));
+        assert!(!set.is_match("notes.txt"));
+    }
 }
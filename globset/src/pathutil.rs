@@ -91,18 +91,81 @@ pub fn os_str_bytes(s: &OsStr) -> Cow<[u8]> {
 }
 
 /// Return the raw bytes of the given OS string, possibly transcoded to UTF-8.
-#[cfg(not(unix))]
+#[cfg(windows)]
+pub fn os_str_bytes(s: &OsStr) -> Cow<[u8]> {
+    use std::os::windows::ffi::OsStrExt;
+
+    // On Windows, OS strings are WTF-8, which is a superset of UTF-8. The
+    // standard library doesn't expose the raw bytes, but they can be
+    // losslessly reconstructed from the UTF-16 code units. Valid Unicode
+    // is encoded exactly as UTF-8 would encode it, so globs (which are
+    // always valid UTF-8) match as usual, while unpaired surrogates
+    // survive as distinct byte sequences instead of all being mangled to
+    // U+FFFD.
+    match s.to_str() {
+        Some(s) => Cow::Borrowed(s.as_bytes()),
+        None => Cow::Owned(wtf8_bytes(s.encode_wide())),
+    }
+}
+
+/// Return the raw bytes of the given OS string, possibly transcoded to UTF-8.
+#[cfg(not(any(unix, windows)))]
 pub fn os_str_bytes(s: &OsStr) -> Cow<[u8]> {
-    // TODO(burntsushi): On Windows, OS strings are WTF-8, which is a superset
-    // of UTF-8, so even if we could get at the raw bytes, they wouldn't
-    // be useful. We *must* convert to UTF-8 before doing path matching.
-    // Unfortunate, but necessary.
     match s.to_string_lossy() {
         Cow::Owned(s) => Cow::Owned(s.into_bytes()),
         Cow::Borrowed(s) => Cow::Borrowed(s.as_bytes()),
     }
 }
 
+/// Encode a sequence of UTF-16 code units as WTF-8.
+///
+/// Surrogate pairs are combined and encoded as the code point that they
+/// represent. Unpaired surrogates are encoded directly, which is what
+/// distinguishes WTF-8 from UTF-8.
+#[cfg(any(windows, test))]
+fn wtf8_bytes<I: IntoIterator<Item=u16>>(units: I) -> Vec<u8> {
+    let mut bytes = vec![];
+    let mut units = units.into_iter().peekable();
+    while let Some(u) = units.next() {
+        let cp = if u < 0xD800 || u >= 0xDC00 {
+            // A non-surrogate or an unpaired low surrogate.
+            u as u32
+        } else {
+            match units.peek().map(|&u2| u2) {
+                Some(u2) if u2 >= 0xDC00 && u2 < 0xE000 => {
+                    units.next();
+                    0x10000
+                        + (((u as u32) - 0xD800) << 10)
+                        + ((u2 as u32) - 0xDC00)
+                }
+                // An unpaired high surrogate.
+                _ => u as u32,
+            }
+        };
+        push_wtf8_code_point(&mut bytes, cp);
+    }
+    bytes
+}
+
+#[cfg(any(windows, test))]
+fn push_wtf8_code_point(bytes: &mut Vec<u8>, cp: u32) {
+    if cp < 0x80 {
+        bytes.push(cp as u8);
+    } else if cp < 0x800 {
+        bytes.push(0b1100_0000 | (cp >> 6) as u8);
+        bytes.push(0b1000_0000 | (cp as u8 & 0b11_1111));
+    } else if cp < 0x10000 {
+        bytes.push(0b1110_0000 | (cp >> 12) as u8);
+        bytes.push(0b1000_0000 | ((cp >> 6) as u8 & 0b11_1111));
+        bytes.push(0b1000_0000 | (cp as u8 & 0b11_1111));
+    } else {
+        bytes.push(0b1111_0000 | (cp >> 18) as u8);
+        bytes.push(0b1000_0000 | ((cp >> 12) as u8 & 0b11_1111));
+        bytes.push(0b1000_0000 | ((cp >> 6) as u8 & 0b11_1111));
+        bytes.push(0b1000_0000 | (cp as u8 & 0b11_1111));
+    }
+}
+
 /// Normalizes a path to use `/` as a separator everywhere, even on platforms
 /// that recognize other characters as separators.
 #[cfg(unix)]
@@ -131,7 +194,7 @@ mod tests {
     use std::borrow::Cow;
     use std::ffi::OsStr;
 
-    use super::{file_name_ext, normalize_path};
+    use super::{file_name_ext, normalize_path, wtf8_bytes};
 
     macro_rules! ext {
         ($name:ident, $file_name:expr, $ext:expr) => {
@@ -159,6 +222,24 @@ mod tests {
         };
     }
 
+    macro_rules! wtf8 {
+        ($name:ident, $units:expr, $expected:expr) => {
+            #[test]
+            fn $name() {
+                let got = wtf8_bytes($units.iter().map(|&u| u));
+                assert_eq!(&$expected[..], &*got);
+            }
+        };
+    }
+
+    // Valid Unicode is encoded as it would be in UTF-8...
+    wtf8!(wtf8_1, [0x61u16, 0x62, 0x63], b"abc");
+    wtf8!(wtf8_2, [0x20ACu16], "€".as_bytes());
+    wtf8!(wtf8_3, [0xD83Du16, 0xDE00], "😀".as_bytes());
+    // ... while unpaired surrogates are encoded directly.
+    wtf8!(wtf8_4, [0x61u16, 0xD800, 0x62], b"a\xED\xA0\x80b");
+    wtf8!(wtf8_5, [0xDE00u16, 0xD83D], b"\xED\xB8\x80\xED\xA0\xBD");
+
     normalize!(normal1, b"foo", b"foo");
     normalize!(normal2, b"foo/bar", b"foo/bar");
     #[cfg(unix)]
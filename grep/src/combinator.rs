@@ -0,0 +1,117 @@
+use search::Grep;
+
+/// A predicate over a single line of text.
+///
+/// This is the interface used by the boolean combinators in this module. It
+/// is implemented by `Grep` (a line matches if the underlying regex matches
+/// anywhere in it), so searchers can be composed into queries like "lines
+/// containing foo AND bar but NOT baz".
+pub trait LineMatcher {
+    /// Returns true if and only if the given line matches.
+    ///
+    /// The line given should not contain the line terminator.
+    fn is_match(&self, line: &[u8]) -> bool;
+}
+
+impl LineMatcher for Grep {
+    fn is_match(&self, line: &[u8]) -> bool {
+        self.regex().is_match(line)
+    }
+}
+
+impl<'a, M: LineMatcher + ?Sized> LineMatcher for &'a M {
+    fn is_match(&self, line: &[u8]) -> bool {
+        (**self).is_match(line)
+    }
+}
+
+impl<M: LineMatcher + ?Sized> LineMatcher for Box<M> {
+    fn is_match(&self, line: &[u8]) -> bool {
+        (**self).is_match(line)
+    }
+}
+
+/// A matcher that matches a line if and only if every sub-matcher matches.
+///
+/// An `AllOf` over no sub-matchers matches every line.
+pub struct AllOf<M>(Vec<M>);
+
+impl<M: LineMatcher> AllOf<M> {
+    /// Create a new conjunction over the given sub-matchers.
+    pub fn new(matchers: Vec<M>) -> AllOf<M> {
+        AllOf(matchers)
+    }
+}
+
+impl<M: LineMatcher> LineMatcher for AllOf<M> {
+    fn is_match(&self, line: &[u8]) -> bool {
+        self.0.iter().all(|m| m.is_match(line))
+    }
+}
+
+/// A matcher that matches a line if and only if at least one sub-matcher
+/// matches.
+///
+/// An `AnyOf` over no sub-matchers matches no lines.
+pub struct AnyOf<M>(Vec<M>);
+
+impl<M: LineMatcher> AnyOf<M> {
+    /// Create a new disjunction over the given sub-matchers.
+    pub fn new(matchers: Vec<M>) -> AnyOf<M> {
+        AnyOf(matchers)
+    }
+}
+
+impl<M: LineMatcher> LineMatcher for AnyOf<M> {
+    fn is_match(&self, line: &[u8]) -> bool {
+        self.0.iter().any(|m| m.is_match(line))
+    }
+}
+
+/// A matcher that matches a line if and only if its sub-matcher does not.
+pub struct Not<M>(M);
+
+impl<M: LineMatcher> Not<M> {
+    /// Create a new negation of the given sub-matcher.
+    pub fn new(matcher: M) -> Not<M> {
+        Not(matcher)
+    }
+}
+
+impl<M: LineMatcher> LineMatcher for Not<M> {
+    fn is_match(&self, line: &[u8]) -> bool {
+        !self.0.is_match(line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use search::GrepBuilder;
+    use super::{AllOf, AnyOf, LineMatcher, Not};
+
+    fn grep(pat: &str) -> Box<LineMatcher> {
+        Box::new(GrepBuilder::new(pat).build().unwrap())
+    }
+
+    #[test]
+    fn combinators() {
+        // foo AND bar AND NOT baz
+        let q = AllOf::new(vec![
+            grep("foo"),
+            grep("bar"),
+            Box::new(Not::new(grep("baz"))) as Box<LineMatcher>,
+        ]);
+        assert!(q.is_match(b"foo bar"));
+        assert!(q.is_match(b"bar quux foo"));
+        assert!(!q.is_match(b"foo baz bar"));
+        assert!(!q.is_match(b"foo quux"));
+
+        let q = AnyOf::new(vec![grep("foo"), grep("bar")]);
+        assert!(q.is_match(b"just foo"));
+        assert!(q.is_match(b"just bar"));
+        assert!(!q.is_match(b"neither"));
+
+        assert!(AllOf::<Box<LineMatcher>>::new(vec![]).is_match(b"any"));
+        assert!(!AnyOf::<Box<LineMatcher>>::new(vec![]).is_match(b"any"));
+    }
+}
@@ -4,6 +4,7 @@
 A fast line oriented regex searcher.
 */
 
+extern crate aho_corasick;
 #[macro_use]
 extern crate log;
 extern crate memchr;
@@ -14,12 +15,21 @@ use std::error;
 use std::fmt;
 use std::result;
 
+pub use combinator::{AllOf, AnyOf, LineMatcher, Not};
+pub use multi::{MultiLiteral, MultiLiteralBuilder, MultiLiteralIter};
+pub use prefilter::{ByteSet, Prefilter};
 pub use search::{Grep, GrepBuilder, Iter, Match};
+pub use smart_case::Cased;
+pub use stream::StreamIter;
 
+mod combinator;
 mod literals;
+mod multi;
 mod nonl;
+mod prefilter;
 mod search;
 mod smart_case;
+mod stream;
 mod word_boundary;
 
 /// Result is a convenient type alias that fixes the type of the error to
@@ -13,6 +13,8 @@ use regex::bytes::RegexBuilder;
 use syntax::hir::{self, Hir, HirKind};
 use syntax::hir::literal::{Literal, Literals};
 
+use prefilter::{ByteSet, Prefilter};
+
 #[derive(Clone, Debug)]
 pub struct LiteralSets {
     prefixes: Literals,
@@ -99,6 +101,48 @@ impl LiteralSets {
             Some(builder)
         }
     }
+
+    /// Derives a prefilter from these literal sets, if a useful one exists.
+    ///
+    /// A single literal that every match must contain is preferred. Failing
+    /// that, if every match must contain one of a set of literals, then the
+    /// set of their leading bytes is used instead.
+    pub fn to_prefilter(&self) -> Option<Prefilter> {
+        let pre_lcp = self.prefixes.longest_common_prefix();
+        let pre_lcs = self.prefixes.longest_common_suffix();
+        let suf_lcp = self.suffixes.longest_common_prefix();
+        let suf_lcs = self.suffixes.longest_common_suffix();
+        let req_lits = self.required.literals();
+        let req = match req_lits.iter().max_by_key(|lit| lit.len()) {
+            None => &[],
+            Some(req) => &***req,
+        };
+
+        let mut lit = pre_lcp;
+        if pre_lcs.len() > lit.len() {
+            lit = pre_lcs;
+        }
+        if suf_lcp.len() > lit.len() {
+            lit = suf_lcp;
+        }
+        if suf_lcs.len() > lit.len() {
+            lit = suf_lcs;
+        }
+        if req_lits.len() == 1 && req.len() > lit.len() {
+            lit = req;
+        }
+        if !lit.is_empty() {
+            return Some(Prefilter::Literal(lit.to_vec()));
+        }
+        if !req_lits.is_empty() && req_lits.iter().all(|lit| !lit.is_empty()) {
+            let mut set = ByteSet::new();
+            for lit in req_lits {
+                set.add(lit[0]);
+            }
+            return Some(Prefilter::ByteSet(set));
+        }
+        None
+    }
 }
 
 fn union_required(expr: &Hir, lits: &mut Literals) {
@@ -0,0 +1,166 @@
+use aho_corasick::{AcAutomaton, Automaton};
+use memchr::{memchr, memrchr};
+
+use search::Match;
+use {Error, Result};
+
+/// A line oriented searcher for a set of plain literals.
+///
+/// This is built directly on an Aho-Corasick automaton, which makes it
+/// suitable for very large sets of literals (hundreds of thousands of
+/// strings) that would either exceed the regex engine's compilation limits
+/// or compile far slower as one giant alternation.
+pub struct MultiLiteral {
+    ac: AcAutomaton<Vec<u8>>,
+    line_terminator: u8,
+}
+
+/// A builder for a multi-literal line searcher.
+#[derive(Clone, Debug)]
+pub struct MultiLiteralBuilder {
+    literals: Vec<Vec<u8>>,
+    line_terminator: u8,
+}
+
+impl MultiLiteralBuilder {
+    /// Create a new builder from the given literals.
+    ///
+    /// Each literal is matched exactly. No escaping is necessary and no
+    /// regex syntax is recognized.
+    pub fn new<I, P>(literals: I) -> MultiLiteralBuilder
+            where I: IntoIterator<Item=P>, P: AsRef<[u8]> {
+        MultiLiteralBuilder {
+            literals:
+                literals.into_iter().map(|p| p.as_ref().to_vec()).collect(),
+            line_terminator: b'\n',
+        }
+    }
+
+    /// Set the line terminator.
+    ///
+    /// The line terminator can be any ASCII character and serves to
+    /// delineate the match boundaries in the text searched.
+    ///
+    /// This panics if `ascii_byte` is greater than `0x7F` (i.e., not ASCII).
+    pub fn line_terminator(mut self, ascii_byte: u8) -> MultiLiteralBuilder {
+        assert!(ascii_byte <= 0x7F);
+        self.line_terminator = ascii_byte;
+        self
+    }
+
+    /// Create a multi-literal line searcher.
+    ///
+    /// If any of the literals contain the line terminator, then an error is
+    /// returned, since such a literal could never match within a single
+    /// line.
+    pub fn build(self) -> Result<MultiLiteral> {
+        for lit in &self.literals {
+            if lit.contains(&self.line_terminator) {
+                return Err(Error::LiteralNotAllowed(
+                    self.line_terminator as char));
+            }
+        }
+        Ok(MultiLiteral {
+            ac: AcAutomaton::new(self.literals),
+            line_terminator: self.line_terminator,
+        })
+    }
+}
+
+impl MultiLiteral {
+    /// Returns the index of the literal matching earliest in the given line.
+    ///
+    /// Literal indices correspond to the order in which literals were given
+    /// to `MultiLiteralBuilder::new`. If no literal matches the line, then
+    /// `None` is returned.
+    pub fn matched_pattern(&self, line: &[u8]) -> Option<usize> {
+        self.ac.find(line).next().map(|m| m.pati)
+    }
+
+    /// Returns an iterator over all matching lines in the given buffer.
+    pub fn iter<'b, 's>(&'s self, buf: &'b [u8]) -> MultiLiteralIter<'b, 's> {
+        MultiLiteralIter {
+            searcher: self,
+            buf: buf,
+            start: 0,
+        }
+    }
+
+    /// Fills in the next line that matches in the given buffer starting at
+    /// the position given.
+    ///
+    /// If no match could be found, `false` is returned, otherwise, `true` is
+    /// returned.
+    pub fn read_match(
+        &self,
+        mat: &mut Match,
+        buf: &[u8],
+        start: usize,
+    ) -> bool {
+        if start >= buf.len() {
+            return false;
+        }
+        let e = match self.ac.find(&buf[start..]).next() {
+            None => return false,
+            Some(m) => start + m.end,
+        };
+        let (s, e) = self.find_line(buf, e);
+        *mat = Match::from_span(s, e);
+        true
+    }
+
+    fn find_line(&self, buf: &[u8], pos: usize) -> (usize, usize) {
+        let s = memrchr(self.line_terminator, &buf[0..pos])
+            .map_or(0, |i| i + 1);
+        let e = memchr(self.line_terminator, &buf[pos..])
+            .map_or(buf.len(), |i| pos + i + 1);
+        (s, e)
+    }
+}
+
+/// An iterator over all matching lines in a particular buffer.
+///
+/// `'b` refers to the lifetime of the buffer, and `'s` refers to the
+/// lifetime of the searcher.
+pub struct MultiLiteralIter<'b, 's> {
+    searcher: &'s MultiLiteral,
+    buf: &'b [u8],
+    start: usize,
+}
+
+impl<'b, 's> Iterator for MultiLiteralIter<'b, 's> {
+    type Item = Match;
+
+    fn next(&mut self) -> Option<Match> {
+        let mut mat = Match::default();
+        if !self.searcher.read_match(&mut mat, self.buf, self.start) {
+            self.start = self.buf.len();
+            return None;
+        }
+        self.start = mat.end();
+        Some(mat)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MultiLiteralBuilder;
+
+    #[test]
+    fn literal_lines() {
+        let hay = &b"foo one\nbar two\nbaz three\nquux four\n"[..];
+        let m = MultiLiteralBuilder::new(&["bar", "quux"]).build().unwrap();
+        let lines: Vec<(usize, usize)> =
+            m.iter(hay).map(|m| (m.start(), m.end())).collect();
+        assert_eq!(lines, vec![(8, 16), (26, 36)]);
+
+        assert_eq!(m.matched_pattern(b"say quux"), Some(1));
+        assert_eq!(m.matched_pattern(b"bar quux"), Some(0));
+        assert_eq!(m.matched_pattern(b"nothing"), None);
+    }
+
+    #[test]
+    fn literal_with_line_terminator() {
+        assert!(MultiLiteralBuilder::new(&["foo\nbar"]).build().is_err());
+    }
+}
@@ -0,0 +1,138 @@
+/*!
+The prefilter module exposes cheap necessary conditions for a match that
+callers can check before running the full regex engine. A prefilter is
+derived from the literals of a pattern: either a single literal that every
+match must contain, or a set of bytes such that every match contains at
+least one of them. Either form can be fed to memchr-style scanning to skip
+over regions that cannot possibly participate in a match.
+*/
+
+use memchr::{memchr, memchr2, memchr3};
+
+/// A cheap necessary condition for a match.
+///
+/// A prefilter never produces false negatives: if a prefilter reports no
+/// candidate in some region, then the pattern cannot match in that region.
+/// It may produce false positives, so candidates still need to be confirmed
+/// with the full pattern.
+#[derive(Clone, Debug)]
+pub enum Prefilter {
+    /// Every match contains this literal.
+    Literal(Vec<u8>),
+    /// Every match contains at least one byte from this set.
+    ByteSet(ByteSet),
+    /// An unused enum variant that indicates this enum may be expanded in
+    /// the future and therefore should not be exhaustively matched.
+    #[doc(hidden)]
+    __Nonexhaustive,
+}
+
+impl Prefilter {
+    /// Returns the position of the first candidate in the given haystack,
+    /// or `None` if the pattern cannot match anywhere in it.
+    ///
+    /// The returned offset points at the candidate itself (the start of the
+    /// literal or the position of the set byte), which does not necessarily
+    /// correspond to the start of a match.
+    pub fn find(&self, haystack: &[u8]) -> Option<usize> {
+        match *self {
+            Prefilter::Literal(ref lit) => find_literal(lit, haystack),
+            Prefilter::ByteSet(ref set) => set.find(haystack),
+            Prefilter::__Nonexhaustive => unreachable!(),
+        }
+    }
+}
+
+/// A set of bytes represented as a 256-element membership table.
+#[derive(Clone)]
+pub struct ByteSet([bool; 256]);
+
+impl ByteSet {
+    /// Create a new empty byte set.
+    pub fn new() -> ByteSet {
+        ByteSet([false; 256])
+    }
+
+    /// Add a byte to this set.
+    pub fn add(&mut self, byte: u8) {
+        self.0[byte as usize] = true;
+    }
+
+    /// Returns true if and only if the given byte is in this set.
+    pub fn contains(&self, byte: u8) -> bool {
+        self.0[byte as usize]
+    }
+
+    /// Returns all bytes in this set in ascending order.
+    pub fn bytes(&self) -> Vec<u8> {
+        (0..256).map(|b| b as u8).filter(|&b| self.contains(b)).collect()
+    }
+
+    /// Returns the position of the first byte in the haystack that is a
+    /// member of this set, using memchr when the set is small enough.
+    pub fn find(&self, haystack: &[u8]) -> Option<usize> {
+        let bytes = self.bytes();
+        match bytes.len() {
+            0 => None,
+            1 => memchr(bytes[0], haystack),
+            2 => memchr2(bytes[0], bytes[1], haystack),
+            3 => memchr3(bytes[0], bytes[1], bytes[2], haystack),
+            _ => haystack.iter().position(|&b| self.contains(b)),
+        }
+    }
+}
+
+impl Default for ByteSet {
+    fn default() -> ByteSet {
+        ByteSet::new()
+    }
+}
+
+impl ::std::fmt::Debug for ByteSet {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        f.debug_tuple("ByteSet").field(&self.bytes()).finish()
+    }
+}
+
+/// Finds the first occurrence of a literal in the haystack, accelerating
+/// the scan for its leading byte with memchr.
+fn find_literal(lit: &[u8], haystack: &[u8]) -> Option<usize> {
+    if lit.is_empty() || lit.len() > haystack.len() {
+        return None;
+    }
+    let mut pos = 0;
+    while let Some(i) = memchr(lit[0], &haystack[pos..]) {
+        let start = pos + i;
+        if haystack.len() - start < lit.len() {
+            return None;
+        }
+        if &haystack[start..start + lit.len()] == lit {
+            return Some(start);
+        }
+        pos = start + 1;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ByteSet, Prefilter};
+
+    #[test]
+    fn literal() {
+        let pre = Prefilter::Literal(b"holmes".to_vec());
+        assert_eq!(pre.find(b"sherlock holmes"), Some(9));
+        assert_eq!(pre.find(b"sherlock hol"), None);
+        assert_eq!(pre.find(b"hohoholmes"), Some(4));
+    }
+
+    #[test]
+    fn byte_set() {
+        let mut set = ByteSet::new();
+        set.add(b'x');
+        set.add(b'y');
+        let pre = Prefilter::ByteSet(set);
+        assert_eq!(pre.find(b"abcydef"), Some(3));
+        assert_eq!(pre.find(b"abcdef"), None);
+    }
+}
@@ -1,12 +1,13 @@
 use memchr::{memchr, memrchr};
 use syntax::ParserBuilder;
 use syntax::hir::Hir;
-use regex::bytes::{Regex, RegexBuilder};
+use regex::bytes::{Regex, RegexBuilder, RegexSet, RegexSetBuilder};
 
 use literals::LiteralSets;
 use nonl;
+use prefilter::Prefilter;
 use smart_case::Cased;
-use word_boundary::strip_unicode_word_boundaries;
+use word_boundary::{ascii_word_boundaries, strip_unicode_word_boundaries};
 use Result;
 
 /// A matched line.
@@ -22,6 +23,11 @@ impl Match {
         Match::default()
     }
 
+    /// Create a new match value spanning the given offsets.
+    pub(crate) fn from_span(start: usize, end: usize) -> Match {
+        Match { start: start, end: end }
+    }
+
     /// Return the starting byte offset of the line that matched.
     #[inline]
     pub fn start(&self) -> usize {
@@ -40,6 +46,8 @@ impl Match {
 pub struct Grep {
     re: Regex,
     required: Option<Regex>,
+    pattern_set: Option<RegexSet>,
+    prefilter: Option<Prefilter>,
     opts: Options,
 }
 
@@ -47,6 +55,7 @@ pub struct Grep {
 #[derive(Clone, Debug)]
 pub struct GrepBuilder {
     pattern: String,
+    patterns: Vec<String>,
     opts: Options,
 }
 
@@ -57,6 +66,8 @@ struct Options {
     line_terminator: u8,
     size_limit: usize,
     dfa_size_limit: usize,
+    nest_limit: u32,
+    ascii_word_boundaries: bool,
 }
 
 impl Default for Options {
@@ -67,6 +78,8 @@ impl Default for Options {
             line_terminator: b'\n',
             size_limit: 10 * (1 << 20),
             dfa_size_limit: 10 * (1 << 20),
+            nest_limit: 250,
+            ascii_word_boundaries: false,
         }
     }
 }
@@ -79,6 +92,25 @@ impl GrepBuilder {
     pub fn new(pattern: &str) -> GrepBuilder {
         GrepBuilder {
             pattern: pattern.to_string(),
+            patterns: vec![],
+            opts: Options::default(),
+        }
+    }
+
+    /// Create a new builder from several distinct patterns.
+    ///
+    /// The searcher matches a line if any of the patterns match, exactly
+    /// as if the patterns had been joined into a single alternation.
+    /// Additionally, the identity of each pattern is retained, so that
+    /// `Grep::matched_pattern` can report which of the patterns matched a
+    /// particular line.
+    pub fn new_many<I, P>(patterns: I) -> GrepBuilder
+            where I: IntoIterator<Item=P>, P: AsRef<str> {
+        let patterns: Vec<String> =
+            patterns.into_iter().map(|p| p.as_ref().to_string()).collect();
+        GrepBuilder {
+            pattern: patterns.join("|"),
+            patterns: patterns,
             opts: Options::default(),
         }
     }
@@ -113,6 +145,19 @@ impl GrepBuilder {
         self
     }
 
+    /// Whether to give `\b` and `\B` ASCII-only semantics (disabled by
+    /// default).
+    ///
+    /// Unicode word boundaries can be dramatically slower on some patterns,
+    /// since they force the regex engine off its fastest paths. When this is
+    /// enabled, every word boundary in the pattern uses the ASCII-only
+    /// definition of a word character, independent of the general Unicode
+    /// setting.
+    pub fn ascii_word_boundaries(mut self, yes: bool) -> GrepBuilder {
+        self.opts.ascii_word_boundaries = yes;
+        self
+    }
+
     /// Set the approximate size limit of the compiled regular expression.
     ///
     /// This roughly corresponds to the number of bytes occupied by a
@@ -137,6 +182,18 @@ impl GrepBuilder {
         self
     }
 
+    /// Set the nesting limit used by the regex parser.
+    ///
+    /// This limits the depth of the pattern's abstract syntax tree. If the
+    /// pattern nests more deeply than this, then a parse error is returned.
+    /// Raising it permits huge machine generated patterns, while lowering it
+    /// guards against pathological patterns that would otherwise exhaust the
+    /// stack during parsing.
+    pub fn nest_limit(mut self, limit: u32) -> GrepBuilder {
+        self.opts.nest_limit = limit;
+        self
+    }
+
     /// Create a line searcher.
     ///
     /// If there was a problem parsing or compiling the regex with the given
@@ -158,13 +215,42 @@ impl GrepBuilder {
                 }
             }
         };
+        let pattern_set =
+            if self.patterns.len() >= 2 {
+                Some(self.regex_set()?)
+            } else {
+                None
+            };
         Ok(Grep {
             re: re,
             required: required,
+            pattern_set: pattern_set,
+            prefilter: literals.to_prefilter(),
             opts: self.opts,
         })
     }
 
+    /// Builds a regex set over the individual patterns, used to report
+    /// which pattern matched a line.
+    ///
+    /// Each pattern goes through the same parsing and transformations as
+    /// the primary regex (including case folding and ASCII word boundary
+    /// rewriting), so that a line matched by the primary regex is
+    /// guaranteed to match at least one pattern in the set.
+    fn regex_set(&self) -> Result<RegexSet> {
+        let mut exprs = Vec::with_capacity(self.patterns.len());
+        for pattern in &self.patterns {
+            exprs.push(self.parse_pattern(pattern)?.to_string());
+        }
+        RegexSetBuilder::new(&exprs)
+            .multi_line(true)
+            .unicode(true)
+            .size_limit(self.opts.size_limit)
+            .dfa_size_limit(self.opts.dfa_size_limit)
+            .build()
+            .map_err(From::from)
+    }
+
     /// Creates a new regex from the given expression with the current
     /// configuration.
     fn regex(&self, expr: &Hir) -> Result<Regex> {
@@ -186,14 +272,27 @@ impl GrepBuilder {
     /// Parses the underlying pattern and ensures the pattern can never match
     /// the line terminator.
     fn parse(&self) -> Result<Hir> {
+        self.parse_pattern(&self.pattern)
+    }
+
+    /// Parses the given pattern and ensures the pattern can never match the
+    /// line terminator.
+    fn parse_pattern(&self, pattern: &str) -> Result<Hir> {
         let expr = ParserBuilder::new()
             .allow_invalid_utf8(true)
             .case_insensitive(self.is_case_insensitive()?)
             .multi_line(true)
+            .nest_limit(self.opts.nest_limit)
             .build()
-            .parse(&self.pattern)?;
+            .parse(pattern)?;
         debug!("original regex HIR pattern:\n{}", expr);
         let expr = nonl::remove(expr, self.opts.line_terminator)?;
+        let expr =
+            if self.opts.ascii_word_boundaries {
+                ascii_word_boundaries(expr)
+            } else {
+                expr
+            };
         debug!("transformed regex HIR pattern:\n{}", expr);
         Ok(expr)
     }
@@ -220,6 +319,41 @@ impl Grep {
         &self.re
     }
 
+    /// Returns the line terminator used by this searcher.
+    pub(crate) fn line_terminator(&self) -> u8 {
+        self.opts.line_terminator
+    }
+
+    /// Returns a prefilter for this searcher's pattern, if a useful one
+    /// could be derived from it.
+    ///
+    /// A prefilter is a cheap necessary condition for a match. Callers can
+    /// use it to skip over regions of the haystack that cannot contain a
+    /// match without running the regex engine over them. Candidates found
+    /// by the prefilter still need to be confirmed with a real search.
+    pub fn prefilter(&self) -> Option<&Prefilter> {
+        self.prefilter.as_ref()
+    }
+
+    /// Returns the index of the first pattern that matches the given line.
+    ///
+    /// Pattern indices correspond to the order in which patterns were given
+    /// to `GrepBuilder::new_many`. If this searcher was built from a single
+    /// pattern, then the index is always `0` for matching lines. If no
+    /// pattern matches the line, then `None` is returned.
+    pub fn matched_pattern(&self, line: &[u8]) -> Option<usize> {
+        match self.pattern_set {
+            Some(ref set) => set.matches(line).iter().next(),
+            None => {
+                if self.re.is_match(line) {
+                    Some(0)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
     /// Returns an iterator over all matches in the given buffer.
     pub fn iter<'b, 's>(&'s self, buf: &'b [u8]) -> Iter<'b, 's> {
         Iter {
@@ -353,4 +487,66 @@ mod tests {
         assert_eq!(expected.len(), got.len());
         assert_eq!(expected, got);
     }
+
+    #[test]
+    fn ascii_word_boundaries() {
+        let haystack = "caf\u{e9}foo caf\u{e9}".as_bytes();
+
+        // With Unicode semantics, é is a word character, so there is no
+        // boundary in front of `foo`.
+        let g = GrepBuilder::new(r"\bfoo\b").build().unwrap();
+        assert_eq!(g.iter(haystack).count(), 0);
+
+        let g = GrepBuilder::new(r"\bfoo\b")
+            .ascii_word_boundaries(true)
+            .build()
+            .unwrap();
+        assert_eq!(g.iter(haystack).count(), 1);
+    }
+
+    #[test]
+    fn matched_pattern() {
+        let g = GrepBuilder::new_many(&["Watson", "Sherlock"])
+            .build()
+            .unwrap();
+        assert_eq!(g.matched_pattern(b"Sherlock Holmes"), Some(1));
+        assert_eq!(g.matched_pattern(b"Dr. Watson"), Some(0));
+        assert_eq!(g.matched_pattern(b"Watson and Sherlock"), Some(0));
+        assert_eq!(g.matched_pattern(b"Moriarty"), None);
+    }
+
+    #[test]
+    fn matched_pattern_ascii_word_boundaries() {
+        // The set must apply the same word boundary rewriting as the
+        // primary regex: with Unicode semantics, there is no boundary
+        // after the accented e below, so a naive set would miss a line
+        // the searcher just reported as matching.
+        let haystack = "caf\u{e9}foo".as_bytes();
+        let g = GrepBuilder::new_many(&["xyz", r"caf\x{e9}\b"])
+            .ascii_word_boundaries(true)
+            .build()
+            .unwrap();
+        assert_eq!(g.iter(haystack).count(), 1);
+        assert_eq!(g.matched_pattern(haystack), Some(1));
+    }
+
+    #[test]
+    fn prefilter() {
+        use prefilter::Prefilter;
+
+        let g = GrepBuilder::new(r"\w+foobar\w+").build().unwrap();
+        match g.prefilter() {
+            Some(&Prefilter::Literal(ref lit)) => assert_eq!(lit, b"foobar"),
+            pre => panic!("unexpected prefilter: {:?}", pre),
+        }
+
+        let g = GrepBuilder::new(r"\w+").build().unwrap();
+        assert!(g.prefilter().is_none());
+    }
+
+    #[test]
+    fn nest_limit() {
+        assert!(GrepBuilder::new("((((a))))").nest_limit(3).build().is_err());
+        assert!(GrepBuilder::new("((((a))))").nest_limit(10).build().is_ok());
+    }
 }
@@ -0,0 +1,183 @@
+use std::io::{self, Read};
+
+use memchr::memrchr;
+
+use search::{Grep, Match};
+
+/// The size of the chunks read from the underlying reader.
+const CHUNK_SIZE: usize = 8 * (1 << 10);
+
+/// An iterator over all matching lines read from a stream.
+///
+/// This drives a `Grep` searcher over any `io::Read` implementation by
+/// reading it in chunks. Only complete lines are ever searched, so a line
+/// straddling a chunk boundary is carried over to the next chunk instead of
+/// being matched partially. The yielded match offsets are absolute byte
+/// offsets from the start of the stream.
+///
+/// `'g` refers to the lifetime of the searcher.
+pub struct StreamIter<'g, R> {
+    searcher: &'g Grep,
+    rdr: R,
+    /// Buffered but not yet fully searched data.
+    buf: Vec<u8>,
+    /// The absolute stream offset of the first byte in `buf`.
+    base: usize,
+    /// The position in `buf` at which the search resumes.
+    pos: usize,
+    /// Set once the underlying reader is exhausted.
+    eof: bool,
+    /// Set when iteration is finished, either normally or via an error.
+    done: bool,
+}
+
+impl<'g, R: Read> StreamIter<'g, R> {
+    /// Create a new streaming iterator over matching lines.
+    pub fn new(searcher: &'g Grep, rdr: R) -> StreamIter<'g, R> {
+        StreamIter {
+            searcher: searcher,
+            rdr: rdr,
+            buf: vec![],
+            base: 0,
+            pos: 0,
+            eof: false,
+            done: false,
+        }
+    }
+
+    /// Reads another chunk into the buffer and returns the number of bytes
+    /// read, where zero indicates EOF. Interrupted reads are retried.
+    fn fill(&mut self) -> io::Result<usize> {
+        let oldlen = self.buf.len();
+        self.buf.resize(oldlen + CHUNK_SIZE, 0);
+        let result = loop {
+            match self.rdr.read(&mut self.buf[oldlen..]) {
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {
+                    continue;
+                }
+                result => break result,
+            }
+        };
+        let n = *result.as_ref().unwrap_or(&0);
+        self.buf.truncate(oldlen + n);
+        result
+    }
+}
+
+impl<'g, R: Read> Iterator for StreamIter<'g, R> {
+    type Item = io::Result<Match>;
+
+    fn next(&mut self) -> Option<io::Result<Match>> {
+        if self.done {
+            return None;
+        }
+        loop {
+            // Restrict the search to complete lines, except at EOF, where
+            // the remainder is treated as the final line.
+            let end =
+                if self.eof {
+                    self.buf.len()
+                } else {
+                    let line_term = self.searcher.line_terminator();
+                    memrchr(line_term, &self.buf).map_or(0, |i| i + 1)
+                };
+            let mut mat = Match::default();
+            if self.searcher.read_match(&mut mat, &self.buf[..end], self.pos) {
+                self.pos = mat.end();
+                return Some(Ok(Match::from_span(
+                    self.base + mat.start(),
+                    self.base + mat.end(),
+                )));
+            }
+            if self.eof {
+                self.done = true;
+                return None;
+            }
+            // Drop the searched region and carry over any incomplete line.
+            self.buf.drain(..end);
+            self.base += end;
+            self.pos = 0;
+            match self.fill() {
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+                Ok(0) => self.eof = true,
+                Ok(_) => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp;
+    use std::io;
+
+    use search::GrepBuilder;
+    use super::StreamIter;
+
+    static SHERLOCK: &'static [u8] = include_bytes!("./data/sherlock.txt");
+
+    /// A reader that yields its contents one tiny chunk at a time, to
+    /// exercise the boundary handling.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl<'a> io::Read for Trickle<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = cmp::min(7, cmp::min(buf.len(), self.0.len()));
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn stream_matches_buffer() {
+        let g = GrepBuilder::new("Sherlock Holmes").build().unwrap();
+        let expected: Vec<_> = g.iter(SHERLOCK).collect();
+        let got: Vec<_> = StreamIter::new(&g, Trickle(SHERLOCK))
+            .map(|result| result.unwrap())
+            .collect();
+        assert!(!expected.is_empty());
+        assert_eq!(expected, got);
+    }
+
+    /// A reader that yields `Interrupted` before every successful read.
+    struct Interrupting<'a> {
+        inner: Trickle<'a>,
+        interrupt: bool,
+    }
+
+    impl<'a> io::Read for Interrupting<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.interrupt = !self.interrupt;
+            if self.interrupt {
+                Err(io::Error::new(io::ErrorKind::Interrupted, "EINTR"))
+            } else {
+                self.inner.read(buf)
+            }
+        }
+    }
+
+    #[test]
+    fn stream_retries_interrupted_reads() {
+        let g = GrepBuilder::new("Sherlock Holmes").build().unwrap();
+        let expected: Vec<_> = g.iter(SHERLOCK).collect();
+        let rdr = Interrupting { inner: Trickle(SHERLOCK), interrupt: false };
+        let got: Vec<_> = StreamIter::new(&g, rdr)
+            .map(|result| result.unwrap())
+            .collect();
+        assert_eq!(expected, got);
+    }
+
+    #[test]
+    fn stream_no_trailing_line_terminator() {
+        let g = GrepBuilder::new("bar").build().unwrap();
+        let got: Vec<_> = StreamIter::new(&g, Trickle(b"foo\nbar"))
+            .map(|result| result.unwrap())
+            .collect();
+        assert_eq!(got.len(), 1);
+        assert_eq!((got[0].start(), got[0].end()), (4, 7));
+    }
+}
@@ -42,6 +42,43 @@ pub fn strip_unicode_word_boundaries(expr: &Hir) -> Option<Hir> {
     }
 }
 
+/// Returns a new expression in which every Unicode word boundary has been
+/// replaced with an ASCII word boundary.
+///
+/// ASCII word boundaries are considerably cheaper to execute, since a
+/// Unicode word boundary forces the regex engine off its fastest paths, and
+/// ASCII semantics are frequently what is wanted when searching source code.
+pub fn ascii_word_boundaries(expr: Hir) -> Hir {
+    match expr.into_kind() {
+        HirKind::Empty => Hir::empty(),
+        HirKind::Literal(x) => Hir::literal(x),
+        HirKind::Class(x) => Hir::class(x),
+        HirKind::Anchor(x) => Hir::anchor(x),
+        HirKind::WordBoundary(hir::WordBoundary::Unicode) => {
+            Hir::word_boundary(hir::WordBoundary::Ascii)
+        }
+        HirKind::WordBoundary(hir::WordBoundary::UnicodeNegate) => {
+            Hir::word_boundary(hir::WordBoundary::AsciiNegate)
+        }
+        HirKind::WordBoundary(x) => Hir::word_boundary(x),
+        HirKind::Repetition(mut x) => {
+            x.hir = Box::new(ascii_word_boundaries(*x.hir));
+            Hir::repetition(x)
+        }
+        HirKind::Group(mut x) => {
+            x.hir = Box::new(ascii_word_boundaries(*x.hir));
+            Hir::group(x)
+        }
+        HirKind::Concat(xs) => {
+            Hir::concat(xs.into_iter().map(ascii_word_boundaries).collect())
+        }
+        HirKind::Alternation(xs) => {
+            Hir::alternation(
+                xs.into_iter().map(ascii_word_boundaries).collect())
+        }
+    }
+}
+
 /// Returns true if the given expression is a Unicode word boundary.
 fn is_unicode_word_boundary(expr: &Hir) -> bool {
     match *expr.kind() {
@@ -0,0 +1,422 @@
+/*!
+The cache module provides a persistent cache of ignore match outcomes,
+which is used by the parallel walker to avoid re-evaluating ignore rules
+for directories that have not changed between runs.
+
+The cache maps each visited directory to its modification time and the
+ignore match outcome of every entry in it. A directory whose modification
+time still matches on a later walk reuses the recorded outcomes instead of
+running the ignore matchers again. A directory whose modification time
+changed invalidates itself and everything beneath it, since a new or
+removed ignore file in it may change the outcomes of its descendants.
+Additionally, the modification times of all ignore files seen during a walk
+are recorded, and the entire cache is discarded if any of them changed,
+along with a fingerprint of the walker configuration.
+
+The cache is strictly best effort: any problem reading, parsing or writing
+the cache file is logged and otherwise ignored.
+*/
+use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::UNIX_EPOCH;
+
+/// The modification time of a file, as a duration since the Unix epoch.
+type ModTime = (u64, u32);
+
+/// A persistent cache of ignore match outcomes, keyed by directory path
+/// and modification time.
+///
+/// A cache is shared by all workers of a parallel walk. It is loaded from
+/// its file when the walk starts and saved back when the walk finishes.
+#[derive(Debug)]
+pub struct TraversalCache {
+    /// The path of the cache file.
+    path: PathBuf,
+    /// A fingerprint of the walker configuration. Outcomes recorded by a
+    /// differently configured walker are not reused.
+    config: u64,
+    /// The names of the ignore files that the walker reads in each
+    /// directory, used to record their modification times.
+    ignore_names: Vec<OsString>,
+    /// The cached state, shared by all workers.
+    inner: Mutex<Inner>,
+}
+
+/// The mutable state of a traversal cache.
+#[derive(Debug)]
+struct Inner {
+    /// The cached directories, keyed by the path used to visit them.
+    dirs: HashMap<PathBuf, CachedDir>,
+    /// The modification times of all ignore files seen during the walk.
+    ignore_files: HashMap<PathBuf, ModTime>,
+    /// Whether the state has changed since it was loaded.
+    dirty: bool,
+}
+
+/// The cached state of a single directory.
+#[derive(Debug)]
+struct CachedDir {
+    /// The modification time of the directory when its entries were
+    /// recorded.
+    mtime: ModTime,
+    /// For every entry in the directory, whether the ignore rules said to
+    /// skip it.
+    entries: Arc<HashMap<OsString, bool>>,
+}
+
+impl TraversalCache {
+    /// Loads a traversal cache from the file at the given path.
+    ///
+    /// This always succeeds. If the file does not exist, cannot be parsed,
+    /// was written by a differently configured walker or any ignore file
+    /// recorded in it has changed, then the returned cache starts out
+    /// empty.
+    pub fn load(
+        path: &Path,
+        config: u64,
+        ignore_names: Vec<OsString>,
+    ) -> TraversalCache {
+        let inner = match read_cache_file(path, config) {
+            Ok(inner) => inner,
+            Err(err) => {
+                debug!("ignoring traversal cache {}: {}",
+                       path.display(), err);
+                Inner {
+                    dirs: HashMap::new(),
+                    ignore_files: HashMap::new(),
+                    dirty: false,
+                }
+            }
+        };
+        TraversalCache {
+            path: path.to_path_buf(),
+            config: config,
+            ignore_names: ignore_names,
+            inner: Mutex::new(inner),
+        }
+    }
+
+    /// Looks up the cached entries of the given directory.
+    ///
+    /// If the directory is cached with the given modification time, then
+    /// the recorded ignore match outcomes of its entries are returned.
+    /// Otherwise, the directory and everything cached beneath it are
+    /// invalidated, since a changed directory may contain a new or removed
+    /// ignore file that affects its descendants.
+    pub fn lookup(
+        &self,
+        dir: &Path,
+        mtime: ModTime,
+    ) -> Option<Arc<HashMap<OsString, bool>>> {
+        let mut inner = self.inner.lock().unwrap();
+        let hit = match inner.dirs.get(dir) {
+            None => return None,
+            Some(cached) => {
+                if cached.mtime == mtime {
+                    Some(cached.entries.clone())
+                } else {
+                    None
+                }
+            }
+        };
+        if hit.is_none() {
+            inner.dirs.retain(|path, _| !path.starts_with(dir));
+            inner.dirty = true;
+        }
+        hit
+    }
+
+    /// Records the ignore match outcomes of every entry in the given
+    /// directory, which had the given modification time when it was read.
+    pub fn record_dir(
+        &self,
+        dir: &Path,
+        mtime: ModTime,
+        entries: HashMap<OsString, bool>,
+    ) {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(cached) = inner.dirs.get(dir) {
+            if cached.mtime == mtime && *cached.entries == entries {
+                return;
+            }
+        }
+        inner.dirs.insert(dir.to_path_buf(), CachedDir {
+            mtime: mtime,
+            entries: Arc::new(entries),
+        });
+        inner.dirty = true;
+    }
+
+    /// Records the modification times of the ignore files in the given
+    /// directory, so that an edit to any of them discards the cache on the
+    /// next load.
+    pub fn record_ignore_files(&self, dir: &Path) {
+        for name in &self.ignore_names {
+            let path = dir.join(name);
+            let mtime = match fs::metadata(&path) {
+                Err(_) => continue,
+                Ok(md) => match modified(&md) {
+                    None => continue,
+                    Some(mtime) => mtime,
+                },
+            };
+            let mut inner = self.inner.lock().unwrap();
+            if inner.ignore_files.get(&path) != Some(&mtime) {
+                inner.ignore_files.insert(path, mtime);
+                inner.dirty = true;
+            }
+        }
+    }
+
+    /// Writes the cache back to its file if it has changed.
+    pub fn save(&self) -> io::Result<()> {
+        let inner = self.inner.lock().unwrap();
+        if !inner.dirty {
+            return Ok(());
+        }
+        let mut wtr = BufWriter::new(File::create(&self.path)?);
+        writeln!(wtr, "ignore-cache 1")?;
+        writeln!(wtr, "config {:016x}", self.config)?;
+        for (path, mtime) in &inner.ignore_files {
+            let path = match os_to_bytes(path.as_os_str()) {
+                None => continue,
+                Some(path) => path,
+            };
+            writeln!(
+                wtr, "ignorefile {} {} {}",
+                mtime.0, mtime.1, escape(&path))?;
+        }
+        for (dir, cached) in &inner.dirs {
+            let dir = match os_to_bytes(dir.as_os_str()) {
+                None => continue,
+                Some(dir) => dir,
+            };
+            writeln!(
+                wtr, "dir {} {} {}",
+                cached.mtime.0, cached.mtime.1, escape(&dir))?;
+            for (name, &skip) in cached.entries.iter() {
+                let name = match os_to_bytes(name) {
+                    None => continue,
+                    Some(name) => name,
+                };
+                let skip = if skip { 1 } else { 0 };
+                writeln!(wtr, "entry {} {}", skip, escape(&name))?;
+            }
+        }
+        wtr.flush()
+    }
+}
+
+/// Returns the modification time of the given metadata as a duration since
+/// the Unix epoch, or `None` if it is unavailable or precedes the epoch.
+pub fn modified(md: &fs::Metadata) -> Option<ModTime> {
+    md.modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|dur| (dur.as_secs(), dur.subsec_nanos()))
+}
+
+/// Reads and validates the cache file at the given path.
+fn read_cache_file(path: &Path, config: u64) -> Result<Inner, String> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => {
+            return Ok(Inner {
+                dirs: HashMap::new(),
+                ignore_files: HashMap::new(),
+                dirty: false,
+            });
+        }
+        Err(err) => return Err(err.to_string()),
+    };
+    let mut lines = BufReader::new(file).lines();
+    match lines.next() {
+        Some(Ok(ref line)) if line == "ignore-cache 1" => {}
+        _ => return Err("unsupported cache format".to_string()),
+    }
+    match lines.next() {
+        Some(Ok(ref line))
+            if *line == format!("config {:016x}", config) => {}
+        _ => return Err("walker configuration changed".to_string()),
+    }
+    let mut inner = Inner {
+        dirs: HashMap::new(),
+        ignore_files: HashMap::new(),
+        dirty: false,
+    };
+    let mut cur: Option<(PathBuf, CachedDir)> = None;
+    let mut entries = HashMap::new();
+    for line in lines {
+        let line = match line {
+            Ok(line) => line,
+            Err(err) => return Err(err.to_string()),
+        };
+        let mut fields = line.splitn(2, ' ');
+        let tag = fields.next().unwrap_or("");
+        let rest = fields.next().unwrap_or("");
+        match tag {
+            "ignorefile" => {
+                let (mtime, path) = parse_timed_path(rest)?;
+                inner.ignore_files.insert(path, mtime);
+            }
+            "dir" => {
+                if let Some((dir, mut cached)) = cur.take() {
+                    cached.entries = Arc::new(entries);
+                    entries = HashMap::new();
+                    inner.dirs.insert(dir, cached);
+                }
+                let (mtime, dir) = parse_timed_path(rest)?;
+                cur = Some((dir, CachedDir {
+                    mtime: mtime,
+                    entries: Arc::new(HashMap::new()),
+                }));
+            }
+            "entry" => {
+                if cur.is_none() {
+                    return Err("entry without a directory".to_string());
+                }
+                let mut fields = rest.splitn(2, ' ');
+                let skip = match fields.next() {
+                    Some("0") => false,
+                    Some("1") => true,
+                    _ => return Err("invalid entry line".to_string()),
+                };
+                let name = fields
+                    .next()
+                    .and_then(|name| unescape(name))
+                    .and_then(bytes_to_os);
+                match name {
+                    None => return Err("invalid entry line".to_string()),
+                    Some(name) => {
+                        entries.insert(name, skip);
+                    }
+                }
+            }
+            _ => return Err(format!("invalid cache line: {}", line)),
+        }
+    }
+    if let Some((dir, mut cached)) = cur.take() {
+        cached.entries = Arc::new(entries);
+        inner.dirs.insert(dir, cached);
+    }
+    // An edit to any previously seen ignore file may change outcomes
+    // anywhere in the tree, so it discards the cache in its entirety.
+    for (path, &mtime) in &inner.ignore_files {
+        let current = fs::metadata(path).ok().and_then(|md| modified(&md));
+        if current != Some(mtime) {
+            return Err(format!("ignore file {} changed", path.display()));
+        }
+    }
+    Ok(inner)
+}
+
+/// Parses `<secs> <nanos> <escaped path>`, as used by the `ignorefile` and
+/// `dir` lines of the cache file.
+fn parse_timed_path(s: &str) -> Result<(ModTime, PathBuf), String> {
+    let mut fields = s.splitn(3, ' ');
+    let secs = fields
+        .next()
+        .and_then(|f| f.parse::<u64>().ok());
+    let nanos = fields
+        .next()
+        .and_then(|f| f.parse::<u32>().ok());
+    let path = fields
+        .next()
+        .and_then(|f| unescape(f))
+        .and_then(bytes_to_os)
+        .map(PathBuf::from);
+    match (secs, nanos, path) {
+        (Some(secs), Some(nanos), Some(path)) => Ok(((secs, nanos), path)),
+        _ => Err("invalid cache line".to_string()),
+    }
+}
+
+/// Escapes arbitrary bytes into a printable ASCII string with no spaces,
+/// so that paths and file names survive the line oriented cache format.
+fn escape(bytes: &[u8]) -> String {
+    let mut s = String::with_capacity(bytes.len());
+    for &b in bytes {
+        if b == b'\\' {
+            s.push_str("\\\\");
+        } else if b > 0x20 && b < 0x7f {
+            s.push(b as char);
+        } else {
+            s.push_str(&format!("\\x{:02x}", b));
+        }
+    }
+    s
+}
+
+/// Reverses `escape`. Returns `None` if the string is not a valid escape.
+fn unescape(s: &str) -> Option<Vec<u8>> {
+    let mut bytes = vec![];
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            if !(c > '\x20' && c < '\x7f') {
+                return None;
+            }
+            bytes.push(c as u8);
+            continue;
+        }
+        match chars.next() {
+            Some('\\') => bytes.push(b'\\'),
+            Some('x') => {
+                let hi = chars.next().and_then(|c| c.to_digit(16));
+                let lo = chars.next().and_then(|c| c.to_digit(16));
+                match (hi, lo) {
+                    (Some(hi), Some(lo)) => bytes.push((hi * 16 + lo) as u8),
+                    _ => return None,
+                }
+            }
+            _ => return None,
+        }
+    }
+    Some(bytes)
+}
+
+#[cfg(unix)]
+fn os_to_bytes(s: &OsStr) -> Option<Vec<u8>> {
+    use std::os::unix::ffi::OsStrExt;
+    Some(s.as_bytes().to_vec())
+}
+
+#[cfg(not(unix))]
+fn os_to_bytes(s: &OsStr) -> Option<Vec<u8>> {
+    s.to_str().map(|s| s.as_bytes().to_vec())
+}
+
+#[cfg(unix)]
+fn bytes_to_os(bytes: Vec<u8>) -> Option<OsString> {
+    use std::os::unix::ffi::OsStringExt;
+    Some(OsString::from_vec(bytes))
+}
+
+#[cfg(not(unix))]
+fn bytes_to_os(bytes: Vec<u8>) -> Option<OsString> {
+    String::from_utf8(bytes).ok().map(OsString::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{escape, unescape};
+
+    #[test]
+    fn escape_round_trip() {
+        let all: Vec<u8> = (0..256).map(|b| b as u8).collect();
+        let escaped = escape(&all);
+        assert!(escaped.chars().all(|c| c > '\x20' && c < '\x7f'));
+        assert_eq!(unescape(&escaped), Some(all));
+    }
+
+    #[test]
+    fn unescape_rejects_garbage() {
+        assert_eq!(unescape("a b"), None);
+        assert_eq!(unescape("a\\"), None);
+        assert_eq!(unescape("\\xzz"), None);
+    }
+}
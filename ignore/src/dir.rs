@@ -15,10 +15,13 @@
 
 use std::collections::HashMap;
 use std::ffi::{OsString, OsStr};
+use std::fs::File;
+use std::io::Read;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, RwLock};
 
 use gitignore::{self, Gitignore, GitignoreBuilder};
+use hgignore::{self, Hgignore};
 use pathutil::{is_hidden, strip_prefix};
 use overrides::{self, Override};
 use types::{self, Types};
@@ -35,7 +38,9 @@ pub struct IgnoreMatch<'a>(IgnoreMatchInner<'a>);
 enum IgnoreMatchInner<'a> {
     Override(overrides::Glob<'a>),
     Gitignore(&'a gitignore::Glob),
+    Hgignore(&'a hgignore::Pattern),
     Types(types::Glob<'a>),
+    SparseCheckout,
     Hidden,
 }
 
@@ -48,13 +53,117 @@ impl<'a> IgnoreMatch<'a> {
         IgnoreMatch(IgnoreMatchInner::Gitignore(x))
     }
 
+    fn hgignore(x: &'a hgignore::Pattern) -> IgnoreMatch<'a> {
+        IgnoreMatch(IgnoreMatchInner::Hgignore(x))
+    }
+
     fn types(x: types::Glob<'a>) -> IgnoreMatch<'a> {
         IgnoreMatch(IgnoreMatchInner::Types(x))
     }
 
+    fn sparse() -> IgnoreMatch<'static> {
+        IgnoreMatch(IgnoreMatchInner::SparseCheckout)
+    }
+
     fn hidden() -> IgnoreMatch<'static> {
         IgnoreMatch(IgnoreMatchInner::Hidden)
     }
+
+    /// The path of the file that the matching rule was read from, if the
+    /// rule came from a file.
+    pub fn from(&self) -> Option<&Path> {
+        match self.0 {
+            IgnoreMatchInner::Override(ref glob) => glob.from(),
+            IgnoreMatchInner::Gitignore(glob) => glob.from(),
+            IgnoreMatchInner::Hgignore(pat) => pat.from(),
+            _ => None,
+        }
+    }
+
+    /// The line number in the file that the matching rule was read from,
+    /// starting at 1, if the rule came from a file.
+    pub fn line(&self) -> Option<u64> {
+        match self.0 {
+            IgnoreMatchInner::Override(ref glob) => glob.line(),
+            IgnoreMatchInner::Gitignore(glob) => glob.line(),
+            IgnoreMatchInner::Hgignore(pat) => pat.line(),
+            _ => None,
+        }
+    }
+
+    /// The original text of the matching rule, if there is one.
+    pub fn pattern(&self) -> Option<&str> {
+        match self.0 {
+            IgnoreMatchInner::Override(ref glob) => glob.original(),
+            IgnoreMatchInner::Gitignore(glob) => Some(glob.original()),
+            IgnoreMatchInner::Hgignore(pat) => Some(pat.original()),
+            _ => None,
+        }
+    }
+}
+
+/// A single ignore rule that matched a path, as reported by
+/// `WalkBuilder::explain`.
+///
+/// A decision records which kind of matcher the rule came from, the file it
+/// was read from (if any), the original rule text (if any) and whether the
+/// rule ignores or whitelists the path.
+#[derive(Clone, Debug)]
+pub struct IgnoreDecision {
+    source: &'static str,
+    from: Option<PathBuf>,
+    line: Option<u64>,
+    pattern: Option<String>,
+    is_whitelist: bool,
+}
+
+impl IgnoreDecision {
+    /// The kind of matcher that produced this decision, e.g., `override`,
+    /// `gitignore`, `types` or `hidden`.
+    pub fn source(&self) -> &str {
+        self.source
+    }
+
+    /// The file that the matching rule was read from, if the rule came from
+    /// a file.
+    pub fn from(&self) -> Option<&Path> {
+        self.from.as_ref().map(|p| &**p)
+    }
+
+    /// The line number in the file that the matching rule was read from,
+    /// starting at 1, if the rule came from a file.
+    pub fn line(&self) -> Option<u64> {
+        self.line
+    }
+
+    /// The original text of the matching rule, if there is one.
+    pub fn pattern(&self) -> Option<&str> {
+        self.pattern.as_ref().map(|p| &**p)
+    }
+
+    /// Whether the rule whitelists the path instead of ignoring it.
+    pub fn is_whitelist(&self) -> bool {
+        self.is_whitelist
+    }
+}
+
+/// Records a decision for the given source if the match isn't `None`.
+fn push_decision(
+    decisions: &mut Vec<IgnoreDecision>,
+    source: &'static str,
+    m: &Match<IgnoreMatch>,
+) {
+    let inner = match m.inner() {
+        None => return,
+        Some(inner) => inner,
+    };
+    decisions.push(IgnoreDecision {
+        source: source,
+        from: inner.from().map(|p| p.to_path_buf()),
+        line: inner.line(),
+        pattern: inner.pattern().map(|p| p.to_string()),
+        is_whitelist: m.is_whitelist(),
+    });
 }
 
 /// Options for the ignore matcher, shared between the matcher itself and the
@@ -73,6 +182,42 @@ struct IgnoreOptions {
     git_ignore: bool,
     /// Whether to read .git/info/exclude files.
     git_exclude: bool,
+    /// Whether to read .hgignore files.
+    hg_ignore: bool,
+    /// Whether to match ignore globs case insensitively.
+    ignore_case_insensitive: bool,
+}
+
+/// The precedence of a custom ignore file relative to the standard ignore
+/// files.
+///
+/// When several ignore files contain rules matching the same path, the rule
+/// from the file with the highest precedence wins, whether it ignores or
+/// whitelists the path.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CustomIgnorePrecedence {
+    /// Rules outrank both `.ignore` and `.gitignore` rules. This is the
+    /// precedence used by `add_custom_ignore_filename`.
+    BeforeIgnore,
+    /// Rules rank below `.ignore` rules but above `.gitignore` rules.
+    AfterIgnore,
+    /// Rules rank below both `.ignore` and `.gitignore` rules (but above
+    /// `.git/info/exclude` and global gitignore rules).
+    AfterGitignore,
+}
+
+/// A custom ignore file name registered by the caller, along with where its
+/// rules slot into the precedence order and whether the file is read in
+/// every directory or only in traversal roots.
+#[derive(Clone, Debug)]
+struct CustomIgnore {
+    /// The name of the ignore file, e.g., `.toolignore`.
+    file_name: OsString,
+    /// Where rules from this file rank relative to the standard files.
+    precedence: CustomIgnorePrecedence,
+    /// When true, the file is read in every directory, like `.gitignore`.
+    /// When false, it is only read in the root of a traversal.
+    hierarchical: bool,
 }
 
 /// Ignore is a matcher useful for recursively walking one or more directories.
@@ -107,19 +252,29 @@ struct IgnoreInner {
     /// Explicit global ignore matchers specified by the caller.
     explicit_ignores: Arc<Vec<Gitignore>>,
     /// Ignore files used in addition to `.ignore`
-    custom_ignore_filenames: Arc<Vec<OsString>>,
-    /// The matcher for custom ignore files
+    custom_ignores: Arc<Vec<CustomIgnore>>,
+    /// The matcher for custom ignore files ranking above `.ignore`.
     custom_ignore_matcher: Gitignore,
+    /// The matcher for custom ignore files ranking below `.ignore`.
+    custom_after_ignore_matcher: Gitignore,
+    /// The matcher for custom ignore files ranking below `.gitignore`.
+    custom_after_gitignore_matcher: Gitignore,
     /// The matcher for .ignore files.
     ignore_matcher: Gitignore,
     /// A global gitignore matcher, usually from $XDG_CONFIG_HOME/git/ignore.
     git_global_matcher: Arc<Gitignore>,
     /// The matcher for .gitignore files.
     git_ignore_matcher: Gitignore,
-    /// Special matcher for `.git/info/exclude` files.
+    /// Special matcher for `$GIT_DIR/info/exclude` files.
     git_exclude_matcher: Gitignore,
+    /// Special matcher for `$GIT_DIR/info/sparse-checkout` files.
+    git_sparse_matcher: Gitignore,
+    /// The matcher for .hgignore files.
+    hg_ignore_matcher: Hgignore,
     /// Whether this directory contains a .git sub-directory.
     has_git: bool,
+    /// Whether this directory contains a .hg sub-directory.
+    has_hg: bool,
     /// Ignore config.
     opts: IgnoreOptions,
 }
@@ -145,6 +300,28 @@ impl Ignore {
         self.0.parent.clone()
     }
 
+    /// Returns the names of the ignore files that this matcher reads in
+    /// the directories it visits, given its current configuration.
+    pub fn ignore_file_names(&self) -> Vec<OsString> {
+        let mut names = vec![];
+        for ci in self.0.custom_ignores.iter() {
+            names.push(ci.file_name.clone());
+        }
+        if self.0.opts.ignore {
+            names.push(OsString::from(".ignore"));
+        }
+        if self.0.opts.git_ignore {
+            names.push(OsString::from(".gitignore"));
+        }
+        if self.0.opts.git_exclude {
+            names.push(OsString::from(".git/info/exclude"));
+        }
+        if self.0.opts.hg_ignore {
+            names.push(OsString::from(".hgignore"));
+        }
+        names
+    }
+
     /// Create a new `Ignore` matcher with the parent directories of `dir`.
     ///
     /// Note that this can only be called on an `Ignore` matcher with no
@@ -157,6 +334,7 @@ impl Ignore {
             && !self.0.opts.git_ignore
             && !self.0.opts.git_exclude
             && !self.0.opts.git_global
+            && !self.0.opts.hg_ignore
         {
             // If we never need info from parent directories, then don't do
             // anything.
@@ -190,11 +368,12 @@ impl Ignore {
                 ig = prebuilt.clone();
                 continue;
             }
-            let (mut igtmp, err) = ig.add_child_path(parent);
+            let (mut igtmp, err) = ig.add_child_path(parent, false);
             errs.maybe_push(err);
             igtmp.is_absolute_parent = true;
             igtmp.absolute_base = Some(absolute_base.clone());
             igtmp.has_git = parent.join(".git").exists();
+            igtmp.has_hg = parent.join(".hg").exists();
             ig = Ignore(Arc::new(igtmp));
             compiled.insert(parent.as_os_str().to_os_string(), ig.clone());
         }
@@ -213,27 +392,42 @@ impl Ignore {
         &self,
         dir: P,
     ) -> (Ignore, Option<Error>) {
-        let (ig, err) = self.add_child_path(dir.as_ref());
+        // A child of the root matcher (or of the deepest absolute parent
+        // added by add_parents) is the root of a traversal, which is where
+        // root-only custom ignore files apply.
+        let is_walk_root = self.is_root() || self.0.is_absolute_parent;
+        let (ig, err) = self.add_child_path(dir.as_ref(), is_walk_root);
         (Ignore(Arc::new(ig)), err)
     }
 
     /// Like add_child, but takes a full path and returns an IgnoreInner.
-    fn add_child_path(&self, dir: &Path) -> (IgnoreInner, Option<Error>) {
+    ///
+    /// `is_walk_root` should be true if `dir` is the root of a traversal,
+    /// which determines whether root-only custom ignore files are read.
+    fn add_child_path(
+        &self,
+        dir: &Path,
+        is_walk_root: bool,
+    ) -> (IgnoreInner, Option<Error>) {
         let mut errs = PartialErrorBuilder::default();
-        let custom_ig_matcher =
-            if self.0.custom_ignore_filenames.is_empty() {
-                Gitignore::empty()
-            } else {
-                let (m, err) =
-                    create_gitignore(&dir, &self.0.custom_ignore_filenames);
-                errs.maybe_push(err);
-                m
-            };
+        let custom_ig_matcher = self.custom_ignore_matcher(
+            dir, CustomIgnorePrecedence::BeforeIgnore, is_walk_root,
+            &mut errs);
+        let custom_after_ig_matcher = self.custom_ignore_matcher(
+            dir, CustomIgnorePrecedence::AfterIgnore, is_walk_root,
+            &mut errs);
+        let custom_after_gi_matcher = self.custom_ignore_matcher(
+            dir, CustomIgnorePrecedence::AfterGitignore, is_walk_root,
+            &mut errs);
         let ig_matcher =
             if !self.0.opts.ignore {
                 Gitignore::empty()
             } else {
-                let (m, err) = create_gitignore(&dir, &[".ignore"]);
+                let (m, err) = create_gitignore(
+                    &dir,
+                    &[".ignore"],
+                    self.0.opts.ignore_case_insensitive,
+                );
                 errs.maybe_push(err);
                 m
             };
@@ -241,15 +435,69 @@ impl Ignore {
             if !self.0.opts.git_ignore {
                 Gitignore::empty()
             } else {
-                let (m, err) = create_gitignore(&dir, &[".gitignore"]);
+                let (m, err) = create_gitignore(
+                    &dir,
+                    &[".gitignore"],
+                    self.0.opts.ignore_case_insensitive,
+                );
                 errs.maybe_push(err);
                 m
             };
-        let gi_exclude_matcher =
-            if !self.0.opts.git_exclude {
+        let git_dir =
+            if self.0.opts.git_exclude {
+                resolve_git_dir(dir)
+            } else {
+                None
+            };
+        let gi_exclude_matcher = match git_dir {
+            None => Gitignore::empty(),
+            Some(ref git_dir) => {
+                let mut builder = GitignoreBuilder::new(dir);
+                if let Err(err) = builder.case_insensitive(
+                    self.0.opts.ignore_case_insensitive,
+                ) {
+                    errs.push(err);
+                }
+                // In linked worktrees, the shared info/exclude file lives in
+                // the git directory of the main worktree.
+                if let Some(common) = git_common_dir(git_dir) {
+                    errs.maybe_push_ignore_io(
+                        builder.add(common.join("info/exclude")));
+                }
+                errs.maybe_push_ignore_io(
+                    builder.add(git_dir.join("info/exclude")));
+                match builder.build() {
+                    Ok(gi) => gi,
+                    Err(err) => {
+                        errs.push(err);
+                        Gitignore::empty()
+                    }
+                }
+            }
+        };
+        let git_sparse_matcher = match git_dir {
+            None => Gitignore::empty(),
+            Some(ref git_dir) if !sparse_checkout_enabled(git_dir) => {
                 Gitignore::empty()
+            }
+            Some(ref git_dir) => {
+                let mut builder = GitignoreBuilder::new(dir);
+                errs.maybe_push_ignore_io(
+                    builder.add(git_dir.join("info/sparse-checkout")));
+                match builder.build() {
+                    Ok(gi) => gi,
+                    Err(err) => {
+                        errs.push(err);
+                        Gitignore::empty()
+                    }
+                }
+            }
+        };
+        let hgi_matcher =
+            if !self.0.opts.hg_ignore {
+                Hgignore::empty()
             } else {
-                let (m, err) = create_gitignore(&dir, &[".git/info/exclude"]);
+                let (m, err) = Hgignore::new(dir.join(".hgignore"));
                 errs.maybe_push(err);
                 m
             };
@@ -262,26 +510,57 @@ impl Ignore {
             is_absolute_parent: false,
             absolute_base: self.0.absolute_base.clone(),
             explicit_ignores: self.0.explicit_ignores.clone(),
-            custom_ignore_filenames: self.0.custom_ignore_filenames.clone(),
+            custom_ignores: self.0.custom_ignores.clone(),
             custom_ignore_matcher: custom_ig_matcher,
+            custom_after_ignore_matcher: custom_after_ig_matcher,
+            custom_after_gitignore_matcher: custom_after_gi_matcher,
             ignore_matcher: ig_matcher,
             git_global_matcher: self.0.git_global_matcher.clone(),
             git_ignore_matcher: gi_matcher,
             git_exclude_matcher: gi_exclude_matcher,
+            git_sparse_matcher: git_sparse_matcher,
+            hg_ignore_matcher: hgi_matcher,
             has_git: dir.join(".git").exists(),
+            has_hg: dir.join(".hg").exists(),
             opts: self.0.opts,
         };
         (ig, errs.into_error_option())
     }
 
+    /// Builds the matcher for the custom ignore files with the given
+    /// precedence in the given directory. Root-only ignore files are only
+    /// read when `is_walk_root` is true.
+    fn custom_ignore_matcher(
+        &self,
+        dir: &Path,
+        precedence: CustomIgnorePrecedence,
+        is_walk_root: bool,
+        errs: &mut PartialErrorBuilder,
+    ) -> Gitignore {
+        let names: Vec<&OsStr> = self.0.custom_ignores
+            .iter()
+            .filter(|ci| ci.precedence == precedence)
+            .filter(|ci| ci.hierarchical || is_walk_root)
+            .map(|ci| ci.file_name.as_os_str())
+            .collect();
+        if names.is_empty() {
+            return Gitignore::empty();
+        }
+        let (m, err) = create_gitignore(
+            &dir, &names, self.0.opts.ignore_case_insensitive);
+        errs.maybe_push(err);
+        m
+    }
+
     /// Returns true if at least one type of ignore rule should be matched.
     fn has_any_ignore_rules(&self) -> bool {
         let opts = self.0.opts;
-        let has_custom_ignore_files = !self.0.custom_ignore_filenames.is_empty();
+        let has_custom_ignore_files = !self.0.custom_ignores.is_empty();
         let has_explicit_ignores = !self.0.explicit_ignores.is_empty();
 
         opts.ignore || opts.git_global || opts.git_ignore
-                    || opts.git_exclude || has_custom_ignore_files
+                    || opts.git_exclude || opts.hg_ignore
+                    || has_custom_ignore_files
                     || has_explicit_ignores
     }
 
@@ -343,16 +622,201 @@ impl Ignore {
         path: &Path,
         is_dir: bool,
     ) -> Match<IgnoreMatch<'a>> {
-        let (mut m_custom_ignore, mut m_ignore, mut m_gi, mut m_gi_exclude, mut m_explicit) =
-            (Match::None, Match::None, Match::None, Match::None, Match::None);
+        let (mut m_custom_ignore, mut m_custom_after_ignore, mut m_custom_after_gi, mut m_ignore, mut m_gi, mut m_gi_exclude, mut m_sparse, mut m_hgi, mut m_explicit) =
+            (Match::None, Match::None, Match::None, Match::None, Match::None, Match::None, Match::None, Match::None, Match::None);
+        let any_git = self.parents().any(|ig| ig.0.has_git);
+        let any_hg = self.parents().any(|ig| ig.0.has_hg);
+        let mut saw_git = false;
+        let mut saw_hg = false;
+        for ig in self.parents().take_while(|ig| !ig.0.is_absolute_parent) {
+            if m_custom_ignore.is_none() {
+                m_custom_ignore =
+                    ig.0.custom_ignore_matcher.matched(path, is_dir)
+                      .map(IgnoreMatch::gitignore);
+            }
+            if m_custom_after_ignore.is_none() {
+                m_custom_after_ignore =
+                    ig.0.custom_after_ignore_matcher.matched(path, is_dir)
+                      .map(IgnoreMatch::gitignore);
+            }
+            if m_custom_after_gi.is_none() {
+                m_custom_after_gi =
+                    ig.0.custom_after_gitignore_matcher.matched(path, is_dir)
+                      .map(IgnoreMatch::gitignore);
+            }
+            if m_ignore.is_none() {
+                m_ignore =
+                    ig.0.ignore_matcher.matched(path, is_dir)
+                      .map(IgnoreMatch::gitignore);
+            }
+            if any_git && !saw_git && m_gi.is_none() {
+                m_gi =
+                    ig.0.git_ignore_matcher.matched(path, is_dir)
+                      .map(IgnoreMatch::gitignore);
+            }
+            if any_git && !saw_git && m_gi_exclude.is_none() {
+                m_gi_exclude =
+                    ig.0.git_exclude_matcher.matched(path, is_dir)
+                      .map(IgnoreMatch::gitignore);
+            }
+            if any_git && !saw_git && m_sparse.is_none() {
+                m_sparse =
+                    matched_sparse(&ig.0.git_sparse_matcher, path, is_dir);
+            }
+            if any_hg && !saw_hg && m_hgi.is_none() {
+                m_hgi =
+                    ig.0.hg_ignore_matcher.matched(path, is_dir)
+                      .map(IgnoreMatch::hgignore);
+            }
+            saw_git = saw_git || ig.0.has_git;
+            saw_hg = saw_hg || ig.0.has_hg;
+        }
+        if self.0.opts.parents {
+            if let Some(abs_parent_path) = self.absolute_base() {
+                let path = abs_parent_path.join(path);
+                for ig in self.parents().skip_while(|ig|!ig.0.is_absolute_parent) {
+                    if m_custom_ignore.is_none() {
+                        m_custom_ignore =
+                            ig.0.custom_ignore_matcher.matched(&path, is_dir)
+                              .map(IgnoreMatch::gitignore);
+                    }
+                    if m_custom_after_ignore.is_none() {
+                        m_custom_after_ignore =
+                            ig.0.custom_after_ignore_matcher
+                              .matched(&path, is_dir)
+                              .map(IgnoreMatch::gitignore);
+                    }
+                    if m_custom_after_gi.is_none() {
+                        m_custom_after_gi =
+                            ig.0.custom_after_gitignore_matcher
+                              .matched(&path, is_dir)
+                              .map(IgnoreMatch::gitignore);
+                    }
+                    if m_ignore.is_none() {
+                        m_ignore =
+                            ig.0.ignore_matcher.matched(&path, is_dir)
+                              .map(IgnoreMatch::gitignore);
+                    }
+                    if any_git && !saw_git && m_gi.is_none() {
+                        m_gi =
+                            ig.0.git_ignore_matcher.matched(&path, is_dir)
+                              .map(IgnoreMatch::gitignore);
+                    }
+                    if any_git && !saw_git && m_gi_exclude.is_none() {
+                        m_gi_exclude =
+                            ig.0.git_exclude_matcher.matched(&path, is_dir)
+                              .map(IgnoreMatch::gitignore);
+                    }
+                    if any_git && !saw_git && m_sparse.is_none() {
+                        m_sparse = matched_sparse(
+                            &ig.0.git_sparse_matcher, &path, is_dir);
+                    }
+                    if any_hg && !saw_hg && m_hgi.is_none() {
+                        m_hgi =
+                            ig.0.hg_ignore_matcher.matched(&path, is_dir)
+                              .map(IgnoreMatch::hgignore);
+                    }
+                    saw_git = saw_git || ig.0.has_git;
+                    saw_hg = saw_hg || ig.0.has_hg;
+                }
+            }
+        }
+        for gi in self.0.explicit_ignores.iter().rev() {
+            if !m_explicit.is_none() {
+                break;
+            }
+            m_explicit = gi.matched(&path, is_dir).map(IgnoreMatch::gitignore);
+        }
+        let m_global =
+            if any_git {
+                self.0.git_global_matcher
+                    .matched(&path, is_dir)
+                    .map(IgnoreMatch::gitignore)
+            } else {
+                Match::None
+            };
+
+        m_custom_ignore.or(m_ignore).or(m_custom_after_ignore).or(m_gi).or(m_custom_after_gi).or(m_gi_exclude).or(m_hgi).or(m_global).or(m_explicit).or(m_sparse)
+    }
+
+    /// Returns the chain of ignore rules that apply to the given path.
+    ///
+    /// The decisions are returned roughly in order of precedence, from
+    /// highest to lowest: earlier entries outrank later ones, so the first
+    /// entry is usually the rule that decides whether the path is ignored.
+    /// An empty vec means that no rule applies to the path. This is useful
+    /// for answering the question "why is this file being skipped?"
+    ///
+    /// `is_dir` should be true if the path refers to a directory and false
+    /// otherwise.
+    pub fn explain<P: AsRef<Path>>(
+        &self,
+        path: P,
+        is_dir: bool,
+    ) -> Vec<IgnoreDecision> {
+        let mut path = path.as_ref();
+        if let Some(p) = strip_prefix("./", path) {
+            path = p;
+        }
+        let mut decisions = vec![];
+        if !self.0.overrides.is_empty() {
+            let m = self.0.overrides.matched(path, is_dir)
+                .map(IgnoreMatch::overrides);
+            push_decision(&mut decisions, "override", &m);
+        }
+        if self.has_any_ignore_rules() {
+            self.explain_ignore(path, is_dir, &mut decisions);
+        }
+        if !self.0.types.is_empty() {
+            let m = self.0.types.matched(path, is_dir).map(IgnoreMatch::types);
+            push_decision(&mut decisions, "types", &m);
+        }
+        if self.0.opts.hidden && is_hidden(path) {
+            decisions.push(IgnoreDecision {
+                source: "hidden",
+                from: None,
+                line: None,
+                pattern: None,
+                is_whitelist: false,
+            });
+        }
+        decisions
+    }
+
+    /// Records the effective rule from each ignore file source for the given
+    /// path.
+    ///
+    /// This mirrors `matched_ignore`, except that instead of reducing the
+    /// per-source matches to a single verdict, the match from every source is
+    /// recorded in order of precedence.
+    fn explain_ignore(
+        &self,
+        path: &Path,
+        is_dir: bool,
+        decisions: &mut Vec<IgnoreDecision>,
+    ) {
+        let (mut m_custom_ignore, mut m_custom_after_ignore, mut m_custom_after_gi, mut m_ignore, mut m_gi, mut m_gi_exclude, mut m_sparse, mut m_hgi, mut m_explicit) =
+            (Match::None, Match::None, Match::None, Match::None, Match::None, Match::None, Match::None, Match::None, Match::None);
         let any_git = self.parents().any(|ig| ig.0.has_git);
+        let any_hg = self.parents().any(|ig| ig.0.has_hg);
         let mut saw_git = false;
+        let mut saw_hg = false;
         for ig in self.parents().take_while(|ig| !ig.0.is_absolute_parent) {
             if m_custom_ignore.is_none() {
                 m_custom_ignore =
                     ig.0.custom_ignore_matcher.matched(path, is_dir)
                       .map(IgnoreMatch::gitignore);
             }
+            if m_custom_after_ignore.is_none() {
+                m_custom_after_ignore =
+                    ig.0.custom_after_ignore_matcher.matched(path, is_dir)
+                      .map(IgnoreMatch::gitignore);
+            }
+            if m_custom_after_gi.is_none() {
+                m_custom_after_gi =
+                    ig.0.custom_after_gitignore_matcher.matched(path, is_dir)
+                      .map(IgnoreMatch::gitignore);
+            }
             if m_ignore.is_none() {
                 m_ignore =
                     ig.0.ignore_matcher.matched(path, is_dir)
@@ -368,7 +832,17 @@ impl Ignore {
                     ig.0.git_exclude_matcher.matched(path, is_dir)
                       .map(IgnoreMatch::gitignore);
             }
+            if any_git && !saw_git && m_sparse.is_none() {
+                m_sparse =
+                    matched_sparse(&ig.0.git_sparse_matcher, path, is_dir);
+            }
+            if any_hg && !saw_hg && m_hgi.is_none() {
+                m_hgi =
+                    ig.0.hg_ignore_matcher.matched(path, is_dir)
+                      .map(IgnoreMatch::hgignore);
+            }
             saw_git = saw_git || ig.0.has_git;
+            saw_hg = saw_hg || ig.0.has_hg;
         }
         if self.0.opts.parents {
             if let Some(abs_parent_path) = self.absolute_base() {
@@ -379,6 +853,18 @@ impl Ignore {
                             ig.0.custom_ignore_matcher.matched(&path, is_dir)
                               .map(IgnoreMatch::gitignore);
                     }
+                    if m_custom_after_ignore.is_none() {
+                        m_custom_after_ignore =
+                            ig.0.custom_after_ignore_matcher
+                              .matched(&path, is_dir)
+                              .map(IgnoreMatch::gitignore);
+                    }
+                    if m_custom_after_gi.is_none() {
+                        m_custom_after_gi =
+                            ig.0.custom_after_gitignore_matcher
+                              .matched(&path, is_dir)
+                              .map(IgnoreMatch::gitignore);
+                    }
                     if m_ignore.is_none() {
                         m_ignore =
                             ig.0.ignore_matcher.matched(&path, is_dir)
@@ -394,7 +880,17 @@ impl Ignore {
                             ig.0.git_exclude_matcher.matched(&path, is_dir)
                               .map(IgnoreMatch::gitignore);
                     }
+                    if any_git && !saw_git && m_sparse.is_none() {
+                        m_sparse = matched_sparse(
+                            &ig.0.git_sparse_matcher, &path, is_dir);
+                    }
+                    if any_hg && !saw_hg && m_hgi.is_none() {
+                        m_hgi =
+                            ig.0.hg_ignore_matcher.matched(&path, is_dir)
+                              .map(IgnoreMatch::hgignore);
+                    }
                     saw_git = saw_git || ig.0.has_git;
+                    saw_hg = saw_hg || ig.0.has_hg;
                 }
             }
         }
@@ -413,7 +909,16 @@ impl Ignore {
                 Match::None
             };
 
-        m_custom_ignore.or(m_ignore).or(m_gi).or(m_gi_exclude).or(m_global).or(m_explicit)
+        push_decision(decisions, "custom-ignore", &m_custom_ignore);
+        push_decision(decisions, "ignore", &m_ignore);
+        push_decision(decisions, "custom-ignore", &m_custom_after_ignore);
+        push_decision(decisions, "gitignore", &m_gi);
+        push_decision(decisions, "custom-ignore", &m_custom_after_gi);
+        push_decision(decisions, "git-exclude", &m_gi_exclude);
+        push_decision(decisions, "hgignore", &m_hgi);
+        push_decision(decisions, "gitignore-global", &m_global);
+        push_decision(decisions, "explicit-ignore", &m_explicit);
+        push_decision(decisions, "sparse-checkout", &m_sparse);
     }
 
     /// Returns an iterator over parent ignore matchers, including this one.
@@ -459,7 +964,7 @@ pub struct IgnoreBuilder {
     /// Explicit global ignore matchers.
     explicit_ignores: Vec<Gitignore>,
     /// Ignore files in addition to .ignore.
-    custom_ignore_filenames: Vec<OsString>,
+    custom_ignores: Vec<CustomIgnore>,
     /// Ignore config.
     opts: IgnoreOptions,
 }
@@ -475,7 +980,7 @@ impl IgnoreBuilder {
             overrides: Arc::new(Override::empty()),
             types: Arc::new(Types::empty()),
             explicit_ignores: vec![],
-            custom_ignore_filenames: vec![],
+            custom_ignores: vec![],
             opts: IgnoreOptions {
                 hidden: true,
                 ignore: true,
@@ -483,6 +988,8 @@ impl IgnoreBuilder {
                 git_global: true,
                 git_ignore: true,
                 git_exclude: true,
+                hg_ignore: true,
+                ignore_case_insensitive: false,
             },
         }
     }
@@ -512,13 +1019,18 @@ impl IgnoreBuilder {
             is_absolute_parent: true,
             absolute_base: None,
             explicit_ignores: Arc::new(self.explicit_ignores.clone()),
-            custom_ignore_filenames: Arc::new(self.custom_ignore_filenames.clone()),
+            custom_ignores: Arc::new(self.custom_ignores.clone()),
             custom_ignore_matcher: Gitignore::empty(),
+            custom_after_ignore_matcher: Gitignore::empty(),
+            custom_after_gitignore_matcher: Gitignore::empty(),
             ignore_matcher: Gitignore::empty(),
             git_global_matcher: Arc::new(git_global_matcher),
             git_ignore_matcher: Gitignore::empty(),
             git_exclude_matcher: Gitignore::empty(),
+            git_sparse_matcher: Gitignore::empty(),
+            hg_ignore_matcher: Hgignore::empty(),
             has_git: false,
+            has_hg: false,
             opts: self.opts,
         }))
     }
@@ -559,7 +1071,30 @@ impl IgnoreBuilder {
         &mut self,
         file_name: S
     ) -> &mut IgnoreBuilder {
-        self.custom_ignore_filenames.push(file_name.as_ref().to_os_string());
+        self.add_custom_ignore_filename_with(
+            file_name, CustomIgnorePrecedence::BeforeIgnore, true)
+    }
+
+    /// Add a custom ignore file name with explicit precedence and scope.
+    ///
+    /// `precedence` controls where rules from this file rank relative to
+    /// `.ignore` and `.gitignore` rules. When `hierarchical` is true, the
+    /// file is read in every directory, like `.gitignore`. Otherwise, it is
+    /// only read in the root of a traversal.
+    ///
+    /// Among custom ignore files with the same precedence, earlier names
+    /// have lower precedence than later names.
+    pub fn add_custom_ignore_filename_with<S: AsRef<OsStr>>(
+        &mut self,
+        file_name: S,
+        precedence: CustomIgnorePrecedence,
+        hierarchical: bool,
+    ) -> &mut IgnoreBuilder {
+        self.custom_ignores.push(CustomIgnore {
+            file_name: file_name.as_ref().to_os_string(),
+            precedence: precedence,
+            hierarchical: hierarchical,
+        });
         self
     }
 
@@ -627,6 +1162,161 @@ impl IgnoreBuilder {
         self.opts.git_exclude = yes;
         self
     }
+
+    /// Enables reading `.hgignore` files.
+    ///
+    /// `.hgignore` files have match semantics as described in the `hgignore`
+    /// man page. They are only used inside Mercurial repositories, i.e., in
+    /// directories containing an `.hg` directory.
+    ///
+    /// This is enabled by default.
+    pub fn hg_ignore(&mut self, yes: bool) -> &mut IgnoreBuilder {
+        self.opts.hg_ignore = yes;
+        self
+    }
+
+    /// Process ignore globs case insensitively.
+    ///
+    /// This is useful on case insensitive file systems (such as the defaults
+    /// on Windows and macOS), where git itself honors `core.ignoreCase`.
+    ///
+    /// This is disabled by default.
+    pub fn ignore_case_insensitive(
+        &mut self,
+        yes: bool,
+    ) -> &mut IgnoreBuilder {
+        self.opts.ignore_case_insensitive = yes;
+        self
+    }
+}
+
+/// Returns the sparse checkout verdict for the given path.
+///
+/// Sparse checkout patterns select the paths that are part of the working
+/// tree, so the gitignore match is inverted: a matching pattern whitelists
+/// the path and a `!` pattern ignores it. Matching considers parent
+/// directories so that files inside an included directory are kept. Files
+/// matching no pattern at all are not part of the sparse checkout and are
+/// ignored; directories are exempt since they may contain included paths.
+fn matched_sparse<'a>(
+    sparse: &'a Gitignore,
+    path: &Path,
+    is_dir: bool,
+) -> Match<IgnoreMatch<'a>> {
+    if sparse.is_empty() {
+        return Match::None;
+    }
+    let m = sparse.matched_path_or_any_parents(path, is_dir).invert();
+    if m.is_none() && !is_dir {
+        Match::Ignore(IgnoreMatch::sparse())
+    } else {
+        m.map(IgnoreMatch::gitignore)
+    }
+}
+
+/// Returns true if the repository with the given git directory has the
+/// `core.sparseCheckout` config setting enabled.
+///
+/// Git only honors `info/sparse-checkout` when this setting is true, and
+/// `git sparse-checkout disable` leaves the file in place, so the file's
+/// existence alone is not enough to apply its patterns.
+fn sparse_checkout_enabled(git_dir: &Path) -> bool {
+    // In linked worktrees, the repository config lives in the git directory
+    // of the main worktree.
+    let config = git_common_dir(git_dir)
+        .unwrap_or_else(|| git_dir.to_path_buf())
+        .join("config");
+    let mut contents = String::new();
+    match File::open(&config) {
+        Err(_) => return false,
+        Ok(mut file) => {
+            if file.read_to_string(&mut contents).is_err() {
+                return false;
+            }
+        }
+    }
+    let mut in_core = false;
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.starts_with("[") {
+            in_core = line.to_lowercase().starts_with("[core]");
+            continue;
+        }
+        if !in_core {
+            continue;
+        }
+        let mut pieces = line.splitn(2, '=');
+        if pieces.next().unwrap().trim().to_lowercase() != "sparsecheckout" {
+            continue;
+        }
+        return match pieces.next() {
+            // A key without a value is true in git config syntax.
+            None => true,
+            Some(value) => match &*value.trim().to_lowercase() {
+                "true" | "yes" | "on" | "1" => true,
+                _ => false,
+            },
+        };
+    }
+    false
+}
+
+/// Resolves the git directory for a worktree rooted at `dir`, if one exists.
+///
+/// In linked worktrees, `.git` is a regular file containing a line of the
+/// form `gitdir: <path>` pointing to the real git directory.
+fn resolve_git_dir(dir: &Path) -> Option<PathBuf> {
+    let dot_git = dir.join(".git");
+    let md = match dot_git.metadata() {
+        Err(_) => return None,
+        Ok(md) => md,
+    };
+    if md.is_dir() {
+        return Some(dot_git);
+    }
+    let mut contents = String::new();
+    match File::open(&dot_git) {
+        Err(_) => return None,
+        Ok(mut file) => {
+            if file.read_to_string(&mut contents).is_err() {
+                return None;
+            }
+        }
+    }
+    let line = contents.lines().next().unwrap_or("");
+    if !line.starts_with("gitdir:") {
+        return None;
+    }
+    let path = Path::new(line["gitdir:".len()..].trim());
+    if path.is_absolute() {
+        Some(path.to_path_buf())
+    } else {
+        Some(dir.join(path))
+    }
+}
+
+/// Returns the common git directory for the given git directory, if there
+/// is a distinct one.
+///
+/// Linked worktree git directories contain a `commondir` file pointing at
+/// the git directory of the main worktree, which holds the shared
+/// `info/exclude` file.
+fn git_common_dir(git_dir: &Path) -> Option<PathBuf> {
+    let mut contents = String::new();
+    match File::open(git_dir.join("commondir")) {
+        Err(_) => return None,
+        Ok(mut file) => {
+            if file.read_to_string(&mut contents).is_err() {
+                return None;
+            }
+        }
+    }
+    let path = Path::new(contents.trim());
+    if path.is_absolute() {
+        Some(path.to_path_buf())
+    } else {
+        Some(git_dir.join(path))
+    }
 }
 
 /// Creates a new gitignore matcher for the directory given.
@@ -638,9 +1328,13 @@ impl IgnoreBuilder {
 pub fn create_gitignore<T: AsRef<OsStr>>(
     dir: &Path,
     names: &[T],
+    case_insensitive: bool,
 ) -> (Gitignore, Option<Error>) {
     let mut builder = GitignoreBuilder::new(dir);
     let mut errs = PartialErrorBuilder::default();
+    if let Err(err) = builder.case_insensitive(case_insensitive) {
+        errs.push(err);
+    }
     for name in names {
         let gipath = dir.join(name.as_ref());
         errs.maybe_push_ignore_io(builder.add(gipath));
@@ -663,7 +1357,7 @@ mod tests {
 
     use tempdir::TempDir;
 
-    use dir::IgnoreBuilder;
+    use dir::{CustomIgnorePrecedence, IgnoreBuilder};
     use gitignore::Gitignore;
     use Error;
 
@@ -711,6 +1405,82 @@ mod tests {
         assert!(ig.matched("baz", false).is_none());
     }
 
+    #[test]
+    fn git_exclude_linked_worktree() {
+        // In a linked worktree, .git is a file pointing at the real git
+        // directory, and the shared info/exclude file lives in the git
+        // directory of the main worktree.
+        let td = TempDir::new("ignore-test-").unwrap();
+        mkdirp(td.path().join("main/.git/info"));
+        mkdirp(td.path().join("main/.git/worktrees/wt"));
+        mkdirp(td.path().join("wt"));
+        wfile(td.path().join("main/.git/info/exclude"), "foo");
+        wfile(td.path().join("main/.git/worktrees/wt/commondir"), "../..");
+        wfile(
+            td.path().join("wt/.git"),
+            &format!(
+                "gitdir: {}",
+                td.path().join("main/.git/worktrees/wt").display()
+            ),
+        );
+
+        let (ig, err) =
+            IgnoreBuilder::new().build().add_child(td.path().join("wt"));
+        assert!(err.is_none());
+        assert!(ig.matched("foo", false).is_ignore());
+        assert!(ig.matched("bar", false).is_none());
+    }
+
+    #[test]
+    fn git_sparse_checkout() {
+        let td = TempDir::new("ignore-test-").unwrap();
+        mkdirp(td.path().join(".git/info"));
+        wfile(
+            td.path().join(".git/config"),
+            "[core]\n\tsparseCheckout = true\n",
+        );
+        wfile(
+            td.path().join(".git/info/sparse-checkout"),
+            "/*\n!/*/\n/src/\n",
+        );
+
+        let (ig, err) = IgnoreBuilder::new().build().add_child(td.path());
+        assert!(err.is_none());
+        // Root files and the src directory are part of the checkout.
+        assert!(!ig.matched("README", false).is_ignore());
+        assert!(!ig.matched("src", true).is_ignore());
+        assert!(!ig.matched("src/main.rs", false).is_ignore());
+        // Other directories and their contents are not.
+        assert!(ig.matched("vendor", true).is_ignore());
+        assert!(ig.matched("vendor/lib.c", false).is_ignore());
+    }
+
+    #[test]
+    fn git_sparse_checkout_disabled() {
+        // Without core.sparseCheckout enabled, git ignores a leftover
+        // info/sparse-checkout file (e.g. after `git sparse-checkout
+        // disable`), and so must we.
+        let td = TempDir::new("ignore-test-").unwrap();
+        mkdirp(td.path().join(".git/info"));
+        wfile(
+            td.path().join(".git/info/sparse-checkout"),
+            "/*\n!/*/\n/src/\n",
+        );
+
+        let (ig, err) = IgnoreBuilder::new().build().add_child(td.path());
+        assert!(err.is_none());
+        assert!(!ig.matched("vendor", true).is_ignore());
+        assert!(!ig.matched("vendor/lib.c", false).is_ignore());
+
+        wfile(
+            td.path().join(".git/config"),
+            "[core]\n\tsparseCheckout = false\n",
+        );
+        let (ig, err) = IgnoreBuilder::new().build().add_child(td.path());
+        assert!(err.is_none());
+        assert!(!ig.matched("vendor/lib.c", false).is_ignore());
+    }
+
     #[test]
     fn gitignore() {
         let td = TempDir::new("ignore-test-").unwrap();
@@ -736,6 +1506,68 @@ mod tests {
         assert!(ig.matched("baz", false).is_none());
     }
 
+    #[test]
+    fn explain() {
+        let td = TempDir::new("ignore-test-").unwrap();
+        mkdirp(td.path().join(".git"));
+        wfile(td.path().join(".gitignore"), "foo\nbar");
+        wfile(td.path().join(".ignore"), "!foo");
+
+        let (ig, err) = IgnoreBuilder::new().build().add_child(td.path());
+        assert!(err.is_none());
+
+        // The .ignore whitelist outranks the .gitignore rule.
+        let decisions = ig.explain("foo", false);
+        assert_eq!(2, decisions.len());
+        assert_eq!("ignore", decisions[0].source());
+        assert_eq!(Some("!foo"), decisions[0].pattern());
+        assert!(decisions[0].is_whitelist());
+        assert_eq!("gitignore", decisions[1].source());
+        assert_eq!(Some("foo"), decisions[1].pattern());
+        assert!(!decisions[1].is_whitelist());
+        assert_eq!(
+            Some(&*td.path().join(".gitignore")),
+            decisions[1].from()
+        );
+
+        // Only the .gitignore rule matches bar.
+        let decisions = ig.explain("bar", false);
+        assert_eq!(1, decisions.len());
+        assert_eq!("gitignore", decisions[0].source());
+
+        // Hidden files are reported too.
+        let decisions = ig.explain(".baz", false);
+        assert_eq!(1, decisions.len());
+        assert_eq!("hidden", decisions[0].source());
+        assert_eq!(None, decisions[0].pattern());
+
+        // No rule matches at all.
+        assert!(ig.explain("quux", false).is_empty());
+    }
+
+    #[test]
+    fn hgignore() {
+        let td = TempDir::new("ignore-test-").unwrap();
+        mkdirp(td.path().join(".hg"));
+        wfile(td.path().join(".hgignore"), "syntax: glob\nfoo\nsyntax: regexp\n\\.o$");
+
+        let (ig, err) = IgnoreBuilder::new().build().add_child(td.path());
+        assert!(err.is_none());
+        assert!(ig.matched("foo", false).is_ignore());
+        assert!(ig.matched("bar.o", false).is_ignore());
+        assert!(ig.matched("baz", false).is_none());
+    }
+
+    #[test]
+    fn hgignore_no_hg() {
+        let td = TempDir::new("ignore-test-").unwrap();
+        wfile(td.path().join(".hgignore"), "syntax: glob\nfoo");
+
+        let (ig, err) = IgnoreBuilder::new().build().add_child(td.path());
+        assert!(err.is_none());
+        assert!(ig.matched("foo", false).is_none());
+    }
+
     #[test]
     fn ignore() {
         let td = TempDir::new("ignore-test-").unwrap();
@@ -795,6 +1627,75 @@ mod tests {
         assert!(ig.matched("foo", false).is_whitelist());
     }
 
+    // Tests that a custom ignore file can rank between .ignore and
+    // .gitignore.
+    #[test]
+    fn custom_ignore_after_ignore() {
+        let td = TempDir::new("ignore-test-").unwrap();
+        mkdirp(td.path().join(".git"));
+        let custom_ignore = ".customignore";
+        wfile(td.path().join(".ignore"), "foo");
+        wfile(td.path().join(".gitignore"), "bar");
+        wfile(td.path().join(custom_ignore), "!foo\n!bar");
+
+        let (ig, err) = IgnoreBuilder::new()
+            .add_custom_ignore_filename_with(
+                custom_ignore, CustomIgnorePrecedence::AfterIgnore, true)
+            .build().add_child(td.path());
+        assert!(err.is_none());
+        // The .ignore rule outranks the custom whitelist, but the custom
+        // whitelist outranks the .gitignore rule.
+        assert!(ig.matched("foo", false).is_ignore());
+        assert!(ig.matched("bar", false).is_whitelist());
+    }
+
+    // Tests that a custom ignore file can rank below both .ignore and
+    // .gitignore.
+    #[test]
+    fn custom_ignore_after_gitignore() {
+        let td = TempDir::new("ignore-test-").unwrap();
+        mkdirp(td.path().join(".git"));
+        let custom_ignore = ".customignore";
+        wfile(td.path().join(".gitignore"), "foo");
+        wfile(td.path().join(custom_ignore), "!foo\nbar");
+
+        let (ig, err) = IgnoreBuilder::new()
+            .add_custom_ignore_filename_with(
+                custom_ignore, CustomIgnorePrecedence::AfterGitignore, true)
+            .build().add_child(td.path());
+        assert!(err.is_none());
+        // The .gitignore rule outranks the custom whitelist.
+        assert!(ig.matched("foo", false).is_ignore());
+        // Rules that no other file contradicts still apply.
+        assert!(ig.matched("bar", false).is_ignore());
+    }
+
+    // Tests that a root-only custom ignore file is only read in the root of
+    // a traversal.
+    #[test]
+    fn custom_ignore_root_only() {
+        let td = TempDir::new("ignore-test-").unwrap();
+        mkdirp(td.path().join("a"));
+        let custom_ignore = ".customignore";
+        wfile(td.path().join(custom_ignore), "foo");
+        wfile(td.path().join("a").join(custom_ignore), "bar");
+
+        let ig0 = IgnoreBuilder::new()
+            .add_custom_ignore_filename_with(
+                custom_ignore, CustomIgnorePrecedence::BeforeIgnore, false)
+            .build();
+        let (ig1, err) = ig0.add_child(td.path());
+        assert!(err.is_none());
+        let (ig2, err) = ig1.add_child(td.path().join("a"));
+        assert!(err.is_none());
+
+        // The root file applies everywhere in the traversal, but the file
+        // in the child directory is never read.
+        assert!(ig1.matched("foo", false).is_ignore());
+        assert!(ig2.matched("foo", false).is_ignore());
+        assert!(ig2.matched("bar", false).is_none());
+    }
+
     // Tests that an .ignore will override a .gitignore.
     #[test]
     fn ignore_over_gitignore() {
@@ -937,4 +1838,25 @@ mod tests {
         assert!(ig2.matched("foo", false).is_ignore());
         assert!(ig2.matched("src/foo", false).is_ignore());
     }
+
+    #[test]
+    fn match_provenance() {
+        let td = TempDir::new("ignore-test-").unwrap();
+        mkdirp(td.path().join(".git"));
+        wfile(td.path().join(".gitignore"), "# comment\nfoo\n!bar\n");
+
+        let (ig, err) = IgnoreBuilder::new().build().add_child(td.path());
+        assert!(err.is_none());
+
+        let m = ig.matched("foo", false);
+        let im = m.inner().unwrap();
+        assert_eq!(im.from(), Some(&*td.path().join(".gitignore")));
+        assert_eq!(im.line(), Some(2));
+        assert_eq!(im.pattern(), Some("foo"));
+
+        let m = ig.matched("bar", false);
+        let im = m.inner().unwrap();
+        assert_eq!(im.line(), Some(3));
+        assert_eq!(im.pattern(), Some("!bar"));
+    }
 }
@@ -30,6 +30,9 @@ use {Error, Match, PartialErrorBuilder};
 pub struct Glob {
     /// The file path that this glob was extracted from.
     from: Option<PathBuf>,
+    /// The line number in the file that this glob was extracted from,
+    /// starting at 1.
+    line: Option<u64>,
     /// The original glob string.
     original: String,
     /// The actual glob string used to convert to a regex.
@@ -46,6 +49,12 @@ impl Glob {
         self.from.as_ref().map(|p| &**p)
     }
 
+    /// Returns the line number in the file that defined this glob, starting
+    /// at 1. This is `None` when the glob was not read from a file.
+    pub fn line(&self) -> Option<u64> {
+        self.line
+    }
+
     /// The original glob as it was defined in a gitignore file.
     pub fn original(&self) -> &str {
         &self.original
@@ -380,7 +389,8 @@ impl GitignoreBuilder {
                     break;
                 }
             };
-            if let Err(err) = self.add_line(Some(path.to_path_buf()), &line) {
+            let from = Some(path.to_path_buf());
+            if let Err(err) = self.add_line_at(from, Some(lineno), &line) {
                 errs.push(err.tagged(path, lineno));
             }
         }
@@ -414,6 +424,17 @@ impl GitignoreBuilder {
     pub fn add_line(
         &mut self,
         from: Option<PathBuf>,
+        line: &str,
+    ) -> Result<&mut GitignoreBuilder, Error> {
+        self.add_line_at(from, None, line)
+    }
+
+    /// Like add_line, but additionally records the line number that the glob
+    /// came from, so that it can be reported on matches.
+    fn add_line_at(
+        &mut self,
+        from: Option<PathBuf>,
+        lineno: Option<u64>,
         mut line: &str,
     ) -> Result<&mut GitignoreBuilder, Error> {
         if line.starts_with("#") {
@@ -427,6 +448,7 @@ impl GitignoreBuilder {
         }
         let mut glob = Glob {
             from: from,
+            line: lineno,
             original: line.to_string(),
             actual: String::new(),
             is_whitelist: false,
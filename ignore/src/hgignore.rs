@@ -0,0 +1,498 @@
+/*!
+The hgignore module provides a way to match patterns from a Mercurial
+`.hgignore` file against file paths.
+
+Unlike gitignore files, `.hgignore` files may contain patterns in one of two
+syntaxes: shell-style globs or regular expressions. The active syntax is
+selected with `syntax: glob` or `syntax: regexp` lines and applies to all
+patterns that follow, until the next `syntax` line. The default syntax is
+`regexp`. All patterns are unrooted, i.e., they may match anywhere in a file
+path, and there is no whitelisting.
+*/
+
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::{self, BufRead};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use globset::{Candidate, GlobBuilder, GlobSet, GlobSetBuilder};
+use regex::RegexSet;
+use thread_local::ThreadLocal;
+
+use pathutil::{is_file_name, strip_prefix};
+use {Error, Match, PartialErrorBuilder};
+
+/// The syntax of a single pattern in a `.hgignore` file.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Syntax {
+    /// A shell-style glob, as selected by a `syntax: glob` line.
+    Glob,
+    /// A regular expression. This is the default syntax.
+    Regexp,
+}
+
+/// Pattern represents a single pattern in a `.hgignore` file.
+///
+/// This is used to report information about the pattern that matched in a
+/// hgignore file.
+#[derive(Clone, Debug)]
+pub struct Pattern {
+    /// The file path that this pattern was extracted from.
+    from: Option<PathBuf>,
+    /// The line number in the file that this pattern was extracted from,
+    /// starting at 1.
+    line: Option<u64>,
+    /// The original pattern string.
+    original: String,
+    /// The syntax that was in effect when the pattern was added.
+    syntax: Syntax,
+}
+
+impl Pattern {
+    /// Returns the file path that defined this pattern.
+    pub fn from(&self) -> Option<&Path> {
+        self.from.as_ref().map(|p| &**p)
+    }
+
+    /// Returns the line number in the file that defined this pattern,
+    /// starting at 1. This is `None` when the pattern was not read from a
+    /// file.
+    pub fn line(&self) -> Option<u64> {
+        self.line
+    }
+
+    /// The original pattern as it was defined in a hgignore file.
+    pub fn original(&self) -> &str {
+        &self.original
+    }
+
+    /// The syntax that was in effect when this pattern was defined.
+    pub fn syntax(&self) -> Syntax {
+        self.syntax
+    }
+}
+
+/// Hgignore is a matcher for the patterns in one or more hgignore files
+/// in the same directory.
+#[derive(Clone, Debug)]
+pub struct Hgignore {
+    set: GlobSet,
+    regex_set: RegexSet,
+    root: PathBuf,
+    globs: Vec<Pattern>,
+    regexps: Vec<Pattern>,
+    matches: Option<Arc<ThreadLocal<RefCell<Vec<usize>>>>>,
+}
+
+impl Hgignore {
+    /// Creates a new hgignore matcher from the hgignore file path given.
+    ///
+    /// This always returns a valid matcher, even if it's empty. In
+    /// particular, a hgignore file can be partially valid, e.g., when one
+    /// pattern is invalid but the rest aren't.
+    ///
+    /// Note that I/O errors are ignored. For more granular control over
+    /// errors, use `HgignoreBuilder`.
+    pub fn new<P: AsRef<Path>>(
+        hgignore_path: P,
+    ) -> (Hgignore, Option<Error>) {
+        let path = hgignore_path.as_ref();
+        let parent = path.parent().unwrap_or(Path::new("/"));
+        let mut builder = HgignoreBuilder::new(parent);
+        let mut errs = PartialErrorBuilder::default();
+        errs.maybe_push_ignore_io(builder.add(path));
+        match builder.build() {
+            Ok(hgi) => (hgi, errs.into_error_option()),
+            Err(err) => {
+                errs.push(err);
+                (Hgignore::empty(), errs.into_error_option())
+            }
+        }
+    }
+
+    /// Creates a new empty hgignore matcher that never matches anything.
+    ///
+    /// Its path is empty.
+    pub fn empty() -> Hgignore {
+        HgignoreBuilder::new("").build().unwrap()
+    }
+
+    /// Returns the directory containing this hgignore matcher.
+    ///
+    /// All matches are done relative to this path.
+    pub fn path(&self) -> &Path {
+        &*self.root
+    }
+
+    /// Returns true if and only if this hgignore has zero patterns, and
+    /// therefore never matches any file path.
+    pub fn is_empty(&self) -> bool {
+        self.globs.is_empty() && self.regexps.is_empty()
+    }
+
+    /// Returns the total number of patterns.
+    pub fn len(&self) -> usize {
+        self.globs.len() + self.regexps.len()
+    }
+
+    /// Returns whether the given path (file or directory) matched a pattern
+    /// in this hgignore matcher.
+    ///
+    /// `is_dir` should be true if the path refers to a directory and false
+    /// otherwise. (It is accepted for symmetry with gitignore matchers, but
+    /// hgignore patterns apply to files and directories alike.)
+    ///
+    /// The given path is matched relative to the path given when building
+    /// the matcher. Specifically, before matching `path`, its prefix (as
+    /// determined by a common suffix of the directory containing this
+    /// hgignore) is stripped. If there is no common suffix/prefix overlap,
+    /// then `path` is assumed to be relative to this matcher.
+    pub fn matched<P: AsRef<Path>>(
+        &self,
+        path: P,
+        is_dir: bool,
+    ) -> Match<&Pattern> {
+        if self.is_empty() {
+            return Match::None;
+        }
+        self.matched_stripped(self.strip(path.as_ref()), is_dir)
+    }
+
+    /// Like matched, but takes a path that has already been stripped.
+    fn matched_stripped<P: AsRef<Path>>(
+        &self,
+        path: P,
+        _is_dir: bool,
+    ) -> Match<&Pattern> {
+        let path = path.as_ref();
+        if !self.globs.is_empty() {
+            let _matches = self.matches.as_ref().unwrap().get_default();
+            let mut matches = _matches.borrow_mut();
+            let candidate = Candidate::new(path);
+            self.set.matches_candidate_into(&candidate, &mut *matches);
+            if let Some(&i) = matches.iter().next() {
+                return Match::Ignore(&self.globs[i]);
+            }
+        }
+        if !self.regexps.is_empty() {
+            let path = path.to_string_lossy();
+            if let Some(i) = self.regex_set.matches(&path).iter().next() {
+                return Match::Ignore(&self.regexps[i]);
+            }
+        }
+        Match::None
+    }
+
+    /// Strips the given path such that it's suitable for matching with this
+    /// hgignore matcher.
+    fn strip<'a, P: 'a + AsRef<Path> + ?Sized>(
+        &'a self,
+        path: &'a P,
+    ) -> &'a Path {
+        let mut path = path.as_ref();
+        // A leading ./ is completely superfluous. We also strip it from
+        // our hgignore root path, so we need to strip it from our candidate
+        // path too.
+        if let Some(p) = strip_prefix("./", path) {
+            path = p;
+        }
+        // Strip any common prefix between the candidate path and the root
+        // of the hgignore, to make sure we get relative matching right.
+        // BUT, a file name might not have any directory components to it,
+        // in which case, we don't want to accidentally strip any part of the
+        // file name.
+        if self.root != Path::new(".") && !is_file_name(path) {
+            if let Some(p) = strip_prefix(&self.root, path) {
+                path = p;
+                // If we're left with a leading slash, get rid of it.
+                if let Some(p) = strip_prefix("/", path) {
+                    path = p;
+                }
+            }
+        }
+        path
+    }
+}
+
+/// Builds a matcher for a single set of patterns from a `.hgignore` file.
+#[derive(Clone, Debug)]
+pub struct HgignoreBuilder {
+    builder: GlobSetBuilder,
+    root: PathBuf,
+    globs: Vec<Pattern>,
+    regexps: Vec<Pattern>,
+    regex_patterns: Vec<String>,
+    syntax: Syntax,
+}
+
+impl HgignoreBuilder {
+    /// Create a new builder for a hgignore file.
+    ///
+    /// The path given should be the path at which the patterns for this
+    /// hgignore file should be matched. Note that paths are always matched
+    /// relative to the root path given here. Generally, the root path should
+    /// correspond to the *directory* containing a `.hgignore` file, which is
+    /// usually the root of a Mercurial repository.
+    pub fn new<P: AsRef<Path>>(root: P) -> HgignoreBuilder {
+        let root = root.as_ref();
+        HgignoreBuilder {
+            builder: GlobSetBuilder::new(),
+            root: strip_prefix("./", root).unwrap_or(root).to_path_buf(),
+            globs: vec![],
+            regexps: vec![],
+            regex_patterns: vec![],
+            syntax: Syntax::Regexp,
+        }
+    }
+
+    /// Builds a new matcher from the patterns added so far.
+    ///
+    /// Once a matcher is built, no new patterns can be added to it.
+    pub fn build(&self) -> Result<Hgignore, Error> {
+        let set =
+            self.builder.build().map_err(|err| {
+                Error::Glob {
+                    glob: None,
+                    err: err.to_string(),
+                }
+            })?;
+        let regex_set =
+            RegexSet::new(&self.regex_patterns).map_err(|err| {
+                Error::Glob {
+                    glob: None,
+                    err: err.to_string(),
+                }
+            })?;
+        Ok(Hgignore {
+            set: set,
+            regex_set: regex_set,
+            root: self.root.clone(),
+            globs: self.globs.clone(),
+            regexps: self.regexps.clone(),
+            matches: Some(Arc::new(ThreadLocal::default())),
+        })
+    }
+
+    /// Add each pattern from the file path given.
+    ///
+    /// The file given should be formatted as a `.hgignore` file.
+    ///
+    /// Note that partial errors can be returned. For example, if there was
+    /// a problem adding one pattern, an error for that will be returned, but
+    /// all other valid patterns will still be added.
+    pub fn add<P: AsRef<Path>>(&mut self, path: P) -> Option<Error> {
+        let path = path.as_ref();
+        let file = match File::open(path) {
+            Err(err) => return Some(Error::Io(err).with_path(path)),
+            Ok(file) => file,
+        };
+        let rdr = io::BufReader::new(file);
+        let mut errs = PartialErrorBuilder::default();
+        for (i, line) in rdr.lines().enumerate() {
+            let lineno = (i + 1) as u64;
+            let line = match line {
+                Ok(line) => line,
+                Err(err) => {
+                    errs.push(Error::Io(err).tagged(path, lineno));
+                    break;
+                }
+            };
+            let from = Some(path.to_path_buf());
+            if let Err(err) = self.add_line_at(from, Some(lineno), &line) {
+                errs.push(err.tagged(path, lineno));
+            }
+        }
+        errs.into_error_option()
+    }
+
+    /// Add each pattern line from the string given.
+    ///
+    /// If this string came from a particular `.hgignore` file, then its path
+    /// should be provided here.
+    ///
+    /// The string given should be formatted as a `.hgignore` file.
+    #[cfg(test)]
+    fn add_str(
+        &mut self,
+        from: Option<PathBuf>,
+        hgignore: &str,
+    ) -> Result<&mut HgignoreBuilder, Error> {
+        for line in hgignore.lines() {
+            self.add_line(from.clone(), line)?;
+        }
+        Ok(self)
+    }
+
+    /// Add a line from a hgignore file to this builder.
+    ///
+    /// If this line came from a particular `.hgignore` file, then its path
+    /// should be provided here.
+    ///
+    /// A `syntax: glob` or `syntax: regexp` line changes the syntax for all
+    /// subsequent patterns. If the line could not be parsed as a pattern,
+    /// then an error is returned.
+    pub fn add_line(
+        &mut self,
+        from: Option<PathBuf>,
+        line: &str,
+    ) -> Result<&mut HgignoreBuilder, Error> {
+        self.add_line_at(from, None, line)
+    }
+
+    /// Like add_line, but additionally records the line number that the
+    /// pattern came from, so that it can be reported on matches.
+    fn add_line_at(
+        &mut self,
+        from: Option<PathBuf>,
+        lineno: Option<u64>,
+        line: &str,
+    ) -> Result<&mut HgignoreBuilder, Error> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with("#") {
+            return Ok(self);
+        }
+        if line.starts_with("syntax:") {
+            self.syntax = match line["syntax:".len()..].trim() {
+                "glob" => Syntax::Glob,
+                "regexp" | "re" => Syntax::Regexp,
+                unk => {
+                    return Err(Error::Glob {
+                        glob: Some(line.to_string()),
+                        err: format!("unrecognized syntax: {}", unk),
+                    });
+                }
+            };
+            return Ok(self);
+        }
+        let pat = Pattern {
+            from: from,
+            line: lineno,
+            original: line.to_string(),
+            syntax: self.syntax,
+        };
+        match self.syntax {
+            Syntax::Glob => {
+                // hgignore globs are unrooted, so let them match anywhere in
+                // a path, but don't let wildcards match a path separator.
+                let mut actual = line.to_string();
+                if !actual.starts_with("**/") {
+                    actual = format!("**/{}", actual);
+                }
+                let parsed =
+                    GlobBuilder::new(&actual)
+                        .literal_separator(true)
+                        .backslash_escape(true)
+                        .build()
+                        .map_err(|err| {
+                            Error::Glob {
+                                glob: Some(pat.original.clone()),
+                                err: err.kind().to_string(),
+                            }
+                        })?;
+                self.builder.add(parsed);
+                self.globs.push(pat);
+            }
+            Syntax::Regexp => {
+                // Compile the regex eagerly so that an invalid pattern is
+                // reported with the offending line, even though matching
+                // itself uses a single RegexSet built from all patterns.
+                if let Err(err) = ::regex::Regex::new(line) {
+                    return Err(Error::Glob {
+                        glob: Some(pat.original.clone()),
+                        err: err.to_string(),
+                    });
+                }
+                self.regex_patterns.push(line.to_string());
+                self.regexps.push(pat);
+            }
+        }
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use super::{Hgignore, HgignoreBuilder, Syntax};
+
+    fn hgi_from_str<P: AsRef<Path>>(root: P, s: &str) -> Hgignore {
+        let mut builder = HgignoreBuilder::new(root);
+        builder.add_str(None, s).unwrap();
+        builder.build().unwrap()
+    }
+
+    macro_rules! ignored {
+        ($name:ident, $root:expr, $hgi:expr, $path:expr) => {
+            ignored!($name, $root, $hgi, $path, false);
+        };
+        ($name:ident, $root:expr, $hgi:expr, $path:expr, $is_dir:expr) => {
+            #[test]
+            fn $name() {
+                let hgi = hgi_from_str($root, $hgi);
+                assert!(hgi.matched($path, $is_dir).is_ignore());
+            }
+        };
+    }
+
+    macro_rules! not_ignored {
+        ($name:ident, $root:expr, $hgi:expr, $path:expr) => {
+            not_ignored!($name, $root, $hgi, $path, false);
+        };
+        ($name:ident, $root:expr, $hgi:expr, $path:expr, $is_dir:expr) => {
+            #[test]
+            fn $name() {
+                let hgi = hgi_from_str($root, $hgi);
+                assert!(!hgi.matched($path, $is_dir).is_ignore());
+            }
+        };
+    }
+
+    const ROOT: &'static str = "/home/foobar/hg/rg";
+
+    // The default syntax is regexp and regexps are unrooted.
+    ignored!(ig1, ROOT, r"\.pyc$", "foo.pyc");
+    ignored!(ig2, ROOT, r"\.pyc$", "src/foo.pyc");
+    ignored!(ig3, ROOT, "^build/", "build/foo");
+    ignored!(ig4, ROOT, "target", "src/target/debug");
+    ignored!(ig5, ROOT, "syntax: glob\n*.pyc", "foo.pyc");
+    ignored!(ig6, ROOT, "syntax: glob\n*.pyc", "src/foo.pyc");
+    ignored!(ig7, ROOT, "syntax: glob\nbuild", "src/build", true);
+    ignored!(ig8, ROOT, "syntax: glob\nfoo/bar", "foo/bar");
+    ignored!(ig9, ROOT, "syntax: glob\nfoo/bar", "a/foo/bar");
+    ignored!(ig10, ROOT, "# comment\n\nmonths", "months");
+    ignored!(ig11, ROOT, "syntax: glob\n*.o\nsyntax: regexp\n~$", "a.o");
+    ignored!(ig12, ROOT, "syntax: glob\n*.o\nsyntax: regexp\n~$", "foo~");
+    ignored!(ig13, ROOT, "syntax: re\n\\.o$", "foo.o");
+    ignored!(ig14, ROOT, "syntax: glob\nfoo", "./foo");
+
+    not_ignored!(ignot1, ROOT, r"\.pyc$", "foo.pyc.bak");
+    not_ignored!(ignot2, ROOT, "^build/", "src/build/foo");
+    not_ignored!(ignot3, ROOT, "syntax: glob\n*.pyc", "foo.pyc.bak");
+    // Globs don't let wildcards match a path separator.
+    not_ignored!(ignot4, ROOT, "syntax: glob\nfoo/*.o", "foo/bar/baz.o");
+    not_ignored!(ignot5, ROOT, "# months", "months");
+    not_ignored!(ignot6, ROOT, "\n\n\n", "foo");
+
+    #[test]
+    fn pattern_info() {
+        let hgi = hgi_from_str(ROOT, "syntax: glob\n*.pyc");
+        let mat = hgi.matched("foo.pyc", false);
+        let pat = mat.inner().unwrap();
+        assert_eq!("*.pyc", pat.original());
+        assert_eq!(Syntax::Glob, pat.syntax());
+        assert!(pat.from().is_none());
+    }
+
+    #[test]
+    fn invalid_syntax() {
+        let mut builder = HgignoreBuilder::new(ROOT);
+        assert!(builder.add_line(None, "syntax: wat").is_err());
+    }
+
+    #[test]
+    fn invalid_regexp() {
+        let mut builder = HgignoreBuilder::new(ROOT);
+        assert!(builder.add_line(None, "*.pyc").is_err());
+    }
+}
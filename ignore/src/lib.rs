@@ -46,7 +46,6 @@ See the documentation for `WalkBuilder` for many other options.
 
 #![deny(missing_docs)]
 
-extern crate crossbeam;
 extern crate globset;
 #[macro_use]
 extern crate lazy_static;
@@ -67,10 +66,16 @@ use std::fmt;
 use std::io;
 use std::path::{Path, PathBuf};
 
-pub use walk::{DirEntry, Walk, WalkBuilder, WalkParallel, WalkState};
+pub use dir::{CustomIgnorePrecedence, IgnoreDecision};
+pub use walk::{
+    DirEntry, Walk, WalkBuilder, WalkEvent, WalkEvents, WalkParallel,
+    WalkParallelIter, WalkState, WalkStrategy,
+};
 
+mod cache;
 mod dir;
 pub mod gitignore;
+pub mod hgignore;
 mod pathutil;
 pub mod overrides;
 pub mod types;
@@ -111,6 +116,10 @@ pub enum Error {
         ancestor: PathBuf,
         /// The child file path in the loop.
         child: PathBuf,
+        /// The chain of directory paths that forms the cycle, from the
+        /// ancestor down to the child. This always contains at least the
+        /// ancestor and the child.
+        chain: Vec<PathBuf>,
     },
     /// An error that occurs when doing I/O, such as reading an ignore file.
     Io(io::Error),
@@ -145,10 +154,11 @@ impl Clone for Error {
             Error::WithDepth { depth, ref err } => {
                 Error::WithDepth { depth: depth, err: err.clone() }
             }
-            Error::Loop { ref ancestor, ref child } => {
+            Error::Loop { ref ancestor, ref child, ref chain } => {
                 Error::Loop {
                     ancestor: ancestor.clone(),
-                    child: child.clone()
+                    child: child.clone(),
+                    chain: chain.clone(),
                 }
             }
             Error::Io(ref err) => {
@@ -249,6 +259,7 @@ impl Error {
                 err: Box::new(Error::Loop {
                     ancestor: anc.to_path_buf(),
                     child: child.to_path_buf(),
+                    chain: vec![anc.to_path_buf(), child.to_path_buf()],
                 }),
             };
         }
@@ -295,10 +306,21 @@ impl fmt::Display for Error {
                 write!(f, "{}: {}", path.display(), err)
             }
             Error::WithDepth { ref err, .. } => err.fmt(f),
-            Error::Loop { ref ancestor, ref child } => {
+            Error::Loop { ref ancestor, ref child, ref chain } => {
                 write!(f, "File system loop found: \
                            {} points to an ancestor {}",
-                          child.display(), ancestor.display())
+                          child.display(), ancestor.display())?;
+                if chain.len() > 2 {
+                    write!(f, " (cycle: ")?;
+                    for (i, p) in chain.iter().enumerate() {
+                        if i > 0 {
+                            write!(f, " -> ")?;
+                        }
+                        write!(f, "{}", p.display())?;
+                    }
+                    write!(f, ")")?;
+                }
+                Ok(())
             }
             Error::Io(ref err) => err.fmt(f),
             Error::Glob { glob: None, ref err } => write!(f, "{}", err),
@@ -6,6 +6,8 @@ line tools.
 
 use std::path::Path;
 
+use globset::GlobSet;
+
 use gitignore::{self, Gitignore, GitignoreBuilder};
 use {Error, Match};
 
@@ -29,46 +31,99 @@ enum GlobInner<'a> {
     UnmatchedIgnore,
     /// A glob matched.
     Matched(&'a gitignore::Glob),
+    /// A glob from a precompiled glob set matched. No information about
+    /// the individual glob is available.
+    MatchedSet,
 }
 
 impl<'a> Glob<'a> {
     fn unmatched() -> Glob<'a> {
         Glob(GlobInner::UnmatchedIgnore)
     }
+
+    /// Returns the file path that defined the matching glob, if the glob
+    /// came from a file.
+    pub fn from(&self) -> Option<&Path> {
+        match self.0 {
+            GlobInner::Matched(glob) => glob.from(),
+            _ => None,
+        }
+    }
+
+    /// Returns the line number in the file that defined the matching glob,
+    /// starting at 1, if the glob came from a file.
+    pub fn line(&self) -> Option<u64> {
+        match self.0 {
+            GlobInner::Matched(glob) => glob.line(),
+            _ => None,
+        }
+    }
+
+    /// The original text of the matching glob, if there is one.
+    pub fn original(&self) -> Option<&str> {
+        match self.0 {
+            GlobInner::Matched(glob) => Some(glob.original()),
+            _ => None,
+        }
+    }
+}
+
+/// A precompiled glob set added to an override matcher, along with whether
+/// its matches whitelist or ignore file paths.
+#[derive(Clone, Debug)]
+struct OverrideGlobSet {
+    set: GlobSet,
+    whitelist: bool,
 }
 
 /// Manages a set of overrides provided explicitly by the end user.
 #[derive(Clone, Debug)]
-pub struct Override(Gitignore);
+pub struct Override {
+    ignore: Gitignore,
+    globsets: Vec<OverrideGlobSet>,
+}
 
 impl Override {
     /// Returns an empty matcher that never matches any file path.
     pub fn empty() -> Override {
-        Override(Gitignore::empty())
+        Override {
+            ignore: Gitignore::empty(),
+            globsets: vec![],
+        }
     }
 
     /// Returns the directory of this override set.
     ///
     /// All matches are done relative to this path.
     pub fn path(&self) -> &Path {
-        self.0.path()
+        self.ignore.path()
     }
 
     /// Returns true if and only if this matcher is empty.
     ///
     /// When a matcher is empty, it will never match any file path.
     pub fn is_empty(&self) -> bool {
-        self.0.is_empty()
+        self.ignore.is_empty() && self.globsets.is_empty()
     }
 
     /// Returns the total number of ignore globs.
     pub fn num_ignores(&self) -> u64 {
-        self.0.num_whitelists()
+        self.ignore.num_whitelists() + self.num_globset_globs(false)
     }
 
     /// Returns the total number of whitelisted globs.
     pub fn num_whitelists(&self) -> u64 {
-        self.0.num_ignores()
+        self.ignore.num_ignores() + self.num_globset_globs(true)
+    }
+
+    /// Returns the total number of globs in precompiled glob sets that
+    /// whitelist (or ignore, if `whitelist` is false) file paths.
+    fn num_globset_globs(&self, whitelist: bool) -> u64 {
+        self.globsets
+            .iter()
+            .filter(|gs| gs.whitelist == whitelist)
+            .map(|gs| gs.set.len() as u64)
+            .sum()
     }
 
     /// Returns whether the given file path matched a pattern in this override
@@ -97,7 +152,23 @@ impl Override {
         if self.is_empty() {
             return Match::None;
         }
-        let mat = self.0.matched(path, is_dir).invert();
+        let path = path.as_ref();
+        // Precompiled glob sets have the highest precedence, with later
+        // sets outranking earlier ones. Raw glob sets carry no root
+        // directory of their own, so strip our root from the path before
+        // matching, just as the gitignore matcher does.
+        let rel = path.strip_prefix(self.path()).unwrap_or(path);
+        for gs in self.globsets.iter().rev() {
+            if gs.set.is_match(rel) {
+                let glob = Glob(GlobInner::MatchedSet);
+                return if gs.whitelist {
+                    Match::Whitelist(glob)
+                } else {
+                    Match::Ignore(glob)
+                };
+            }
+        }
+        let mat = self.ignore.matched(path, is_dir).invert();
         if mat.is_none() && self.num_whitelists() > 0 && !is_dir {
             return Match::Ignore(Glob::unmatched());
         }
@@ -108,6 +179,7 @@ impl Override {
 /// Builds a matcher for a set of glob overrides.
 pub struct OverrideBuilder {
     builder: GitignoreBuilder,
+    globsets: Vec<OverrideGlobSet>,
 }
 
 impl OverrideBuilder {
@@ -117,6 +189,7 @@ impl OverrideBuilder {
     pub fn new<P: AsRef<Path>>(path: P) -> OverrideBuilder {
         OverrideBuilder {
             builder: GitignoreBuilder::new(path),
+            globsets: vec![],
         }
     }
 
@@ -124,7 +197,10 @@ impl OverrideBuilder {
     ///
     /// Once a matcher is built, no new globs can be added to it.
     pub fn build(&self) -> Result<Override, Error> {
-        Ok(Override(self.builder.build()?))
+        Ok(Override {
+            ignore: self.builder.build()?,
+            globsets: self.globsets.clone(),
+        })
     }
 
     /// Add a glob to the set of overrides.
@@ -138,6 +214,32 @@ impl OverrideBuilder {
         Ok(self)
     }
 
+    /// Add a precompiled set of globs to the overrides.
+    ///
+    /// When `whitelist` is true, paths matching the set are whitelisted,
+    /// which is equivalent to adding each glob in the set via `add`.
+    /// Otherwise, paths matching the set are ignored, which is equivalent
+    /// to adding each glob with a `!` prefix.
+    ///
+    /// This avoids re-parsing glob strings when a compiled `GlobSet` is
+    /// already at hand. Glob sets have higher precedence than globs added
+    /// via `add`, and later sets have higher precedence than earlier ones.
+    /// Since a compiled set carries no per-glob information, matches
+    /// reported from it don't identify an individual glob. Paths are
+    /// matched against the set relative to the directory path given to
+    /// `OverrideBuilder::new`.
+    pub fn add_globset(
+        &mut self,
+        set: GlobSet,
+        whitelist: bool,
+    ) -> &mut OverrideBuilder {
+        self.globsets.push(OverrideGlobSet {
+            set: set,
+            whitelist: whitelist,
+        });
+        self
+    }
+
     /// Toggle whether the globs should be matched case insensitively or not.
     /// 
     /// When this option is changed, only globs added after the change will be affected.
@@ -153,6 +255,8 @@ impl OverrideBuilder {
 
 #[cfg(test)]
 mod tests {
+    use globset::{GlobBuilder, GlobSetBuilder};
+
     use super::{Override, OverrideBuilder};
 
     const ROOT: &'static str = "/home/andrew/foo";
@@ -246,6 +350,54 @@ mod tests {
         assert!(ov.matched("foo.HTM", false).is_ignore());
     }
 
+    #[test]
+    fn globset_whitelist() {
+        let mut set = GlobSetBuilder::new();
+        set.add(GlobBuilder::new("*.foo").build().unwrap());
+        let set = set.build().unwrap();
+
+        let ov = OverrideBuilder::new(ROOT)
+            .add_globset(set, true)
+            .build().unwrap();
+        assert!(ov.matched("a.foo", false).is_whitelist());
+        // With at least one whitelist glob, unmatched files are ignored.
+        assert!(ov.matched("a.rs", false).is_ignore());
+        assert!(ov.matched("a.rs", true).is_none());
+    }
+
+    #[test]
+    fn globset_ignore() {
+        let mut set = GlobSetBuilder::new();
+        set.add(GlobBuilder::new("*.bar").build().unwrap());
+        let set = set.build().unwrap();
+
+        let ov = OverrideBuilder::new(ROOT)
+            .add_globset(set, false)
+            .build().unwrap();
+        assert!(ov.matched("a.bar", false).is_ignore());
+        assert!(ov.matched("a.rs", false).is_none());
+    }
+
+    #[test]
+    fn globset_precedence() {
+        // Glob sets outrank globs added as strings, and later sets outrank
+        // earlier ones.
+        let mut set1 = GlobSetBuilder::new();
+        set1.add(GlobBuilder::new("*.foo").build().unwrap());
+        let set1 = set1.build().unwrap();
+        let mut set2 = GlobSetBuilder::new();
+        set2.add(GlobBuilder::new("a.foo").build().unwrap());
+        let set2 = set2.build().unwrap();
+
+        let ov = OverrideBuilder::new(ROOT)
+            .add("!*.foo").unwrap()
+            .add_globset(set1, true)
+            .add_globset(set2, false)
+            .build().unwrap();
+        assert!(ov.matched("a.foo", false).is_ignore());
+        assert!(ov.matched("b.foo", false).is_whitelist());
+    }
+
     #[test]
     fn default_case_sensitive() {
         let ov = OverrideBuilder::new(ROOT)
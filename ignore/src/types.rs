@@ -82,6 +82,23 @@ let matcher = builder.build().unwrap();
 assert!(matcher.matched("x.foo", false).is_whitelist());
 assert!(matcher.matched("y.cpp", false).is_whitelist());
 ```
+
+Finally, a glob prefixed with `!` excludes matching file paths from a type
+instead of adding them, overriding any globs defined before it.
+
+```
+use ignore::types::TypesBuilder;
+
+let mut builder = TypesBuilder::new();
+builder.add_defaults();
+builder.add_def("web:include:html,js");
+builder.add_def("web:!*.min.js");
+builder.select("web");
+let matcher = builder.build().unwrap();
+
+assert!(matcher.matched("app.js", false).is_whitelist());
+assert!(matcher.matched("app.min.js", false).is_ignore());
+```
 */
 
 use std::cell::RefCell;
@@ -484,10 +501,21 @@ impl Types {
         };
         let mut matches = self.matches.get_default().borrow_mut();
         self.set.matches_into(name, &mut *matches);
-        // The highest precedent match is the last one.
-        if let Some(&i) = matches.last() {
+        // The highest precedent match is the last one, so visit matches in
+        // reverse. A glob prefixed with `!` excludes the path from its file
+        // type, which suppresses every glob defined before it in the same
+        // selection.
+        let mut excluded: Vec<usize> = vec![];
+        for &i in matches.iter().rev() {
             let (isel, iglob) = self.glob_to_selection[i];
             let sel = &self.selections[isel];
+            if sel.inner().globs[iglob].starts_with('!') {
+                excluded.push(isel);
+                continue;
+            }
+            if excluded.contains(&isel) {
+                continue;
+            }
             let glob = Glob(GlobInner::Matched {
                 def: sel.inner(),
                 which: iglob,
@@ -545,8 +573,13 @@ impl TypesBuilder {
                 }
             };
             for (iglob, glob) in def.globs.iter().enumerate() {
+                // Strip the `!` marker from exclusion globs; the marker is
+                // kept in the definition itself so that matching can tell
+                // exclusions apart.
+                let pat =
+                    if glob.starts_with('!') { &glob[1..] } else { &**glob };
                 build_set.add(
-                    GlobBuilder::new(glob)
+                    GlobBuilder::new(pat)
                         .literal_separator(true)
                         .build()
                         .map_err(|err| {
@@ -622,6 +655,8 @@ impl TypesBuilder {
 
     /// Add a new file type definition. `name` can be arbitrary and `pat`
     /// should be a glob recognizing file paths belonging to the `name` type.
+    /// A glob prefixed with `!` instead *excludes* matching file paths from
+    /// the type, overriding any globs added to the type before it.
     ///
     /// If `name` is `all` or otherwise contains any character that is not a
     /// Unicode letter or number, then an error is returned.
@@ -647,7 +682,8 @@ impl TypesBuilder {
     ///     This defines an 'include' definition that associates the given name
     ///     with the definitions of the given existing types.
     /// Names may not include any characters that are not
-    /// Unicode letters or numbers.
+    /// Unicode letters or numbers. A glob may be prefixed with `!` to exclude
+    /// matching file paths from the type instead; see `add` for details.
     pub fn add_def(&mut self, def: &str) -> Result<(), Error> {
         let parts: Vec<&str> = def.split(':').collect();
         match parts.len() {
@@ -736,7 +772,9 @@ mod tests {
             "rust:*.rs",
             "js:*.js",
             "foo:*.{rs,foo}",
-            "combo:include:html,rust"
+            "combo:include:html,rust",
+            "web:include:html,js",
+            "web:!*.min.js",
         ]
     }
 
@@ -757,6 +795,12 @@ mod tests {
     matched!(not, matchnot5, types(), vec!["rust"], vec!["foo"], "main.foo");
     matched!(not, matchnot6, types(), vec!["combo"], vec![], "leftpad.js");
 
+    matched!(matchexclude1, types(), vec!["web"], vec![], "index.html");
+    matched!(matchexclude2, types(), vec!["web"], vec![], "app.js");
+    matched!(not, matchexclude3, types(), vec!["web"], vec![], "app.min.js");
+    // An exclusion in one type doesn't affect another type's globs.
+    matched!(matchexclude4, types(), vec!["web", "js"], vec![], "app.min.js");
+
     #[test]
     fn test_invalid_defs() {
         let mut btypes = TypesBuilder::new();
@@ -1,20 +1,25 @@
 use std::cmp;
-use std::ffi::OsStr;
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::ffi::{OsStr, OsString};
 use std::fmt;
 use std::fs::{self, FileType, Metadata};
-use std::io;
+use std::hash::{Hash, Hasher};
+use std::io::{self, Read};
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
+use std::collections::VecDeque;
+use std::sync::{Arc, Condvar, Mutex};
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 use std::vec;
 
-use crossbeam::sync::MsQueue;
 use same_file::Handle;
 use walkdir::{self, WalkDir};
 
-use dir::{Ignore, IgnoreBuilder};
+use cache::{self, TraversalCache};
+use dir::{CustomIgnorePrecedence, Ignore, IgnoreBuilder, IgnoreDecision};
 use gitignore::GitignoreBuilder;
 use overrides::Override;
 use types::Types;
@@ -459,11 +464,26 @@ pub struct WalkBuilder {
     ig_builder: IgnoreBuilder,
     max_depth: Option<usize>,
     max_filesize: Option<u64>,
+    filesize_skips: Option<Arc<AtomicUsize>>,
+    max_symlink_depth: Option<usize>,
+    filter: MetadataFilter,
+    sniff: Option<SniffFilter>,
     follow_links: bool,
-    sorter: Option<Arc<
-        Fn(&OsStr, &OsStr) -> cmp::Ordering + Send + Sync + 'static
-    >>,
+    same_file_system: bool,
+    max_open: Option<usize>,
+    steal_batch_size: usize,
+    traversal_cache: Option<PathBuf>,
+    sorter: Option<Sorter>,
     threads: usize,
+    strategy: WalkStrategy,
+}
+
+/// A function for sorting directory entries, either by file name or by full
+/// file path.
+#[derive(Clone)]
+enum Sorter {
+    ByName(Arc<Fn(&OsStr, &OsStr) -> cmp::Ordering + Send + Sync + 'static>),
+    ByPath(Arc<Fn(&Path, &Path) -> cmp::Ordering + Send + Sync + 'static>),
 }
 
 impl fmt::Debug for WalkBuilder {
@@ -473,8 +493,17 @@ impl fmt::Debug for WalkBuilder {
             .field("ig_builder", &self.ig_builder)
             .field("max_depth", &self.max_depth)
             .field("max_filesize", &self.max_filesize)
+            .field("filesize_skips", &self.filesize_skips)
+            .field("max_symlink_depth", &self.max_symlink_depth)
+            .field("filter", &self.filter)
+            .field("sniff", &self.sniff)
             .field("follow_links", &self.follow_links)
+            .field("same_file_system", &self.same_file_system)
+            .field("max_open", &self.max_open)
+            .field("steal_batch_size", &self.steal_batch_size)
+            .field("traversal_cache", &self.traversal_cache)
             .field("threads", &self.threads)
+            .field("strategy", &self.strategy)
             .finish()
     }
 }
@@ -492,9 +521,18 @@ impl WalkBuilder {
             ig_builder: IgnoreBuilder::new(),
             max_depth: None,
             max_filesize: None,
+            filesize_skips: None,
+            max_symlink_depth: None,
+            filter: MetadataFilter::default(),
+            sniff: None,
             follow_links: false,
+            same_file_system: false,
+            max_open: None,
+            steal_batch_size: 1,
+            traversal_cache: None,
             sorter: None,
             threads: 0,
+            strategy: WalkStrategy::default(),
         }
     }
 
@@ -512,13 +550,22 @@ impl WalkBuilder {
                 if let Some(max_depth) = max_depth {
                     wd = wd.max_depth(max_depth);
                 }
-                if let Some(ref cmp) = cmp {
-                    let cmp = cmp.clone();
-                    wd = wd.sort_by(move |a, b| {
-                        cmp(a.file_name(), b.file_name())
-                    });
+                match cmp {
+                    None => {}
+                    Some(Sorter::ByName(ref cmp)) => {
+                        let cmp = cmp.clone();
+                        wd = wd.sort_by(move |a, b| {
+                            cmp(a.file_name(), b.file_name())
+                        });
+                    }
+                    Some(Sorter::ByPath(ref cmp)) => {
+                        let cmp = cmp.clone();
+                        wd = wd.sort_by(move |a, b| {
+                            cmp(a.path(), b.path())
+                        });
+                    }
                 }
-                (p.to_path_buf(), Some(WalkEventIter::from(wd)))
+                (p.to_path_buf(), Some(WalkDirEventIter::from(wd)))
             }
         }).collect::<Vec<_>>().into_iter();
         let ig_root = self.ig_builder.build();
@@ -528,6 +575,25 @@ impl WalkBuilder {
             ig_root: ig_root.clone(),
             ig: ig_root.clone(),
             max_filesize: self.max_filesize,
+            filesize_skips: self.filesize_skips.clone(),
+            max_symlink_depth: self.max_symlink_depth,
+            symlink_depth: 0,
+            symlink_stack: vec![],
+            same_file_system: self.same_file_system,
+            root_device: None,
+            filter: self.filter.clone(),
+            sniff: self.sniff.clone(),
+        }
+    }
+
+    /// Build a new `WalkEvents` iterator, which yields directory enter/leave
+    /// events in addition to file entries. See `WalkEvent` for the precise
+    /// ordering guarantees.
+    pub fn build_events(&self) -> WalkEvents {
+        WalkEvents {
+            walk: self.build(),
+            stack: vec![],
+            next: None,
         }
     }
 
@@ -542,11 +608,50 @@ impl WalkBuilder {
             ig_root: self.ig_builder.build(),
             max_depth: self.max_depth,
             max_filesize: self.max_filesize,
+            filesize_skips: self.filesize_skips.clone(),
+            max_symlink_depth: self.max_symlink_depth,
+            filter: self.filter.clone(),
+            sniff: self.sniff.clone(),
             follow_links: self.follow_links,
+            same_file_system: self.same_file_system,
+            max_open: self.max_open,
+            steal_batch_size: self.steal_batch_size,
+            traversal_cache: self.traversal_cache.clone(),
             threads: self.threads,
+            strategy: self.strategy,
         }
     }
 
+    /// Explain which ignore rules apply to the given path.
+    ///
+    /// This builds the same ignore matchers that a walk would consult for
+    /// the path's directory (including ignore files in parent directories)
+    /// and returns every rule that matched, roughly in order of precedence.
+    /// An empty vec means that no ignore rule applies to the path. See
+    /// `IgnoreDecision` for the information reported about each rule.
+    ///
+    /// As with walking itself, I/O errors encountered while reading ignore
+    /// files are reported but don't prevent an answer.
+    pub fn explain<P: AsRef<Path>>(
+        &self,
+        path: P,
+    ) -> (Vec<IgnoreDecision>, Option<Error>) {
+        let path = path.as_ref();
+        let is_dir = path.is_dir();
+        let dir =
+            if is_dir {
+                path
+            } else {
+                path.parent().unwrap_or(Path::new(""))
+            };
+        let mut errs = PartialErrorBuilder::default();
+        let (ig, err) = self.ig_builder.build().add_parents(dir);
+        errs.maybe_push(err);
+        let (ig, err) = ig.add_child(dir);
+        errs.maybe_push(err);
+        (ig.explain(path, is_dir), errs.into_error_option())
+    }
+
     /// Add a file path to the iterator.
     ///
     /// Each additional file path added is traversed recursively. This should
@@ -571,12 +676,172 @@ impl WalkBuilder {
         self
     }
 
+    /// The maximum number of nested symbolic links to follow.
+    ///
+    /// This only has an effect when following symbolic links is enabled. A
+    /// directory reached through more than `depth` symbolic links is skipped,
+    /// which bounds traversal of pathological link structures that are deep
+    /// but not actually cyclic (cycles are always detected and reported as
+    /// errors, regardless of this setting).
+    ///
+    /// The default, `None`, imposes no restriction.
+    pub fn max_symlink_depth(
+        &mut self,
+        depth: Option<usize>,
+    ) -> &mut WalkBuilder {
+        self.max_symlink_depth = depth;
+        self
+    }
+
+    /// Do not cross file system boundaries.
+    ///
+    /// When this option is enabled, directory traversal will not descend
+    /// into directories that are on a different file system from the root
+    /// path. This is useful for searching paths like `/` without wandering
+    /// into `/proc`, network mounts or bind mounts.
+    ///
+    /// Currently, this option is only supported on Unix platforms. On other
+    /// platforms, enabling it causes traversal to report an error for each
+    /// root path.
+    pub fn same_file_system(&mut self, yes: bool) -> &mut WalkBuilder {
+        self.same_file_system = yes;
+        self
+    }
+
+    /// Set a cap on the number of simultaneously open directory handles
+    /// used by the parallel walker.
+    ///
+    /// Each thread of the parallel walker keeps at most one directory
+    /// handle open at a time, so the number of open handles is otherwise
+    /// bounded by the number of threads. Use this to lower that bound when
+    /// running with high thread counts under a tight file descriptor limit.
+    /// A limit of `0` is treated as `1`. This has no effect on the serial
+    /// walker.
+    ///
+    /// The default, `None`, imposes no cap.
+    pub fn max_open(&mut self, limit: Option<usize>) -> &mut WalkBuilder {
+        self.max_open = limit;
+        self
+    }
+
+    /// Set the number of work items moved between threads per steal in the
+    /// parallel walker.
+    ///
+    /// A thread of the parallel walker that runs out of work steals work
+    /// from the queues of the other threads. Stealing more than one item
+    /// at a time amortizes the synchronization cost, which can reduce
+    /// contention when traversing very wide directories with many threads.
+    /// A batch size of `0` is treated as `1`. This has no effect on the
+    /// serial walker.
+    ///
+    /// The default batch size is `1`.
+    pub fn steal_batch_size(&mut self, size: usize) -> &mut WalkBuilder {
+        self.steal_batch_size = size;
+        self
+    }
+
+    /// Set the path of a persistent traversal cache.
+    ///
+    /// When set, the parallel walker records the modification time and the
+    /// ignore match outcome of every entry of each directory it visits in
+    /// the given file, and reuses the outcomes on subsequent walks for
+    /// directories that have not changed. This can substantially speed up
+    /// repeated walks of large, mostly unchanged trees.
+    ///
+    /// A directory whose modification time changed invalidates the cached
+    /// state of its entire subtree, and an edit to any previously seen
+    /// ignore file (or a change to the walker configuration) discards the
+    /// cache as a whole. The cache is strictly best effort: problems
+    /// reading or writing the cache file are logged and otherwise ignored.
+    ///
+    /// This has no effect on the single threaded walkers.
+    ///
+    /// This is disabled by default.
+    pub fn traversal_cache(
+        &mut self,
+        path: Option<PathBuf>,
+    ) -> &mut WalkBuilder {
+        self.traversal_cache = path;
+        self
+    }
+
     /// Whether to ignore files above the specified limit.
     pub fn max_filesize(&mut self, filesize: Option<u64>) -> &mut WalkBuilder {
         self.max_filesize = filesize;
         self
     }
 
+    /// Set a counter that is incremented once for each file skipped because
+    /// it is larger than the maximum file size.
+    pub fn skipped_filesize_counter(
+        &mut self,
+        counter: Arc<AtomicUsize>,
+    ) -> &mut WalkBuilder {
+        self.filesize_skips = Some(counter);
+        self
+    }
+
+    /// Whether to ignore files below the specified limit.
+    pub fn min_filesize(&mut self, filesize: Option<u64>) -> &mut WalkBuilder {
+        self.filter.min_filesize = filesize;
+        self
+    }
+
+    /// Whether to ignore files last modified before the given time.
+    pub fn modified_after(
+        &mut self,
+        time: Option<SystemTime>,
+    ) -> &mut WalkBuilder {
+        self.filter.modified_after = time;
+        self
+    }
+
+    /// Whether to ignore files last modified after the given time.
+    pub fn modified_before(
+        &mut self,
+        time: Option<SystemTime>,
+    ) -> &mut WalkBuilder {
+        self.filter.modified_before = time;
+        self
+    }
+
+    /// Set a filter on the file type of yielded entries.
+    ///
+    /// Entries whose file type does not satisfy the predicate are skipped.
+    /// This can be used to restrict traversal to, e.g., regular files, or
+    /// to drop special files like sockets. Platform specific file types are
+    /// available through the extension traits on `std::fs::FileType`.
+    ///
+    /// Like the other metadata filters, this does not apply to directories,
+    /// since skipping a directory would silently drop everything beneath it.
+    pub fn entry_type_filter<F>(&mut self, filter: F) -> &mut WalkBuilder
+            where F: Fn(&FileType) -> bool + Send + Sync + 'static {
+        self.filter.entry_type = Some(Arc::new(filter));
+        self
+    }
+
+    /// Set a filter on the leading bytes of yielded files.
+    ///
+    /// The given predicate is called with a file's path and its first `len`
+    /// bytes (fewer if the file is shorter). If the predicate returns
+    /// `false`, then the file is skipped. This enables content based file
+    /// type detection, e.g., treating extensionless scripts with a
+    /// `#!/usr/bin/env python` line as python files.
+    ///
+    /// The bytes are read lazily: a file is only opened once every other
+    /// filter has accepted it. Files that cannot be opened or read are not
+    /// skipped, and directories are never sniffed.
+    ///
+    /// This is disabled by default.
+    pub fn sniff_filter<F>(&mut self, len: usize, filter: F) -> &mut WalkBuilder
+            where F: Fn(&Path, &[u8]) -> bool + Send + Sync + 'static {
+        self.sniff = Some(SniffFilter {
+            len: len,
+            pred: Arc::new(filter),
+        });
+        self
+    }
+
     /// The number of threads to use for traversal.
     ///
     /// Note that this only has an effect when using `build_parallel`.
@@ -588,6 +853,17 @@ impl WalkBuilder {
         self
     }
 
+    /// The order in which directories are visited.
+    ///
+    /// Note that this only has an effect when using `build_parallel`. The
+    /// serial iterator always visits directories depth first. Also note
+    /// that either strategy is only approximate, since entries are consumed
+    /// concurrently by multiple workers.
+    pub fn strategy(&mut self, strategy: WalkStrategy) -> &mut WalkBuilder {
+        self.strategy = strategy;
+        self
+    }
+
     /// Add a global ignore file to the matcher.
     ///
     /// This has lower precedence than all other sources of ignore rules.
@@ -621,6 +897,26 @@ impl WalkBuilder {
         self
     }
 
+    /// Add a custom ignore file name with explicit precedence and scope.
+    ///
+    /// `precedence` controls where rules from this file rank relative to
+    /// `.ignore` and `.gitignore` rules. When `hierarchical` is true, the
+    /// file is read in every directory, like `.gitignore`. Otherwise, it is
+    /// only read in the root directories of the traversal.
+    ///
+    /// Among custom ignore files with the same precedence, earlier names
+    /// have lower precedence than later names.
+    pub fn add_custom_ignore_filename_with<S: AsRef<OsStr>>(
+        &mut self,
+        file_name: S,
+        precedence: CustomIgnorePrecedence,
+        hierarchical: bool,
+    ) -> &mut WalkBuilder {
+        self.ig_builder.add_custom_ignore_filename_with(
+            file_name, precedence, hierarchical);
+        self
+    }
+
     /// Add an override matcher.
     ///
     /// By default, no override matcher is used.
@@ -651,6 +947,7 @@ impl WalkBuilder {
     /// - [git_ignore()](#method.git_ignore)
     /// - [git_global()](#method.git_global)
     /// - [git_exclude()](#method.git_exclude)
+    /// - [hg_ignore()](#method.hg_ignore)
     ///
     /// They may still be toggled individually after calling this function.
     ///
@@ -662,6 +959,7 @@ impl WalkBuilder {
             .git_ignore(yes)
             .git_global(yes)
             .git_exclude(yes)
+            .hg_ignore(yes)
     }
 
     /// Enables ignoring hidden files.
@@ -730,6 +1028,30 @@ impl WalkBuilder {
         self
     }
 
+    /// Enables reading `.hgignore` files.
+    ///
+    /// `.hgignore` files have match semantics as described in the `hgignore`
+    /// man page, including `syntax: glob` and `syntax: regexp` sections. They
+    /// are only used inside Mercurial repositories, i.e., in directories
+    /// containing an `.hg` directory.
+    ///
+    /// This is enabled by default.
+    pub fn hg_ignore(&mut self, yes: bool) -> &mut WalkBuilder {
+        self.ig_builder.hg_ignore(yes);
+        self
+    }
+
+    /// Process ignore globs case insensitively.
+    ///
+    /// This is useful on case insensitive file systems (such as the defaults
+    /// on Windows and macOS), where git itself honors `core.ignoreCase`.
+    ///
+    /// This is disabled by default.
+    pub fn ignore_case_insensitive(&mut self, yes: bool) -> &mut WalkBuilder {
+        self.ig_builder.ignore_case_insensitive(yes);
+        self
+    }
+
     /// Set a function for sorting directory entries by file name.
     ///
     /// If a compare function is set, the resulting iterator will return all
@@ -741,7 +1063,28 @@ impl WalkBuilder {
     pub fn sort_by_file_name<F>(&mut self, cmp: F) -> &mut WalkBuilder
     where F: Fn(&OsStr, &OsStr) -> cmp::Ordering + Send + Sync + 'static
     {
-        self.sorter = Some(Arc::new(cmp));
+        self.sorter = Some(Sorter::ByName(Arc::new(cmp)));
+        self
+    }
+
+    /// Set a function for sorting directory entries by their full file
+    /// paths.
+    ///
+    /// If a compare function is set, the resulting iterator will return all
+    /// paths in sorted order. The compare function will be called to compare
+    /// entries from the same directory.
+    ///
+    /// This is like `sort_by_file_name`, except the comparator has access to
+    /// the full path of each entry, which permits sorting by metadata (such
+    /// as file size or modification time) at the cost of an additional stat
+    /// call per comparison. Setting this overrides any comparator previously
+    /// set by `sort_by_file_name`, and vice versa.
+    ///
+    /// Note that this is not used in the parallel iterator.
+    pub fn sort_by_file_path<F>(&mut self, cmp: F) -> &mut WalkBuilder
+    where F: Fn(&Path, &Path) -> cmp::Ordering + Send + Sync + 'static
+    {
+        self.sorter = Some(Sorter::ByPath(Arc::new(cmp)));
         self
     }
 }
@@ -753,11 +1096,19 @@ impl WalkBuilder {
 /// ignore files like `.gitignore` are respected. The precise matching rules
 /// and precedence is explained in the documentation for `WalkBuilder`.
 pub struct Walk {
-    its: vec::IntoIter<(PathBuf, Option<WalkEventIter>)>,
-    it: Option<WalkEventIter>,
+    its: vec::IntoIter<(PathBuf, Option<WalkDirEventIter>)>,
+    it: Option<WalkDirEventIter>,
     ig_root: Ignore,
     ig: Ignore,
     max_filesize: Option<u64>,
+    filesize_skips: Option<Arc<AtomicUsize>>,
+    max_symlink_depth: Option<usize>,
+    symlink_depth: usize,
+    symlink_stack: Vec<bool>,
+    same_file_system: bool,
+    root_device: Option<u64>,
+    filter: MetadataFilter,
+    sniff: Option<SniffFilter>,
 }
 
 impl Walk {
@@ -783,8 +1134,28 @@ impl Walk {
         } else {
             false
         };
+        if should_skip_filesize {
+            if let Some(ref skips) = self.filesize_skips {
+                skips.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+        let should_skip_metadata = if !is_dir && self.filter.is_active() {
+            self.filter.skip(ent.path(), &ent.metadata().ok())
+        } else {
+            false
+        };
+        // Only sniff files that every other filter accepted, since sniffing
+        // opens the file.
+        let should_skip_sniff = if !is_dir && !should_skip_path
+            && !should_skip_filesize && !should_skip_metadata
+        {
+            self.sniff.as_ref().map_or(false, |f| f.skip(ent.path()))
+        } else {
+            false
+        };
 
-        should_skip_path || should_skip_filesize
+        should_skip_path || should_skip_filesize || should_skip_metadata
+            || should_skip_sniff
     }
 }
 
@@ -804,6 +1175,18 @@ impl Iterator for Walk {
                         }
                         Some((path, Some(it))) => {
                             self.it = Some(it);
+                            if self.same_file_system {
+                                self.root_device =
+                                    match device_num(&path) {
+                                        Ok(dev) => Some(dev),
+                                        Err(err) => {
+                                            self.it = None;
+                                            let err = Error::Io(err)
+                                                .with_path(&path);
+                                            return Some(Err(err));
+                                        }
+                                    };
+                            }
                             if path_is_dir(&path) {
                                 let (ig, err) = self.ig_root.add_parents(path);
                                 self.ig = ig;
@@ -822,14 +1205,48 @@ impl Iterator for Walk {
                 Err(err) => {
                     return Some(Err(Error::from_walkdir(err)));
                 }
-                Ok(WalkEvent::Exit) => {
+                Ok(WalkDirEvent::Exit) => {
                     self.ig = self.ig.parent().unwrap();
+                    if self.symlink_stack.pop().unwrap_or(false) {
+                        self.symlink_depth -= 1;
+                    }
                 }
-                Ok(WalkEvent::Dir(ent)) => {
-                    if self.skip_entry(&ent) {
+                Ok(WalkDirEvent::Dir(ent)) => {
+                    let is_symlink =
+                        ent.depth() > 0 && ent.path_is_symlink();
+                    if is_symlink {
+                        self.symlink_depth += 1;
+                    }
+                    self.symlink_stack.push(is_symlink);
+                    let too_deep =
+                        is_symlink
+                        && self.max_symlink_depth.map_or(
+                            false, |max| self.symlink_depth > max);
+                    let different_fs =
+                        match self.root_device {
+                            None => false,
+                            Some(_) if ent.depth() == 0 => false,
+                            Some(root_device) => {
+                                match is_same_file_system(
+                                    root_device, ent.path()) {
+                                    Ok(same) => !same,
+                                    Err(err) => {
+                                        let it = self.it.as_mut().unwrap();
+                                        it.it.skip_current_dir();
+                                        let (igtmp, _) =
+                                            self.ig.add_child(ent.path());
+                                        self.ig = igtmp;
+                                        let err =
+                                            err.with_depth(ent.depth());
+                                        return Some(Err(err));
+                                    }
+                                }
+                            }
+                        };
+                    if too_deep || different_fs || self.skip_entry(&ent) {
                         self.it.as_mut().unwrap().it.skip_current_dir();
                         // Still need to push this on the stack because
-                        // we'll get a WalkEvent::Exit event for this dir.
+                        // we'll get a WalkDirEvent::Exit event for this dir.
                         // We don't care if it errors though.
                         let (igtmp, _) = self.ig.add_child(ent.path());
                         self.ig = igtmp;
@@ -839,7 +1256,7 @@ impl Iterator for Walk {
                     self.ig = igtmp;
                     return Some(Ok(DirEntry::new_walkdir(ent, err)));
                 }
-                Ok(WalkEvent::File(ent)) => {
+                Ok(WalkDirEvent::File(ent)) => {
                     if self.skip_entry(&ent) {
                         continue;
                     }
@@ -850,34 +1267,107 @@ impl Iterator for Walk {
     }
 }
 
-/// WalkEventIter transforms a WalkDir iterator into an iterator that more
+/// An event in a directory traversal.
+///
+/// Events describe the shape of the directory tree as it is traversed:
+/// every directory produces an `Enter` event before any of its entries and
+/// a matching `Leave` event once all of its entries (including those of its
+/// subdirectories) have been visited. The depth of each event is available
+/// via `DirEntry::depth`.
+///
+/// In the serial iterator, events are delivered in depth first order. In the
+/// parallel iterator, events from different directories may interleave
+/// arbitrarily, but the `Enter`/`Leave` bracketing of each individual
+/// directory is still upheld.
+#[derive(Clone, Debug)]
+pub enum WalkEvent {
+    /// A directory is about to be descended into.
+    Enter(DirEntry),
+    /// A file (or any other non-directory entry) was visited.
+    File(DirEntry),
+    /// All entries beneath the directory have been visited.
+    Leave(DirEntry),
+}
+
+/// WalkEvents is a recursive directory iterator, like `Walk`, except that it
+/// yields directory enter/leave events in addition to file entries.
+///
+/// This makes it possible to do per-directory aggregation or build tree
+/// style displays without reconstructing the hierarchy from flat paths.
+pub struct WalkEvents {
+    walk: Walk,
+    stack: Vec<DirEntry>,
+    next: Option<Option<Result<DirEntry, Error>>>,
+}
+
+impl Iterator for WalkEvents {
+    type Item = Result<WalkEvent, Error>;
+
+    fn next(&mut self) -> Option<Result<WalkEvent, Error>> {
+        let result = match self.next.take() {
+            Some(result) => result,
+            None => self.walk.next(),
+        };
+        let dent = match result {
+            None => {
+                return match self.stack.pop() {
+                    None => None,
+                    Some(dir) => {
+                        // Keep draining the stack on subsequent calls.
+                        self.next = Some(None);
+                        Some(Ok(WalkEvent::Leave(dir)))
+                    }
+                };
+            }
+            Some(Err(err)) => return Some(Err(err)),
+            Some(Ok(dent)) => dent,
+        };
+        // If this entry doesn't live beneath the most recently entered
+        // directory, then that directory's contents have been exhausted.
+        if let Some(dir) = self.stack.last().map(|d| d.depth()) {
+            if dent.depth() <= dir {
+                let dir = self.stack.pop().unwrap();
+                self.next = Some(Some(Ok(dent)));
+                return Some(Ok(WalkEvent::Leave(dir)));
+            }
+        }
+        if dent.is_dir() {
+            self.stack.push(dent.clone());
+            Some(Ok(WalkEvent::Enter(dent)))
+        } else {
+            Some(Ok(WalkEvent::File(dent)))
+        }
+    }
+}
+
+/// WalkDirEventIter transforms a WalkDir iterator into an iterator that more
 /// accurately describes the directory tree. Namely, it emits events that are
 /// one of three types: directory, file or "exit." An "exit" event means that
 /// the entire contents of a directory have been enumerated.
-struct WalkEventIter {
+struct WalkDirEventIter {
     depth: usize,
     it: walkdir::IntoIter,
     next: Option<Result<walkdir::DirEntry, walkdir::Error>>,
 }
 
 #[derive(Debug)]
-enum WalkEvent {
+enum WalkDirEvent {
     Dir(walkdir::DirEntry),
     File(walkdir::DirEntry),
     Exit,
 }
 
-impl From<WalkDir> for WalkEventIter {
-    fn from(it: WalkDir) -> WalkEventIter {
-        WalkEventIter { depth: 0, it: it.into_iter(), next: None }
+impl From<WalkDir> for WalkDirEventIter {
+    fn from(it: WalkDir) -> WalkDirEventIter {
+        WalkDirEventIter { depth: 0, it: it.into_iter(), next: None }
     }
 }
 
-impl Iterator for WalkEventIter {
-    type Item = walkdir::Result<WalkEvent>;
+impl Iterator for WalkDirEventIter {
+    type Item = walkdir::Result<WalkDirEvent>;
 
     #[inline(always)]
-    fn next(&mut self) -> Option<walkdir::Result<WalkEvent>> {
+    fn next(&mut self) -> Option<walkdir::Result<WalkDirEvent>> {
         let dent = self.next.take().or_else(|| self.it.next());
         let depth = match dent {
             None => 0,
@@ -887,7 +1377,7 @@ impl Iterator for WalkEventIter {
         if depth < self.depth {
             self.depth -= 1;
             self.next = dent;
-            return Some(Ok(WalkEvent::Exit));
+            return Some(Ok(WalkDirEvent::Exit));
         }
         self.depth = depth;
         match dent {
@@ -896,9 +1386,9 @@ impl Iterator for WalkEventIter {
             Some(Ok(dent)) => {
                 if walkdir_entry_is_dir(&dent) {
                     self.depth += 1;
-                    Some(Ok(WalkEvent::Dir(dent)))
+                    Some(Ok(WalkDirEvent::Dir(dent)))
                 } else {
-                    Some(Ok(WalkEvent::File(dent)))
+                    Some(Ok(WalkDirEvent::File(dent)))
                 }
             }
         }
@@ -929,6 +1419,27 @@ impl WalkState {
     }
 }
 
+/// WalkStrategy controls the order in which the parallel recursive directory
+/// iterator visits directories.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum WalkStrategy {
+    /// Visit directories approximately breadth first. Shallow entries tend
+    /// to be yielded earlier, which is often preferable in interactive use.
+    ///
+    /// This is the default.
+    BreadthFirst,
+    /// Visit directories approximately depth first. This tends to keep the
+    /// work queue smaller and is therefore better for memory use on very
+    /// deep or wide directory trees.
+    DepthFirst,
+}
+
+impl Default for WalkStrategy {
+    fn default() -> WalkStrategy {
+        WalkStrategy::BreadthFirst
+    }
+}
+
 /// WalkParallel is a parallel recursive directory iterator over files paths
 /// in one or more directories.
 ///
@@ -941,9 +1452,18 @@ pub struct WalkParallel {
     paths: vec::IntoIter<PathBuf>,
     ig_root: Ignore,
     max_filesize: Option<u64>,
+    filesize_skips: Option<Arc<AtomicUsize>>,
+    filter: MetadataFilter,
+    sniff: Option<SniffFilter>,
     max_depth: Option<usize>,
+    max_symlink_depth: Option<usize>,
     follow_links: bool,
+    same_file_system: bool,
+    max_open: Option<usize>,
+    steal_batch_size: usize,
+    traversal_cache: Option<PathBuf>,
     threads: usize,
+    strategy: WalkStrategy,
 }
 
 impl WalkParallel {
@@ -954,20 +1474,87 @@ impl WalkParallel {
         self,
         mut mkf: F,
     ) where F: FnMut() -> Box<FnMut(Result<DirEntry, Error>) -> WalkState + Send + 'static> {
+        self.run_inner(false, || {
+            let mut f = mkf();
+            Box::new(move |event| {
+                match event {
+                    Ok(WalkEvent::Enter(dent))
+                    | Ok(WalkEvent::File(dent)) => f(Ok(dent)),
+                    Ok(WalkEvent::Leave(_)) => WalkState::Continue,
+                    Err(err) => f(Err(err)),
+                }
+            })
+        })
+    }
+
+    /// Execute the parallel recursive directory iterator, delivering
+    /// directory enter/leave events in addition to file entries. `mkf` is
+    /// called for each thread used for iteration, just as with `run`.
+    ///
+    /// Events from different directories may be interleaved arbitrarily
+    /// across threads, but every directory's enter event is delivered before
+    /// any event beneath it and its leave event is delivered after all of
+    /// them. Returning `WalkState::Skip` from an enter event prevents
+    /// descending into the directory; its leave event is still delivered.
+    pub fn run_events<F>(
+        self,
+        mkf: F,
+    ) where F: FnMut() -> Box<FnMut(Result<WalkEvent, Error>) -> WalkState + Send + 'static> {
+        self.run_inner(true, mkf)
+    }
+
+    fn run_inner<F>(
+        self,
+        events: bool,
+        mut mkf: F,
+    ) where F: FnMut() -> Box<FnMut(Result<WalkEvent, Error>) -> WalkState + Send + 'static> {
         let mut f = mkf();
         let threads = self.threads();
-        let queue = Arc::new(MsQueue::new());
-        let mut any_work = false;
-        // Send the initial set of root paths to the pool of workers.
+        let queues = Arc::new(WorkQueues::new(
+            threads, self.strategy, self.steal_batch_size));
+        let open_limit = self.max_open.map(|limit| {
+            Arc::new(OpenLimit::new(cmp::max(1, limit)))
+        });
+        // The fingerprint ties cached outcomes to the matcher configuration
+        // that produced them, so that a differently configured walker never
+        // reuses them.
+        let cache = self.traversal_cache.as_ref().map(|path| {
+            let mut hasher = DefaultHasher::new();
+            format!("{:?}", self.ig_root).hash(&mut hasher);
+            self.follow_links.hash(&mut hasher);
+            Arc::new(TraversalCache::load(
+                path, hasher.finish(), self.ig_root.ignore_file_names()))
+        });
+        let mut num_roots = 0;
+        // Send the initial set of root paths to the pool of workers,
+        // distributed round robin over the per-worker queues.
         // Note that we only send directories. For files, we send to them the
         // callback directly.
         for path in self.paths {
-            let dent =
+            let (dent, root_device) =
                 if path == Path::new("-") {
-                    DirEntry::new_stdin()
+                    (DirEntry::new_stdin(), None)
                 } else {
+                    let root_device =
+                        if !self.same_file_system {
+                            None
+                        } else {
+                            match device_num(&path) {
+                                Ok(root_device) => Some(root_device),
+                                Err(err) => {
+                                    let err = Error::Io(err)
+                                        .with_path(&path);
+                                    if f(Err(err)).is_quit() {
+                                        return;
+                                    }
+                                    continue;
+                                }
+                            }
+                        };
                     match DirEntryRaw::from_link(0, path) {
-                        Ok(dent) => DirEntry::new_raw(dent, None),
+                        Ok(dent) => {
+                            (DirEntry::new_raw(dent, None), root_device)
+                        }
                         Err(err) => {
                             if f(Err(err)).is_quit() {
                                 return;
@@ -976,14 +1563,17 @@ impl WalkParallel {
                         }
                     }
                 };
-            queue.push(Message::Work(Work {
+            queues.push(num_roots % threads, Message::Work(Work {
                 dent: dent,
                 ignore: self.ig_root.clone(),
+                parent: None,
+                symlink_depth: 0,
+                root_device: root_device,
             }));
-            any_work = true;
+            num_roots += 1;
         }
         // ... but there's no need to start workers if we don't need them.
-        if !any_work {
+        if num_roots == 0 {
             return;
         }
         // Create the workers and then wait for them to finish.
@@ -991,10 +1581,13 @@ impl WalkParallel {
         let num_quitting = Arc::new(AtomicUsize::new(0));
         let quit_now = Arc::new(AtomicBool::new(false));
         let mut handles = vec![];
-        for _ in 0..threads {
+        for id in 0..threads {
             let worker = Worker {
+                id: id,
                 f: mkf(),
-                queue: queue.clone(),
+                queues: queues.clone(),
+                open_limit: open_limit.clone(),
+                cache: cache.clone(),
                 quit_now: quit_now.clone(),
                 is_waiting: false,
                 is_quitting: false,
@@ -1003,13 +1596,76 @@ impl WalkParallel {
                 threads: threads,
                 max_depth: self.max_depth,
                 max_filesize: self.max_filesize,
+                filesize_skips: self.filesize_skips.clone(),
+                max_symlink_depth: self.max_symlink_depth,
+                filter: self.filter.clone(),
+                sniff: self.sniff.clone(),
                 follow_links: self.follow_links,
+                events: events,
             };
             handles.push(thread::spawn(|| worker.run()));
         }
         for handle in handles {
             handle.join().unwrap();
         }
+        if let Some(ref cache) = cache {
+            if let Err(err) = cache.save() {
+                debug!("failed to write traversal cache: {}", err);
+            }
+        }
+    }
+
+    /// Execute the parallel recursive directory iterator and deliver the
+    /// results in lexicographic path order.
+    ///
+    /// The traversal itself still uses multiple threads, so the expensive
+    /// work of reading directories and matching ignore rules is done in
+    /// parallel. The results are buffered and reordered before they are
+    /// handed to the callback, which means nothing is delivered until the
+    /// traversal has finished and the peak memory use is proportional to the
+    /// number of results. Returning `WalkState::Quit` from the callback
+    /// stops delivery of any further results.
+    pub fn run_sorted<F>(
+        self,
+        mut f: F,
+    ) where F: FnMut(Result<DirEntry, Error>) -> WalkState {
+        let results = Arc::new(Mutex::new(vec![]));
+        self.run(|| {
+            let results = results.clone();
+            Box::new(move |result| {
+                let key = result_path(&result);
+                results.lock().unwrap().push((key, result));
+                WalkState::Continue
+            })
+        });
+        let mut results = results.lock().unwrap();
+        results.sort_by(|&(ref p1, _), &(ref p2, _)| p1.cmp(p2));
+        for (_, result) in results.drain(..) {
+            if f(result).is_quit() {
+                return;
+            }
+        }
+    }
+
+    /// Turn the parallel traversal into an iterator over its results.
+    ///
+    /// The traversal runs on a pool of background threads and entries are
+    /// handed to the iterator through a bounded channel holding up to
+    /// `capacity` entries, so a slow consumer exerts backpressure on the
+    /// traversal instead of buffering results without bound. Entries are
+    /// yielded in an arbitrary order. Dropping the iterator before it is
+    /// exhausted stops the traversal.
+    pub fn into_iter(self, capacity: usize) -> WalkParallelIter {
+        WalkParallelIter::new(self, capacity, false)
+    }
+
+    /// Like `into_iter`, but yields entries in lexicographic path order.
+    ///
+    /// This has the same caveat as `run_sorted`: results are buffered and
+    /// reordered before any of them are yielded, so nothing is delivered
+    /// until the traversal has finished.
+    pub fn into_iter_sorted(self, capacity: usize) -> WalkParallelIter {
+        WalkParallelIter::new(self, capacity, true)
     }
 
     fn threads(&self) -> usize {
@@ -1021,6 +1677,93 @@ impl WalkParallel {
     }
 }
 
+/// An iterator over the results of a parallel traversal, created by
+/// `WalkParallel::into_iter` or `WalkParallel::into_iter_sorted`.
+pub struct WalkParallelIter {
+    rx: Option<mpsc::Receiver<Result<DirEntry, Error>>>,
+    handle: Option<thread::JoinHandle<()>>,
+}
+
+impl WalkParallelIter {
+    fn new(
+        walker: WalkParallel,
+        capacity: usize,
+        sorted: bool,
+    ) -> WalkParallelIter {
+        let (tx, rx) = mpsc::sync_channel(capacity);
+        let handle = thread::spawn(move || {
+            if sorted {
+                walker.run_sorted(move |result| {
+                    if tx.send(result).is_err() {
+                        WalkState::Quit
+                    } else {
+                        WalkState::Continue
+                    }
+                });
+            } else {
+                walker.run(|| {
+                    let tx = tx.clone();
+                    Box::new(move |result| {
+                        if tx.send(result).is_err() {
+                            WalkState::Quit
+                        } else {
+                            WalkState::Continue
+                        }
+                    })
+                });
+            }
+        });
+        WalkParallelIter {
+            rx: Some(rx),
+            handle: Some(handle),
+        }
+    }
+
+    fn join(&mut self) {
+        // Dropping the receiver first makes any send that a worker is
+        // blocked on fail, which quits the traversal.
+        self.rx = None;
+        if let Some(handle) = self.handle.take() {
+            handle.join().unwrap();
+        }
+    }
+}
+
+impl Iterator for WalkParallelIter {
+    type Item = Result<DirEntry, Error>;
+
+    fn next(&mut self) -> Option<Result<DirEntry, Error>> {
+        let result = match self.rx {
+            None => return None,
+            Some(ref rx) => rx.recv(),
+        };
+        match result {
+            Ok(result) => Some(result),
+            Err(_) => {
+                // All senders are gone, so the traversal has finished.
+                self.join();
+                None
+            }
+        }
+    }
+}
+
+impl Drop for WalkParallelIter {
+    fn drop(&mut self) {
+        self.join();
+    }
+}
+
+/// Returns the path to sort the given walk result by. Errors that don't
+/// carry a path sort before everything else.
+fn result_path(result: &Result<DirEntry, Error>) -> PathBuf {
+    match *result {
+        Ok(ref dent) => dent.path().to_path_buf(),
+        Err(Error::WithPath { ref path, .. }) => path.clone(),
+        Err(_) => PathBuf::new(),
+    }
+}
+
 /// Message is the set of instructions that a worker knows how to process.
 enum Message {
     /// A work item corresponds to a directory that should be descended into.
@@ -1031,6 +1774,142 @@ enum Message {
     Quit,
 }
 
+/// The set of work queues shared by all workers, with work stealing.
+///
+/// Each worker pushes and pops work on its own queue, which keeps
+/// contention low even when many threads churn through a very wide
+/// directory. A worker whose own queue is empty steals a batch of work
+/// from the queue of another worker instead of going idle.
+struct WorkQueues {
+    /// The per-worker queues, indexed by worker id.
+    queues: Vec<Mutex<VecDeque<Message>>>,
+    /// The walk strategy, which decides which end of its own queue a
+    /// worker pops. Stealing always takes the oldest work.
+    strategy: WalkStrategy,
+    /// The maximum number of messages moved per steal.
+    batch_size: usize,
+}
+
+impl WorkQueues {
+    fn new(
+        threads: usize,
+        strategy: WalkStrategy,
+        batch_size: usize,
+    ) -> WorkQueues {
+        WorkQueues {
+            queues: (0..threads).map(|_| Mutex::new(VecDeque::new())).collect(),
+            strategy: strategy,
+            batch_size: cmp::max(1, batch_size),
+        }
+    }
+
+    /// Push a message on to the queue owned by the given worker.
+    fn push(&self, worker: usize, msg: Message) {
+        self.queues[worker].lock().unwrap().push_back(msg);
+    }
+
+    /// Pop the next message for the given worker.
+    ///
+    /// The worker's own queue is popped according to the walk strategy:
+    /// FIFO order gives an approximately breadth first traversal, while
+    /// LIFO order gives an approximately depth first traversal. If the
+    /// worker's own queue is empty, then this attempts to steal a batch of
+    /// work from the other queues. Stolen work beyond the message returned
+    /// is moved to the worker's own queue.
+    fn try_pop(&self, worker: usize) -> Option<Message> {
+        if let Some(msg) = self.pop_own(worker) {
+            return Some(msg);
+        }
+        self.steal(worker)
+    }
+
+    /// Pop a message from the given worker's own queue.
+    fn pop_own(&self, worker: usize) -> Option<Message> {
+        let mut queue = self.queues[worker].lock().unwrap();
+        match self.strategy {
+            WalkStrategy::BreadthFirst => queue.pop_front(),
+            WalkStrategy::DepthFirst => queue.pop_back(),
+        }
+    }
+
+    /// Steal a batch of work from the queues of the other workers.
+    ///
+    /// Quit messages are never stolen, since every worker is sent one of
+    /// its own.
+    fn steal(&self, worker: usize) -> Option<Message> {
+        let threads = self.queues.len();
+        for i in 1..threads {
+            let victim = (worker + i) % threads;
+            let mut stolen = {
+                let mut queue = self.queues[victim].lock().unwrap();
+                let mut stolen = Vec::with_capacity(self.batch_size);
+                let mut j = 0;
+                while j < queue.len() && stolen.len() < self.batch_size {
+                    if let Message::Work(_) = queue[j] {
+                        stolen.push(queue.remove(j).unwrap());
+                    } else {
+                        j += 1;
+                    }
+                }
+                stolen
+            };
+            if stolen.is_empty() {
+                continue;
+            }
+            let first = stolen.remove(0);
+            if !stolen.is_empty() {
+                let mut own = self.queues[worker].lock().unwrap();
+                // Preserve the order in which the work was stolen.
+                for msg in stolen.into_iter().rev() {
+                    own.push_front(msg);
+                }
+            }
+            return Some(first);
+        }
+        None
+    }
+}
+
+/// A counting semaphore that caps the number of simultaneously open
+/// directory handles across all workers.
+struct OpenLimit {
+    /// The number of slots still available.
+    available: Mutex<usize>,
+    /// Signalled when a slot is released.
+    cond: Condvar,
+}
+
+impl OpenLimit {
+    fn new(slots: usize) -> OpenLimit {
+        OpenLimit {
+            available: Mutex::new(slots),
+            cond: Condvar::new(),
+        }
+    }
+
+    /// Acquires a slot, blocking until one is available. The slot is
+    /// released when the returned guard is dropped.
+    fn acquire(lim: &Arc<OpenLimit>) -> OpenLimitGuard {
+        let mut available = lim.available.lock().unwrap();
+        while *available == 0 {
+            available = lim.cond.wait(available).unwrap();
+        }
+        *available -= 1;
+        OpenLimitGuard(lim.clone())
+    }
+}
+
+/// Releases a slot of an `OpenLimit` when dropped.
+struct OpenLimitGuard(Arc<OpenLimit>);
+
+impl Drop for OpenLimitGuard {
+    fn drop(&mut self) {
+        let mut available = self.0.available.lock().unwrap();
+        *available += 1;
+        self.0.cond.notify_one();
+    }
+}
+
 /// A unit of work for each worker to process.
 ///
 /// Each unit of work corresponds to a directory that should be descended
@@ -1040,6 +1919,16 @@ struct Work {
     dent: DirEntry,
     /// Any ignore matchers that have been built for this directory's parents.
     ignore: Ignore,
+    /// Completion state of the parent directory, if leave events were
+    /// requested. This is `None` for root paths and when the caller used
+    /// `run` instead of `run_events`.
+    parent: Option<Arc<DirState>>,
+    /// The number of symbolic links that were followed to reach this
+    /// directory.
+    symlink_depth: usize,
+    /// The device number of the traversal root, if the traversal should
+    /// not cross file system boundaries.
+    root_device: Option<u64>,
 }
 
 impl Work {
@@ -1092,15 +1981,41 @@ impl Work {
     }
 }
 
+/// The completion state of a directory in an event producing parallel
+/// traversal.
+///
+/// Every unit of work beneath a directory (including the enumeration of the
+/// directory itself) holds one outstanding count. The worker that releases
+/// the last count delivers the directory's leave event and then releases the
+/// parent's count, which ratchets leave events up the tree as subtrees
+/// finish.
+struct DirState {
+    /// The directory entry that was delivered with the enter event.
+    dent: DirEntry,
+    /// The state of the parent directory, if any.
+    parent: Option<Arc<DirState>>,
+    /// The number of units of work beneath this directory that have not yet
+    /// completed.
+    outstanding: AtomicUsize,
+}
+
 /// A worker is responsible for descending into directories, updating the
 /// ignore matchers, producing new work and invoking the caller's callback.
 ///
 /// Note that a worker is *both* a producer and a consumer.
 struct Worker {
+    /// The id of this worker, which is the index of its own work queue.
+    id: usize,
     /// The caller's callback.
-    f: Box<FnMut(Result<DirEntry, Error>) -> WalkState + Send + 'static>,
-    /// A queue of work items. This is multi-producer and multi-consumer.
-    queue: Arc<MsQueue<Message>>,
+    f: Box<FnMut(Result<WalkEvent, Error>) -> WalkState + Send + 'static>,
+    /// The work queues of all workers, supporting work stealing.
+    queues: Arc<WorkQueues>,
+    /// A cap on the number of simultaneously open directory handles,
+    /// shared by all workers. `None` means no cap.
+    open_limit: Option<Arc<OpenLimit>>,
+    /// A persistent cache of ignore match outcomes, shared by all workers.
+    /// `None` means no cache is in use.
+    cache: Option<Arc<TraversalCache>>,
     /// Whether all workers should quit at the next opportunity. Note that
     /// this is distinct from quitting because of exhausting the contents of
     /// a directory. Instead, this is used when the caller's callback indicates
@@ -1122,9 +2037,24 @@ struct Worker {
     /// The maximum size a searched file can be (in bytes). If a file exceeds
     /// this size it will be skipped.
     max_filesize: Option<u64>,
+    /// A counter of files skipped because of the maximum file size, if one
+    /// was requested.
+    filesize_skips: Option<Arc<AtomicUsize>>,
+    /// The maximum number of nested symbolic links to follow. A directory
+    /// reached through more symbolic links than this is skipped.
+    max_symlink_depth: Option<usize>,
+    /// Metadata based filters (file size, modification time and file type)
+    /// to apply to non-directory entries.
+    filter: MetadataFilter,
+    /// A content sniffing filter to apply to non-directory entries that
+    /// every other filter accepted. `None` means no sniffing.
+    sniff: Option<SniffFilter>,
     /// Whether to follow symbolic links or not. When this is enabled, loop
     /// detection is performed.
     follow_links: bool,
+    /// Whether leave events were requested. When this is disabled, no
+    /// directory completion state is tracked.
+    events: bool,
 }
 
 impl Worker {
@@ -1134,10 +2064,15 @@ impl Worker {
     /// skipped by the ignore matcher.
     fn run(mut self) {
         while let Some(mut work) = self.get_work() {
+            let parent = work.parent.take();
             // If the work is not a directory, then we can just execute the
             // caller's callback immediately and move on.
             if work.is_symlink() || !work.is_dir() {
-                if (self.f)(Ok(work.dent)).is_quit() {
+                if (self.f)(Ok(WalkEvent::File(work.dent))).is_quit() {
+                    self.quit_now();
+                    return;
+                }
+                if self.finish_dir(parent).is_quit() {
                     self.quit_now();
                     return;
                 }
@@ -1149,6 +2084,9 @@ impl Worker {
                     return;
                 }
             }
+            // Hold a slot of the open handle budget for as long as the
+            // directory iterator is alive.
+            let _open = self.open_limit.as_ref().map(OpenLimit::acquire);
             let readdir = match work.read_dir() {
                 Ok(readdir) => readdir,
                 Err(err) => {
@@ -1156,28 +2094,113 @@ impl Worker {
                         self.quit_now();
                         return;
                     }
+                    if self.finish_dir(parent).is_quit() {
+                        self.quit_now();
+                        return;
+                    }
+                    continue;
+                }
+            };
+            let depth = work.dent.depth();
+            // Consult the traversal cache, if one is in use. A hit means
+            // that this directory has not changed since the cached walk, so
+            // its recorded ignore match outcomes can be reused. A miss
+            // records fresh outcomes for the next walk.
+            let mut cached_entries = None;
+            let mut record = None;
+            if let Some(ref cache) = self.cache {
+                let path = work.dent.path().to_path_buf();
+                let mtime = fs::metadata(&path)
+                    .ok()
+                    .and_then(|md| cache::modified(&md));
+                if let Some(mtime) = mtime {
+                    cache.record_ignore_files(&path);
+                    match cache.lookup(&path, mtime) {
+                        Some(entries) => cached_entries = Some(entries),
+                        None => {
+                            record = Some(
+                                (cache.clone(), path, mtime, HashMap::new()));
+                        }
+                    }
+                }
+            }
+            // Track the completion of this directory so that its leave
+            // event can be delivered once all of its entries have been
+            // visited. The initial count is released after enumeration.
+            let state =
+                if self.events {
+                    Some(Arc::new(DirState {
+                        dent: work.dent.clone(),
+                        parent: parent,
+                        outstanding: AtomicUsize::new(1),
+                    }))
+                } else {
+                    None
+                };
+            match (self.f)(Ok(WalkEvent::Enter(work.dent))) {
+                WalkState::Continue => {}
+                WalkState::Skip => {
+                    if self.finish_dir(state).is_quit() {
+                        self.quit_now();
+                        return;
+                    }
                     continue;
                 }
-            };
-            let depth = work.dent.depth();
-            match (self.f)(Ok(work.dent)) {
-                WalkState::Continue => {}
-                WalkState::Skip => continue,
                 WalkState::Quit => {
                     self.quit_now();
                     return;
                 }
             }
             if self.max_depth.map_or(false, |max| depth >= max) {
+                if self.finish_dir(state).is_quit() {
+                    self.quit_now();
+                    return;
+                }
                 continue;
             }
             for result in readdir {
-                if self.run_one(&work.ignore, depth + 1, result).is_quit() {
+                let st = self.run_one(
+                    &work.ignore,
+                    depth + 1,
+                    work.symlink_depth,
+                    work.root_device,
+                    &state,
+                    cached_entries.as_ref().map(|entries| &**entries),
+                    record.as_mut().map(|r| &mut r.3),
+                    result,
+                );
+                if st.is_quit() {
                     self.quit_now();
                     return;
                 }
             }
+            if let Some((cache, path, mtime, entries)) = record {
+                cache.record_dir(&path, mtime, entries);
+            }
+            if self.finish_dir(state).is_quit() {
+                self.quit_now();
+                return;
+            }
+        }
+    }
+
+    /// Signals that one unit of work beneath the given directory has
+    /// completed. Delivers leave events for every directory whose subtree
+    /// has now been fully visited.
+    ///
+    /// This is a no-op when leave events weren't requested.
+    fn finish_dir(&mut self, state: Option<Arc<DirState>>) -> WalkState {
+        let mut cur = state;
+        while let Some(state) = cur {
+            if state.outstanding.fetch_sub(1, Ordering::SeqCst) != 1 {
+                break;
+            }
+            if (self.f)(Ok(WalkEvent::Leave(state.dent.clone()))).is_quit() {
+                return WalkState::Quit;
+            }
+            cur = state.parent.clone();
         }
+        WalkState::Continue
     }
 
     /// Runs the worker on a single entry from a directory iterator.
@@ -1190,12 +2213,24 @@ impl Worker {
     /// caller's callback.
     ///
     /// `ig` is the `Ignore` matcher for the parent directory. `depth` should
-    /// be the depth of this entry. `result` should be the item yielded by
-    /// a directory iterator.
+    /// be the depth of this entry. `symlink_depth` should be the number of
+    /// symbolic links followed to reach the parent directory. `root_device`
+    /// should be the device number of the traversal root, if crossing file
+    /// system boundaries is prohibited. `parent` should be the completion
+    /// state of the parent directory, if leave events were requested.
+    /// `cached` should be the cached ignore match outcomes of the parent
+    /// directory, if the traversal cache had them, while `record` should be
+    /// the map that fresh outcomes are recorded in otherwise. `result`
+    /// should be the item yielded by a directory iterator.
     fn run_one(
         &mut self,
         ig: &Ignore,
         depth: usize,
+        symlink_depth: usize,
+        root_device: Option<u64>,
+        parent: &Option<Arc<DirState>>,
+        cached: Option<&HashMap<OsString, bool>>,
+        record: Option<&mut HashMap<OsString, bool>>,
         result: Result<fs::DirEntry, io::Error>,
     ) -> WalkState {
         let fs_dent = match result {
@@ -1204,6 +2239,7 @@ impl Worker {
                 return (self.f)(Err(Error::from(err).with_depth(depth)));
             }
         };
+        let name = fs_dent.file_name();
         let mut dent = match DirEntryRaw::from_entry(depth, &fs_dent) {
             Ok(dent) => DirEntry::new_raw(dent, None),
             Err(err) => {
@@ -1225,19 +2261,83 @@ impl Worker {
                 }
             }
         }
+        let symlink_depth =
+            if self.follow_links && is_symlink {
+                symlink_depth + 1
+            } else {
+                symlink_depth
+            };
         let is_dir = dent.is_dir();
+        if is_dir && is_symlink
+            && self.max_symlink_depth.map_or(
+                false, |max| symlink_depth > max)
+        {
+            debug!("ignoring {}: max symlink depth exceeded",
+                   dent.path().display());
+            return WalkState::Continue;
+        }
+        if is_dir {
+            if let Some(root_device) = root_device {
+                match is_same_file_system(root_device, dent.path()) {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        debug!("ignoring {}: different file system",
+                               dent.path().display());
+                        return WalkState::Continue;
+                    }
+                    Err(err) => {
+                        return (self.f)(Err(err.with_depth(depth)));
+                    }
+                }
+            }
+        }
         let max_size = self.max_filesize;
-        let should_skip_path = skip_path(ig, dent.path(), is_dir);
+        let should_skip_path = match cached.and_then(|m| m.get(&name)) {
+            Some(&skip) => skip,
+            None => {
+                let skip = skip_path(ig, dent.path(), is_dir);
+                if let Some(record) = record {
+                    record.insert(name, skip);
+                }
+                skip
+            }
+        };
         let should_skip_filesize = if !is_dir && max_size.is_some() {
             skip_filesize(max_size.unwrap(), dent.path(), &dent.metadata().ok())
         } else {
             false
         };
+        if should_skip_filesize {
+            if let Some(ref skips) = self.filesize_skips {
+                skips.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+        let should_skip_metadata = if !is_dir && self.filter.is_active() {
+            self.filter.skip(dent.path(), &dent.metadata().ok())
+        } else {
+            false
+        };
+        // Only sniff files that every other filter accepted, since sniffing
+        // opens the file.
+        let should_skip_sniff = if !is_dir && !should_skip_path
+            && !should_skip_filesize && !should_skip_metadata
+        {
+            self.sniff.as_ref().map_or(false, |f| f.skip(dent.path()))
+        } else {
+            false
+        };
 
-        if !should_skip_path && !should_skip_filesize {
-            self.queue.push(Message::Work(Work {
+        if !should_skip_path && !should_skip_filesize
+            && !should_skip_metadata && !should_skip_sniff {
+            if let Some(ref parent) = *parent {
+                parent.outstanding.fetch_add(1, Ordering::SeqCst);
+            }
+            self.queues.push(self.id, Message::Work(Work {
                 dent: dent,
                 ignore: ig.clone(),
+                parent: parent.clone(),
+                symlink_depth: symlink_depth,
+                root_device: root_device,
             }));
         }
         WalkState::Continue
@@ -1252,7 +2352,7 @@ impl Worker {
             if self.is_quit_now() {
                 return None;
             }
-            match self.queue.try_pop() {
+            match self.queues.try_pop(self.id) {
                 Some(Message::Work(work)) => {
                     self.waiting(false);
                     self.quitting(false);
@@ -1293,8 +2393,8 @@ impl Worker {
                     self.waiting(true);
                     self.quitting(false);
                     if self.num_waiting() == self.threads {
-                        for _ in 0..self.threads {
-                            self.queue.push(Message::Quit);
+                        for id in 0..self.threads {
+                            self.queues.push(id, Message::Quit);
                         }
                     } else {
                         // You're right to consider this suspicious, but it's
@@ -1370,20 +2470,173 @@ fn check_symlink_loop(
     let hchild = Handle::from_path(child_path).map_err(|err| {
         Error::from(err).with_path(child_path).with_depth(child_depth)
     })?;
+    // The directories visited on the way up, so that a loop error can
+    // report the entire cycle instead of just its two end points.
+    let mut visited = vec![];
     for ig in ig_parent.parents().take_while(|ig| !ig.is_absolute_parent()) {
         let h = Handle::from_path(ig.path()).map_err(|err| {
             Error::from(err).with_path(child_path).with_depth(child_depth)
         })?;
         if hchild == h {
+            visited.push(ig.path().to_path_buf());
+            visited.reverse();
+            visited.push(child_path.to_path_buf());
             return Err(Error::Loop {
                 ancestor: ig.path().to_path_buf(),
                 child: child_path.to_path_buf(),
+                chain: visited,
             }.with_depth(child_depth));
         }
+        visited.push(ig.path().to_path_buf());
     }
     Ok(())
 }
 
+/// Returns true if and only if the given path is on the file system
+/// identified by the given root device number.
+fn is_same_file_system(root_device: u64, path: &Path) -> Result<bool, Error> {
+    let dent_device = device_num(path)
+        .map_err(|err| Error::Io(err).with_path(path))?;
+    Ok(root_device == dent_device)
+}
+
+/// Returns the device number of the file system containing the given path.
+#[cfg(unix)]
+fn device_num<P: AsRef<Path>>(path: P) -> io::Result<u64> {
+    use std::os::unix::fs::MetadataExt;
+
+    path.as_ref().metadata().map(|md| md.dev())
+}
+
+/// Returns the device number of the file system containing the given path.
+#[cfg(not(unix))]
+fn device_num<P: AsRef<Path>>(_: P) -> io::Result<u64> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "ignore: same_file_system is not supported on this platform",
+    ))
+}
+
+/// A set of metadata based filters applied to non-directory entries during
+/// traversal. An entry that is skipped by any one of the filters is never
+/// yielded.
+#[derive(Clone, Default)]
+struct MetadataFilter {
+    min_filesize: Option<u64>,
+    modified_after: Option<SystemTime>,
+    modified_before: Option<SystemTime>,
+    entry_type: Option<Arc<Fn(&FileType) -> bool + Send + Sync + 'static>>,
+}
+
+impl MetadataFilter {
+    /// Returns true if at least one filter is set. When this returns false,
+    /// callers can avoid the file stat implied by `skip`.
+    fn is_active(&self) -> bool {
+        self.min_filesize.is_some()
+        || self.modified_after.is_some()
+        || self.modified_before.is_some()
+        || self.entry_type.is_some()
+    }
+
+    /// Returns true if the entry with the given metadata should be skipped.
+    ///
+    /// If the metadata could not be read, then the entry is not skipped.
+    fn skip(&self, path: &Path, ent: &Option<Metadata>) -> bool {
+        let md = match *ent {
+            Some(ref md) => md,
+            None => return false,
+        };
+        if let Some(min) = self.min_filesize {
+            if md.len() < min {
+                debug!("ignoring {}: {} bytes", path.display(), md.len());
+                return true;
+            }
+        }
+        if self.modified_after.is_some() || self.modified_before.is_some() {
+            if let Ok(modified) = md.modified() {
+                let too_old =
+                    self.modified_after.map_or(false, |t| modified < t);
+                let too_new =
+                    self.modified_before.map_or(false, |t| modified > t);
+                if too_old || too_new {
+                    debug!("ignoring {}: {:?}", path.display(), modified);
+                    return true;
+                }
+            }
+        }
+        if let Some(ref entry_type) = self.entry_type {
+            if !entry_type(&md.file_type()) {
+                debug!("ignoring {}: {:?}", path.display(), md.file_type());
+                return true;
+            }
+        }
+        false
+    }
+}
+
+impl fmt::Debug for MetadataFilter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MetadataFilter")
+            .field("min_filesize", &self.min_filesize)
+            .field("modified_after", &self.modified_after)
+            .field("modified_before", &self.modified_before)
+            .field(
+                "entry_type",
+                if self.entry_type.is_some() { &"Some(...)" } else { &"None" },
+            )
+            .finish()
+    }
+}
+
+/// A filter that inspects the leading bytes of candidate files.
+#[derive(Clone)]
+struct SniffFilter {
+    /// The number of leading bytes handed to the predicate.
+    len: usize,
+    /// The predicate. Files for which this returns `false` are skipped.
+    pred: Arc<Fn(&Path, &[u8]) -> bool + Send + Sync + 'static>,
+}
+
+impl SniffFilter {
+    /// Returns true if the file at the given path should be skipped.
+    ///
+    /// This opens the file and reads up to `self.len` leading bytes (fewer
+    /// if the file is shorter). If the file cannot be read, then it is not
+    /// skipped.
+    fn skip(&self, path: &Path) -> bool {
+        let mut file = match fs::File::open(path) {
+            Ok(file) => file,
+            Err(_) => return false,
+        };
+        let mut buf = vec![0; self.len];
+        let mut n = 0;
+        while n < buf.len() {
+            match file.read(&mut buf[n..]) {
+                Ok(0) => break,
+                Ok(m) => n += m,
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {
+                    continue;
+                }
+                Err(_) => return false,
+            }
+        }
+        let skip = !(self.pred)(path, &buf[..n]);
+        if skip {
+            debug!("ignoring {}: rejected by sniff filter", path.display());
+        }
+        skip
+    }
+}
+
+impl fmt::Debug for SniffFilter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SniffFilter")
+            .field("len", &self.len)
+            .field("pred", &"...")
+            .finish()
+    }
+}
+
 // Before calling this function, make sure that you ensure that is really
 // necessary as the arguments imply a file stat.
 fn skip_filesize(
@@ -1483,10 +2736,11 @@ mod tests {
     use std::io::Write;
     use std::path::Path;
     use std::sync::{Arc, Mutex};
+    use std::time::{Duration, SystemTime};
 
     use tempdir::TempDir;
 
-    use super::{WalkBuilder, WalkState};
+    use super::{WalkBuilder, WalkEvent, WalkState};
 
     fn wfile<P: AsRef<Path>>(path: P, contents: &str) {
         let mut file = File::create(path).unwrap();
@@ -1567,6 +2821,16 @@ mod tests {
         paths
     }
 
+    fn describe_event(prefix: &Path, ev: &WalkEvent) -> String {
+        let (kind, dent) = match *ev {
+            WalkEvent::Enter(ref dent) => ("enter", dent),
+            WalkEvent::File(ref dent) => ("file", dent),
+            WalkEvent::Leave(ref dent) => ("leave", dent),
+        };
+        let path = dent.path().strip_prefix(prefix).unwrap();
+        format!("{} {}", kind, normal_path(path.to_str().unwrap()))
+    }
+
     fn assert_paths(
         prefix: &Path,
         builder: &WalkBuilder,
@@ -1591,6 +2855,122 @@ mod tests {
         ]);
     }
 
+    #[test]
+    fn gitignore_case_insensitive() {
+        let td = TempDir::new("walk-test-").unwrap();
+        mkdirp(td.path().join(".git"));
+        mkdirp(td.path().join("build"));
+        wfile(td.path().join(".gitignore"), "Build/");
+        wfile(td.path().join("build/foo"), "");
+
+        // By default, gitignore globs are case sensitive.
+        assert_paths(td.path(), &WalkBuilder::new(td.path()), &[
+            "build", "build/foo",
+        ]);
+
+        let mut builder = WalkBuilder::new(td.path());
+        builder.ignore_case_insensitive(true);
+        assert_paths(td.path(), &builder, &[]);
+    }
+
+    #[test]
+    fn parallel_iter() {
+        let td = TempDir::new("walk-test-").unwrap();
+        mkdirp(td.path().join("a/b/c"));
+        mkdirp(td.path().join("x/y"));
+        wfile(td.path().join("a/b/foo"), "");
+        wfile(td.path().join("x/y/foo"), "");
+
+        let expected = mkpaths(&[
+            "x", "x/y", "x/y/foo", "a", "a/b", "a/b/foo", "a/b/c",
+        ]);
+        // A capacity of 1 exercises backpressure on the traversal.
+        let mut got: Vec<String> = WalkBuilder::new(td.path())
+            .build_parallel()
+            .into_iter(1)
+            .filter_map(|result| result.ok())
+            .filter_map(|dent| {
+                let path = dent.path().strip_prefix(td.path()).unwrap();
+                path.to_str().map(normal_path).filter(|p| !p.is_empty())
+            })
+            .collect();
+        got.sort();
+        assert_eq!(got, expected);
+
+        // The sorted bridge yields entries in lexicographic path order.
+        let got: Vec<String> = WalkBuilder::new(td.path())
+            .build_parallel()
+            .into_iter_sorted(1)
+            .filter_map(|result| result.ok())
+            .filter_map(|dent| {
+                let path = dent.path().strip_prefix(td.path()).unwrap();
+                path.to_str().map(normal_path).filter(|p| !p.is_empty())
+            })
+            .collect();
+        assert_eq!(got, expected);
+
+        // Dropping the iterator early stops the traversal cleanly.
+        let mut it = WalkBuilder::new(td.path()).build_parallel().into_iter(1);
+        it.next().unwrap().unwrap();
+        drop(it);
+    }
+
+    #[test]
+    fn events() {
+        let td = TempDir::new("walk-test-").unwrap();
+        mkdirp(td.path().join("a/b"));
+        wfile(td.path().join("a/b/foo"), "");
+
+        let got: Vec<String> = WalkBuilder::new(td.path())
+            .build_events()
+            .filter_map(|result| result.ok())
+            .map(|ev| describe_event(td.path(), &ev))
+            .collect();
+        assert_eq!(got, vec![
+            "enter ", "enter a", "enter a/b", "file a/b/foo",
+            "leave a/b", "leave a", "leave ",
+        ]);
+    }
+
+    #[test]
+    fn events_parallel() {
+        let td = TempDir::new("walk-test-").unwrap();
+        mkdirp(td.path().join("a/b"));
+        mkdirp(td.path().join("x"));
+        wfile(td.path().join("a/b/foo"), "");
+        wfile(td.path().join("x/bar"), "");
+
+        let events = Arc::new(Mutex::new(vec![]));
+        let prefix = Arc::new(td.path().to_path_buf());
+        WalkBuilder::new(td.path()).build_parallel().run_events(|| {
+            let events = events.clone();
+            let prefix = prefix.clone();
+            Box::new(move |result| {
+                if let Ok(ev) = result {
+                    events.lock().unwrap().push(describe_event(&prefix, &ev));
+                }
+                WalkState::Continue
+            })
+        });
+        let events = events.lock().unwrap();
+        assert_eq!(10, events.len());
+        let pos = |ev: &str| {
+            events.iter().position(|e| e == ev).unwrap()
+        };
+        // Every directory's enter event must come before all events beneath
+        // it and its leave event must come after all of them.
+        for &(dir, below) in &[
+            ("", "enter a"), ("", "leave a"),
+            ("", "enter x"), ("", "leave x"),
+            ("a", "enter a/b"), ("a", "leave a/b"),
+            ("a/b", "file a/b/foo"),
+            ("x", "file x/bar"),
+        ] {
+            assert!(pos(&format!("enter {}", dir)) < pos(below));
+            assert!(pos(below) < pos(&format!("leave {}", dir)));
+        }
+    }
+
     #[test]
     fn custom_ignore() {
         let td = TempDir::new("walk-test-").unwrap();
@@ -1736,6 +3116,130 @@ mod tests {
         ]);
     }
 
+    #[test]
+    fn explain() {
+        let td = TempDir::new("walk-test-").unwrap();
+        mkdirp(td.path().join(".git"));
+        mkdirp(td.path().join("a"));
+        wfile(td.path().join(".gitignore"), "foo");
+        wfile(td.path().join("a/foo"), "");
+
+        let builder = WalkBuilder::new(td.path());
+        let (decisions, err) = builder.explain(td.path().join("a/foo"));
+        assert!(err.is_none());
+        assert_eq!(1, decisions.len());
+        assert_eq!("gitignore", decisions[0].source());
+        assert_eq!(Some(1), decisions[0].line());
+        assert_eq!(Some("foo"), decisions[0].pattern());
+        assert!(!decisions[0].is_whitelist());
+
+        let (decisions, err) = builder.explain(td.path().join("a"));
+        assert!(err.is_none());
+        assert!(decisions.is_empty());
+    }
+
+    #[test]
+    fn traversal_cache() {
+        let td = TempDir::new("walk-test-").unwrap();
+        let cache_td = TempDir::new("walk-test-cache-").unwrap();
+        let cache_path = cache_td.path().join("cache");
+        mkdirp(td.path().join(".git"));
+        mkdirp(td.path().join("a"));
+        wfile(td.path().join(".gitignore"), "foo");
+        wfile(td.path().join("foo"), "");
+        wfile(td.path().join("a/foo"), "");
+        wfile(td.path().join("a/bar"), "");
+
+        let mut builder = WalkBuilder::new(td.path());
+        builder.traversal_cache(Some(cache_path.clone()));
+        // The first walk populates the cache and the second one reuses it.
+        assert_paths(td.path(), &builder, &["a", "a/bar"]);
+        assert!(cache_path.exists());
+        assert_paths(td.path(), &builder, &["a", "a/bar"]);
+
+        // Changing a directory invalidates its cached entries.
+        wfile(td.path().join("a/baz"), "");
+        assert_paths(td.path(), &builder, &["a", "a/bar", "a/baz"]);
+
+        // Editing an ignore file discards the cache in its entirety.
+        wfile(td.path().join(".gitignore"), "");
+        assert_paths(td.path(), &builder, &[
+            "a", "foo", "a/foo", "a/bar", "a/baz",
+        ]);
+    }
+
+    #[test]
+    fn min_filesize() {
+        let td = TempDir::new("walk-test-").unwrap();
+        mkdirp(td.path().join("a"));
+        wfile_size(td.path().join("foo"), 0);
+        wfile_size(td.path().join("bar"), 400);
+        wfile_size(td.path().join("a/baz"), 600);
+
+        let mut builder = WalkBuilder::new(td.path());
+        assert_paths(td.path(), &builder, &[
+            "a", "foo", "bar", "a/baz",
+        ]);
+        assert_paths(td.path(), builder.min_filesize(Some(1)), &[
+            "a", "bar", "a/baz",
+        ]);
+        assert_paths(td.path(), builder.min_filesize(Some(500)), &[
+            "a", "a/baz",
+        ]);
+        assert_paths(td.path(), builder.min_filesize(Some(50000)), &["a"]);
+    }
+
+    #[test]
+    fn modified_time() {
+        let td = TempDir::new("walk-test-").unwrap();
+        wfile(td.path().join("foo"), "");
+        let now = SystemTime::now();
+        let later = now + Duration::from_secs(3600);
+
+        let mut builder = WalkBuilder::new(td.path());
+        assert_paths(td.path(), builder.modified_before(Some(later)), &[
+            "foo",
+        ]);
+        assert_paths(td.path(), builder.modified_before(None), &["foo"]);
+        assert_paths(td.path(), builder.modified_after(Some(later)), &[]);
+        assert_paths(td.path(), builder.modified_after(None), &["foo"]);
+    }
+
+    #[cfg(unix)] // because symlinks on windows are weird
+    #[test]
+    fn entry_type_filter() {
+        let td = TempDir::new("walk-test-").unwrap();
+        wfile(td.path().join("foo"), "");
+        symlink(td.path().join("foo"), td.path().join("bar"));
+
+        let mut builder = WalkBuilder::new(td.path());
+        assert_paths(td.path(), &builder, &["foo", "bar"]);
+        assert_paths(
+            td.path(),
+            builder.entry_type_filter(|ft| !ft.is_symlink()),
+            &["foo"],
+        );
+    }
+
+    #[test]
+    fn sniff_filter() {
+        let td = TempDir::new("walk-test-").unwrap();
+        mkdirp(td.path().join("a"));
+        wfile(td.path().join("foo"), "#!/usr/bin/env python\nprint(1)\n");
+        wfile(td.path().join("bar"), "#!/bin/sh\necho hi\n");
+        wfile(td.path().join("a/baz"), "");
+
+        let mut builder = WalkBuilder::new(td.path());
+        assert_paths(td.path(), &builder, &["a", "foo", "bar", "a/baz"]);
+        assert_paths(
+            td.path(),
+            builder.sniff_filter(64, |_, bytes| {
+                bytes.starts_with(b"#!/usr/bin/env python")
+            }),
+            &["a", "foo"],
+        );
+    }
+
     #[cfg(unix)] // because symlinks on windows are weird
     #[test]
     fn symlinks() {
@@ -1768,4 +3272,122 @@ mod tests {
             "a", "a/b",
         ]);
     }
+
+    #[cfg(unix)] // because symlinks on windows are weird
+    #[test]
+    fn symlink_loop_chain() {
+        use std::path::PathBuf;
+        use Error;
+
+        let td = TempDir::new("walk-test-").unwrap();
+        mkdirp(td.path().join("a/b"));
+        symlink(td.path().join("a"), td.path().join("a/b/c"));
+
+        let errs = Arc::new(Mutex::new(vec![]));
+        let mut builder = WalkBuilder::new(td.path());
+        builder.follow_links(true).build_parallel().run(|| {
+            let errs = errs.clone();
+            Box::new(move |result| {
+                if let Err(err) = result {
+                    errs.lock().unwrap().push(err);
+                }
+                WalkState::Continue
+            })
+        });
+        let errs = errs.lock().unwrap();
+        assert_eq!(1, errs.len());
+        let mut err = &errs[0];
+        while let Error::WithDepth { err: ref e, .. } = *err {
+            err = e;
+        }
+        match *err {
+            Error::Loop { ref chain, .. } => {
+                let expected: Vec<PathBuf> = vec![
+                    td.path().join("a"),
+                    td.path().join("a/b"),
+                    td.path().join("a/b/c"),
+                ];
+                assert_eq!(expected, *chain);
+            }
+            ref err => panic!("expected a loop error, but got {:?}", err),
+        }
+    }
+
+    #[cfg(unix)] // because symlinks on windows are weird
+    #[test]
+    fn max_symlink_depth() {
+        let td = TempDir::new("walk-test-").unwrap();
+        mkdirp(td.path().join("a/b"));
+        symlink(td.path().join("a/b"), td.path().join("z"));
+        wfile(td.path().join("a/b/foo"), "");
+
+        let mut builder = WalkBuilder::new(td.path());
+        builder.follow_links(true);
+        assert_paths(td.path(), builder.max_symlink_depth(Some(0)), &[
+            "a", "a/b", "a/b/foo",
+        ]);
+        assert_paths(td.path(), builder.max_symlink_depth(Some(1)), &[
+            "a", "a/b", "a/b/foo", "z", "z/foo",
+        ]);
+    }
+
+    #[cfg(unix)] // device numbers are only available on Unix
+    #[test]
+    fn same_file_system() {
+        let td = TempDir::new("walk-test-").unwrap();
+        mkdirp(td.path().join("a"));
+        wfile(td.path().join("a/foo"), "");
+
+        // A temporary directory lives on a single file system, so this
+        // merely checks that enabling the option doesn't change the
+        // results. Actually crossing a mount point requires a mount,
+        // which we can't do in a test.
+        let mut builder = WalkBuilder::new(td.path());
+        assert_paths(td.path(), builder.same_file_system(true), &[
+            "a", "a/foo",
+        ]);
+    }
+
+    #[test]
+    fn wide_directory_parallel() {
+        // Exercises work stealing and the open handle budget on a wide
+        // directory with more threads than open handle slots.
+        let td = TempDir::new("walk-test-").unwrap();
+        let mut expected = vec![];
+        for i in 0..20 {
+            let dir = format!("d{:02}", i);
+            mkdirp(td.path().join(&dir));
+            wfile(td.path().join(&dir).join("foo"), "");
+            expected.push(dir.clone());
+            expected.push(format!("{}/foo", dir));
+        }
+        let expected: Vec<&str> = expected.iter().map(|s| &**s).collect();
+
+        let mut builder = WalkBuilder::new(td.path());
+        builder.threads(8).max_open(Some(2)).steal_batch_size(4);
+        assert_paths(td.path(), &builder, &expected);
+    }
+
+    #[test]
+    fn sorted_parallel() {
+        let td = TempDir::new("walk-test-").unwrap();
+        mkdirp(td.path().join("a/b"));
+        mkdirp(td.path().join("x/y"));
+        wfile(td.path().join("a/b/foo"), "");
+        wfile(td.path().join("a/bar"), "");
+        wfile(td.path().join("x/y/foo"), "");
+
+        let mut paths = vec![];
+        WalkBuilder::new(td.path()).build_parallel().run_sorted(|result| {
+            let dent = result.unwrap();
+            let path = dent.path().strip_prefix(td.path()).unwrap();
+            if !path.as_os_str().is_empty() {
+                paths.push(normal_path(path.to_str().unwrap()));
+            }
+            WalkState::Continue
+        });
+        assert_eq!(paths, vec![
+            "a", "a/b", "a/b/foo", "a/bar", "x", "x/y", "x/y/foo",
+        ]);
+    }
 }
@@ -492,41 +492,70 @@ pub fn all_args_and_flags() -> Vec<RGArg> {
     arg_path(&mut args);
     // Flags can be defined in any order, but we do it alphabetically.
     flag_after_context(&mut args);
+    flag_and(&mut args);
+    flag_backup_suffix(&mut args);
     flag_before_context(&mut args);
+    flag_binary(&mut args);
+    flag_blame(&mut args);
+    flag_block_buffered(&mut args);
+    flag_by_type(&mut args);
     flag_byte_offset(&mut args);
     flag_case_sensitive(&mut args);
     flag_color(&mut args);
     flag_colors(&mut args);
     flag_column(&mut args);
     flag_context(&mut args);
+    flag_context_block(&mut args);
     flag_context_separator(&mut args);
     flag_count(&mut args);
     flag_count_matches(&mut args);
     flag_debug(&mut args);
     flag_dfa_size_limit(&mut args);
+    flag_dry_run(&mut args);
+    flag_dup_lines(&mut args);
     flag_encoding(&mut args);
+    flag_engine(&mut args);
+    flag_field_context_separator(&mut args);
+    flag_field_match_separator(&mut args);
     flag_file(&mut args);
+    flag_file_comments(&mut args);
     flag_files(&mut args);
+    flag_files_with_all_matches(&mut args);
     flag_files_with_matches(&mut args);
     flag_files_without_match(&mut args);
     flag_fixed_strings(&mut args);
     flag_follow(&mut args);
+    flag_fuzzy(&mut args);
+    flag_generate(&mut args);
     flag_glob(&mut args);
+    flag_glob_case_insensitive(&mut args);
     flag_heading(&mut args);
     flag_hidden(&mut args);
+    flag_hyperlink_format(&mut args);
     flag_iglob(&mut args);
     flag_ignore_case(&mut args);
     flag_ignore_file(&mut args);
+    flag_in_place(&mut args);
+    flag_include_zero(&mut args);
     flag_invert_match(&mut args);
+    flag_json(&mut args);
+    flag_json_file(&mut args);
+    flag_line_buffered(&mut args);
     flag_line_number(&mut args);
     flag_line_regexp(&mut args);
+    flag_max_bytes_per_file(&mut args);
     flag_max_columns(&mut args);
     flag_max_count(&mut args);
     flag_max_depth(&mut args);
     flag_max_filesize(&mut args);
     flag_mmap(&mut args);
     flag_no_config(&mut args);
+    flag_no_context_separator(&mut args);
+    flag_no_dedup_paths(&mut args);
     flag_no_ignore(&mut args);
+    flag_no_ignore_dot(&mut args);
+    flag_no_ignore_exclude(&mut args);
+    flag_no_ignore_files(&mut args);
     flag_no_ignore_global(&mut args);
     flag_no_ignore_messages(&mut args);
     flag_no_ignore_parent(&mut args);
@@ -537,19 +566,27 @@ pub fn all_args_and_flags() -> Vec<RGArg> {
     flag_path_separator(&mut args);
     flag_passthru(&mut args);
     flag_pre(&mut args);
+    flag_pre_glob(&mut args);
     flag_pretty(&mut args);
     flag_quiet(&mut args);
+    flag_record(&mut args);
     flag_regex_size_limit(&mut args);
     flag_regexp(&mut args);
     flag_replace(&mut args);
+    flag_replay(&mut args);
     flag_search_zip(&mut args);
     flag_smart_case(&mut args);
+    flag_sort(&mut args);
     flag_sort_files(&mut args);
+    flag_sortr(&mut args);
     flag_stats(&mut args);
     flag_text(&mut args);
     flag_threads(&mut args);
+    flag_timeout(&mut args);
+    flag_traversal(&mut args);
     flag_type(&mut args);
     flag_type_add(&mut args);
+    flag_type_add_file(&mut args);
     flag_type_clear(&mut args);
     flag_type_list(&mut args);
     flag_type_not(&mut args);
@@ -557,6 +594,7 @@ pub fn all_args_and_flags() -> Vec<RGArg> {
     flag_vimgrep(&mut args);
     flag_with_filename(&mut args);
     flag_word_regexp(&mut args);
+    flag_wordlist(&mut args);
     args
 }
 
@@ -578,7 +616,8 @@ will be provided. Namely, the following is equivalent to the above:
     let arg = RGArg::positional("pattern", "PATTERN")
         .help(SHORT).long_help(LONG)
         .required_unless(&[
-            "file", "files", "regexp", "type-list",
+            "dup-lines", "file", "files", "generate", "regexp", "replay",
+            "type-list", "wordlist",
         ]);
     args.push(arg);
 }
@@ -609,6 +648,42 @@ This overrides the --context flag.
     args.push(arg);
 }
 
+fn flag_and(args: &mut Vec<RGArg>) {
+    const SHORT: &str = "Require lines to also match PATTERN.";
+    const LONG: &str = long!("\
+Only print lines that, in addition to matching the pattern given on the
+command line (or with -e/--regexp or -f/--file), also match PATTERN. This
+flag may be given multiple times, in which case a line must match every
+PATTERN. Each match contributing to a printed line is highlighted.
+
+When combined with the --files-with-all-matches flag, the patterns apply to
+the file as a whole instead: each pattern must match somewhere in the file,
+but not necessarily on the same line.
+
+--and patterns honor the same case sensitivity, word boundary and literal
+flags as regular patterns.
+");
+    let arg = RGArg::flag("and", "PATTERN")
+        .help(SHORT).long_help(LONG)
+        .multiple()
+        .allow_leading_hyphen();
+    args.push(arg);
+}
+
+fn flag_backup_suffix(args: &mut Vec<RGArg>) {
+    const SHORT: &str = "Back up files rewritten by --in-place.";
+    const LONG: &str = long!("\
+When used with the --in-place flag, save a copy of each rewritten file at its
+original path with SUFFIX appended, before the replacement is written. For
+example, --backup-suffix .bak keeps the original foo.txt as foo.txt.bak.
+
+This flag has no effect without the --in-place flag.
+");
+    let arg = RGArg::flag("backup-suffix", "SUFFIX")
+        .help(SHORT).long_help(LONG);
+    args.push(arg);
+}
+
 fn flag_before_context(args: &mut Vec<RGArg>) {
     const SHORT: &str = "Show NUM lines before each match.";
     const LONG: &str = long!("\
@@ -623,6 +698,95 @@ This overrides the --context flag.
     args.push(arg);
 }
 
+fn flag_binary(args: &mut Vec<RGArg>) {
+    const SHORT: &str = "Search binary files, but only report that they match.";
+    const LONG: &str = long!("\
+Enabling this flag will cause ripgrep to search binary files. By default,
+ripgrep skips binary files entirely in order to improve the relevance of
+results and to make searches faster.
+
+Unlike the -a/--text flag, this flag never prints the raw contents of a
+binary file. If a binary file contains a match, then ripgrep prints a single
+'Binary file ... matches' summary line for it instead. Counting flags such as
+-c/--count and listing flags such as -l/--files-with-matches behave as they
+do for any other file.
+
+To actually print the matching lines of a binary file, use the -a/--text
+flag instead.
+
+This flag can be disabled with --no-binary.
+");
+    let arg = RGArg::switch("binary")
+        .help(SHORT).long_help(LONG)
+        .overrides("no-binary");
+    args.push(arg);
+
+    let arg = RGArg::switch("no-binary")
+        .hidden()
+        .overrides("binary");
+    args.push(arg);
+}
+
+fn flag_blame(args: &mut Vec<RGArg>) {
+    const SHORT: &str = "Annotate matches with git blame information.";
+    const LONG: &str = long!("\
+Annotate every matching line with information from git blame: the
+abbreviated commit hash, the author and the age of the commit that last
+touched the line, e.g.,
+
+    5:5fed981d alice 2y:fn main() {
+
+Blame information is gathered with a single git invocation per file and
+is omitted for files that are not tracked by git. This flag implies line
+numbers, since blame information is attached to lines.
+");
+    let arg = RGArg::switch("blame")
+        .help(SHORT).long_help(LONG);
+    args.push(arg);
+}
+
+fn flag_block_buffered(args: &mut Vec<RGArg>) {
+    const SHORT: &str = "Force block buffering.";
+    const LONG: &str = long!("\
+When enabled, ripgrep will use block buffering. That is, whenever a matching
+line is found, it will be written to an in-memory buffer and will not be
+written to stdout until the buffer reaches a certain size. This is the default
+when ripgrep's stdout is redirected to a pipeline or a file. When ripgrep's
+stdout is connected to a terminal, line buffering will be used by default.
+Forcing block buffering can be useful when dumping a large amount of contents
+to a terminal.
+
+Forceful block buffering can be disabled with --no-block-buffered. Note that
+using --no-block-buffered causes ripgrep to revert to its default behavior.
+");
+    let arg = RGArg::switch("block-buffered")
+        .help(SHORT).long_help(LONG)
+        .overrides("no-block-buffered")
+        .overrides("line-buffered");
+    args.push(arg);
+
+    let arg = RGArg::switch("no-block-buffered")
+        .hidden()
+        .overrides("block-buffered");
+    args.push(arg);
+}
+
+fn flag_by_type(args: &mut Vec<RGArg>) {
+    const SHORT: &str = "Show match and file counts aggregated by file type.";
+    const LONG: &str = long!("\
+After the search finishes, print a summary of how many matching lines and how
+many files with matches were found per detected file type, e.g.,
+
+    rust: 200 matches in 31 files
+
+Files not covered by any file type definition are reported under 'other'.
+This is most useful in combination with the -c/--count flag.
+");
+    let arg = RGArg::switch("by-type")
+        .help(SHORT).long_help(LONG);
+    args.push(arg);
+}
+
 fn flag_byte_offset(args: &mut Vec<RGArg>) {
     const SHORT: &str =
         "Print the 0-based byte offset for each matching line.";
@@ -658,8 +822,13 @@ means ripgrep will try to guess when to use colors. For example, if ripgrep is
 printing to a terminal, then it will use colors, but if it is redirected to a
 file or a pipe, then it will suppress color output. ripgrep will suppress color
 output in some other circumstances as well. For example, if the TERM
-environment variable is not set or set to 'dumb', then ripgrep will not use
-colors.
+environment variable is set to 'dumb', or if the NO_COLOR environment variable
+is set (to any value), then ripgrep will not use colors. Setting the
+CLICOLOR_FORCE environment variable to a non-zero value forces colors on in
+'auto' mode, even when the output is not a terminal.
+
+Explicit settings always win: 'never' and 'always' ignore the environment
+variables above.
 
 The possible values for this flag are:
 
@@ -711,8 +880,20 @@ or, equivalently,
 
     rg --colors 'match:bg:0x0,0x80,0xFF'
 
+24-bit truecolor values may also be written as hex color specs of the form
+#rrggbb, e.g.,
+
+    rg --colors 'match:bg:#0080ff'
+
 Note that the the intense and nointense style flags will have no effect when
 used alongside these extended color codes.
+
+When this flag is not given, the GREP_COLORS environment variable is honored
+instead, using the same syntax as GNU grep, e.g., GREP_COLORS='ms=01;31:fn=35'.
+The `mt`, `ms` and `mc` capabilities color matches, `fn` colors file paths,
+`ln` colors line numbers and `bn` colors column numbers. Unrecognized
+capabilities and SGR parameters are ignored. Providing this flag at least once
+causes GREP_COLORS to be ignored entirely.
 ");
     let arg = RGArg::flag("colors", "COLOR_SPEC")
         .help(SHORT).long_help(LONG)
@@ -756,14 +937,38 @@ This overrides both the -B/--before-context and -A/--after-context flags.
     args.push(arg);
 }
 
+fn flag_context_block(args: &mut Vec<RGArg>) {
+    const SHORT: &str = "Show context up to the nearest blank lines.";
+    const LONG: &str = long!("\
+Show context around every match up to the nearest blank lines before and
+after it, instead of a fixed number of lines. For prose, configuration files
+and other paragraph structured text this shows the natural block that each
+match belongs to.
+
+This overrides the -A/--after-context, -B/--before-context and -C/--context
+flags.
+");
+    let arg = RGArg::switch("context-block")
+        .help(SHORT).long_help(LONG)
+        .overrides("after-context")
+        .overrides("before-context")
+        .overrides("context");
+    args.push(arg);
+}
+
 fn flag_context_separator(args: &mut Vec<RGArg>) {
     const SHORT: &str = "Set the context separator string.";
     const LONG: &str = long!("\
 The string used to separate non-contiguous context lines in the output. Escape
 sequences like \\x7F or \\t may be used. The default value is --.
+
+Passing an empty separator suppresses the separator lines entirely, including
+the separator printed between files. This is equivalent to the
+--no-context-separator flag.
 ");
     let arg = RGArg::flag("context-separator", "SEPARATOR")
-        .help(SHORT).long_help(LONG);
+        .help(SHORT).long_help(LONG)
+        .overrides("no-context-separator");
     args.push(arg);
 }
 
@@ -834,6 +1039,39 @@ The argument accepts the same size suffixes as allowed in with the
     args.push(arg);
 }
 
+fn flag_dry_run(args: &mut Vec<RGArg>) {
+    const SHORT: &str = "Show --in-place changes without writing them.";
+    const LONG: &str = long!("\
+When used with the --in-place flag, print a preview of every line that would
+change instead of rewriting any files. Each changed line is shown twice,
+prefixed with the file path and line number: once with '-' for the old text
+and once with '+' for the new text.
+
+This flag has no effect without the --in-place flag.
+");
+    let arg = RGArg::switch("dry-run")
+        .help(SHORT).long_help(LONG);
+    args.push(arg);
+}
+
+fn flag_dup_lines(args: &mut Vec<RGArg>) {
+    const SHORT: &str = "Report groups of NUM+ lines duplicated across files.";
+    const LONG: &str = long!("\
+Instead of searching for a pattern, report every group of NUM or more
+consecutive lines that appears in two or more of the searched files. Lines
+are compared after trimming surrounding whitespace, and overlapping groups
+belonging to the same run of duplicated lines are merged. This provides a
+lightweight duplication detector over exactly the set of files that a search
+would visit, i.e., the usual ignore rules apply.
+
+No pattern is required when this flag is used.
+");
+    let arg = RGArg::flag("dup-lines", "NUM")
+        .help(SHORT).long_help(LONG)
+        .number();
+    args.push(arg);
+}
+
 fn flag_encoding(args: &mut Vec<RGArg>) {
     const SHORT: &str = "Specify the text encoding of files to search.";
     const LONG: &str = long!("\
@@ -848,15 +1086,64 @@ https://encoding.spec.whatwg.org/#concept-encoding-get
     args.push(arg);
 }
 
+fn flag_engine(args: &mut Vec<RGArg>) {
+    const SHORT: &str = "Specify which regexp engine to use.";
+    const LONG: &str = long!("\
+Specify which regular expression engine to use. When you choose a regex
+engine, it applies that choice for every regex provided to ripgrep (e.g., via
+multiple -e/--regexp or -f/--file flags).
+
+Accepted values are 'default', 'pcre2' or 'auto'.
+
+This build of ripgrep includes only the default engine, so 'default' and
+'auto' behave identically and 'pcre2' results in an error. The flag exists so
+that scripts can pin their expectations deterministically across builds.
+");
+    let arg = RGArg::flag("engine", "ENGINE")
+        .help(SHORT).long_help(LONG)
+        .possible_values(&["default", "pcre2", "auto"]);
+    args.push(arg);
+}
+
+fn flag_field_context_separator(args: &mut Vec<RGArg>) {
+    const SHORT: &str = "Set the field context separator.";
+    const LONG: &str = long!("\
+Set the field context separator, which is used to delimit file paths, line
+numbers and byte offsets when printing context lines. Escape sequences like
+\\x7F or \\t may be used, as may a NUL byte via \\x00. The default value is -.
+");
+    let arg = RGArg::flag("field-context-separator", "SEPARATOR")
+        .help(SHORT).long_help(LONG);
+    args.push(arg);
+}
+
+fn flag_field_match_separator(args: &mut Vec<RGArg>) {
+    const SHORT: &str = "Set the match separator.";
+    const LONG: &str = long!("\
+Set the field match separator, which is used to delimit file paths, line
+numbers, columns and byte offsets when printing matching lines. Escape
+sequences like \\x7F or \\t may be used, as may a NUL byte via \\x00. This is
+useful when paths may themselves contain the default ':' separator, such as
+on Windows. The default value is :.
+");
+    let arg = RGArg::flag("field-match-separator", "SEPARATOR")
+        .help(SHORT).long_help(LONG);
+    args.push(arg);
+}
+
 fn flag_file(args: &mut Vec<RGArg>) {
     const SHORT: &str = "Search for patterns from the given file.";
     const LONG: &str = long!("\
 Search for patterns from the given file, with one pattern per line. When this
 flag is used multiple times or in combination with the -e/--regexp flag,
 then all patterns provided are searched. Empty pattern lines will match all
-input lines, and the newline is not counted as part of the pattern.
+input lines, and the newline is not counted as part of the pattern. A
+pattern file of - reads patterns from stdin.
 
 A line is printed if and only if it matches at least one of the patterns.
+
+To skip blank lines and lines starting with # in pattern files, use the
+--file-comments flag.
 ");
     let arg = RGArg::flag("file", "PATTERNFILE").short("f")
         .help(SHORT).long_help(LONG)
@@ -865,6 +1152,30 @@ A line is printed if and only if it matches at least one of the patterns.
     args.push(arg);
 }
 
+fn flag_file_comments(args: &mut Vec<RGArg>) {
+    const SHORT: &str = "Skip comments and blank lines in pattern files.";
+    const LONG: &str = long!("\
+When reading patterns with the -f/--file flag, skip lines that start with #
+and lines that are empty. By default, every line of a pattern file is used
+verbatim, which means that an empty line matches all input lines. This flag
+makes it practical to maintain annotated pattern files.
+
+Note that this only changes how pattern files are read. Patterns given with
+the -e/--regexp flag are never treated as comments.
+
+This flag can be disabled with --no-file-comments.
+");
+    let arg = RGArg::switch("file-comments")
+        .help(SHORT).long_help(LONG)
+        .overrides("no-file-comments");
+    args.push(arg);
+
+    let arg = RGArg::switch("no-file-comments")
+        .hidden()
+        .overrides("file-comments");
+    args.push(arg);
+}
+
 fn flag_files(args: &mut Vec<RGArg>) {
     const SHORT: &str = "Print each file that would be searched.";
     const LONG: &str = long!("\
@@ -879,6 +1190,22 @@ This is useful to determine whether a particular file is being search or not.
     args.push(arg);
 }
 
+fn flag_files_with_all_matches(args: &mut Vec<RGArg>) {
+    const SHORT: &str = "Only print the paths where every pattern matches.";
+    const LONG: &str = long!("\
+Only print the paths of files where the pattern and every --and pattern
+match, each somewhere in the file, but not necessarily on the same line.
+Without any --and patterns, this behaves like -l/--files-with-matches.
+
+This overrides --files-with-matches and --files-without-match.
+");
+    let arg = RGArg::switch("files-with-all-matches")
+        .help(SHORT).long_help(LONG)
+        .overrides("files-with-matches")
+        .overrides("files-without-match");
+    args.push(arg);
+}
+
 fn flag_files_with_matches(args: &mut Vec<RGArg>) {
     const SHORT: &str = "Only print the paths with at least one match.";
     const LONG: &str = long!("\
@@ -946,6 +1273,50 @@ This flag can be disabled with --no-follow.
     args.push(arg);
 }
 
+fn flag_fuzzy(args: &mut Vec<RGArg>) {
+    const SHORT: &str = "Approximate matching with the given edit distance.";
+    const LONG: &str = long!("\
+Match the pattern approximately, permitting up to NUM single character edits
+(insertions, deletions or substitutions), similar to agrep. This flag requires
+the -F/--fixed-strings flag, since approximate matching is only defined for
+literal strings.
+
+Note that the number of alternations generated grows quickly with both the
+pattern length and the edit distance, so large values of NUM may be slow or
+exceed the regex size limit.
+");
+    let arg = RGArg::flag("fuzzy", "NUM")
+        .help(SHORT).long_help(LONG)
+        .number();
+    args.push(arg);
+}
+
+fn flag_generate(args: &mut Vec<RGArg>) {
+    const SHORT: &str = "Generate man pages and completion scripts.";
+    const LONG: &str = long!("\
+This flag instructs ripgrep to generate some special kind of output identified
+by KIND and then exit without searching. KIND can be one of the following
+values:
+
+    complete-bash        Generate a completion script for the bash shell.
+    complete-fish        Generate a completion script for the fish shell.
+    complete-powershell  Generate a completion script for PowerShell.
+    complete-zsh         Generate a completion script for the zsh shell.
+    man                  Generate a roff formatted man page.
+
+The output is written to stdout. The completion scripts and the man page are
+generated from ripgrep's actual flag definitions, so they are always in sync
+with the version of ripgrep that generated them.
+");
+    let arg = RGArg::flag("generate", "KIND")
+        .help(SHORT).long_help(LONG)
+        .possible_values(&[
+            "complete-bash", "complete-fish", "complete-powershell",
+            "complete-zsh", "man",
+        ]);
+    args.push(arg);
+}
+
 fn flag_glob(args: &mut Vec<RGArg>) {
     const SHORT: &str = "Include or exclude files.";
     const LONG: &str = long!("\
@@ -961,6 +1332,25 @@ it.
     args.push(arg);
 }
 
+fn flag_glob_case_insensitive(args: &mut Vec<RGArg>) {
+    const SHORT: &str = "Process all glob patterns case insensitively.";
+    const LONG: &str = long!("\
+Process all glob patterns given with the -g/--glob flag case insensitively.
+This effectively treats --glob as --iglob.
+
+This flag can be disabled with the --no-glob-case-insensitive flag.
+");
+    let arg = RGArg::switch("glob-case-insensitive")
+        .help(SHORT).long_help(LONG)
+        .overrides("no-glob-case-insensitive");
+    args.push(arg);
+
+    let arg = RGArg::switch("no-glob-case-insensitive")
+        .hidden()
+        .overrides("glob-case-insensitive");
+    args.push(arg);
+}
+
 fn flag_heading(args: &mut Vec<RGArg>) {
     const SHORT: &str = "Print matches grouped by each file.";
     const LONG: &str = long!("\
@@ -1009,6 +1399,29 @@ This flag can be disabled with --no-hidden.
     args.push(arg);
 }
 
+fn flag_hyperlink_format(args: &mut Vec<RGArg>) {
+    const SHORT: &str = "Set the format of hyperlinks around file paths.";
+    const LONG: &str = long!("\
+Set the format of hyperlinks to emit around file paths in the output, using
+the OSC 8 terminal escape sequence. Hyperlinks make results clickable in
+terminal emulators that support them, and are only emitted when colors are
+in use (e.g., when printing to a tty or with --color always).
+
+The format may be one of the preset names 'file', 'vscode' or 'idea', the
+value 'none' to disable hyperlinks, or a custom format string containing the
+variables {path}, {line}, {column} and {host}. A custom format must contain
+{path}. For example, the 'vscode' preset is equivalent to:
+
+    --hyperlink-format 'vscode://file{path}:{line}:{column}'
+
+Relative paths are made absolute before substitution. The {host} variable is
+taken from the HOSTNAME environment variable.
+");
+    let arg = RGArg::flag("hyperlink-format", "FORMAT")
+        .help(SHORT).long_help(LONG);
+    args.push(arg);
+}
+
 fn flag_iglob(args: &mut Vec<RGArg>) {
     const SHORT: &str =
         "Include or exclude files case insensitively.";
@@ -1061,6 +1474,39 @@ directly on the command line, then used -g instead.
     args.push(arg);
 }
 
+fn flag_in_place(args: &mut Vec<RGArg>) {
+    const SHORT: &str = "Write replacements back to the searched files.";
+    const LONG: &str = long!("\
+Rewrite every file containing a match by applying the -r/--replace text to
+each matching line, instead of printing search results. This flag requires
+the -r/--replace flag.
+
+Files are rewritten atomically: the new contents are written to a temporary
+file in the same directory, which then replaces the original. File
+permissions are preserved. Files that look binary (i.e., contain a NUL byte)
+are never rewritten. Use the --backup-suffix flag to keep a copy of each
+original file, and the --dry-run flag to preview the changes without writing
+anything.
+");
+    let arg = RGArg::switch("in-place")
+        .help(SHORT).long_help(LONG);
+    args.push(arg);
+}
+
+fn flag_include_zero(args: &mut Vec<RGArg>) {
+    const SHORT: &str = "Include files with zero matches in count output.";
+    const LONG: &str = long!("\
+When used with the -c/--count or --count-matches flags, print the path of
+every searched file, including those with zero matches. Without this flag,
+only files with at least one match are listed.
+
+This flag has no effect without one of the counting flags.
+");
+    let arg = RGArg::switch("include-zero")
+        .help(SHORT).long_help(LONG);
+    args.push(arg);
+}
+
 fn flag_invert_match(args: &mut Vec<RGArg>) {
     const SHORT: &str = "Invert matching.";
     const LONG: &str = long!("\
@@ -1071,6 +1517,76 @@ Invert matching. Show lines that do not match the given patterns.
     args.push(arg);
 }
 
+fn flag_json(args: &mut Vec<RGArg>) {
+    const SHORT: &str = "Show search results in a JSON Lines format.";
+    const LONG: &str = long!("\
+Enable printing results in a JSON Lines format.
+
+When this flag is provided, ripgrep will emit a series of messages, each
+encoded as a JSON object on its own line. The first message for each file
+that contains a match has the type 'begin', each matching line produces a
+message with the type 'match' and each context line produces a message with
+the type 'context'. After the search completes, a final message with the
+type 'summary' is emitted with aggregate statistics, including the number
+of matched lines and the number of files searched.
+
+Paths and line contents that are not valid UTF-8 are converted lossily
+before being emitted.
+
+This flag only applies to printing search results, and is ignored by flags
+such as --files and --type-list. It also causes most other output formatting
+flags, such as --heading and --column, to be ignored.
+");
+    let arg = RGArg::switch("json")
+        .help(SHORT).long_help(LONG);
+    args.push(arg);
+}
+
+fn flag_json_file(args: &mut Vec<RGArg>) {
+    const SHORT: &str = "Write search results in a JSON Lines format to PATH.";
+    const LONG: &str = long!("\
+Write a copy of the search results, in the same JSON Lines format produced
+by the --json flag, to the file given. The regular output is unaffected,
+which makes it possible to show the standard grep-like results on a terminal
+while simultaneously recording a machine readable log of the same search.
+
+If the file already exists, then it is truncated. If it cannot be created,
+then ripgrep reports an error and exits without searching.
+
+As with --json, this flag only applies to printing search results, and is
+ignored by flags such as --files and --type-list.
+");
+    let arg = RGArg::flag("json-file", "PATH")
+        .help(SHORT).long_help(LONG);
+    args.push(arg);
+}
+
+fn flag_line_buffered(args: &mut Vec<RGArg>) {
+    const SHORT: &str = "Force line buffering.";
+    const LONG: &str = long!("\
+When enabled, ripgrep will use line buffering. That is, whenever a matching
+line is found, it will be flushed to stdout immediately. This is the default
+when ripgrep's stdout is connected to a terminal, but otherwise, ripgrep will
+use block buffering, which is typically faster. This flag forces ripgrep to
+use line buffering even if it would otherwise use block buffering. This is
+typically useful in shell pipelines, e.g.,
+'tail -f something.log | rg foo --line-buffered | rg bar'.
+
+Forceful line buffering can be disabled with --no-line-buffered. Note that
+using --no-line-buffered causes ripgrep to revert to its default behavior.
+");
+    let arg = RGArg::switch("line-buffered")
+        .help(SHORT).long_help(LONG)
+        .overrides("no-line-buffered")
+        .overrides("block-buffered");
+    args.push(arg);
+
+    let arg = RGArg::switch("no-line-buffered")
+        .hidden()
+        .overrides("line-buffered");
+    args.push(arg);
+}
+
 fn flag_line_number(args: &mut Vec<RGArg>) {
     const SHORT: &str = "Show line numbers.";
     const LONG: &str = long!("\
@@ -1108,6 +1624,28 @@ This overrides the --word-regexp flag.
     args.push(arg);
 }
 
+fn flag_max_bytes_per_file(args: &mut Vec<RGArg>) {
+    const SHORT: &str = "Search at most NUM bytes of each file.";
+    const LONG: &str = long!("\
+Search at most NUM bytes from the beginning of each file. The rest of the file
+is ignored. This is useful for heuristic scans that only care about the start
+of each file, such as license header or shebang audits, where it can speed up
+searches over large trees dramatically.
+
+Note that the limit is approximate: the last line searched may straddle the
+boundary, in which case it is truncated at the limit.
+
+The input format accepts suffixes of K, M or G which correspond to kilobytes,
+megabytes and gigabytes, respectively. If no suffix is provided the input is
+treated as bytes.
+
+Examples: --max-bytes-per-file 1K or --max-bytes-per-file 80M
+");
+    let arg = RGArg::flag("max-bytes-per-file", "NUM+SUFFIX?")
+        .help(SHORT).long_help(LONG);
+    args.push(arg);
+}
+
 fn flag_max_columns(args: &mut Vec<RGArg>) {
     const SHORT: &str = "Don't print lines longer than this limit.";
     const LONG: &str = long!("\
@@ -1212,6 +1750,47 @@ pre-defined locations, then this flag will also disable that behavior as well.
     args.push(arg);
 }
 
+fn flag_no_context_separator(args: &mut Vec<RGArg>) {
+    const SHORT: &str = "Don't print context separators.";
+    const LONG: &str = long!("\
+When enabled, no context separators are printed, even when the -A/--after-
+context, -B/--before-context or -C/--context flags are used. This includes
+the separator printed between files. This flag overrides any previous use of
+the --context-separator flag.
+");
+    let arg = RGArg::switch("no-context-separator")
+        .help(SHORT).long_help(LONG)
+        .overrides("context-separator");
+    args.push(arg);
+}
+
+fn flag_no_dedup_paths(args: &mut Vec<RGArg>) {
+    const SHORT: &str = "Don't remove overlapping search paths.";
+    const LONG: &str = long!("\
+Don't remove overlapping search paths given on the command line. By default,
+ripgrep drops paths that are given more than once, as well as paths contained
+in a directory given as another path, so that no file is searched (and
+printed) more than once. For example, in
+
+    rg foo . src/
+
+the src/ path is dropped since it is already covered by '.'. Symlinks given
+on the command line are never dropped, since they are searched and printed
+through the link name.
+
+This flag can be disabled with the --dedup-paths flag.
+");
+    let arg = RGArg::switch("no-dedup-paths")
+        .help(SHORT).long_help(LONG)
+        .overrides("dedup-paths");
+    args.push(arg);
+
+    let arg = RGArg::switch("dedup-paths")
+        .hidden()
+        .overrides("no-dedup-paths");
+    args.push(arg);
+}
+
 fn flag_no_ignore(args: &mut Vec<RGArg>) {
     const SHORT: &str = "Don't respect ignore files.";
     const LONG: &str = long!("\
@@ -1231,6 +1810,64 @@ This flag can be disabled with the --ignore flag.
     args.push(arg);
 }
 
+fn flag_no_ignore_dot(args: &mut Vec<RGArg>) {
+    const SHORT: &str = "Don't respect .ignore files.";
+    const LONG: &str = long!("\
+Don't respect .ignore or .rgignore files. Other ignore sources, such as
+.gitignore files, are still respected.
+
+This flag can be disabled with the --ignore-dot flag.
+");
+    let arg = RGArg::switch("no-ignore-dot")
+        .help(SHORT).long_help(LONG)
+        .overrides("ignore-dot");
+    args.push(arg);
+
+    let arg = RGArg::switch("ignore-dot")
+        .hidden()
+        .overrides("no-ignore-dot");
+    args.push(arg);
+}
+
+fn flag_no_ignore_exclude(args: &mut Vec<RGArg>) {
+    const SHORT: &str = "Don't respect local exclusion files.";
+    const LONG: &str = long!("\
+Don't respect ignore files that are manually configured for the repository,
+such as git's '.git/info/exclude'.
+
+This flag can be disabled with the --ignore-exclude flag.
+");
+    let arg = RGArg::switch("no-ignore-exclude")
+        .help(SHORT).long_help(LONG)
+        .overrides("ignore-exclude");
+    args.push(arg);
+
+    let arg = RGArg::switch("ignore-exclude")
+        .hidden()
+        .overrides("no-ignore-exclude");
+    args.push(arg);
+}
+
+fn flag_no_ignore_files(args: &mut Vec<RGArg>) {
+    const SHORT: &str = "Don't respect --ignore-file flags.";
+    const LONG: &str = long!("\
+When given, any --ignore-file flags, even ones that come from a configuration
+file, are ignored. Unlike the other ignore sources, this one is NOT implied
+by --no-ignore, since an explicit --ignore-file flag states clear intent.
+
+This flag can be disabled with the --ignore-files flag.
+");
+    let arg = RGArg::switch("no-ignore-files")
+        .help(SHORT).long_help(LONG)
+        .overrides("ignore-files");
+    args.push(arg);
+
+    let arg = RGArg::switch("ignore-files")
+        .hidden()
+        .overrides("no-ignore-files");
+    args.push(arg);
+}
+
 fn flag_no_ignore_global(args: &mut Vec<RGArg>) {
     const SHORT: &str = "Don't respect global ignore files.";
     const LONG: &str = long!("\
@@ -1411,6 +2048,22 @@ first file that matches all ignore rules.
     args.push(arg);
 }
 
+fn flag_record(args: &mut Vec<RGArg>) {
+    const SHORT: &str = "Record the search session to the given file.";
+    const LONG: &str = long!("\
+Record the search session to the given file. The recording captures the list
+of files searched, a hash of each file's contents and the rendered search
+results. A recorded session can be re-rendered later with the --replay flag,
+which makes it useful for reproducible bug reports about search behavior.
+
+This flag conflicts with the --replay flag.
+");
+    let arg = RGArg::flag("record", "FILE")
+        .help(SHORT).long_help(LONG)
+        .conflicts(&["replay"]);
+    args.push(arg);
+}
+
 fn flag_regex_size_limit(args: &mut Vec<RGArg>) {
     const SHORT: &str = "The upper size limit of the compiled regex.";
     const LONG: &str = long!("\
@@ -1451,11 +2104,13 @@ will be provided. Namely, the following is equivalent to the above:
 fn flag_replace(args: &mut Vec<RGArg>) {
     const SHORT: &str = "Replace matches with the given text.";
     const LONG: &str = long!("\
-Replace every match with the text given when printing results. Neither this
-flag nor any other ripgrep flag will modify your files.
+Replace every match with the text given when printing results. This flag
+never modifies your files unless the --in-place flag is also given.
 
 Capture group indices (e.g., $5) and names (e.g., $foo) are supported in the
-replacement string.
+replacement string. A group reference can be wrapped in curly braces (e.g.,
+${foo}) to unambiguously separate it from surrounding literal text (e.g.,
+${foo}bar).
 
 Note that the replacement by default replaces each match, and NOT the entire
 line. To replace the entire line, you should match the entire line.
@@ -1468,6 +2123,26 @@ This flag can be used with the -o/--only-matching flag.
     args.push(arg);
 }
 
+fn flag_replay(args: &mut Vec<RGArg>) {
+    const SHORT: &str = "Replay a search session recorded with --record.";
+    const LONG: &str = long!("\
+Replay a search session previously recorded with the --record flag. The
+recorded results are printed as-is without searching anything. If any of the
+recorded files have changed (or no longer exist) since the recording was made,
+then a warning is printed to stderr for each such file (unless --no-messages
+is given).
+
+When this flag is used, no pattern or path arguments are required and any that
+are given are ignored.
+
+This flag conflicts with the --record flag.
+");
+    let arg = RGArg::flag("replay", "FILE")
+        .help(SHORT).long_help(LONG)
+        .conflicts(&["record"]);
+    args.push(arg);
+}
+
 fn flag_search_zip(args: &mut Vec<RGArg>) {
     const SHORT: &str = "Search in compressed files.";
     const LONG: &str = long!("\
@@ -1547,6 +2222,31 @@ This overrides the -z/--search-zip flag.
     args.push(arg);
 }
 
+fn flag_pre_glob(args: &mut Vec<RGArg>) {
+    const SHORT: &str =
+        "Include files in the preprocessor command based on a glob.";
+    const LONG: &str = long!("\
+This flag works in conjunction with the --pre flag. Namely, when one or more
+--pre-glob flags are given, then only files that match the given set of globs
+will be handed to the command specified by the --pre flag. Any non-matching
+files will be searched without using the preprocessor command. Globbing rules
+match .gitignore globs.
+
+This flag is useful when sparing the relatively expensive preprocessor step
+for a small subset of files. For example:
+
+    rg --pre pdftotext --pre-glob '*.pdf' foo
+
+Multiple --pre-glob flags may be used. This flag has no effect if the --pre
+flag is not used.
+");
+    let arg = RGArg::flag("pre-glob", "GLOB")
+        .help(SHORT).long_help(LONG)
+        .multiple()
+        .allow_leading_hyphen();
+    args.push(arg);
+}
+
 fn flag_smart_case(args: &mut Vec<RGArg>) {
     const SHORT: &str = "Smart case search.";
     const LONG: &str = long!("\
@@ -1562,17 +2262,86 @@ This overrides the -s/--case-sensitive and -i/--ignore-case flags.
     args.push(arg);
 }
 
+fn flag_sort(args: &mut Vec<RGArg>) {
+    const SHORT: &str =
+        "Sort results in ascending order. Implies --threads=1.";
+    const LONG: &str = long!("\
+This flag enables sorting of results in ascending order. The possible values
+for this flag are:
+
+    path        Sort by file path.
+    modified    Sort by the last modified time on a file.
+    accessed    Sort by the last accessed time on a file.
+    created     Sort by the creation time on a file.
+    size        Sort by file size.
+    none        Do not sort results.
+
+If the chosen (manually or by-default) sorting criteria isn't available on
+your system (for example, creation time is not available on ext4 file
+systems), then sorted results are not guaranteed.
+
+Note that sorting results currently disables all parallelism and runs the
+search in a single thread.
+
+This flag overrides --sortr and --sort-files.
+");
+    let arg = RGArg::flag("sort", "SORTBY")
+        .help(SHORT).long_help(LONG)
+        .possible_values(&[
+            "path", "modified", "accessed", "created", "size", "none",
+        ])
+        .overrides("sortr")
+        .overrides("sort-files");
+    args.push(arg);
+}
+
+fn flag_sortr(args: &mut Vec<RGArg>) {
+    const SHORT: &str =
+        "Sort results in descending order. Implies --threads=1.";
+    const LONG: &str = long!("\
+This flag enables sorting of results in descending order. The possible values
+for this flag are:
+
+    path        Sort by file path.
+    modified    Sort by the last modified time on a file.
+    accessed    Sort by the last accessed time on a file.
+    created     Sort by the creation time on a file.
+    size        Sort by file size.
+    none        Do not sort results.
+
+If the chosen (manually or by-default) sorting criteria isn't available on
+your system (for example, creation time is not available on ext4 file
+systems), then sorted results are not guaranteed.
+
+Note that sorting results currently disables all parallelism and runs the
+search in a single thread.
+
+This flag overrides --sort and --sort-files.
+");
+    let arg = RGArg::flag("sortr", "SORTBY")
+        .help(SHORT).long_help(LONG)
+        .possible_values(&[
+            "path", "modified", "accessed", "created", "size", "none",
+        ])
+        .overrides("sort")
+        .overrides("sort-files");
+    args.push(arg);
+}
+
 fn flag_sort_files(args: &mut Vec<RGArg>) {
     const SHORT: &str = "Sort results by file path. Implies --threads=1.";
     const LONG: &str = long!("\
 Sort results by file path. Note that this currently disables all parallelism
 and runs search in a single thread.
 
-This flag can be disabled with --no-sort-files.
+This flag is a deprecated alias for '--sort path' and can be disabled with
+--no-sort-files.
 ");
     let arg = RGArg::switch("sort-files")
         .help(SHORT).long_help(LONG)
-        .overrides("no-sort-files");
+        .overrides("no-sort-files")
+        .overrides("sort")
+        .overrides("sortr");
     args.push(arg);
 
     let arg = RGArg::switch("no-sort-files")
@@ -1586,8 +2355,9 @@ fn flag_stats(args: &mut Vec<RGArg>) {
     const LONG: &str = long!("\
 Print aggregate statistics about this ripgrep search. When this flag is
 present, ripgrep will print the following stats to stdout at the end of the
-search: number of matched lines, number of files with matches, number of files
-searched, and the time taken for the entire search to complete.
+search: number of individual matches, number of matched lines, number of files
+with matches, number of files searched, number of bytes searched, the time
+spent searching and the time taken for the entire run to complete.
 
 This set of aggregate statistics may expand over time.
 
@@ -1632,13 +2402,53 @@ fn flag_threads(args: &mut Vec<RGArg>) {
     const SHORT: &str = "The approximate number of threads to use.";
     const LONG: &str = long!("\
 The approximate number of threads to use. A value of 0 (which is the default)
-causes ripgrep to choose the thread count using heuristics.
+causes ripgrep to choose the thread count using heuristics. In particular,
+ripgrep sizes the thread pool to the number of CPUs (with a cap) for local
+searches, and oversubscribes the CPUs when the search is rooted on a network
+file system, where the workload is IO-bound. A non-zero value always overrides
+the heuristics.
 ");
     let arg = RGArg::flag("threads", "NUM").short("j")
         .help(SHORT).long_help(LONG);
     args.push(arg);
 }
 
+fn flag_timeout(args: &mut Vec<RGArg>) {
+    const SHORT: &str = "Stop searching after NUM seconds.";
+    const LONG: &str = long!("\
+Stop the entire search after approximately NUM seconds of wall clock time.
+Results produced before the deadline are printed as usual and a message noting
+that the search timed out is printed to stderr. The deadline is only checked
+between files, so a single large file may overrun the budget.
+
+This is useful for bounding the worst case runtime of ripgrep in scripts and
+CI steps.
+");
+    let arg = RGArg::flag("timeout", "NUM")
+        .help(SHORT).long_help(LONG)
+        .number();
+    args.push(arg);
+}
+
+fn flag_traversal(args: &mut Vec<RGArg>) {
+    const SHORT: &str = "Directory traversal order: breadth-first or depth-first.";
+    const LONG: &str = long!("\
+This flag controls the order in which the parallel directory walker visits
+directories. Breadth-first order (the default) tends to surface shallow files
+earlier, which is usually preferable in interactive use. Depth-first order
+keeps the internal work queue smaller and is therefore better for memory use
+on very deep or wide directory trees.
+
+Note that this only affects parallel traversal; with a single thread, the
+traversal is always depth-first. Either order is approximate, since entries
+are consumed concurrently by multiple threads.
+");
+    let arg = RGArg::flag("traversal", "ORDER")
+        .help(SHORT).long_help(LONG)
+        .possible_values(&["breadth-first", "depth-first"]);
+    args.push(arg);
+}
+
 fn flag_type(args: &mut Vec<RGArg>) {
     const SHORT: &str = "Only search files matching TYPE.";
     const LONG: &str = long!("\
@@ -1678,6 +2488,12 @@ Additional glob rules can still be added to the src type by using the
 
     --type-add 'src:include:cpp,py,md' --type-add 'src:*.foo'
 
+A glob may be prefixed with '!' to exclude matching files from a type instead
+of adding them. An exclusion overrides any globs given to the type before it.
+For example, to search web sources while skipping minified Javascript:
+
+    --type-add 'web:include:html,css,js' --type-add 'web:!*.min.js' -tweb
+
 Note that type names must consist only of Unicode letters or numbers.
 Punctuation characters are not allowed.
 ");
@@ -1687,6 +2503,31 @@ Punctuation characters are not allowed.
     args.push(arg);
 }
 
+fn flag_type_add_file(args: &mut Vec<RGArg>) {
+    const SHORT: &str = "Load file type definitions from a file.";
+    const LONG: &str = long!("\
+Load additional file type definitions from the given file. Each line of the
+file holds one definition in the same format accepted by the --type-add flag.
+Empty lines and lines starting with '#' are ignored. Definitions loaded this
+way may be referenced by later definitions and by the --type-add flag.
+
+This flag can be provided multiple times. Putting it in a ripgrep
+configuration file (see RIPGREP_CONFIG_PATH) makes a shared set of custom
+types available to every invocation.
+
+Example file contents:
+
+    # Front end sources.
+    web:include:html,css,js
+    web:*.vue
+    web:!*.min.js
+");
+    let arg = RGArg::flag("type-add-file", "FILE")
+        .help(SHORT).long_help(LONG)
+        .multiple();
+    args.push(arg);
+}
+
 fn flag_type_clear(args: &mut Vec<RGArg>) {
     const SHORT: &str = "Clear globs for a file type.";
     const LONG: &str = long!("\
@@ -1796,3 +2637,22 @@ This overrides the --line-regexp flag.
         .overrides("line-regexp");
     args.push(arg);
 }
+
+fn flag_wordlist(args: &mut Vec<RGArg>) {
+    const SHORT: &str = "Search for literal strings from the given file.";
+    const LONG: &str = long!("\
+Search for literal strings from the given file, with one string per line.
+Every string is treated literally, so regular expression meta characters
+never need to be escaped. This is useful for efficiently matching against a
+large dictionary of fixed strings. When this flag is used multiple times or
+in combination with the -e/--regexp or -f/--file flags, then all patterns
+provided are searched.
+
+A line is printed if and only if it matches at least one of the patterns.
+");
+    let arg = RGArg::flag("wordlist", "WORDLISTFILE")
+        .help(SHORT).long_help(LONG)
+        .multiple()
+        .allow_leading_hyphen();
+    args.push(arg);
+}
@@ -1,14 +1,16 @@
 use std::cmp;
+use std::collections::BTreeSet;
 use std::env;
-use std::ffi::OsStr;
+use std::ffi::{OsStr, OsString};
 use std::fs;
 use std::io::{self, BufRead};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime};
 
 use clap;
-use encoding_rs::Encoding;
+use encoding_rs::{Encoding, WINDOWS_1252};
 use grep::{Grep, GrepBuilder};
 use log;
 use num_cpus;
@@ -21,11 +23,14 @@ use atty;
 use ignore::overrides::{Override, OverrideBuilder};
 use ignore::types::{FileTypeDef, Types, TypesBuilder};
 use ignore;
-use printer::{ColorSpecs, Printer};
+use preprocessor::{PreprocessorGlobs, PreprocessorGlobsBuilder};
+use printer::{self, ColorSpecs, Printer};
+use stats::Stats;
 use unescape::{escape, unescape};
-use worker::{Worker, WorkerBuilder};
+use worker::{BufferPool, Worker, WorkerBuilder};
 
 use config;
+use human;
 use logger::Logger;
 use Result;
 
@@ -34,35 +39,59 @@ use Result;
 pub struct Args {
     paths: Vec<PathBuf>,
     after_context: usize,
+    and: Vec<Grep>,
     before_context: usize,
+    binary: bool,
+    blame: bool,
+    buffer_mode: BufferMode,
+    by_type: bool,
     byte_offset: bool,
     can_match: bool,
     color_choice: termcolor::ColorChoice,
     colors: ColorSpecs,
+    backup_suffix: Option<OsString>,
     column: bool,
+    context_block: bool,
     context_separator: Vec<u8>,
     count: bool,
     count_matches: bool,
+    dry_run: bool,
+    dup_lines: Option<usize>,
     encoding: Option<&'static Encoding>,
+    field_context_separator: Vec<u8>,
+    field_match_separator: Vec<u8>,
+    files_with_all_matches: bool,
     files_with_matches: bool,
     files_without_matches: bool,
+    highlight: Option<Grep>,
     eol: u8,
     files: bool,
     follow: bool,
+    generate: Option<String>,
     glob_overrides: Override,
     grep: Grep,
     heading: bool,
     hidden: bool,
+    hyperlink_format: Option<String>,
     ignore_files: Vec<PathBuf>,
+    include_zero: bool,
+    in_place: bool,
     invert_match: bool,
+    json: bool,
+    json_file: Option<PathBuf>,
     line_number: bool,
     line_per_match: bool,
+    max_bytes_per_file: Option<u64>,
     max_columns: Option<usize>,
     max_count: Option<u64>,
     max_depth: Option<usize>,
     max_filesize: Option<u64>,
+    filesize_skips: Arc<AtomicUsize>,
     mmap: bool,
     no_ignore: bool,
+    no_ignore_dot: bool,
+    no_ignore_exclude: bool,
+    no_ignore_files: bool,
     no_ignore_global: bool,
     no_ignore_messages: bool,
     no_ignore_parent: bool,
@@ -73,17 +102,24 @@ pub struct Args {
     path_separator: Option<u8>,
     quiet: bool,
     quiet_matched: QuietMatched,
+    record: Option<PathBuf>,
     replace: Option<Vec<u8>>,
-    sort_files: bool,
+    replay: Option<PathBuf>,
+    sort_by: SortBy,
     stdout_handle: Option<same_file::Handle>,
     text: bool,
     threads: usize,
+    timeout: Option<Duration>,
+    traversal: ignore::WalkStrategy,
     type_list: bool,
     types: Types,
     with_filename: bool,
     search_zip_files: bool,
     preprocessor: Option<PathBuf>,
-    stats: bool
+    preprocessor_globs: Option<PreprocessorGlobs>,
+    stats: bool,
+    search_stats: Arc<Stats>,
+    buffer_pool: Arc<BufferPool>,
 }
 
 impl Args {
@@ -156,6 +192,12 @@ impl Args {
         self.files
     }
 
+    /// Returns the kind of special output that ripgrep should generate and
+    /// then exit with, if the --generate flag was given.
+    pub fn generate(&self) -> Option<&str> {
+        self.generate.as_ref().map(|s| &**s)
+    }
+
     /// Create a new line based matcher. The matcher returned can be used
     /// across multiple threads simultaneously. This matcher only supports
     /// basic searching of regular expressions in a single buffer.
@@ -165,6 +207,24 @@ impl Args {
         self.grep.clone()
     }
 
+    /// Returns the number of files skipped so far because they exceed the
+    /// --max-filesize limit.
+    pub fn filesize_skips(&self) -> u64 {
+        self.filesize_skips.load(Ordering::SeqCst) as u64
+    }
+
+    /// Returns true if search results should be printed as JSON Lines
+    /// messages.
+    pub fn json(&self) -> bool {
+        self.json
+    }
+
+    /// Returns the file path to write a copy of the search results to, in
+    /// JSON Lines format, if the --json-file flag was given.
+    pub fn json_file(&self) -> Option<&Path> {
+        self.json_file.as_ref().map(|p| &**p)
+    }
+
     /// Whether ripgrep should be quiet or not.
     pub fn quiet(&self) -> bool {
         self.quiet
@@ -179,15 +239,33 @@ impl Args {
         self.quiet_matched.clone()
     }
 
+    /// Returns the file path to record the search session to, if the
+    /// --record flag was given.
+    pub fn record(&self) -> Option<&Path> {
+        self.record.as_ref().map(|p| &**p)
+    }
+
+    /// Returns the file path of a recorded search session to replay, if the
+    /// --replay flag was given.
+    pub fn replay(&self) -> Option<&Path> {
+        self.replay.as_ref().map(|p| &**p)
+    }
+
     /// Create a new printer of individual search results that writes to the
     /// writer given.
     pub fn printer<W: termcolor::WriteColor>(&self, wtr: W) -> Printer<W> {
         let mut p = Printer::new(wtr)
+            .blame(self.blame)
             .colors(self.colors.clone())
             .column(self.column)
             .context_separator(self.context_separator.clone())
             .eol(self.eol)
+            .field_context_separator(self.field_context_separator.clone())
+            .field_match_separator(self.field_match_separator.clone())
             .heading(self.heading)
+            .hyperlink_format(self.hyperlink_format.clone())
+            .json(self.json)
+            .json_tee(self.json_file.is_some())
             .line_per_match(self.line_per_match)
             .null(self.null)
             .only_matching(self.only_matching)
@@ -212,7 +290,10 @@ impl Args {
         if use_heading_sep {
             Some(b"".to_vec())
         } else if !contextless
-            && (self.before_context > 0 || self.after_context > 0) {
+            && !self.context_separator.is_empty()
+            && (self.before_context > 0
+                || self.after_context > 0
+                || self.context_block) {
             Some(self.context_separator.clone())
         } else {
             None
@@ -229,9 +310,55 @@ impl Args {
         self.stats
     }
 
+    /// Returns the aggregate statistics recorded by the search workers so
+    /// far.
+    pub fn search_stats(&self) -> &Stats {
+        &self.search_stats
+    }
+
+    /// Returns whether ripgrep should print a per-file-type summary of
+    /// match and file counts after the search.
+    pub fn by_type(&self) -> bool {
+        self.by_type
+    }
+
+    /// Returns the window size for cross-file duplication detection, if
+    /// that mode was requested.
+    pub fn dup_lines(&self) -> Option<usize> {
+        self.dup_lines
+    }
+
+    /// Returns true if matching files should be rewritten in place with the
+    /// replacement applied, instead of printing search results.
+    pub fn in_place(&self) -> bool {
+        self.in_place
+    }
+
+    /// Returns true if --in-place should print a preview of the changes
+    /// instead of writing them.
+    pub fn dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    /// Returns the suffix to append to backups of files rewritten by
+    /// --in-place, if the --backup-suffix flag was given.
+    pub fn backup_suffix(&self) -> Option<&OsStr> {
+        self.backup_suffix.as_ref().map(|s| &**s)
+    }
+
+    /// Returns the replacement text given by the -r/--replace flag, if any.
+    pub fn replacement(&self) -> Option<&[u8]> {
+        self.replace.as_ref().map(|r| &**r)
+    }
+
     /// Create a new writer for single-threaded searching with color support.
     pub fn stdout(&self) -> Box<termcolor::WriteColor> {
-        if atty::is(atty::Stream::Stdout) {
+        let line_buffered = match self.buffer_mode {
+            BufferMode::Line => true,
+            BufferMode::Block => false,
+            BufferMode::Auto => atty::is(atty::Stream::Stdout),
+        };
+        if line_buffered {
             Box::new(termcolor::StandardStream::stdout(self.color_choice))
         } else {
             Box::new(
@@ -274,24 +401,39 @@ impl Args {
     pub fn worker(&self) -> Worker {
         WorkerBuilder::new(self.grep())
             .after_context(self.after_context)
+            .and(self.and.clone())
             .before_context(self.before_context)
+            .binary(self.binary)
+            .context_block(self.context_block)
             .byte_offset(self.byte_offset)
             .count(self.count)
             .count_matches(self.count_matches)
             .encoding(self.encoding)
             .files_with_matches(self.files_with_matches)
+            .files_with_all_matches(self.files_with_all_matches)
             .files_without_matches(self.files_without_matches)
+            .highlight(self.highlight.clone())
+            .include_zero(self.include_zero)
             .eol(self.eol)
             .line_number(self.line_number)
             .invert_match(self.invert_match)
+            .max_bytes_per_file(self.max_bytes_per_file)
             .max_count(self.max_count)
             .mmap(self.mmap)
             .no_messages(self.no_messages)
             .quiet(self.quiet)
+            .stats(
+                if self.stats || self.json || self.json_file.is_some() {
+                    Some(self.search_stats.clone())
+                } else {
+                    None
+                },
+            )
             .text(self.text)
             .search_zip_files(self.search_zip_files)
             .preprocessor(self.preprocessor.clone())
-            .build()
+            .preprocessor_globs(self.preprocessor_globs.clone())
+            .build_with_pool(self.buffer_pool.clone())
     }
 
     /// Returns the number of worker search threads that should be used.
@@ -299,6 +441,12 @@ impl Args {
         self.threads
     }
 
+    /// Returns the wall clock budget for the entire search, if one was
+    /// given.
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
     /// Returns a list of type definitions currently loaded.
     pub fn type_defs(&self) -> &[FileTypeDef] {
         self.types.definitions()
@@ -338,10 +486,12 @@ impl Args {
         for path in &paths[1..] {
             wd.add(path);
         }
-        for path in &self.ignore_files {
-            if let Some(err) = wd.add_ignore(path) {
-                if !self.no_messages && !self.no_ignore_messages {
-                    eprintln!("{}", err);
+        if !self.no_ignore_files {
+            for path in &self.ignore_files {
+                if let Some(err) = wd.add_ignore(path) {
+                    if !self.no_messages && !self.no_ignore_messages {
+                        eprintln!("{}", err);
+                    }
                 }
             }
         }
@@ -350,26 +500,228 @@ impl Args {
         wd.hidden(!self.hidden);
         wd.max_depth(self.max_depth);
         wd.max_filesize(self.max_filesize);
+        if self.max_filesize.is_some() {
+            wd.skipped_filesize_counter(self.filesize_skips.clone());
+        }
         wd.overrides(self.glob_overrides.clone());
         wd.types(self.types.clone());
         wd.git_global(
             !self.no_ignore && !self.no_ignore_vcs && !self.no_ignore_global
         );
         wd.git_ignore(!self.no_ignore && !self.no_ignore_vcs);
-        wd.git_exclude(!self.no_ignore && !self.no_ignore_vcs);
-        wd.ignore(!self.no_ignore);
-        if !self.no_ignore {
+        wd.git_exclude(
+            !self.no_ignore && !self.no_ignore_vcs && !self.no_ignore_exclude
+        );
+        wd.ignore(!self.no_ignore && !self.no_ignore_dot);
+        if !self.no_ignore && !self.no_ignore_dot {
             wd.add_custom_ignore_filename(".rgignore");
         }
         wd.parents(!self.no_ignore_parent);
         wd.threads(self.threads());
-        if self.sort_files {
-            wd.sort_by_file_name(|a, b| a.cmp(b));
-        }
+        wd.strategy(self.traversal);
+        self.sort_by.configure_walker(&mut wd);
         wd
     }
 }
 
+/// The output buffering strategy, as requested by the --line-buffered and
+/// --block-buffered flags.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum BufferMode {
+    /// Choose between line and block buffering based on whether stdout is
+    /// connected to a terminal.
+    Auto,
+    /// Flush the output after every line.
+    Line,
+    /// Flush the output only when an internal buffer fills up.
+    Block,
+}
+
+/// The sort criteria for search results, as requested by the --sort and
+/// --sortr flags.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct SortBy {
+    /// Whether to reverse the sort criteria (i.e., descending order).
+    reverse: bool,
+    /// The actual sorting criteria.
+    kind: SortByKind,
+}
+
+/// The criterion to sort search results by.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum SortByKind {
+    /// No sorting at all.
+    None,
+    /// Sort by file path.
+    Path,
+    /// Sort by last modified time.
+    LastModified,
+    /// Sort by last accessed time.
+    LastAccessed,
+    /// Sort by creation time.
+    Created,
+    /// Sort by file size.
+    Size,
+}
+
+impl SortBy {
+    fn asc(kind: SortByKind) -> SortBy {
+        SortBy { reverse: false, kind: kind }
+    }
+
+    fn desc(kind: SortByKind) -> SortBy {
+        SortBy { reverse: true, kind: kind }
+    }
+
+    fn none() -> SortBy {
+        SortBy::asc(SortByKind::None)
+    }
+
+    /// Configure the given walker builder to yield entries according to
+    /// this sort criteria.
+    fn configure_walker(&self, builder: &mut ignore::WalkBuilder) {
+        let reverse = self.reverse;
+        match self.kind {
+            SortByKind::None => {}
+            SortByKind::Path => {
+                if reverse {
+                    builder.sort_by_file_name(|a, b| a.cmp(b).reverse());
+                } else {
+                    builder.sort_by_file_name(|a, b| a.cmp(b));
+                }
+            }
+            SortByKind::LastModified => {
+                builder.sort_by_file_path(move |a, b| {
+                    sort_by_metadata_time(a, b, reverse, |md| md.modified())
+                });
+            }
+            SortByKind::LastAccessed => {
+                builder.sort_by_file_path(move |a, b| {
+                    sort_by_metadata_time(a, b, reverse, |md| md.accessed())
+                });
+            }
+            SortByKind::Created => {
+                builder.sort_by_file_path(move |a, b| {
+                    sort_by_metadata_time(a, b, reverse, |md| md.created())
+                });
+            }
+            SortByKind::Size => {
+                builder.sort_by_file_path(move |a, b| {
+                    let s1 = a.metadata().ok().map(|md| md.len());
+                    let s2 = b.metadata().ok().map(|md| md.len());
+                    if reverse { s2.cmp(&s1) } else { s1.cmp(&s2) }
+                });
+            }
+        }
+    }
+}
+
+impl SortByKind {
+    fn new(kind: &str) -> SortByKind {
+        match kind {
+            "path" => SortByKind::Path,
+            "modified" => SortByKind::LastModified,
+            "accessed" => SortByKind::LastAccessed,
+            "created" => SortByKind::Created,
+            "size" => SortByKind::Size,
+            _ => SortByKind::None,
+        }
+    }
+}
+
+/// Compare two paths by a timestamp drawn from their metadata. Paths for
+/// which the timestamp is unavailable sort before everything else.
+fn sort_by_metadata_time<G>(
+    p1: &Path,
+    p2: &Path,
+    reverse: bool,
+    get_time: G,
+) -> cmp::Ordering
+where G: Fn(&fs::Metadata) -> io::Result<SystemTime>
+{
+    let t1 = p1.metadata().ok().and_then(|md| get_time(&md).ok());
+    let t2 = p2.metadata().ok().and_then(|md| get_time(&md).ok());
+    if reverse {
+        t2.cmp(&t1)
+    } else {
+        t1.cmp(&t2)
+    }
+}
+
+/// Adds every file type definition in the file at `path` to the given
+/// builder. Each line holds one definition in the syntax accepted by the
+/// --type-add flag. Empty lines and lines starting with `#` are ignored.
+fn add_type_defs_from_file(
+    btypes: &mut TypesBuilder,
+    path: &Path,
+) -> Result<()> {
+    let mut contents = vec![];
+    let result = fs::File::open(path)
+        .and_then(|mut file| io::Read::read_to_end(&mut file, &mut contents));
+    if let Err(err) = result {
+        return Err(From::from(format!("{}: {}", path.display(), err)));
+    }
+    let contents = String::from_utf8_lossy(&contents);
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        btypes.add_def(line).map_err(|err| {
+            format!("{}:{}: {}", path.display(), i + 1, err)
+        })?;
+    }
+    Ok(())
+}
+
+/// Returns true if the given path is believed to reside on a network file
+/// system, where searching is overwhelmingly IO-bound.
+#[cfg(target_os = "linux")]
+fn is_network_fs(path: &Path) -> bool {
+    use std::ffi::CString;
+    use std::mem;
+    use std::os::unix::ffi::OsStrExt;
+
+    use libc;
+
+    // Values from linux/magic.h.
+    const NFS_SUPER_MAGIC: i64 = 0x6969;
+    const SMB_SUPER_MAGIC: i64 = 0x517B;
+    const SMB2_MAGIC_NUMBER: i64 = 0xFE534D42;
+    const CIFS_MAGIC_NUMBER: i64 = 0xFF534D42;
+    const CODA_SUPER_MAGIC: i64 = 0x73757245;
+    const AFS_SUPER_MAGIC: i64 = 0x5346414F;
+    const FUSE_SUPER_MAGIC: i64 = 0x65735546;
+
+    let cpath = match CString::new(path.as_os_str().as_bytes()) {
+        Ok(cpath) => cpath,
+        Err(_) => return false,
+    };
+    let mut stat: libc::statfs = unsafe { mem::zeroed() };
+    if unsafe { libc::statfs(cpath.as_ptr(), &mut stat) } != 0 {
+        return false;
+    }
+    match stat.f_type as i64 {
+        NFS_SUPER_MAGIC
+        | SMB_SUPER_MAGIC
+        | SMB2_MAGIC_NUMBER
+        | CIFS_MAGIC_NUMBER
+        | CODA_SUPER_MAGIC
+        | AFS_SUPER_MAGIC
+        // FUSE hosts sshfs and most other user space network file systems.
+        // Treating all of FUSE as "network" overshoots a little, but the
+        // cost of extra threads on a local FUSE mount is far smaller than
+        // the cost of too few threads on a remote one.
+        | FUSE_SUPER_MAGIC => true,
+        _ => false,
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn is_network_fs(_: &Path) -> bool {
+    false
+}
+
 /// `ArgMatches` wraps `clap::ArgMatches` and provides semantic meaning to
 /// several options/flags.
 struct ArgMatches<'a>(clap::ArgMatches<'a>);
@@ -385,39 +737,100 @@ impl<'a> ArgMatches<'a> {
         let (before_context, after_context) = self.contexts()?;
         let (count, count_matches) = self.counts();
         let quiet = self.is_present("quiet");
-        let (grep, can_match) = self.grep()?;
+        let pats = self.patterns()?;
+        let can_match = !pats.is_empty();
+        let and_pats: Vec<String> = self.values_of_lossy_vec("and")
+            .iter()
+            .map(|pat| self.str_pattern(pat))
+            .collect();
+        let grep = self.grep(&pats.join("|"))?;
+        let mut and = vec![];
+        for pat in &and_pats {
+            and.push(self.new_grep(pat)?);
+        }
+        // When --and patterns are present, build a regex covering all of
+        // them for the printer, so that every contributing match on a line
+        // is highlighted.
+        let highlight =
+            if and_pats.is_empty() {
+                None
+            } else {
+                let mut all = pats.clone();
+                all.extend(and_pats);
+                Some(self.new_grep(&all.join("|"))?)
+            };
+        // When --pre-glob is given, the preprocessor is scoped to matching
+        // files only, so the unconditional preprocessor is left unset.
+        let preprocessor_globs = self.preprocessor_globs()?;
+        let preprocessor = if preprocessor_globs.is_some() {
+            None
+        } else {
+            self.preprocessor()
+        };
         let args = Args {
             paths: paths,
             after_context: after_context,
+            and: and,
             before_context: before_context,
-            byte_offset: self.is_present("byte-offset"),
+            binary: self.is_present("binary"),
+            blame: self.is_present("blame"),
+            buffer_mode: self.buffer_mode(),
+            by_type: self.is_present("by-type"),
+            // The JSON format always carries absolute offsets, so byte
+            // offset tracking is forced on when --json is used.
+            byte_offset:
+                self.is_present("byte-offset") || self.is_present("json"),
             can_match: can_match,
             color_choice: self.color_choice(),
             colors: self.color_specs()?,
+            backup_suffix: self.value_of_os("backup-suffix")
+                .map(|s| s.to_os_string()),
             column: self.column(),
+            context_block: self.is_present("context-block"),
             context_separator: self.context_separator(),
             count: count,
             count_matches: count_matches,
+            dry_run: self.is_present("dry-run"),
+            dup_lines: self.usize_of_nonzero("dup-lines")?,
             encoding: self.encoding()?,
+            field_context_separator:
+                self.field_separator("field-context-separator", b"-"),
+            field_match_separator:
+                self.field_separator("field-match-separator", b":"),
+            files_with_all_matches:
+                self.is_present("files-with-all-matches"),
             files_with_matches: self.is_present("files-with-matches"),
             files_without_matches: self.is_present("files-without-match"),
+            highlight: highlight,
             eol: b'\n',
             files: self.is_present("files"),
             follow: self.is_present("follow"),
+            generate: self.value_of_lossy("generate"),
             glob_overrides: self.overrides()?,
             grep: grep,
             heading: self.heading(),
             hidden: self.hidden(),
+            hyperlink_format: self.hyperlink_format()?,
             ignore_files: self.ignore_files(),
+            include_zero: self.is_present("include-zero"),
+            in_place: self.in_place()?,
             invert_match: self.is_present("invert-match"),
+            json: self.is_present("json"),
+            json_file: self.value_of_os("json-file").map(PathBuf::from),
             line_number: line_number,
             line_per_match: self.is_present("vimgrep"),
+            max_bytes_per_file:
+                self.parse_human_readable_size_arg("max-bytes-per-file")?,
             max_columns: self.usize_of_nonzero("max-columns")?,
             max_count: self.usize_of("max-count")?.map(|n| n as u64),
             max_depth: self.usize_of("max-depth")?,
             max_filesize: self.max_filesize()?,
+            filesize_skips: Arc::new(AtomicUsize::new(0)),
             mmap: mmap,
             no_ignore: self.no_ignore(),
+            no_ignore_dot: self.no_ignore_dot(),
+            no_ignore_exclude: self.no_ignore_exclude(),
+            no_ignore_files: self.is_present("no-ignore-files"),
             no_ignore_global: self.no_ignore_global(),
             no_ignore_messages: self.is_present("no-ignore-messages"),
             no_ignore_parent: self.no_ignore_parent(),
@@ -428,17 +841,27 @@ impl<'a> ArgMatches<'a> {
             path_separator: self.path_separator()?,
             quiet: quiet,
             quiet_matched: QuietMatched::new(quiet),
+            record: self.value_of_os("record")
+                .map(|p| Path::new(p).to_path_buf()),
             replace: self.replace(),
-            sort_files: self.is_present("sort-files"),
+            replay: self.value_of_os("replay")
+                .map(|p| Path::new(p).to_path_buf()),
+            sort_by: self.sort_by()?,
             stdout_handle: self.stdout_handle(),
             text: self.text(),
             threads: self.threads()?,
+            timeout: self.usize_of("timeout")?
+                .map(|secs| Duration::from_secs(secs as u64)),
+            traversal: self.traversal(),
             type_list: self.is_present("type-list"),
             types: self.types()?,
             with_filename: with_filename,
             search_zip_files: self.is_present("search-zip"),
-            preprocessor: self.preprocessor(),
-            stats: self.stats()
+            preprocessor: preprocessor,
+            preprocessor_globs: preprocessor_globs,
+            stats: self.stats(),
+            search_stats: Arc::new(Stats::new()),
+            buffer_pool: Arc::new(BufferPool::new()),
         };
         if args.mmap {
             debug!("will try to use memory maps");
@@ -452,11 +875,13 @@ impl<'a> ArgMatches<'a> {
             None => vec![],
             Some(vals) => vals.map(|p| Path::new(p).to_path_buf()).collect(),
         };
-        // If --file, --files or --regexp is given, then the first path is
-        // always in `pattern`.
-        if self.is_present("file")
+        // If --dup-lines, --file, --files, --regexp or --wordlist is given,
+        // then the first path is always in `pattern`.
+        if self.is_present("dup-lines")
+            || self.is_present("file")
             || self.is_present("files")
-            || self.is_present("regexp") {
+            || self.is_present("regexp")
+            || self.is_present("wordlist") {
             if let Some(path) = self.value_of_os("pattern") {
                 paths.insert(0, Path::new(path).to_path_buf());
             }
@@ -464,6 +889,9 @@ impl<'a> ArgMatches<'a> {
         if paths.is_empty() {
             paths.push(self.default_path());
         }
+        if paths.len() > 1 && !self.is_present("no-dedup-paths") {
+            paths = dedup_paths(paths);
+        }
         paths
     }
 
@@ -477,7 +905,9 @@ impl<'a> ArgMatches<'a> {
             || !stdin_is_readable()
             || (self.is_present("file") && file_is_stdin)
             || self.is_present("files")
-            || self.is_present("type-list");
+            || self.is_present("type-list")
+            || self.is_present("dup-lines")
+            || self.is_present("replay");
         if search_cwd {
             Path::new("./").to_path_buf()
         } else {
@@ -505,13 +935,20 @@ impl<'a> ArgMatches<'a> {
     ///
     /// If any pattern is invalid UTF-8, then an error is returned.
     fn patterns(&self) -> Result<Vec<String>> {
-        if self.is_present("files") || self.is_present("type-list") {
+        if self.is_present("files")
+            || self.is_present("type-list")
+            || self.is_present("dup-lines")
+            || self.is_present("replay") {
             return Ok(vec![self.empty_pattern()]);
         }
+        // Validate --fuzzy eagerly so that the pattern helpers below can
+        // assume a valid setting.
+        self.fuzzy_distance()?;
         let mut pats = vec![];
         match self.values_of_os("regexp") {
             None => {
-                if self.values_of_os("file").is_none() {
+                if self.values_of_os("file").is_none()
+                    && self.values_of_os("wordlist").is_none() {
                     if let Some(os_pat) = self.value_of_os("pattern") {
                         pats.push(self.os_str_pattern(os_pat)?);
                     }
@@ -524,20 +961,37 @@ impl<'a> ArgMatches<'a> {
             }
         }
         if let Some(files) = self.values_of_os("file") {
+            let skip_comments = self.is_present("file-comments");
             for file in files {
                 if file == "-" {
                     let stdin = io::stdin();
                     for line in stdin.lock().lines() {
-                        pats.push(self.str_pattern(&line?));
+                        let line = line?;
+                        if skip_comments && is_pattern_comment(&line) {
+                            continue;
+                        }
+                        pats.push(self.str_pattern(&line));
                     }
                 } else {
                     let f = fs::File::open(file)?;
                     for line in io::BufReader::new(f).lines() {
-                        pats.push(self.str_pattern(&line?));
+                        let line = line?;
+                        if skip_comments && is_pattern_comment(&line) {
+                            continue;
+                        }
+                        pats.push(self.str_pattern(&line));
                     }
                 }
             }
         }
+        if let Some(files) = self.values_of_os("wordlist") {
+            for file in files {
+                let f = fs::File::open(file)?;
+                for line in io::BufReader::new(f).lines() {
+                    pats.push(self.str_wordlist_pattern(&line?));
+                }
+            }
+        }
         // It's important that this be at the end; otherwise it would always
         // match first, and we wouldn't get colours in the output
         if self.is_present("passthru") && !self.is_present("count") {
@@ -549,10 +1003,11 @@ impl<'a> ArgMatches<'a> {
     /// Converts an OsStr pattern to a String pattern, including line/word
     /// boundaries or escapes if applicable.
     ///
-    /// If the pattern is not valid UTF-8, then an error is returned.
+    /// If the pattern is not valid UTF-8 and can't be transcoded from a
+    /// likely locale encoding, then an error is returned.
     fn os_str_pattern(&self, pat: &OsStr) -> Result<String> {
-        let s = pattern_to_str(pat)?;
-        Ok(self.str_pattern(s))
+        let s = pattern_to_utf8(pat, self.is_present("no-messages"))?;
+        Ok(self.str_pattern(&s))
     }
 
     /// Converts a &str pattern to a String pattern, including line/word
@@ -568,17 +1023,51 @@ impl<'a> ArgMatches<'a> {
         }
     }
 
+    /// Converts a wordlist entry to a String pattern. Entries are always
+    /// escaped as literals, but word/line boundaries are still applied if
+    /// applicable.
+    fn str_wordlist_pattern(&self, pat: &str) -> String {
+        let s = self.line_pattern(self.word_pattern(regex::escape(pat)));
+
+        if s.is_empty() {
+            self.empty_pattern()
+        } else {
+            s
+        }
+    }
+
     /// Returns the given pattern as a literal pattern if the
     /// -F/--fixed-strings flag is set. Otherwise, the pattern is returned
     /// unchanged.
     fn literal_pattern(&self, pat: String) -> String {
         if self.is_present("fixed-strings") {
-            regex::escape(&pat)
+            match self.fuzzy_distance().unwrap_or(None) {
+                None => regex::escape(&pat),
+                Some(dist) => fuzzy_pattern(&pat, dist),
+            }
         } else {
             pat
         }
     }
 
+    /// Returns the edit distance for approximate matching, if the --fuzzy
+    /// flag is set.
+    ///
+    /// If --fuzzy is used without -F/--fixed-strings, then an error is
+    /// returned, since approximate matching is only defined for literal
+    /// strings.
+    fn fuzzy_distance(&self) -> Result<Option<usize>> {
+        let dist = match self.usize_of("fuzzy")? {
+            None => return Ok(None),
+            Some(dist) => dist,
+        };
+        if !self.is_present("fixed-strings") {
+            return Err(From::from(
+                "the --fuzzy flag requires the -F/--fixed-strings flag"));
+        }
+        Ok(Some(dist))
+    }
+
     /// Returns the given pattern as a word pattern if the -w/--word-regexp
     /// flag is set. Otherwise, the pattern is returned unchanged.
     fn word_pattern(&self, pat: String) -> String {
@@ -653,7 +1142,10 @@ impl<'a> ArgMatches<'a> {
     fn mmap(&self, paths: &[PathBuf]) -> Result<bool> {
         let (before, after) = self.contexts()?;
         let enc = self.encoding()?;
-        Ok(if before > 0 || after > 0 || self.is_present("no-mmap") {
+        Ok(if before > 0
+            || after > 0
+            || self.is_present("context-block")
+            || self.is_present("no-mmap") {
             false
         } else if self.is_present("mmap") {
             true
@@ -679,6 +1171,7 @@ impl<'a> ArgMatches<'a> {
             let only_stdin = paths == [Path::new("-")];
             (atty::is(atty::Stream::Stdout) && !only_stdin)
             || self.is_present("line-number")
+            || self.is_present("blame")
             || self.is_present("column")
             || self.is_present("pretty")
             || self.is_present("vimgrep")
@@ -710,14 +1203,62 @@ impl<'a> ArgMatches<'a> {
         self.value_of_lossy("replace").map(|s| s.into_bytes())
     }
 
-    /// Returns the unescaped context separator in UTF-8 bytes.
+    /// Returns true if the --in-place flag was given. It is an error to use
+    /// --in-place without also giving a replacement.
+    fn in_place(&self) -> Result<bool> {
+        if self.is_present("in-place") && !self.is_present("replace") {
+            return Err(From::from(
+                "the --in-place flag requires the -r/--replace flag"));
+        }
+        Ok(self.is_present("in-place"))
+    }
+
+    /// Returns the unescaped context separator in UTF-8 bytes. An empty
+    /// separator indicates that separators should be suppressed entirely.
     fn context_separator(&self) -> Vec<u8> {
+        if self.is_present("no-context-separator") {
+            return vec![];
+        }
         match self.value_of_lossy("context-separator") {
             None => b"--".to_vec(),
             Some(sep) => unescape(&sep),
         }
     }
 
+    /// Returns the hyperlink format to use for wrapping file paths in the
+    /// output, after resolving preset names and the {host} placeholder.
+    fn hyperlink_format(&self) -> Result<Option<String>> {
+        let format = match self.value_of_lossy("hyperlink-format") {
+            None => return Ok(None),
+            Some(format) => format,
+        };
+        let format = match &*format {
+            "none" => return Ok(None),
+            "file" => "file://{host}{path}".to_string(),
+            "vscode" => "vscode://file{path}:{line}:{column}".to_string(),
+            "idea" => "idea://open?file={path}&line={line}".to_string(),
+            custom => {
+                if !custom.contains("{path}") {
+                    return Err(From::from(format!(
+                        "invalid hyperlink format '{}': \
+                         a custom format must contain {{path}}", custom)));
+                }
+                custom.to_string()
+            }
+        };
+        let host = env::var("HOSTNAME").unwrap_or_else(|_| String::new());
+        Ok(Some(format.replace("{host}", &host)))
+    }
+
+    /// Returns the unescaped separator for the given field separator flag,
+    /// or the given default if the flag wasn't used.
+    fn field_separator(&self, name: &str, default: &[u8]) -> Vec<u8> {
+        match self.value_of_lossy(name) {
+            None => default.to_vec(),
+            Some(sep) => unescape(&sep),
+        }
+    }
+
     /// Returns the preprocessor command
     fn preprocessor(&self) -> Option<PathBuf> {
         if let Some(path) = self.value_of_os("pre") {
@@ -731,6 +1272,24 @@ impl<'a> ArgMatches<'a> {
         }
     }
 
+    /// Returns the set of glob scoped preprocessor commands, if both the
+    /// --pre and --pre-glob flags were given.
+    fn preprocessor_globs(&self) -> Result<Option<PreprocessorGlobs>> {
+        let cmd = match self.preprocessor() {
+            None => return Ok(None),
+            Some(cmd) => cmd,
+        };
+        let globs = match self.values_of_lossy("pre-glob") {
+            None => return Ok(None),
+            Some(globs) => globs,
+        };
+        let mut builder = PreprocessorGlobsBuilder::new();
+        for glob in globs {
+            builder.associate(&glob, cmd.clone())?;
+        }
+        Ok(Some(builder.build()?))
+    }
+
     /// Returns the unescaped path separator in UTF-8 bytes.
     fn path_separator(&self) -> Result<Option<u8>> {
         match self.value_of_lossy("path-separator") {
@@ -794,6 +1353,16 @@ impl<'a> ArgMatches<'a> {
         }
     }
 
+    /// Returns the traversal order to use for the parallel directory walker.
+    fn traversal(&self) -> ignore::WalkStrategy {
+        match self.value_of_lossy("traversal") {
+            Some(ref v) if v == "depth-first" => {
+                ignore::WalkStrategy::DepthFirst
+            }
+            _ => ignore::WalkStrategy::BreadthFirst,
+        }
+    }
+
     /// Returns the user's color choice based on command line parameters and
     /// environment.
     fn color_choice(&self) -> termcolor::ColorChoice {
@@ -806,10 +1375,10 @@ impl<'a> ArgMatches<'a> {
         } else if preference == "ansi" {
             termcolor::ColorChoice::AlwaysAnsi
         } else if preference == "auto" {
-            if atty::is(atty::Stream::Stdout) || self.is_present("pretty") {
+            if self.is_present("pretty") {
                 termcolor::ColorChoice::Auto
             } else {
-                termcolor::ColorChoice::Never
+                ripgrep::auto_color_choice()
             }
         } else {
             termcolor::ColorChoice::Never
@@ -818,6 +1387,10 @@ impl<'a> ArgMatches<'a> {
 
     /// Returns the color specifications given by the user on the CLI.
     ///
+    /// If no --colors flags were given, then the GREP_COLORS environment
+    /// variable is consulted instead, using GNU grep's syntax, so that color
+    /// setups carried over from grep work without translation.
+    ///
     /// If the was a problem parsing any of the provided specs, then an error
     /// is returned.
     fn color_specs(&self) -> Result<ColorSpecs> {
@@ -831,7 +1404,13 @@ impl<'a> ArgMatches<'a> {
             "match:fg:red".parse().unwrap(),
             "match:style:bold".parse().unwrap(),
         ];
-        for spec_str in self.values_of_lossy_vec("colors") {
+        let color_args = self.values_of_lossy_vec("colors");
+        if color_args.is_empty() {
+            if let Ok(grep_colors) = env::var("GREP_COLORS") {
+                specs.extend(printer::parse_grep_colors(&grep_colors));
+            }
+        }
+        for spec_str in color_args {
             specs.push(spec_str.parse()?);
         }
         Ok(ColorSpecs::new(&specs))
@@ -873,17 +1452,67 @@ impl<'a> ArgMatches<'a> {
         self.is_present("stats")
     }
 
+    /// Returns the sort criteria based on the --sort, --sortr and
+    /// --sort-files flags.
+    /// Returns the output buffering strategy to use.
+    ///
+    /// When neither --line-buffered nor --block-buffered is given, the
+    /// strategy is chosen based on whether stdout is connected to a
+    /// terminal.
+    fn buffer_mode(&self) -> BufferMode {
+        if self.is_present("line-buffered") {
+            BufferMode::Line
+        } else if self.is_present("block-buffered") {
+            BufferMode::Block
+        } else {
+            BufferMode::Auto
+        }
+    }
+
+    fn sort_by(&self) -> Result<SortBy> {
+        // --sort-files is a deprecated alias for --sort path.
+        if self.is_present("sort-files") {
+            return Ok(SortBy::asc(SortByKind::Path));
+        }
+        let sort = self.value_of_lossy("sort");
+        let sortr = self.value_of_lossy("sortr");
+        Ok(match (sort, sortr) {
+            (None, None) => SortBy::none(),
+            (Some(spec), None) => SortBy::asc(SortByKind::new(&spec)),
+            (None, Some(spec)) => SortBy::desc(SortByKind::new(&spec)),
+            (Some(_), Some(_)) => {
+                // The flags override each other, so this can't happen.
+                return Err(From::from(
+                    "cannot use both --sort and --sortr"));
+            }
+        })
+    }
+
     /// Returns the approximate number of threads that ripgrep should use.
+    ///
+    /// A non-zero -j/--threads value is always used verbatim. Otherwise, the
+    /// thread count is chosen based on the workload: searches rooted on a
+    /// network file system are overwhelmingly IO-bound, so the CPUs are
+    /// oversubscribed, while local searches are sized to the CPU count
+    /// (capped, since the traversal stops scaling long before big machines
+    /// run out of cores).
     fn threads(&self) -> Result<usize> {
-        if self.is_present("sort-files") {
+        if self.sort_by()?.kind != SortByKind::None {
             return Ok(1);
         }
         let threads = self.usize_of("threads")?.unwrap_or(0);
-        Ok(if threads == 0 {
-            cmp::min(12, num_cpus::get())
-        } else {
-            threads
-        })
+        if threads != 0 {
+            return Ok(threads);
+        }
+        let cpus = num_cpus::get();
+        let threads =
+            if self.paths().iter().any(|p| is_network_fs(p)) {
+                cmp::min(4 * cpus, 64)
+            } else {
+                cmp::min(12, cpus)
+            };
+        debug!("chose default thread count of {}", threads);
+        Ok(threads)
     }
 
     /// Builds a grep matcher from the command line flags.
@@ -893,7 +1522,26 @@ impl<'a> ArgMatches<'a> {
     ///
     /// If no match can ever occur, then `false` is returned. Otherwise,
     /// `true` is returned.
-    fn grep(&self) -> Result<(Grep, bool)> {
+    fn grep(&self, pattern: &str) -> Result<Grep> {
+        // Only one engine is compiled into this build, so selecting an
+        // alternative backend explicitly is an error rather than a silent
+        // fallback.
+        match self.value_of_lossy("engine") {
+            None => {}
+            Some(ref engine) if engine == "pcre2" => {
+                return Err(From::from(
+                    "PCRE2 is not available in this build of ripgrep"));
+            }
+            Some(engine) => {
+                debug!("using the {} regex engine", engine);
+            }
+        }
+        self.new_grep(pattern)
+    }
+
+    /// Builds a line matcher for the given pattern, honoring the case and
+    /// size limit flags.
+    fn new_grep(&self, pattern: &str) -> Result<Grep> {
         let smart =
             self.is_present("smart-case")
             && !self.is_present("ignore-case")
@@ -901,9 +1549,7 @@ impl<'a> ArgMatches<'a> {
         let casei =
             self.is_present("ignore-case")
             && !self.is_present("case-sensitive");
-        let pats = self.patterns()?;
-        let ok = !pats.is_empty();
-        let mut gb = GrepBuilder::new(&pats.join("|"))
+        let mut gb = GrepBuilder::new(pattern)
             .case_smart(smart)
             .case_insensitive(casei)
             .line_terminator(b'\n');
@@ -914,12 +1560,15 @@ impl<'a> ArgMatches<'a> {
         if let Some(limit) = self.regex_size_limit()? {
             gb = gb.size_limit(limit);
         }
-        Ok((gb.build()?, ok))
+        Ok(gb.build()?)
     }
 
     /// Builds the set of glob overrides from the command line flags.
     fn overrides(&self) -> Result<Override> {
         let mut ovr = OverrideBuilder::new(env::current_dir()?);
+        if self.is_present("glob-case-insensitive") {
+            ovr.case_insensitive(true)?;
+        }
         for glob in self.values_of_lossy_vec("glob") {
             ovr.add(&glob)?;
         }
@@ -941,6 +1590,11 @@ impl<'a> ArgMatches<'a> {
         for ty in self.values_of_lossy_vec("type-clear") {
             btypes.clear(&ty);
         }
+        if let Some(paths) = self.values_of_os("type-add-file") {
+            for path in paths {
+                add_type_defs_from_file(&mut btypes, Path::new(path))?;
+            }
+        }
         for def in self.values_of_lossy_vec("type-add") {
             btypes.add_def(&def)?;
         }
@@ -965,32 +1619,9 @@ impl<'a> ArgMatches<'a> {
             Some(x) => x,
             None => return Ok(None)
         };
-        let re = regex::Regex::new("^([0-9]+)([KMG])?$").unwrap();
-        let caps =
-            re.captures(&arg_value).ok_or_else(|| {
-                format!("invalid format for {}", arg_name)
-            })?;
-
-        let value = caps[1].parse::<u64>()?;
-        let suffix = caps.get(2).map(|x| x.as_str());
-
-        let v_10 = value.checked_mul(1024);
-        let v_20 = v_10.and_then(|x| x.checked_mul(1024));
-        let v_30 = v_20.and_then(|x| x.checked_mul(1024));
-
-        let try_suffix = |x: Option<u64>| {
-            if x.is_some() {
-                Ok(x)
-            } else {
-                Err(From::from(format!("number too large for {}", arg_name)))
-            }
-        };
-        match suffix {
-            None      => Ok(Some(value)),
-            Some("K") => try_suffix(v_10),
-            Some("M") => try_suffix(v_20),
-            Some("G") => try_suffix(v_30),
-            _ => Err(From::from(format!("invalid suffix for {}", arg_name)))
+        match human::parse_size(&arg_value) {
+            Ok(size) => Ok(Some(size)),
+            Err(err) => Err(From::from(format!("{}: {}", arg_name, err))),
         }
     }
 
@@ -1017,6 +1648,17 @@ impl<'a> ArgMatches<'a> {
         || self.occurrences_of("unrestricted") >= 1
     }
 
+    /// Returns true if .ignore and .rgignore files should be ignored.
+    fn no_ignore_dot(&self) -> bool {
+        self.is_present("no-ignore-dot") || self.no_ignore()
+    }
+
+    /// Returns true if local exclusion files (e.g., .git/info/exclude)
+    /// should be ignored.
+    fn no_ignore_exclude(&self) -> bool {
+        self.is_present("no-ignore-exclude") || self.no_ignore()
+    }
+
     /// Returns true if global ignore files should be ignored.
     fn no_ignore_global(&self) -> bool {
         self.is_present("no-ignore-global") || self.no_ignore()
@@ -1107,15 +1749,74 @@ impl<'a> ArgMatches<'a> {
     }
 }
 
-fn pattern_to_str(s: &OsStr) -> Result<&str> {
-    match s.to_str() {
-        Some(s) => Ok(s),
-        None => Err(From::from(format!(
-            "Argument '{}' is not valid UTF-8. \
-             Use hex escape sequences to match arbitrary \
-             bytes in a pattern (e.g., \\xFF).",
-             s.to_string_lossy()))),
-    }
+/// Converts an OS string pattern to UTF-8.
+///
+/// ripgrep requires patterns to be valid UTF-8, but Windows consoles (and
+/// misconfigured Unix locales) frequently hand us something else. Instead of
+/// failing later with an opaque regex syntax error, this detects the most
+/// common mis-encodings and either transcodes the pattern to UTF-8 with a
+/// warning or reports a diagnostic explaining what went wrong. Warnings are
+/// suppressed when `no_messages` is true.
+fn pattern_to_utf8(pat: &OsStr, no_messages: bool) -> Result<String> {
+    if let Some(s) = pat.to_str() {
+        // A leading U+FEFF is a tell-tale sign of a pattern that was copied
+        // out of a file or console buffer with a byte order mark.
+        if s.starts_with("\u{feff}") {
+            if !no_messages {
+                eprintln!("pattern starts with a byte order mark; \
+                           ignoring it");
+            }
+            return Ok(s.trim_start_matches("\u{feff}").to_string());
+        }
+        return Ok(s.to_string());
+    }
+    let bytes = match pattern_bytes(pat) {
+        None => {
+            return Err(From::from(format!(
+                "Argument '{}' is not valid UTF-8. \
+                 Use hex escape sequences to match arbitrary \
+                 bytes in a pattern (e.g., \\xFF).",
+                 pat.to_string_lossy())));
+        }
+        Some(bytes) => bytes,
+    };
+    if bytes.starts_with(b"\xff\xfe") || bytes.starts_with(b"\xfe\xff") {
+        return Err(From::from(format!(
+            "Argument '{}' starts with a UTF-16 byte order mark. Your \
+             console is probably passing arguments as UTF-16; re-encode \
+             the pattern as UTF-8.",
+             pat.to_string_lossy())));
+    }
+    if bytes.contains(&0) {
+        return Err(From::from(format!(
+            "Argument '{}' contains NUL bytes and looks like UTF-16 \
+             without a byte order mark. Re-encode the pattern as UTF-8.",
+             pat.to_string_lossy())));
+    }
+    // Otherwise, assume the pattern was given in the most common single
+    // byte locale encoding and transcode it.
+    let (s, _) = WINDOWS_1252.decode_without_bom_handling(&bytes);
+    if !no_messages {
+        eprintln!(
+            "pattern '{}' is not valid UTF-8; assuming windows-1252 and \
+             transcoding it (use hex escape sequences like \\xFF to match \
+             raw bytes instead)",
+            s);
+    }
+    Ok(s.into_owned())
+}
+
+/// Returns the raw bytes of the given OS string, if they are available.
+#[cfg(unix)]
+fn pattern_bytes(pat: &OsStr) -> Option<Vec<u8>> {
+    use std::os::unix::ffi::OsStrExt;
+    Some(pat.as_bytes().to_vec())
+}
+
+/// On Windows, there is no way to get at the raw bytes of an OS string.
+#[cfg(not(unix))]
+fn pattern_bytes(_: &OsStr) -> Option<Vec<u8>> {
+    None
 }
 
 /// A simple thread safe abstraction for determining whether a search should
@@ -1178,25 +1879,127 @@ fn human_readable_to_usize(
     }
 }
 
-/// Returns true if and only if stdin is deemed searchable.
-#[cfg(unix)]
-fn stdin_is_readable() -> bool {
-    use std::os::unix::fs::FileTypeExt;
-    use same_file::Handle;
+/// Expands the given literal into a regex alternation that matches the
+/// literal with up to `dist` single character edits (insertions, deletions
+/// or substitutions).
+fn fuzzy_pattern(pat: &str, dist: usize) -> String {
+    // Each variant is a sequence of characters, where `None` stands for
+    // "any character" and is rendered as `.`.
+    let mut variants: BTreeSet<Vec<Option<char>>> = BTreeSet::new();
+    variants.insert(pat.chars().map(Some).collect());
+    for _ in 0..dist {
+        let mut next = variants.clone();
+        for v in &variants {
+            for i in 0..v.len() {
+                let mut sub = v.clone();
+                sub[i] = None;
+                next.insert(sub);
+
+                let mut del = v.clone();
+                del.remove(i);
+                next.insert(del);
+            }
+            for i in 0..v.len() + 1 {
+                let mut ins = v.clone();
+                ins.insert(i, None);
+                next.insert(ins);
+            }
+        }
+        variants = next;
+    }
+    let alts: Vec<String> = variants.into_iter().map(|v| {
+        v.into_iter().map(|c| {
+            match c {
+                None => ".".to_string(),
+                Some(c) => regex::escape(&c.to_string()),
+            }
+        }).collect()
+    }).collect();
+    format!("(?:{})", alts.join("|"))
+}
 
-    let ft = match Handle::stdin().and_then(|h| h.as_file().metadata()) {
-        Err(_) => return false,
-        Ok(md) => md.file_type(),
-    };
-    ft.is_file() || ft.is_fifo()
+/// Removes search paths that overlap with other search paths.
+///
+/// A path is dropped if it is identical to an earlier path, or if any other
+/// path refers to a directory containing it. Containment is determined on
+/// canonicalized paths, but the paths as given are returned so that printed
+/// paths aren't affected. In particular, symlinks given on the command line
+/// are kept even when their target is covered by another path, since they
+/// are searched and printed through the link name. Paths that can't be
+/// canonicalized (e.g., stdin or paths that don't exist) are always kept.
+fn dedup_paths(paths: Vec<PathBuf>) -> Vec<PathBuf> {
+    let canonical: Vec<Option<PathBuf>> =
+        paths.iter().map(|p| {
+            if p == Path::new("-") {
+                None
+            } else {
+                p.canonicalize().ok()
+            }
+        }).collect();
+    let mut keep = vec![true; paths.len()];
+    for i in 0..paths.len() {
+        let ci = match canonical[i] {
+            None => continue,
+            Some(ref ci) => ci,
+        };
+        let is_symlink =
+            paths[i].symlink_metadata()
+                .map(|md| md.file_type().is_symlink())
+                .unwrap_or(false);
+        for j in 0..paths.len() {
+            if i == j {
+                continue;
+            }
+            // When the same path is given more than once, keep the first
+            // occurrence.
+            if paths[i] == paths[j] {
+                if j < i {
+                    keep[i] = false;
+                    break;
+                }
+                continue;
+            }
+            let cj = match canonical[j] {
+                None => continue,
+                Some(ref cj) => cj,
+            };
+            // Drop any path strictly contained in another given path.
+            if !is_symlink && ci != cj && ci.starts_with(cj) {
+                keep[i] = false;
+                break;
+            }
+        }
+        if !keep[i] {
+            debug!("ignoring overlapping search path {}", paths[i].display());
+        }
+    }
+    paths.into_iter()
+        .zip(keep)
+        .filter(|&(_, keep)| keep)
+        .map(|(path, _)| path)
+        .collect()
+}
+
+/// Returns true if and only if the given pattern file line should be skipped
+/// when the --file-comments flag is set. Comment lines start with `#` and
+/// blank lines match everything, which is rarely intended in a maintained
+/// pattern list.
+fn is_pattern_comment(line: &str) -> bool {
+    line.is_empty() || line.starts_with("#")
 }
 
 /// Returns true if and only if stdin is deemed searchable.
-#[cfg(windows)]
 fn stdin_is_readable() -> bool {
-    // On Windows, it's not clear what the possibilities are to me, so just
-    // always return true.
-    true
+    use stream_kind::{stdin_kind, StreamKind};
+
+    match stdin_kind() {
+        StreamKind::File | StreamKind::Pipe => true,
+        // In particular, running with stdin attached to the null device
+        // should fall back to searching the current directory.
+        StreamKind::Tty
+        | StreamKind::Null
+        | StreamKind::Unknown => false,
+    }
 }
 
 /// Returns true if and only if this path points to a directory.
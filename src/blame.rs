@@ -0,0 +1,198 @@
+/*!
+The blame module provides match annotations sourced from `git blame`.
+
+Blame data is gathered with a single `git blame --line-porcelain`
+invocation per file and cached, so annotating every match in a file
+costs one subprocess regardless of how many matches it has. Files that
+are not tracked by git (or when git is unavailable) simply produce no
+annotations.
+*/
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Blame information for a single line of a file.
+#[derive(Clone, Debug)]
+pub struct BlameInfo {
+    /// The abbreviated hash of the commit that introduced the line.
+    commit: String,
+    /// The author of that commit.
+    author: String,
+    /// The author time of that commit, in seconds since the epoch.
+    author_time: u64,
+}
+
+impl BlameInfo {
+    /// Returns this blame information as a short annotation suitable for
+    /// inclusion in printed output, e.g., `5fed981d alice 2y`.
+    pub fn annotation(&self) -> String {
+        format!("{} {} {}", self.commit, self.author, age(self.author_time))
+    }
+}
+
+/// Blamer provides per-line blame information for files, caching the
+/// result of `git blame` for each file it is asked about.
+#[derive(Debug, Default)]
+pub struct Blamer {
+    cache: HashMap<PathBuf, Option<Vec<Option<BlameInfo>>>>,
+}
+
+impl Blamer {
+    /// Create a new blamer with an empty cache.
+    pub fn new() -> Blamer {
+        Blamer::default()
+    }
+
+    /// Returns blame information for the given line (1-based) of the given
+    /// file, if it's available.
+    pub fn blame(
+        &mut self,
+        path: &Path,
+        line_number: u64,
+    ) -> Option<&BlameInfo> {
+        let lines = self.cache
+            .entry(path.to_path_buf())
+            .or_insert_with(|| blame_file(path));
+        lines.as_ref()
+            .and_then(|lines| lines.get(line_number as usize - 1))
+            .and_then(|info| info.as_ref())
+    }
+}
+
+/// Runs `git blame` on the given file and parses its porcelain output into
+/// per-line blame information. Returns `None` if git failed, e.g., because
+/// the file isn't tracked.
+fn blame_file(path: &Path) -> Option<Vec<Option<BlameInfo>>> {
+    let file_name = path.file_name()?;
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let output = Command::new("git")
+        .arg("blame")
+        .arg("--line-porcelain")
+        .arg("--")
+        .arg(file_name)
+        .current_dir(parent)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        debug!("git blame failed on {}: {}",
+               path.display(), String::from_utf8_lossy(&output.stderr));
+        return None;
+    }
+    Some(parse_porcelain(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Parses `git blame --line-porcelain` output. The result is indexed by
+/// line number (0-based), where a `None` entry indicates a line we couldn't
+/// get blame information for.
+fn parse_porcelain(output: &str) -> Vec<Option<BlameInfo>> {
+    let mut lines: Vec<Option<BlameInfo>> = vec![];
+    let mut commit = String::new();
+    let mut line_number = 0;
+    let mut author = String::new();
+    let mut author_time = 0;
+    for line in output.lines() {
+        if let Some(rest) = strip_prefix(line, "author ") {
+            author = rest.to_string();
+        } else if let Some(rest) = strip_prefix(line, "author-time ") {
+            author_time = rest.parse().unwrap_or(0);
+        } else if line.starts_with('\t') {
+            // The content line ends one blame entry.
+            if line_number > lines.len() {
+                lines.resize(line_number, None);
+            }
+            if line_number > 0 {
+                lines[line_number - 1] = Some(BlameInfo {
+                    commit: commit.clone(),
+                    author: author.clone(),
+                    author_time: author_time,
+                });
+            }
+        } else if is_entry_header(line) {
+            let mut fields = line.split(' ');
+            commit = fields.next().unwrap_or("")
+                .chars().take(8).collect();
+            line_number = fields.nth(1)
+                .and_then(|n| n.parse().ok())
+                .unwrap_or(0);
+        }
+    }
+    lines
+}
+
+/// Returns true if the line looks like the header of a blame entry, i.e.,
+/// a full commit hash followed by line numbers.
+fn is_entry_header(line: &str) -> bool {
+    let mut fields = line.split(' ');
+    fields.next().map_or(false, |hash| {
+        hash.len() == 40 && hash.chars().all(|c| c.is_digit(16))
+    }) && fields.next().map_or(false, |n| n.parse::<u64>().is_ok())
+}
+
+/// Returns the remainder of `line` after `prefix`, if it starts with it.
+fn strip_prefix<'a>(line: &'a str, prefix: &str) -> Option<&'a str> {
+    if line.starts_with(prefix) {
+        Some(&line[prefix.len()..])
+    } else {
+        None
+    }
+}
+
+/// Formats the age of the given timestamp (seconds since the epoch) as a
+/// short human readable string, e.g., `3d` or `2y`.
+fn age(author_time: u64) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let secs = now.saturating_sub(author_time);
+    if secs >= 365 * 24 * 3600 {
+        format!("{}y", secs / (365 * 24 * 3600))
+    } else if secs >= 30 * 24 * 3600 {
+        format!("{}mo", secs / (30 * 24 * 3600))
+    } else if secs >= 24 * 3600 {
+        format!("{}d", secs / (24 * 3600))
+    } else if secs >= 3600 {
+        format!("{}h", secs / 3600)
+    } else {
+        format!("{}m", secs / 60)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_porcelain;
+
+    const PORCELAIN: &str = "\
+5fed981d7d471b7a1a9f3e9d04adbe9c3a2d7b1f 1 1 2
+author Alice
+author-mail <alice@example.com>
+author-time 1500000000
+author-tz +0000
+summary add foo
+filename foo.rs
+\tfn foo() {
+5fed981d7d471b7a1a9f3e9d04adbe9c3a2d7b1f 2 2
+author Alice
+author-mail <alice@example.com>
+author-time 1500000000
+author-tz +0000
+summary add foo
+filename foo.rs
+\t}
+";
+
+    #[test]
+    fn porcelain() {
+        let lines = parse_porcelain(PORCELAIN);
+        assert_eq!(lines.len(), 2);
+        let info = lines[0].as_ref().unwrap();
+        assert_eq!(info.commit, "5fed981d");
+        assert_eq!(info.author, "Alice");
+        assert_eq!(info.author_time, 1500000000);
+    }
+}
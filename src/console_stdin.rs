@@ -0,0 +1,161 @@
+/*!
+Reading stdin from an interactive Windows console.
+
+A Windows console hands processes their input in the ANSI codepage, so
+reading stdin with the usual byte oriented APIs mangles any character that
+doesn't fit, which leads to both mojibake in the output and missed matches.
+The fix is to read the console with the wide character APIs and transcode
+the UTF-16 we get back to UTF-8 ourselves. On all other platforms (and for
+redirected stdin on Windows), plain `io::stdin` is correct and
+`console_stdin` returns `None`.
+*/
+
+use std::io;
+
+/// Returns a reader for stdin if and only if stdin is attached to an
+/// interactive Windows console. Returns `None` otherwise, in which case
+/// stdin should be read directly.
+#[cfg(not(windows))]
+pub fn console_stdin() -> Option<ConsoleStdin> {
+    None
+}
+
+/// A reader that reads UTF-16 from an interactive Windows console and
+/// transcodes it to UTF-8.
+///
+/// On non-Windows platforms this is never constructed.
+#[cfg(not(windows))]
+#[derive(Debug)]
+pub struct ConsoleStdin(());
+
+#[cfg(not(windows))]
+impl io::Read for ConsoleStdin {
+    fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+        Ok(0)
+    }
+}
+
+/// Returns a reader for stdin if and only if stdin is attached to an
+/// interactive Windows console. Returns `None` otherwise, in which case
+/// stdin should be read directly.
+#[cfg(windows)]
+pub fn console_stdin() -> Option<ConsoleStdin> {
+    use std::os::windows::io::AsRawHandle;
+    use winapi::um::consoleapi::GetConsoleMode;
+    use winapi::um::winnt::HANDLE;
+
+    let handle = io::stdin().as_raw_handle();
+    if handle.is_null() {
+        return None;
+    }
+    let mut mode = 0;
+    let is_console =
+        unsafe { GetConsoleMode(handle as HANDLE, &mut mode) } != 0;
+    if !is_console {
+        return None;
+    }
+    Some(ConsoleStdin {
+        handle: handle,
+        buf: vec![],
+        pos: 0,
+        pending: None,
+        done: false,
+    })
+}
+
+/// A reader that reads UTF-16 from an interactive Windows console and
+/// transcodes it to UTF-8.
+#[cfg(windows)]
+#[derive(Debug)]
+pub struct ConsoleStdin {
+    handle: ::std::os::windows::io::RawHandle,
+    /// Transcoded bytes that haven't been handed to the caller yet.
+    buf: Vec<u8>,
+    pos: usize,
+    /// A high surrogate that ended the previous console read, to be paired
+    /// with the first unit of the next one.
+    pending: Option<u16>,
+    done: bool,
+}
+
+#[cfg(windows)]
+impl ConsoleStdin {
+    /// Read the next chunk from the console into `buf`, transcoded to
+    /// UTF-8. Returns false at EOF.
+    fn fill(&mut self) -> io::Result<bool> {
+        use std::char;
+        use std::ptr;
+        use winapi::shared::minwindef::{DWORD, LPVOID};
+        use winapi::um::consoleapi::ReadConsoleW;
+        use winapi::um::winnt::HANDLE;
+
+        let mut units = [0u16; 4096];
+        let mut start = 0;
+        if let Some(u) = self.pending.take() {
+            units[0] = u;
+            start = 1;
+        }
+        let mut nread: DWORD = 0;
+        let ok = unsafe {
+            ReadConsoleW(
+                self.handle as HANDLE,
+                units[start..].as_mut_ptr() as LPVOID,
+                (units.len() - start) as DWORD,
+                &mut nread,
+                ptr::null_mut(),
+            )
+        };
+        if ok == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut len = start + nread as usize;
+        // A console signals EOF with a zero length read or with a ^Z at
+        // the start of a line.
+        if len == 0 || units[start] == 0x1A {
+            return Ok(false);
+        }
+        // Hold back a trailing high surrogate, since its partner is in the
+        // next read.
+        if nread > 0 && is_high_surrogate(units[len - 1]) {
+            self.pending = Some(units[len - 1]);
+            len -= 1;
+        }
+        self.buf.clear();
+        self.pos = 0;
+        for result in char::decode_utf16(units[..len].iter().cloned()) {
+            let c = result.unwrap_or('\u{FFFD}');
+            let mut enc = [0u8; 4];
+            self.buf.extend_from_slice(c.encode_utf8(&mut enc).as_bytes());
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(windows)]
+fn is_high_surrogate(unit: u16) -> bool {
+    unit >= 0xD800 && unit < 0xDC00
+}
+
+#[cfg(windows)]
+impl io::Read for ConsoleStdin {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        use std::cmp;
+
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        while self.pos >= self.buf.len() {
+            if self.done {
+                return Ok(0);
+            }
+            if !self.fill()? {
+                self.done = true;
+                return Ok(0);
+            }
+        }
+        let n = cmp::min(buf.len(), self.buf.len() - self.pos);
+        buf[..n].copy_from_slice(&self.buf[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
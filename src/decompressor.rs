@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 use std::ffi::OsStr;
 use std::fmt;
+use std::fs::File;
 use std::io::{self, Read};
 use std::path::Path;
 use std::process::{self, Stdio};
@@ -45,6 +46,8 @@ lazy_static! {
         m.insert("bz2", DecompressionCommand::new("bzip2", ARGS));
         m.insert("xz", DecompressionCommand::new("xz", ARGS));
         m.insert("lz4", DecompressionCommand::new("lz4", ARGS));
+        m.insert("zst", DecompressionCommand::new("zstd", ARGS));
+        m.insert("br", DecompressionCommand::new("brotli", ARGS));
 
         const LZMA_ARGS: &[&str] = &["--format=lzma", "-d", "-c"];
         m.insert("lzma", DecompressionCommand::new("xz", LZMA_ARGS));
@@ -58,6 +61,8 @@ lazy_static! {
         builder.add(Glob::new("*.xz").unwrap());
         builder.add(Glob::new("*.lz4").unwrap());
         builder.add(Glob::new("*.lzma").unwrap());
+        builder.add(Glob::new("*.zst").unwrap());
+        builder.add(Glob::new("*.br").unwrap());
         builder.build().unwrap()
     };
     static ref TAR_ARCHIVE_FORMATS: GlobSet = {
@@ -66,13 +71,50 @@ lazy_static! {
         builder.add(Glob::new("*.tar.xz").unwrap());
         builder.add(Glob::new("*.tar.bz2").unwrap());
         builder.add(Glob::new("*.tar.lz4").unwrap());
+        builder.add(Glob::new("*.tar.zst").unwrap());
+        builder.add(Glob::new("*.tar.br").unwrap());
         builder.add(Glob::new("*.tgz").unwrap());
         builder.add(Glob::new("*.txz").unwrap());
         builder.add(Glob::new("*.tbz2").unwrap());
+        builder.add(Glob::new("*.tzst").unwrap());
         builder.build().unwrap()
     };
 }
 
+/// The leading magic bytes identifying each supported compression format,
+/// mapped to the corresponding key in `DECOMPRESSION_COMMANDS`. Brotli is
+/// absent because its format has no magic number.
+const MAGIC_NUMBERS: &[(&[u8], &str)] = &[
+    (b"\x1F\x8B", "gz"),
+    (b"BZh", "bz2"),
+    (b"\xFD7zXZ\x00", "xz"),
+    (b"\x5D\x00\x00", "lzma"),
+    (b"\x28\xB5\x2F\xFD", "zst"),
+    (b"\x04\x22\x4D\x18", "lz4"),
+];
+
+/// Returns the decompression command key for the given path by sniffing its
+/// leading magic bytes, or `None` if the format isn't recognized. This
+/// catches compressed files whose extension doesn't reveal their format,
+/// such as rotated logs.
+fn sniff_format(path: &Path) -> Option<&'static str> {
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(_) => return None,
+    };
+    let mut buf = [0u8; 6];
+    let nread = match file.read(&mut buf) {
+        Ok(nread) => nread,
+        Err(_) => return None,
+    };
+    for &(magic, format) in MAGIC_NUMBERS {
+        if buf[..nread].starts_with(magic) {
+            return Some(format);
+        }
+    }
+    None
+}
+
 /// DecompressionReader provides an `io::Read` implementation for a limited
 /// set of compression formats.
 #[derive(Debug)]
@@ -92,15 +134,19 @@ impl DecompressionReader {
     /// If there is any error in spawning the decompression command, then
     /// return `None`, after outputting any necessary debug or error messages.
     pub fn from_path(path: &Path) -> Option<DecompressionReader> {
-        let extension = match path.extension().and_then(OsStr::to_str) {
-            Some(extension) => extension,
-            None => {
-                debug!(
-                    "{}: failed to get compresson extension", path.display());
-                return None;
+        let format = match path.extension().and_then(OsStr::to_str) {
+            Some(ext) if DECOMPRESSION_COMMANDS.contains_key(ext) => ext,
+            _ => match sniff_format(path) {
+                Some(format) => format,
+                None => {
+                    debug!(
+                        "{}: failed to detect compression format",
+                        path.display());
+                    return None;
+                }
             }
         };
-        let decompression_cmd = match DECOMPRESSION_COMMANDS.get(extension) {
+        let decompression_cmd = match DECOMPRESSION_COMMANDS.get(format) {
             Some(cmd) => cmd,
             None => {
                 debug!(
@@ -172,10 +218,13 @@ impl io::Read for DecompressionReader {
     }
 }
 
-/// Returns true if the given path contains a supported compression format or
-/// is a TAR archive.
+/// Returns true if the given path contains a supported compression format
+/// (detected by its extension or by its leading magic bytes) or is a TAR
+/// archive.
 pub fn is_compressed(path: &Path) -> bool {
-    is_supported_compression_format(path) || is_tar_archive(path)
+    is_supported_compression_format(path)
+        || is_tar_archive(path)
+        || sniff_format(path).is_some()
 }
 
 /// Returns true if the given path matches any one of the supported compression
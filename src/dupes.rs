@@ -0,0 +1,251 @@
+/*!
+The dupes module implements a lightweight cross-file duplication detector.
+
+Files are indexed by hashing every window of N consecutive lines, where
+each line is normalized by trimming surrounding whitespace. Windows whose
+hash occurs in more than one file are reported as duplicates. Overlapping
+windows belonging to the same run of identical lines are merged, so a run
+of M > N duplicated lines is reported once with its full length rather
+than as M - N + 1 separate windows.
+*/
+
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// An index of line windows across files, used to find duplication.
+#[derive(Debug)]
+pub struct DupLineIndex {
+    /// The number of consecutive lines in each window.
+    window: usize,
+    /// The paths of all indexed files, in the order they were added.
+    files: Vec<PathBuf>,
+    /// A map from window hash to every place that window occurs.
+    groups: HashMap<u64, Vec<Location>>,
+}
+
+/// A single occurrence of a line window.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+struct Location {
+    /// Index into `DupLineIndex::files`.
+    file: usize,
+    /// The 1-based line number of the first line of the window.
+    line: u64,
+}
+
+impl DupLineIndex {
+    /// Create a new index over windows of the given number of lines.
+    pub fn new(window: usize) -> DupLineIndex {
+        assert!(window > 0);
+        DupLineIndex {
+            window: window,
+            files: vec![],
+            groups: HashMap::new(),
+        }
+    }
+
+    /// Add the contents of a single file to the index.
+    pub fn add_file(&mut self, path: &Path, contents: &[u8]) {
+        let file = self.files.len();
+        self.files.push(path.to_path_buf());
+        let line_hashes: Vec<u64> = contents
+            .split(|&b| b == b'\n')
+            .map(|line| hash_bytes(trim(line)))
+            .collect();
+        if line_hashes.len() < self.window {
+            return;
+        }
+        let empty = hash_bytes(b"");
+        for i in 0..line_hashes.len() - self.window + 1 {
+            let window = &line_hashes[i..i + self.window];
+            // Windows of entirely blank lines duplicate everywhere and
+            // aren't interesting.
+            if window.iter().all(|&h| h == empty) {
+                continue;
+            }
+            self.groups
+                .entry(hash_window(window))
+                .or_insert_with(Vec::new)
+                .push(Location { file: file, line: i as u64 + 1 });
+        }
+    }
+
+    /// Write a report of all duplicated line groups, in lexicographic order
+    /// of their first occurrence. Returns the number of groups reported.
+    pub fn print<W: Write>(&self, mut wtr: W) -> io::Result<u64> {
+        let dupes = self.duplicated_groups();
+        let locs: HashMap<Location, u64> = dupes
+            .iter()
+            .flat_map(|(&hash, locations)| {
+                locations.iter().map(move |&loc| (loc, hash))
+            })
+            .collect();
+        let mut starts = vec![];
+        for (&hash, locations) in &dupes {
+            if self.predecessor(&locs, locations).is_none() {
+                starts.push((self.sort_key(locations), hash));
+            }
+        }
+        starts.sort();
+        for &(_, hash) in &starts {
+            let mut extra = 0;
+            while self.successor(&locs, dupes[&hash], extra + 1).is_some() {
+                extra += 1;
+            }
+            let len = self.window as u64 + extra;
+            let mut locations: Vec<Location> = dupes[&hash].to_vec();
+            locations.sort_by_key(|loc| (self.files[loc.file].clone(),
+                                         loc.line));
+            writeln!(wtr, "{} duplicated lines in {} files:",
+                     len, count_files(&locations))?;
+            for loc in &locations {
+                writeln!(wtr, "  {}:{}",
+                         self.files[loc.file].display(), loc.line)?;
+            }
+        }
+        Ok(starts.len() as u64)
+    }
+
+    /// Returns all groups that occur in at least two distinct files.
+    fn duplicated_groups(&self) -> HashMap<u64, &Vec<Location>> {
+        self.groups
+            .iter()
+            .filter(|&(_, locations)| count_files(locations) >= 2)
+            .map(|(&hash, locations)| (hash, locations))
+            .collect()
+    }
+
+    /// If every location in the group is directly preceded by a location
+    /// from one single other duplicated group, then this group merely
+    /// extends that one, and its hash is returned.
+    fn predecessor(
+        &self,
+        locs: &HashMap<Location, u64>,
+        locations: &[Location],
+    ) -> Option<u64> {
+        self.shifted(locs, locations, -1)
+    }
+
+    /// Like `predecessor`, but looks for the group that extends this one
+    /// by `offset` lines.
+    fn successor(
+        &self,
+        locs: &HashMap<Location, u64>,
+        locations: &[Location],
+        offset: u64,
+    ) -> Option<u64> {
+        self.shifted(locs, locations, offset as i64)
+    }
+
+    fn shifted(
+        &self,
+        locs: &HashMap<Location, u64>,
+        locations: &[Location],
+        offset: i64,
+    ) -> Option<u64> {
+        let mut shifted = None;
+        for loc in locations {
+            let line = loc.line as i64 + offset;
+            if line < 1 {
+                return None;
+            }
+            let key = Location { file: loc.file, line: line as u64 };
+            match (shifted, locs.get(&key)) {
+                (_, None) => return None,
+                (None, Some(&hash)) => shifted = Some(hash),
+                (Some(prev), Some(&hash)) if prev != hash => return None,
+                _ => {}
+            }
+        }
+        shifted
+    }
+
+    /// Returns a key that orders groups by their first occurrence.
+    fn sort_key(&self, locations: &[Location]) -> (PathBuf, u64) {
+        locations
+            .iter()
+            .map(|loc| (self.files[loc.file].clone(), loc.line))
+            .min()
+            .expect("at least one location")
+    }
+}
+
+/// Returns the number of distinct files among the given locations.
+fn count_files(locations: &[Location]) -> usize {
+    let mut files: Vec<usize> =
+        locations.iter().map(|loc| loc.file).collect();
+    files.sort();
+    files.dedup();
+    files.len()
+}
+
+/// Returns the given line with surrounding ASCII whitespace trimmed.
+fn trim(mut line: &[u8]) -> &[u8] {
+    while line.first().map_or(false, |b| b.is_ascii_whitespace()) {
+        line = &line[1..];
+    }
+    while line.last().map_or(false, |b| b.is_ascii_whitespace()) {
+        line = &line[..line.len() - 1];
+    }
+    line
+}
+
+fn hash_bytes(bytes: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    bytes.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn hash_window(window: &[u64]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    window.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::DupLineIndex;
+
+    fn report(index: &DupLineIndex) -> (u64, String) {
+        let mut buf = vec![];
+        let count = index.print(&mut buf).unwrap();
+        (count, String::from_utf8(buf).unwrap())
+    }
+
+    #[test]
+    fn basic() {
+        let mut index = DupLineIndex::new(2);
+        index.add_file(Path::new("a"), b"one\ntwo\nthree\nfour\n");
+        index.add_file(Path::new("b"), b"zero\none\ntwo\nthree\nfive\n");
+        let (count, out) = report(&index);
+        assert_eq!(count, 1);
+        assert_eq!(out, "\
+3 duplicated lines in 2 files:
+  a:1
+  b:2
+");
+    }
+
+    #[test]
+    fn no_dupes() {
+        let mut index = DupLineIndex::new(2);
+        index.add_file(Path::new("a"), b"one\ntwo\n");
+        index.add_file(Path::new("b"), b"three\nfour\n");
+        let (count, out) = report(&index);
+        assert_eq!(count, 0);
+        assert_eq!(out, "");
+    }
+
+    #[test]
+    fn normalized() {
+        let mut index = DupLineIndex::new(2);
+        index.add_file(Path::new("a"), b"  one\ntwo  \nthree\n");
+        index.add_file(Path::new("b"), b"one\n  two\nfour\n");
+        let (count, _) = report(&index);
+        assert_eq!(count, 1);
+    }
+}
@@ -0,0 +1,178 @@
+/*!
+The generate module implements the --generate flag, which emits shell
+completion scripts or a roff formatted man page on stdout. Everything
+produced here is derived from ripgrep's actual flag definitions in the app
+module, so the output never drifts out of sync with the binary that
+generated it.
+*/
+
+use std::io::{self, Write};
+
+use clap;
+
+use app::{self, RGArgKind};
+use Result;
+
+/// The zsh completion script is maintained by hand, since it provides a much
+/// better completion experience than anything clap can generate.
+const ZSH_COMPLETION: &str = include_str!("../complete/_rg");
+
+/// Write the output identified by the given --generate kind to stdout.
+///
+/// The kind has already been validated by clap, so an unrecognized kind is
+/// a bug.
+pub fn generate(kind: &str) -> Result<()> {
+    match kind {
+        "complete-bash" => completions(clap::Shell::Bash),
+        "complete-fish" => completions(clap::Shell::Fish),
+        "complete-powershell" => completions(clap::Shell::PowerShell),
+        "complete-zsh" => {
+            io::stdout().write_all(ZSH_COMPLETION.as_bytes())?;
+            Ok(())
+        }
+        "man" => man(),
+        unk => Err(From::from(format!("unknown --generate kind: {}", unk))),
+    }
+}
+
+fn completions(shell: clap::Shell) -> Result<()> {
+    app::app().gen_completions_to("rg", shell, &mut io::stdout());
+    Ok(())
+}
+
+fn man() -> Result<()> {
+    let mut out = String::new();
+    out.push_str(&format!(
+        ".TH RG 1 \"\" \"ripgrep {}\" \"User Commands\"\n",
+        crate_version!(),
+    ));
+    out.push_str(".SH NAME\n");
+    out.push_str(
+        "rg \\- recursively search current directory for lines matching \
+         a pattern\n",
+    );
+    out.push_str(".SH SYNOPSIS\n");
+    let synopses = &[
+        "[\\fIOPTIONS\\fP] \\fIPATTERN\\fP [\\fIPATH\\fP...]",
+        "[\\fIOPTIONS\\fP] \\fB\\-e\\fP \\fIPATTERN\\fP... [\\fIPATH\\fP...]",
+        "[\\fIOPTIONS\\fP] \\fB\\-f\\fP \\fIPATTERNFILE\\fP... \
+         [\\fIPATH\\fP...]",
+        "[\\fIOPTIONS\\fP] \\fB\\-\\-files\\fP [\\fIPATH\\fP...]",
+        "[\\fIOPTIONS\\fP] \\fB\\-\\-type\\-list\\fP",
+        "[\\fIOPTIONS\\fP] \\fB\\-\\-help\\fP",
+        "[\\fIOPTIONS\\fP] \\fB\\-\\-version\\fP",
+    ];
+    for (i, synopsis) in synopses.iter().enumerate() {
+        if i > 0 {
+            out.push_str(".br\n");
+        }
+        out.push_str(&format!(".B rg\n{}\n", synopsis));
+    }
+    out.push_str(".SH DESCRIPTION\n");
+    out.push_str(&man_paragraphs(
+        "ripgrep (rg) recursively searches your current directory for a \
+         regex pattern. By default, ripgrep will respect your .gitignore \
+         and automatically skip hidden files/directories and binary files.",
+    ));
+    out.push_str("\n.SH OPTIONS\n");
+    man_options(&mut out);
+    out.push_str(".SH EXIT STATUS\n");
+    out.push_str(&man_paragraphs(
+        "If ripgrep finds a match, then the exit status of the program is \
+         0. If no match could be found, then the exit status is non-zero.",
+    ));
+    out.push_str("\n.SH VERSION\n");
+    out.push_str(&format!("{}\n", crate_version!()));
+    out.push_str(".SH HOMEPAGE\n");
+    out.push_str("https://github.com/BurntSushi/ripgrep\n");
+
+    io::stdout().write_all(out.as_bytes())?;
+    Ok(())
+}
+
+/// Write a roff .TP entry for every non-hidden flag to the given string.
+fn man_options(out: &mut String) {
+    let mut args = app::all_args_and_flags();
+    args.sort_by(|x1, x2| x1.name.cmp(&x2.name));
+
+    for arg in args {
+        if arg.hidden {
+            continue;
+        }
+        let (long, short, value_name) = match arg.kind {
+            // Positional arguments are documented in the synopsis.
+            RGArgKind::Positional{..} => continue,
+            RGArgKind::Switch { long, short, .. } => (long, short, None),
+            RGArgKind::Flag { long, short, value_name, .. } => {
+                (long, short, Some(value_name))
+            }
+        };
+        let mut header = format!("\\fB\\-\\-{}\\fP", man_escape(long));
+        if let Some(short) = short {
+            header = format!("\\fB\\-{}\\fP, {}", man_escape(short), header);
+        }
+        if let Some(value_name) = value_name {
+            header = format!("{} \\fI{}\\fP", header, man_escape(value_name));
+        }
+        out.push_str(".TP\n");
+        out.push_str(&header);
+        out.push('\n');
+        out.push_str(&man_paragraphs(arg.doc_long.trim()));
+        out.push('\n');
+    }
+}
+
+/// Convert the paragraphs in the given documentation string to roff,
+/// preserving indented example blocks verbatim.
+fn man_paragraphs(doc: &str) -> String {
+    let mut paragraphs = vec![];
+    for para in doc.split("\n\n") {
+        let escaped = man_escape(para);
+        if !para.is_empty() && para.lines().all(|l| l.starts_with(' ')) {
+            paragraphs.push(format!(".nf\n{}\n.fi", escaped));
+        } else {
+            paragraphs.push(escaped);
+        }
+    }
+    paragraphs.join("\n.sp\n")
+}
+
+/// Escape text for inclusion in a roff document.
+fn man_escape(text: &str) -> String {
+    let escaped = text.replace("\\", "\\\\").replace("-", "\\-");
+    let mut lines = vec![];
+    for line in escaped.lines() {
+        if line.starts_with('.') || line.starts_with('\'') {
+            lines.push(format!("\\&{}", line));
+        } else {
+            lines.push(line.to_string());
+        }
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{man_escape, man_paragraphs};
+
+    #[test]
+    fn escape_dashes() {
+        assert_eq!(man_escape("--ignore-case"), "\\-\\-ignore\\-case");
+    }
+
+    #[test]
+    fn escape_control_lines() {
+        assert_eq!(man_escape(".gitignore rules"), "\\&.gitignore rules");
+    }
+
+    #[test]
+    fn example_blocks_are_verbatim() {
+        let doc = "Some prose.\n\n    rg -e -foo\n\nMore prose.";
+        let roff = man_paragraphs(doc);
+        assert_eq!(
+            roff,
+            "Some prose.\n.sp\n.nf\n    rg \\-e \\-foo\n.fi\n.sp\n\
+             More prose.",
+        );
+    }
+}
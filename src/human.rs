@@ -0,0 +1,235 @@
+/*!
+Parsing of human readable sizes (`10M`, `1.5GiB`) and durations (`30s`,
+`5m`), as used by flags like `--max-filesize`.
+*/
+
+use std::error;
+use std::fmt;
+use std::result;
+use std::time::Duration;
+
+/// An error that occurs when parsing a human readable size.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ParseSizeError {
+    original: String,
+    kind: ParseSizeErrorKind,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum ParseSizeErrorKind {
+    InvalidFormat,
+    InvalidSuffix(String),
+    Overflow,
+}
+
+impl ParseSizeError {
+    /// The original size string that failed to parse.
+    #[allow(dead_code)]
+    pub fn original(&self) -> &str {
+        &self.original
+    }
+}
+
+impl error::Error for ParseSizeError {
+    fn description(&self) -> &str {
+        "invalid size"
+    }
+}
+
+impl fmt::Display for ParseSizeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            ParseSizeErrorKind::InvalidFormat => {
+                write!(
+                    f,
+                    "invalid format for size '{}', which should be a \
+                     number with an optional K, M or G suffix",
+                    self.original,
+                )
+            }
+            ParseSizeErrorKind::InvalidSuffix(ref suffix) => {
+                write!(
+                    f,
+                    "invalid suffix '{}' for size '{}', \
+                     expected one of K, M or G",
+                    suffix, self.original,
+                )
+            }
+            ParseSizeErrorKind::Overflow => {
+                write!(f, "size '{}' is too big", self.original)
+            }
+        }
+    }
+}
+
+/// Parse a human readable size like `10M` into a byte count.
+///
+/// The input is a possibly fractional number followed by an optional unit.
+/// The supported units are `K`, `M` and `G` (also written as `KiB`, `MiB`
+/// and `GiB`), which are multiples of 1024. Without a unit, the number is a
+/// plain byte count.
+pub fn parse_size(size: &str) -> result::Result<u64, ParseSizeError> {
+    let err = |kind| {
+        ParseSizeError { original: size.to_string(), kind: kind }
+    };
+    let digits = size
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(size.len());
+    let (number, suffix) = size.split_at(digits);
+    let value: f64 = number
+        .parse()
+        .map_err(|_| err(ParseSizeErrorKind::InvalidFormat))?;
+    let multiplier: u64 = match suffix {
+        "" => 1,
+        "K" | "KiB" => 1 << 10,
+        "M" | "MiB" => 1 << 20,
+        "G" | "GiB" => 1 << 30,
+        _ => {
+            return Err(err(
+                ParseSizeErrorKind::InvalidSuffix(suffix.to_string()),
+            ));
+        }
+    };
+    let bytes = value * multiplier as f64;
+    if !bytes.is_finite() || bytes >= ::std::u64::MAX as f64 {
+        return Err(err(ParseSizeErrorKind::Overflow));
+    }
+    Ok(bytes as u64)
+}
+
+/// An error that occurs when parsing a human readable duration.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ParseDurationError {
+    original: String,
+    kind: ParseDurationErrorKind,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum ParseDurationErrorKind {
+    InvalidFormat,
+    InvalidSuffix(String),
+}
+
+impl ParseDurationError {
+    /// The original duration string that failed to parse.
+    #[allow(dead_code)]
+    pub fn original(&self) -> &str {
+        &self.original
+    }
+}
+
+impl error::Error for ParseDurationError {
+    fn description(&self) -> &str {
+        "invalid duration"
+    }
+}
+
+impl fmt::Display for ParseDurationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            ParseDurationErrorKind::InvalidFormat => {
+                write!(
+                    f,
+                    "invalid format for duration '{}', which should be a \
+                     number with an optional ms, s, m or h suffix",
+                    self.original,
+                )
+            }
+            ParseDurationErrorKind::InvalidSuffix(ref suffix) => {
+                write!(
+                    f,
+                    "invalid suffix '{}' for duration '{}', \
+                     expected one of ms, s, m or h",
+                    suffix, self.original,
+                )
+            }
+        }
+    }
+}
+
+/// Parse a human readable duration like `30s` or `5m` into a `Duration`.
+///
+/// The input is a possibly fractional number followed by an optional unit.
+/// The supported units are `ms`, `s`, `m` and `h`. Without a unit, the
+/// number is a count of seconds.
+#[allow(dead_code)]
+pub fn parse_duration(
+    duration: &str,
+) -> result::Result<Duration, ParseDurationError> {
+    let err = |kind| {
+        ParseDurationError { original: duration.to_string(), kind: kind }
+    };
+    let digits = duration
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(duration.len());
+    let (number, suffix) = duration.split_at(digits);
+    let value: f64 = number
+        .parse()
+        .map_err(|_| err(ParseDurationErrorKind::InvalidFormat))?;
+    if !value.is_finite() || value < 0.0 {
+        return Err(err(ParseDurationErrorKind::InvalidFormat));
+    }
+    let secs = match suffix {
+        "ms" => value / 1000.0,
+        "" | "s" => value,
+        "m" => value * 60.0,
+        "h" => value * 3600.0,
+        _ => {
+            return Err(err(
+                ParseDurationErrorKind::InvalidSuffix(suffix.to_string()),
+            ));
+        }
+    };
+    Ok(Duration::new(secs.trunc() as u64, (secs.fract() * 1e9) as u32))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{parse_duration, parse_size};
+
+    #[test]
+    fn sizes() {
+        assert_eq!(0, parse_size("0").unwrap());
+        assert_eq!(42, parse_size("42").unwrap());
+        assert_eq!(10 << 20, parse_size("10M").unwrap());
+        assert_eq!(2 << 30, parse_size("2G").unwrap());
+        assert_eq!(1 << 10, parse_size("1KiB").unwrap());
+        assert_eq!(3 << 20, parse_size("3MiB").unwrap());
+        assert_eq!((1 << 30) + (1 << 29), parse_size("1.5GiB").unwrap());
+    }
+
+    #[test]
+    fn invalid_sizes() {
+        assert!(parse_size("").is_err());
+        assert!(parse_size("K").is_err());
+        assert!(parse_size("1T").is_err());
+        assert!(parse_size("1.2.3M").is_err());
+        assert!(parse_size("9999999999999G").is_err());
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(Duration::new(30, 0), parse_duration("30s").unwrap());
+        assert_eq!(Duration::new(30, 0), parse_duration("30").unwrap());
+        assert_eq!(Duration::new(300, 0), parse_duration("5m").unwrap());
+        assert_eq!(Duration::new(7200, 0), parse_duration("2h").unwrap());
+        assert_eq!(
+            Duration::new(0, 250_000_000),
+            parse_duration("250ms").unwrap()
+        );
+        assert_eq!(
+            Duration::new(1, 500_000_000),
+            parse_duration("1.5s").unwrap()
+        );
+    }
+
+    #[test]
+    fn invalid_durations() {
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("s").is_err());
+        assert!(parse_duration("1d").is_err());
+        assert!(parse_duration("1.2.3s").is_err());
+    }
+}
@@ -0,0 +1,219 @@
+/*!
+A hand-rolled serializer for the JSON Lines output format enabled by the
+--json flag.
+
+Every message is a single JSON object on its own line with a `type` field
+(`begin`, `match`, `context` or `summary`) and a `data` field carrying the
+payload. Text that may not be valid UTF-8 (paths and line contents) is
+converted lossily before being emitted, so the output is always valid JSON.
+*/
+
+use std::path::Path;
+use std::time::Duration;
+
+/// Returns the `begin` message for the given file path.
+pub fn begin(path: &Path) -> String {
+    let mut msg = String::from("{\"type\":\"begin\",\"data\":{\"path\":");
+    push_text(&mut msg, path.to_string_lossy().as_bytes());
+    msg.push_str("}}");
+    msg
+}
+
+/// Returns a `match` message for a single matching line.
+///
+/// `submatches` gives the start and end offset of each match, relative to
+/// the beginning of `line`. `absolute_offset` is the offset of `line`
+/// relative to the beginning of the file, if it is known.
+pub fn matched(
+    path: &Path,
+    line: &[u8],
+    line_number: Option<u64>,
+    absolute_offset: Option<u64>,
+    submatches: &[(usize, usize)],
+) -> String {
+    let mut msg = message_prelude(
+        "match", path, line, line_number, absolute_offset);
+    msg.push_str(",\"submatches\":[");
+    for (i, &(start, end)) in submatches.iter().enumerate() {
+        if i > 0 {
+            msg.push(',');
+        }
+        msg.push_str("{\"match\":");
+        push_text(&mut msg, &line[start..end]);
+        msg.push_str(&format!(",\"start\":{},\"end\":{}}}", start, end));
+    }
+    msg.push_str("]}}");
+    msg
+}
+
+/// Returns a `context` message for a single context line.
+pub fn context(
+    path: &Path,
+    line: &[u8],
+    line_number: Option<u64>,
+    absolute_offset: Option<u64>,
+) -> String {
+    let mut msg = message_prelude(
+        "context", path, line, line_number, absolute_offset);
+    msg.push_str(",\"submatches\":[]}}");
+    msg
+}
+
+/// Returns the final `summary` message with aggregate statistics.
+///
+/// `elapsed` is the wall clock time of the entire run, while `search_time`
+/// and `cpu_time` are summed across all search workers, so both may exceed
+/// `elapsed` when searching in parallel.
+pub fn summary(
+    matched_lines: u64,
+    paths_searched: u64,
+    paths_matched: u64,
+    elapsed: Duration,
+    search_time: Duration,
+    cpu_time: Duration,
+) -> String {
+    let mut msg = String::from("{\"type\":\"summary\",\"data\":{\"elapsed\":");
+    push_duration(&mut msg, elapsed);
+    msg.push_str(&format!(
+        ",\"stats\":{{\"matched_lines\":{},\
+         \"searches\":{},\"searches_with_match\":{},\"search_time\":",
+        matched_lines,
+        paths_searched,
+        paths_matched,
+    ));
+    push_duration(&mut msg, search_time);
+    msg.push_str(",\"cpu_time\":");
+    push_duration(&mut msg, cpu_time);
+    msg.push_str("}}}");
+    msg
+}
+
+/// Writes the fields shared by `match` and `context` messages, leaving the
+/// `data` object open so the caller can append message specific fields.
+fn message_prelude(
+    kind: &str,
+    path: &Path,
+    line: &[u8],
+    line_number: Option<u64>,
+    absolute_offset: Option<u64>,
+) -> String {
+    let mut msg = format!("{{\"type\":\"{}\",\"data\":{{\"path\":", kind);
+    push_text(&mut msg, path.to_string_lossy().as_bytes());
+    msg.push_str(",\"lines\":");
+    push_text(&mut msg, line);
+    msg.push_str(",\"line_number\":");
+    push_opt_u64(&mut msg, line_number);
+    msg.push_str(",\"absolute_offset\":");
+    push_opt_u64(&mut msg, absolute_offset);
+    msg
+}
+
+/// Writes bytes as a `{"text": ...}` object, converting them to UTF-8
+/// lossily.
+fn push_text(msg: &mut String, bytes: &[u8]) {
+    msg.push_str("{\"text\":\"");
+    for c in String::from_utf8_lossy(bytes).chars() {
+        match c {
+            '"' => msg.push_str("\\\""),
+            '\\' => msg.push_str("\\\\"),
+            '\n' => msg.push_str("\\n"),
+            '\r' => msg.push_str("\\r"),
+            '\t' => msg.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                msg.push_str(&format!("\\u{:04x}", c as u32));
+            }
+            c => msg.push(c),
+        }
+    }
+    msg.push_str("\"}");
+}
+
+/// Writes a duration as an object with machine readable `secs` and `nanos`
+/// fields and a preformatted `human` field, e.g. `"0.104201s"`.
+fn push_duration(msg: &mut String, d: Duration) {
+    let fractional =
+        d.as_secs() as f64 + d.subsec_nanos() as f64 * 1e-9;
+    msg.push_str(&format!(
+        "{{\"secs\":{},\"nanos\":{},\"human\":\"{:0.6}s\"}}",
+        d.as_secs(),
+        d.subsec_nanos(),
+        fractional,
+    ));
+}
+
+/// Writes a number or `null`.
+fn push_opt_u64(msg: &mut String, n: Option<u64>) {
+    match n {
+        None => msg.push_str("null"),
+        Some(n) => msg.push_str(&n.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::time::Duration;
+
+    use super::{begin, context, matched, summary};
+
+    #[test]
+    fn begin_message() {
+        let msg = begin(Path::new("a/b.rs"));
+        assert_eq!(
+            msg,
+            r#"{"type":"begin","data":{"path":{"text":"a/b.rs"}}}"#);
+    }
+
+    #[test]
+    fn match_message() {
+        let msg = matched(
+            Path::new("a.rs"), b"foo bar\n", Some(3), Some(10), &[(4, 7)]);
+        assert_eq!(
+            msg,
+            r#"{"type":"match","data":{"path":{"text":"a.rs"},"#.to_string()
+            + r#""lines":{"text":"foo bar\n"},"line_number":3,"#
+            + r#""absolute_offset":10,"submatches":[{"match":"#
+            + r#"{"text":"bar"},"start":4,"end":7}]}}"#);
+    }
+
+    #[test]
+    fn context_message() {
+        let msg = context(Path::new("a.rs"), b"fn main() {\n", None, None);
+        assert_eq!(
+            msg,
+            r#"{"type":"context","data":{"path":{"text":"a.rs"},"#.to_string()
+            + r#""lines":{"text":"fn main() {\n"},"line_number":null,"#
+            + r#""absolute_offset":null,"submatches":[]}}"#);
+    }
+
+    #[test]
+    fn summary_message() {
+        let msg = summary(
+            5, 10, 2,
+            Duration::new(1, 500),
+            Duration::new(0, 250_000_000),
+            Duration::new(2, 0),
+        );
+        assert_eq!(
+            msg,
+            r#"{"type":"summary","data":{"elapsed":{"secs":1,"#.to_string()
+            + r#""nanos":500,"human":"1.000001s"},"#
+            + r#""stats":{"matched_lines":5,"searches":10,"#
+            + r#""searches_with_match":2,"#
+            + r#""search_time":{"secs":0,"nanos":250000000,"#
+            + r#""human":"0.250000s"},"#
+            + r#""cpu_time":{"secs":2,"nanos":0,"human":"2.000000s"}}}}"#);
+    }
+
+    #[test]
+    fn escaping() {
+        let msg = begin(Path::new("a\"b\\c\td"));
+        assert_eq!(
+            msg,
+            r#"{"type":"begin","data":{"path":{"text":"a\"b\\c\td"}}}"#);
+
+        let msg = context(Path::new("a"), b"x\x01y\xFFz\n", None, None);
+        assert!(msg.contains(
+            "\"lines\":{\"text\":\"x\\u0001y\u{FFFD}z\\n\"}"));
+    }
+}
@@ -0,0 +1,418 @@
+#![deny(missing_docs)]
+
+/*!
+An embeddable library interface to ripgrep's search pipeline.
+
+This crate wires the building blocks that the `rg` binary is made of — the
+recursive directory walker from the `ignore` crate (gitignore rules, hidden
+file filtering, file type matching) and the line oriented regex searcher
+from the `grep` crate (including smart case) — into a small API that other
+tools can call instead of shelling out to `rg` and re-parsing its output.
+
+The entry point is [`SearchOptions`](struct.SearchOptions.html), which is
+configured in a builder style and turned into an iterator of
+[`ResultEvent`](enum.ResultEvent.html)s with
+[`run`](struct.SearchOptions.html#method.run):
+
+```no_run
+use ripgrep::{ResultEvent, SearchOptions};
+
+let results = SearchOptions::new("fn main").path("src").run().unwrap();
+for event in results {
+    if let ResultEvent::Match { path, line_number, text, .. } = event {
+        println!("{}:{}:{}", path.display(), line_number,
+                 String::from_utf8_lossy(&text));
+    }
+}
+```
+
+Note that this interface intentionally exposes a small, stable subset of the
+command line flags. The `rg` binary remains the only consumer of the more
+exotic options.
+*/
+
+extern crate atty;
+extern crate grep;
+extern crate ignore;
+extern crate memchr;
+extern crate termcolor;
+
+use std::collections::VecDeque;
+use std::env;
+use std::error;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::result;
+
+use grep::{Grep, GrepBuilder};
+use ignore::WalkBuilder;
+use ignore::types::TypesBuilder;
+use memchr::memchr;
+use termcolor::ColorChoice;
+
+/// A type alias for the errors produced by this crate.
+pub type Error = Box<error::Error + Send + Sync>;
+
+/// A type alias for results produced by this crate.
+pub type Result<T> = result::Result<T, Error>;
+
+/// A single event produced while searching.
+///
+/// Events for one file are always contiguous: a `Begin` event, followed by
+/// one `Match` event per matching line, followed by an `End` event. Files
+/// without matches produce no events at all.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ResultEvent {
+    /// Emitted before the first match of a file that contains at least one
+    /// match.
+    Begin {
+        /// The path of the file being searched.
+        path: PathBuf,
+    },
+    /// A single matching line.
+    Match {
+        /// The path of the file containing the match.
+        path: PathBuf,
+        /// The line number of the match, starting at `1`.
+        line_number: u64,
+        /// The absolute byte offset of the start of the matching line.
+        offset: u64,
+        /// The contents of the matching line, without its line terminator.
+        text: Vec<u8>,
+    },
+    /// Emitted after the last match of a file.
+    End {
+        /// The path of the file that was searched.
+        path: PathBuf,
+        /// The number of matching lines found in this file.
+        match_count: u64,
+    },
+}
+
+/// Options describing a single search, in the manner of the `rg` command
+/// line.
+///
+/// The directory walking options (ignore rules, hidden files, file types)
+/// correspond to the flags of the same name on the command line and use the
+/// same defaults.
+#[derive(Clone, Debug)]
+pub struct SearchOptions {
+    pattern: String,
+    paths: Vec<PathBuf>,
+    case_insensitive: bool,
+    case_smart: bool,
+    hidden: bool,
+    follow: bool,
+    no_ignore: bool,
+    max_count: Option<u64>,
+    types: Vec<String>,
+    types_not: Vec<String>,
+}
+
+impl SearchOptions {
+    /// Create options for searching the given regex pattern.
+    ///
+    /// With no further configuration, this searches the current directory
+    /// recursively, case sensitively, respecting gitignore rules and
+    /// skipping hidden files, exactly like `rg PATTERN`.
+    pub fn new<P: Into<String>>(pattern: P) -> SearchOptions {
+        SearchOptions {
+            pattern: pattern.into(),
+            paths: vec![],
+            case_insensitive: false,
+            case_smart: false,
+            hidden: false,
+            follow: false,
+            no_ignore: false,
+            max_count: None,
+            types: vec![],
+            types_not: vec![],
+        }
+    }
+
+    /// Add a file or directory to search. May be called multiple times.
+    /// When no path is added, the current directory is searched.
+    pub fn path<P: Into<PathBuf>>(mut self, path: P) -> SearchOptions {
+        self.paths.push(path.into());
+        self
+    }
+
+    /// Search case insensitively (the -i/--ignore-case flag).
+    pub fn case_insensitive(mut self, yes: bool) -> SearchOptions {
+        self.case_insensitive = yes;
+        self
+    }
+
+    /// Search case insensitively unless the pattern contains an uppercase
+    /// literal (the -S/--smart-case flag).
+    pub fn case_smart(mut self, yes: bool) -> SearchOptions {
+        self.case_smart = yes;
+        self
+    }
+
+    /// Search hidden files and directories (the --hidden flag).
+    pub fn hidden(mut self, yes: bool) -> SearchOptions {
+        self.hidden = yes;
+        self
+    }
+
+    /// Follow symbolic links (the -L/--follow flag).
+    pub fn follow(mut self, yes: bool) -> SearchOptions {
+        self.follow = yes;
+        self
+    }
+
+    /// Don't respect ignore files such as .gitignore (the --no-ignore
+    /// flag).
+    pub fn no_ignore(mut self, yes: bool) -> SearchOptions {
+        self.no_ignore = yes;
+        self
+    }
+
+    /// Limit the number of matching lines per file (the -m/--max-count
+    /// flag).
+    pub fn max_count(mut self, count: Option<u64>) -> SearchOptions {
+        self.max_count = count;
+        self
+    }
+
+    /// Only search files matching the given default file type, e.g. "rust"
+    /// (the -t/--type flag). May be called multiple times.
+    pub fn file_type<T: Into<String>>(mut self, ty: T) -> SearchOptions {
+        self.types.push(ty.into());
+        self
+    }
+
+    /// Don't search files matching the given default file type (the
+    /// -T/--type-not flag). May be called multiple times.
+    pub fn file_type_not<T: Into<String>>(mut self, ty: T) -> SearchOptions {
+        self.types_not.push(ty.into());
+        self
+    }
+
+    /// Build the search pipeline and return an iterator over its results.
+    ///
+    /// This returns an error if the pattern does not compile or if a file
+    /// type is unknown. Errors encountered during the walk itself (e.g.
+    /// permission errors) are skipped, as they are by the `rg` binary.
+    pub fn run(self) -> Result<SearchResults> {
+        let grep = GrepBuilder::new(&self.pattern)
+            .case_insensitive(self.case_insensitive)
+            .case_smart(self.case_smart)
+            .build()?;
+        let mut btypes = TypesBuilder::new();
+        btypes.add_defaults();
+        for ty in &self.types {
+            btypes.select(ty);
+        }
+        for ty in &self.types_not {
+            btypes.negate(ty);
+        }
+        let types = btypes.build()?;
+
+        let mut paths = self.paths.iter();
+        let mut wb = match paths.next() {
+            None => WalkBuilder::new("./"),
+            Some(path) => WalkBuilder::new(path),
+        };
+        for path in paths {
+            wb.add(path);
+        }
+        wb.types(types)
+            .hidden(!self.hidden)
+            .follow_links(self.follow)
+            .ignore(!self.no_ignore)
+            .git_global(!self.no_ignore)
+            .git_ignore(!self.no_ignore)
+            .git_exclude(!self.no_ignore)
+            .parents(!self.no_ignore);
+        Ok(SearchResults {
+            walker: wb.build(),
+            grep: grep,
+            max_count: self.max_count,
+            queue: VecDeque::new(),
+        })
+    }
+}
+
+/// An iterator over the results of a search, created by
+/// [`SearchOptions::run`](struct.SearchOptions.html#method.run).
+///
+/// Files are searched lazily, one at a time, as the iterator is advanced.
+pub struct SearchResults {
+    walker: ignore::Walk,
+    grep: Grep,
+    max_count: Option<u64>,
+    queue: VecDeque<ResultEvent>,
+}
+
+impl Iterator for SearchResults {
+    type Item = ResultEvent;
+
+    fn next(&mut self) -> Option<ResultEvent> {
+        loop {
+            if let Some(event) = self.queue.pop_front() {
+                return Some(event);
+            }
+            let dent = match self.walker.next() {
+                None => return None,
+                Some(Err(_)) => continue,
+                Some(Ok(dent)) => dent,
+            };
+            if !dent.file_type().map_or(false, |ft| ft.is_file()) {
+                continue;
+            }
+            self.search_file(dent.path());
+        }
+    }
+}
+
+impl SearchResults {
+    /// Searches a single file and pushes its events onto the queue. Files
+    /// that cannot be read and files that look binary are skipped, as they
+    /// are by the `rg` binary.
+    fn search_file(&mut self, path: &Path) {
+        let mut buf = vec![];
+        let res = File::open(path).and_then(|mut f| f.read_to_end(&mut buf));
+        if res.is_err() {
+            return;
+        }
+        if is_binary(&buf) {
+            return;
+        }
+        let mut match_count = 0;
+        let mut line_number = 1;
+        let mut last_pos = 0;
+        for m in self.grep.iter(&buf) {
+            if self.max_count.map_or(false, |max| match_count >= max) {
+                break;
+            }
+            if match_count == 0 {
+                self.queue.push_back(ResultEvent::Begin {
+                    path: path.to_path_buf(),
+                });
+            }
+            line_number += count_lines(&buf[last_pos..m.start()]);
+            last_pos = m.start();
+            let line = trim_terminator(&buf[m.start()..m.end()]);
+            self.queue.push_back(ResultEvent::Match {
+                path: path.to_path_buf(),
+                line_number: line_number,
+                offset: m.start() as u64,
+                text: line.to_vec(),
+            });
+            match_count += 1;
+        }
+        if match_count > 0 {
+            self.queue.push_back(ResultEvent::End {
+                path: path.to_path_buf(),
+                match_count: match_count,
+            });
+        }
+    }
+}
+
+/// Runs the search described by the given options. This is a convenience
+/// for `options.run()`.
+pub fn run(options: SearchOptions) -> Result<SearchResults> {
+    options.run()
+}
+
+/// Picks a color choice for writing to stdout automatically, the way
+/// `rg --color auto` does.
+///
+/// Colors are disabled when the `NO_COLOR` environment variable is set (to
+/// any value), when `TERM` is set to `dumb` or when stdout is not connected
+/// to a terminal. Setting `CLICOLOR_FORCE` to a non-empty value other than
+/// `0` forces colors on regardless of the above.
+///
+/// This is the single source of truth for terminal capability detection:
+/// the `rg` binary and library embedders should both use it rather than
+/// rolling their own checks.
+pub fn auto_color_choice() -> ColorChoice {
+    match env::var_os("CLICOLOR_FORCE") {
+        Some(ref force) if !force.is_empty() && force != "0" => {
+            return ColorChoice::Always;
+        }
+        _ => {}
+    }
+    if env::var_os("NO_COLOR").is_some() {
+        return ColorChoice::Never;
+    }
+    if env::var_os("TERM").map_or(false, |term| term == "dumb") {
+        return ColorChoice::Never;
+    }
+    if !atty::is(atty::Stream::Stdout) {
+        return ColorChoice::Never;
+    }
+    ColorChoice::Auto
+}
+
+/// Returns true if the given buffer looks like it contains binary data,
+/// using the same heuristic as the `rg` binary: a NUL byte in the first
+/// 10KB.
+fn is_binary(buf: &[u8]) -> bool {
+    let upto = ::std::cmp::min(10_240, buf.len());
+    memchr(b'\x00', &buf[..upto]).is_some()
+}
+
+/// Counts the number of line terminators in the given buffer.
+fn count_lines(buf: &[u8]) -> u64 {
+    let mut count = 0;
+    let mut pos = 0;
+    while let Some(i) = memchr(b'\n', &buf[pos..]) {
+        count += 1;
+        pos += i + 1;
+    }
+    count
+}
+
+/// Strips a trailing line terminator from the given line, if present.
+fn trim_terminator(line: &[u8]) -> &[u8] {
+    if line.last() == Some(&b'\n') {
+        let line = &line[..line.len() - 1];
+        if line.last() == Some(&b'\r') {
+            &line[..line.len() - 1]
+        } else {
+            line
+        }
+    } else {
+        line
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SearchOptions, count_lines, is_binary, trim_terminator};
+
+    #[test]
+    fn pattern_error() {
+        assert!(SearchOptions::new("(").run().is_err());
+    }
+
+    #[test]
+    fn unknown_type_error() {
+        assert!(SearchOptions::new("a").file_type("wat").run().is_err());
+    }
+
+    #[test]
+    fn line_counting() {
+        assert_eq!(count_lines(b""), 0);
+        assert_eq!(count_lines(b"a\nb\n"), 2);
+        assert_eq!(count_lines(b"a\nb"), 1);
+    }
+
+    #[test]
+    fn binary_detection() {
+        assert!(is_binary(b"foo\x00bar"));
+        assert!(!is_binary(b"foo bar"));
+    }
+
+    #[test]
+    fn terminator_trimming() {
+        assert_eq!(trim_terminator(b"foo\n"), b"foo");
+        assert_eq!(trim_terminator(b"foo\r\n"), b"foo");
+        assert_eq!(trim_terminator(b"foo"), b"foo");
+    }
+}
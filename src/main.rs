@@ -16,39 +16,55 @@ extern crate memchr;
 extern crate memmap;
 extern crate num_cpus;
 extern crate regex;
+extern crate ripgrep;
 extern crate same_file;
 extern crate termcolor;
 #[cfg(windows)]
 extern crate winapi;
 
+use std::collections::HashMap;
 use std::error::Error;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::Path;
 use std::process;
 use std::result;
-use std::sync::Arc;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::mpsc;
 use std::thread;
 use std::time::{Duration, Instant};
 
 use args::Args;
+use session::{Session, SessionRecorder};
 use worker::Work;
 
 macro_rules! errored {
     ($($tt:tt)*) => {
-        return Err(From::from(format!($($tt)*)));
+        return Err(From::from(format!($($tt)*)))
     }
 }
 
 mod app;
 mod args;
+mod blame;
 mod config;
+mod console_stdin;
 mod decompressor;
+mod dupes;
+mod generate;
+mod human;
+mod json;
 mod preprocessor;
 mod logger;
 mod pathutil;
 mod printer;
+mod replace;
 mod search_buffer;
 mod search_stream;
+mod session;
+mod stats;
+mod stream_kind;
 mod unescape;
 mod worker;
 
@@ -67,6 +83,12 @@ fn main() {
 }
 
 fn run(args: Arc<Args>) -> Result<u64> {
+    if let Some(kind) = args.generate() {
+        // Generating output isn't searching, but a successful run should
+        // exit with status 0, which corresponds to "match found."
+        generate::generate(kind)?;
+        return Ok(1);
+    }
     if args.never_match() {
         return Ok(0);
     }
@@ -79,7 +101,16 @@ fn run(args: Arc<Args>) -> Result<u64> {
         }
     } else if args.type_list() {
         run_types(&args)
-    } else if threads == 1 || args.is_one_path() {
+    } else if args.in_place() {
+        run_in_place(&args)
+    } else if let Some(window) = args.dup_lines() {
+        run_dup_lines(&args, window)
+    } else if let Some(path) = args.replay() {
+        run_replay(&args, path)
+    } else if args.record().is_none()
+        && (threads == 1 || args.is_one_path()) {
+        // Recording needs the per-file output buffers of the parallel
+        // searcher, so --record always takes that code path.
         run_one_thread(&args)
     } else {
         run_parallel(&args)
@@ -88,18 +119,35 @@ fn run(args: Arc<Args>) -> Result<u64> {
 
 fn run_parallel(args: &Arc<Args>) -> Result<u64> {
     let start_time = Instant::now();
+    let deadline = args.timeout().map(|timeout| start_time + timeout);
+    let timed_out = Arc::new(AtomicBool::new(false));
     let bufwtr = Arc::new(args.buffer_writer());
     let quiet_matched = args.quiet_matched();
     let paths_searched = Arc::new(AtomicUsize::new(0));
     let match_line_count = Arc::new(AtomicUsize::new(0));
     let paths_matched = Arc::new(AtomicUsize::new(0));
+    let type_summary = Arc::new(Mutex::new(TypeSummary::new(args)));
+    let recorder = args.record().map(|_| Arc::new(SessionRecorder::new()));
+    let json_file = match args.json_file() {
+        None => None,
+        Some(path) => match fs::File::create(path) {
+            Ok(file) => Some(Arc::new(Mutex::new(file))),
+            Err(err) => {
+                errored!("error creating {}: {}", path.display(), err);
+            }
+        },
+    };
 
     args.walker_parallel().run(|| {
         let args = Arc::clone(args);
         let quiet_matched = quiet_matched.clone();
+        let recorder = recorder.clone();
+        let json_file = json_file.clone();
+        let timed_out = timed_out.clone();
         let paths_searched = paths_searched.clone();
         let match_line_count = match_line_count.clone();
         let paths_matched = paths_matched.clone();
+        let type_summary = type_summary.clone();
         let bufwtr = Arc::clone(&bufwtr);
         let mut buf = bufwtr.buffer();
         let mut worker = args.worker();
@@ -109,6 +157,10 @@ fn run_parallel(args: &Arc<Args>) -> Result<u64> {
             if quiet_matched.has_match() {
                 return Quit;
             }
+            if deadline.map_or(false, |d| Instant::now() >= d) {
+                timed_out.store(true, Ordering::SeqCst);
+                return Quit;
+            }
             let dent = match get_or_log_dir_entry(
                 result,
                 args.stdout_handle(),
@@ -120,58 +172,133 @@ fn run_parallel(args: &Arc<Args>) -> Result<u64> {
                 Some(dent) => dent,
             };
             paths_searched.fetch_add(1, Ordering::SeqCst);
+            let path = dent.path().to_path_buf();
+            let is_stdin = dent.is_stdin();
             buf.clear();
             {
                 // This block actually executes the search and prints the
                 // results into outbuf.
                 let mut printer = args.printer(&mut buf);
                 let count =
-                    if dent.is_stdin() {
+                    if is_stdin {
                         worker.run(&mut printer, Work::Stdin)
                     } else {
                         worker.run(&mut printer, Work::DirEntry(dent))
                     };
                 match_line_count.fetch_add(count as usize, Ordering::SeqCst);
+                if count > 0 {
+                    let mut summary = type_summary.lock().unwrap();
+                    if let Some(ref mut summary) = *summary {
+                        summary.add(&path, count);
+                    }
+                }
+                if let Some(ref json_file) = json_file {
+                    if let Some(bytes) = printer.take_json_tee() {
+                        let mut file = json_file.lock().unwrap();
+                        let _ = file.write_all(&bytes);
+                    }
+                }
                 if quiet_matched.set_match(count > 0) {
                     return Quit;
                 }
-                if args.stats() && count > 0 {
+                let counting = args.stats()
+                    || args.json()
+                    || args.json_file().is_some();
+                if counting && count > 0 {
                     paths_matched.fetch_add(1, Ordering::SeqCst);
                 }
             }
             // BUG(burntsushi): We should handle this error instead of ignoring
             // it. See: https://github.com/BurntSushi/ripgrep/issues/200
             let _ = bufwtr.print(&buf);
+            if let Some(ref recorder) = recorder {
+                recorder.record(
+                    if is_stdin { None } else { Some(&path) },
+                    buf.as_slice(),
+                );
+            }
             Continue
         })
     });
-    if !args.paths().is_empty() && paths_searched.load(Ordering::SeqCst) == 0 {
+    let timed_out = timed_out.load(Ordering::SeqCst);
+    if timed_out && !args.no_messages() {
+        eprint_timed_out(args.timeout().unwrap());
+    }
+    if !timed_out
+        && !args.paths().is_empty()
+        && paths_searched.load(Ordering::SeqCst) == 0 {
         if !args.no_messages() {
             eprint_nothing_searched();
         }
     }
+    if let Some(ref summary) = *type_summary.lock().unwrap() {
+        summary.print();
+    }
     let match_line_count = match_line_count.load(Ordering::SeqCst) as u64;
     let paths_searched = paths_searched.load(Ordering::SeqCst) as u64;
     let paths_matched = paths_matched.load(Ordering::SeqCst) as u64;
-    if args.stats() {
+    if args.json() {
+        println!("{}", json::summary(
+            match_line_count,
+            paths_searched,
+            paths_matched,
+            start_time.elapsed(),
+            args.search_stats().search_time(),
+            args.search_stats().cpu_time(),
+        ));
+    } else if args.stats() {
         print_stats(
+            args.search_stats(),
             match_line_count,
             paths_searched,
             paths_matched,
+            args.filesize_skips(),
             start_time.elapsed(),
         );
     }
+    if let Some(ref json_file) = json_file {
+        let mut file = json_file.lock().unwrap();
+        let _ = writeln!(file, "{}", json::summary(
+            match_line_count,
+            paths_searched,
+            paths_matched,
+            start_time.elapsed(),
+            args.search_stats().search_time(),
+            args.search_stats().cpu_time(),
+        ));
+    }
+    if let (Some(recorder), Some(path)) = (recorder, args.record()) {
+        if let Err(err) = recorder.write_to(path, match_line_count) {
+            errored!("error writing session to {}: {}", path.display(), err);
+        }
+    }
     Ok(match_line_count)
 }
 
 fn run_one_thread(args: &Arc<Args>) -> Result<u64> {
     let start_time = Instant::now();
+    let deadline = args.timeout().map(|timeout| start_time + timeout);
+    let mut timed_out = false;
     let mut stdout = args.stdout();
     let mut worker = args.worker();
     let mut paths_searched: u64 = 0;
     let mut match_line_count = 0;
     let mut paths_matched: u64 = 0;
+    let mut type_summary = TypeSummary::new(args);
+    let mut json_file = match args.json_file() {
+        None => None,
+        Some(path) => match fs::File::create(path) {
+            Ok(file) => Some(file),
+            Err(err) => {
+                errored!("error creating {}: {}", path.display(), err);
+            }
+        },
+    };
     for result in args.walker() {
+        if deadline.map_or(false, |d| Instant::now() >= d) {
+            timed_out = true;
+            break;
+        }
         let dent = match get_or_log_dir_entry(
             result,
             args.stdout_handle(),
@@ -192,6 +319,7 @@ fn run_one_thread(args: &Arc<Args>) -> Result<u64> {
             }
         }
         paths_searched += 1;
+        let path = dent.path().to_path_buf();
         let count =
             if dent.is_stdin() {
                 worker.run(&mut printer, Work::Stdin)
@@ -199,27 +327,77 @@ fn run_one_thread(args: &Arc<Args>) -> Result<u64> {
                 worker.run(&mut printer, Work::DirEntry(dent))
             };
         match_line_count += count;
-        if args.stats() && count > 0 {
+        if let Some(ref mut file) = json_file {
+            if let Some(bytes) = printer.take_json_tee() {
+                let _ = file.write_all(&bytes);
+            }
+        }
+        if count > 0 {
+            if let Some(ref mut summary) = type_summary {
+                summary.add(&path, count);
+            }
+        }
+        let counting = args.stats()
+            || args.json()
+            || args.json_file().is_some();
+        if counting && count > 0 {
             paths_matched += 1;
         }
     }
-    if !args.paths().is_empty() && paths_searched == 0 {
+    if let Some(ref summary) = type_summary {
+        // Matches are written through a buffered writer, so flush it first
+        // to keep the summary after all of the search output.
+        let _ = stdout.flush();
+        summary.print();
+    }
+    if timed_out && !args.no_messages() {
+        eprint_timed_out(args.timeout().unwrap());
+    }
+    if !timed_out && !args.paths().is_empty() && paths_searched == 0 {
         if !args.no_messages() {
             eprint_nothing_searched();
         }
     }
-    if args.stats() {
+    if args.json() {
+        // The matches themselves are written through a buffered writer, so
+        // flush it first to keep the summary message last.
+        let _ = stdout.flush();
+        println!("{}", json::summary(
+            match_line_count,
+            paths_searched,
+            paths_matched,
+            start_time.elapsed(),
+            args.search_stats().search_time(),
+            args.search_stats().cpu_time(),
+        ));
+    } else if args.stats() {
+        // As above, flush the buffered matches first to keep the stats
+        // summary last.
+        let _ = stdout.flush();
         print_stats(
+            args.search_stats(),
             match_line_count,
             paths_searched,
             paths_matched,
+            args.filesize_skips(),
             start_time.elapsed(),
         );
     }
+    if let Some(ref mut file) = json_file {
+        let _ = writeln!(file, "{}", json::summary(
+            match_line_count,
+            paths_searched,
+            paths_matched,
+            start_time.elapsed(),
+            args.search_stats().search_time(),
+            args.search_stats().cpu_time(),
+        ));
+    }
     Ok(match_line_count)
 }
 
 fn run_files_parallel(args: Arc<Args>) -> Result<u64> {
+    let deadline = args.timeout().map(|timeout| Instant::now() + timeout);
     let print_args = Arc::clone(&args);
     let (tx, rx) = mpsc::channel::<ignore::DirEntry>();
     let print_thread = thread::spawn(move || {
@@ -237,6 +415,9 @@ fn run_files_parallel(args: Arc<Args>) -> Result<u64> {
         let args = Arc::clone(&args);
         let tx = tx.clone();
         Box::new(move |result| {
+            if deadline.map_or(false, |d| Instant::now() >= d) {
+                return ignore::WalkState::Quit;
+            }
             if let Some(dent) = get_or_log_dir_entry(
                 result,
                 args.stdout_handle(),
@@ -256,9 +437,13 @@ fn run_files_parallel(args: Arc<Args>) -> Result<u64> {
 }
 
 fn run_files_one_thread(args: &Arc<Args>) -> Result<u64> {
+    let deadline = args.timeout().map(|timeout| Instant::now() + timeout);
     let mut printer = args.printer(args.stdout());
     let mut file_count = 0;
     for result in args.walker() {
+        if deadline.map_or(false, |d| Instant::now() >= d) {
+            break;
+        }
         let dent = match get_or_log_dir_entry(
             result,
             args.stdout_handle(),
@@ -279,6 +464,100 @@ fn run_files_one_thread(args: &Arc<Args>) -> Result<u64> {
     Ok(file_count)
 }
 
+fn run_dup_lines(args: &Arc<Args>, window: usize) -> Result<u64> {
+    let mut index = dupes::DupLineIndex::new(window);
+    for result in args.walker() {
+        let dent = match get_or_log_dir_entry(
+            result,
+            args.stdout_handle(),
+            true,
+            args.no_messages(),
+            args.no_ignore_messages(),
+        ) {
+            None => continue,
+            Some(dent) => dent,
+        };
+        let mut contents = vec![];
+        let result = fs::File::open(dent.path())
+            .and_then(|mut file| file.read_to_end(&mut contents));
+        if let Err(err) = result {
+            if !args.no_messages() {
+                eprintln!("{}: {}", dent.path().display(), err);
+            }
+            continue;
+        }
+        // Skip files that look binary; line based duplication detection
+        // isn't meaningful for them.
+        if memchr::memchr(0, &contents).is_some() {
+            continue;
+        }
+        index.add_file(dent.path(), &contents);
+    }
+    let mut stdout = args.stdout();
+    let group_count = index.print(&mut stdout)
+        .map_err(|err| format!("error writing report: {}", err))?;
+    Ok(group_count)
+}
+
+fn run_in_place(args: &Arc<Args>) -> Result<u64> {
+    let replacement = match args.replacement() {
+        Some(replacement) => replacement.to_vec(),
+        None => errored!("the --in-place flag requires the -r/--replace flag"),
+    };
+    let grep = args.grep();
+    let mut stdout = args.stdout();
+    let mut paths_rewritten = 0;
+    for result in args.walker() {
+        let dent = match get_or_log_dir_entry(
+            result,
+            args.stdout_handle(),
+            true,
+            args.no_messages(),
+            args.no_ignore_messages(),
+        ) {
+            None => continue,
+            Some(dent) => dent,
+        };
+        let result = if args.dry_run() {
+            replace::preview(
+                &mut stdout, grep.regex(), &replacement, dent.path())
+        } else {
+            replace::rewrite(
+                grep.regex(), &replacement, dent.path(),
+                args.backup_suffix())
+        };
+        match result {
+            Ok(0) => {}
+            Ok(_) => paths_rewritten += 1,
+            Err(err) => {
+                if !args.no_messages() {
+                    eprintln!("{}: {}", dent.path().display(), err);
+                }
+            }
+        }
+    }
+    Ok(paths_rewritten)
+}
+
+fn run_replay(args: &Arc<Args>, path: &Path) -> Result<u64> {
+    let session = match Session::load(path) {
+        Ok(session) => session,
+        Err(err) => errored!("{}", err),
+    };
+    if !args.no_messages() {
+        for changed in session.changed_files() {
+            eprintln!(
+                "{}: changed since the session was recorded",
+                changed.display(),
+            );
+        }
+    }
+    let mut stdout = args.stdout();
+    stdout.write_all(session.output())
+        .map_err(|err| format!("error writing recorded output: {}", err))?;
+    Ok(session.count())
+}
+
 fn run_types(args: &Arc<Args>) -> Result<u64> {
     let mut printer = args.printer(args.stdout());
     let mut ty_count = 0;
@@ -406,6 +685,77 @@ fn maybe_dent_eq_handle(_: &ignore::DirEntry, _: &same_file::Handle) -> bool {
     true
 }
 
+/// Aggregates match and file counts per detected file type, for the
+/// --by-type flag.
+struct TypeSummary {
+    set: globset::GlobSet,
+    /// Maps a glob index in `set` to the name of the file type it belongs
+    /// to.
+    names: Vec<String>,
+    /// Maps a file type name to its total match count and the number of
+    /// files with at least one match.
+    counts: HashMap<String, (u64, u64)>,
+}
+
+impl TypeSummary {
+    fn new(args: &Args) -> Option<TypeSummary> {
+        if !args.by_type() {
+            return None;
+        }
+        let mut builder = globset::GlobSetBuilder::new();
+        let mut names = vec![];
+        for def in args.type_defs() {
+            for glob in def.globs() {
+                let glob = match globset::Glob::new(glob) {
+                    Err(_) => continue,
+                    Ok(glob) => glob,
+                };
+                builder.add(glob);
+                names.push(def.name().to_string());
+            }
+        }
+        let set = match builder.build() {
+            Err(_) => return None,
+            Ok(set) => set,
+        };
+        Some(TypeSummary {
+            set: set,
+            names: names,
+            counts: HashMap::new(),
+        })
+    }
+
+    fn add(&mut self, path: &Path, match_count: u64) {
+        let name = match path.file_name() {
+            None => return,
+            Some(name) => name,
+        };
+        // The highest precedent type is the last one that matches.
+        let ty = match self.set.matches(name).last() {
+            None => "other".to_string(),
+            Some(&i) => self.names[i].clone(),
+        };
+        let counts = self.counts.entry(ty).or_insert((0, 0));
+        counts.0 += match_count;
+        counts.1 += 1;
+    }
+
+    fn print(&self) {
+        let mut types: Vec<&String> = self.counts.keys().collect();
+        types.sort();
+        println!("");
+        for ty in types {
+            let (matches, files) = self.counts[ty];
+            println!("{}: {} matches in {} files", ty, matches, files);
+        }
+    }
+}
+
+fn eprint_timed_out(timeout: Duration) {
+    eprintln!("Search timed out after {} second(s); \
+               results may be incomplete.", timeout.as_secs());
+}
+
 fn eprint_nothing_searched() {
     eprintln!("No files were searched, which means ripgrep probably \
                applied a filter you didn't expect. \
@@ -413,19 +763,31 @@ fn eprint_nothing_searched() {
 }
 
 fn print_stats(
-    match_count: u64,
+    stats: &stats::Stats,
+    match_line_count: u64,
     paths_searched: u64,
     paths_matched: u64,
+    filesize_skips: u64,
     time_elapsed: Duration,
 ) {
-    let time_elapsed =
-        time_elapsed.as_secs() as f64
-        + (time_elapsed.subsec_nanos() as f64 * 1e-9);
-    println!("\n{} matched lines\n\
+    println!("\n{} matches\n\
+              {} matched lines\n\
               {} files contained matches\n\
               {} files searched\n\
-              {:.3} seconds", match_count, paths_matched,
-             paths_searched, time_elapsed);
+              {} bytes searched", stats.matches(), match_line_count,
+             paths_matched, paths_searched, stats.bytes_searched());
+    if filesize_skips > 0 {
+        println!("{} files skipped due to --max-filesize", filesize_skips);
+    }
+    println!("{:.6} seconds spent searching",
+             fractional_seconds(stats.search_time()));
+    println!("{:.6} seconds of CPU time spent searching",
+             fractional_seconds(stats.cpu_time()));
+    println!("{:.6} seconds", fractional_seconds(time_elapsed));
+}
+
+fn fractional_seconds(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + (duration.subsec_nanos() as f64 * 1e-9)
 }
 
 // The Rust standard library suppresses the default SIGPIPE behavior, so that
@@ -2,9 +2,142 @@ use std::fs::File;
 use std::io::{self, Read};
 use std::path::{Path, PathBuf};
 use std::process::{self, Stdio};
+use std::sync::{Arc, Condvar, Mutex};
+
+use globset::{Glob, GlobSet, GlobSetBuilder, Precedence};
 
 use Result;
 
+/// A builder for a set of preprocessor commands, where each command is
+/// scoped to the files matching a glob.
+#[derive(Clone, Debug)]
+pub struct PreprocessorGlobsBuilder {
+    globs: GlobSetBuilder,
+    commands: Vec<PathBuf>,
+    max_procs: usize,
+}
+
+impl PreprocessorGlobsBuilder {
+    /// Create a new builder with no associations.
+    pub fn new() -> PreprocessorGlobsBuilder {
+        PreprocessorGlobsBuilder {
+            globs: GlobSetBuilder::new(),
+            commands: vec![],
+            max_procs: 0,
+        }
+    }
+
+    /// Associate a preprocessor command with the files matching the given
+    /// glob. If a file matches more than one glob, then the command of the
+    /// glob added last wins.
+    pub fn associate(
+        &mut self,
+        glob: &str,
+        cmd: PathBuf,
+    ) -> Result<&mut PreprocessorGlobsBuilder> {
+        self.globs.add(Glob::new(glob)?);
+        self.commands.push(cmd);
+        Ok(self)
+    }
+
+    /// Cap the number of preprocessor commands that may run concurrently.
+    ///
+    /// A limit of zero, the default, means no cap.
+    #[allow(dead_code)]
+    pub fn max_procs(&mut self, limit: usize) -> &mut PreprocessorGlobsBuilder {
+        self.max_procs = limit;
+        self
+    }
+
+    /// Build the set of glob scoped preprocessor commands.
+    pub fn build(&self) -> Result<PreprocessorGlobs> {
+        Ok(PreprocessorGlobs {
+            set: self.globs.build()?,
+            commands: self.commands.clone(),
+            limit: if self.max_procs == 0 {
+                None
+            } else {
+                Some(Arc::new(ProcessLimit::new(self.max_procs)))
+            },
+        })
+    }
+}
+
+/// A set of glob scoped preprocessor commands.
+///
+/// Each file is preprocessed by the command associated with the glob that it
+/// matches, e.g., `*.pdf` may be mapped to `pdftotext`. Files that match no
+/// glob are not preprocessed at all.
+#[derive(Clone, Debug)]
+pub struct PreprocessorGlobs {
+    set: GlobSet,
+    commands: Vec<PathBuf>,
+    limit: Option<Arc<ProcessLimit>>,
+}
+
+impl PreprocessorGlobs {
+    /// Returns a reader over the preprocessed contents of `path`, or `None`
+    /// if no glob matches `path`.
+    ///
+    /// If a cap on concurrently running preprocessor commands was set, then
+    /// this blocks while the number of running commands is at the cap. The
+    /// command counts as running until the returned reader is dropped.
+    pub fn reader(&self, path: &Path) -> Option<Result<PreprocessorReader>> {
+        let i = match self.set.best_match(path, Precedence::DefinitionOrder) {
+            None => return None,
+            Some(i) => i,
+        };
+        if let Some(ref limit) = self.limit {
+            limit.acquire();
+        }
+        let rdr = PreprocessorReader::from_cmd_path_limit(
+            self.commands[i].clone(),
+            path,
+            self.limit.clone(),
+        );
+        if rdr.is_err() {
+            // The reader owns the permit only once it exists.
+            if let Some(ref limit) = self.limit {
+                limit.release();
+            }
+        }
+        Some(rdr)
+    }
+}
+
+/// A cap on the number of preprocessor commands that may run at once.
+#[derive(Debug)]
+struct ProcessLimit {
+    max: usize,
+    running: Mutex<usize>,
+    cond: Condvar,
+}
+
+impl ProcessLimit {
+    fn new(max: usize) -> ProcessLimit {
+        assert!(max > 0);
+        ProcessLimit {
+            max: max,
+            running: Mutex::new(0),
+            cond: Condvar::new(),
+        }
+    }
+
+    fn acquire(&self) {
+        let mut running = self.running.lock().unwrap();
+        while *running >= self.max {
+            running = self.cond.wait(running).unwrap();
+        }
+        *running += 1;
+    }
+
+    fn release(&self) {
+        let mut running = self.running.lock().unwrap();
+        *running -= 1;
+        self.cond.notify_one();
+    }
+}
+
 /// PreprocessorReader provides an `io::Read` impl to read kids output.
 #[derive(Debug)]
 pub struct PreprocessorReader {
@@ -12,6 +145,7 @@ pub struct PreprocessorReader {
     path: PathBuf,
     child: process::Child,
     done: bool,
+    limit: Option<Arc<ProcessLimit>>,
 }
 
 impl PreprocessorReader {
@@ -26,6 +160,16 @@ impl PreprocessorReader {
     pub fn from_cmd_path(
         cmd: PathBuf,
         path: &Path,
+    ) -> Result<PreprocessorReader> {
+        PreprocessorReader::from_cmd_path_limit(cmd, path, None)
+    }
+
+    /// Like `from_cmd_path`, but the spawned command holds a permit from the
+    /// given process limit, if any, until the reader is dropped.
+    fn from_cmd_path_limit(
+        cmd: PathBuf,
+        path: &Path,
+        limit: Option<Arc<ProcessLimit>>,
     ) -> Result<PreprocessorReader> {
         let child = process::Command::new(&cmd)
             .arg(path)
@@ -45,6 +189,7 @@ impl PreprocessorReader {
             path: path.to_path_buf(),
             child: child,
             done: false,
+            limit: limit,
         })
     }
 
@@ -73,6 +218,14 @@ impl PreprocessorReader {
     }
 }
 
+impl Drop for PreprocessorReader {
+    fn drop(&mut self) {
+        if let Some(limit) = self.limit.take() {
+            limit.release();
+        }
+    }
+}
+
 impl io::Read for PreprocessorReader {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         if self.done {
@@ -90,3 +243,40 @@ impl io::Read for PreprocessorReader {
         Ok(nread)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io::Read;
+    use std::path::{Path, PathBuf};
+
+    use super::PreprocessorGlobsBuilder;
+
+    #[test]
+    fn non_matching_files_are_not_preprocessed() {
+        let mut builder = PreprocessorGlobsBuilder::new();
+        builder.associate("*.pdf", PathBuf::from("pdftotext")).unwrap();
+        let globs = builder.build().unwrap();
+        assert!(globs.reader(Path::new("foo.rs")).is_none());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn matching_files_are_preprocessed() {
+        let mut builder = PreprocessorGlobsBuilder::new();
+        builder.associate("*.toml", PathBuf::from("cat")).unwrap();
+        builder.max_procs(1);
+        let globs = builder.build().unwrap();
+
+        // Read twice to exercise permit reuse under the process cap.
+        for _ in 0..2 {
+            let mut rdr = globs.reader(Path::new("Cargo.toml"))
+                .unwrap()
+                .unwrap();
+            let mut contents = String::new();
+            rdr.read_to_string(&mut contents).unwrap();
+            let expected = fs::read_to_string("Cargo.toml").unwrap();
+            assert_eq!(expected, contents);
+        }
+    }
+}
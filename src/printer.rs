@@ -1,14 +1,49 @@
+use std::env;
+use std::mem;
 use std::error;
 use std::fmt;
-use std::path::Path;
+use std::io;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
 use regex::bytes::{Captures, Match, Regex, Replacer};
 use termcolor::{Color, ColorSpec, ParseColorError, WriteColor};
 
+use blame::Blamer;
+use json;
 use pathutil::strip_prefix;
 use ignore::types::FileTypeDef;
 
+/// An extension of `WriteColor` that provides terminal hyperlink primitives.
+///
+/// `termcolor` itself has no notion of hyperlinks, so the OSC 8 escape
+/// sequences are centralized here instead of being hand-written at each call
+/// site. The sequences are only emitted when the underlying writer reports
+/// support for escape sequences; on other writers (such as in-memory buffers
+/// used for testing with colors disabled) these methods are no-ops.
+pub trait WriteHyperlink: WriteColor {
+    /// Starts a hyperlink pointing at the given URL. All text written until
+    /// `end_hyperlink` is called becomes the link's label.
+    fn start_hyperlink(&mut self, url: &str) -> io::Result<()> {
+        if !self.supports_color() {
+            return Ok(());
+        }
+        self.write_all(b"\x1b]8;;")?;
+        self.write_all(url.as_bytes())?;
+        self.write_all(b"\x1b\\")
+    }
+
+    /// Ends a hyperlink previously started with `start_hyperlink`.
+    fn end_hyperlink(&mut self) -> io::Result<()> {
+        if !self.supports_color() {
+            return Ok(());
+        }
+        self.write_all(b"\x1b]8;;\x1b\\")
+    }
+}
+
+impl<W: WriteColor> WriteHyperlink for W {}
+
 /// Track the start and end of replacements to allow coloring them on output.
 #[derive(Debug)]
 struct Offset {
@@ -82,6 +117,17 @@ pub struct Printer<W> {
     ///
     /// N.B. If with_filename is false, then this setting has no effect.
     heading: bool,
+    /// Whether to emit matches and context as JSON Lines messages instead
+    /// of the standard grep-like output. When enabled, most of the other
+    /// formatting options are ignored.
+    json: bool,
+    /// When present, a copy of the JSON Lines messages for everything
+    /// printed is buffered here, independently of the regular output. This
+    /// backs the --json-file flag.
+    json_tee: Option<Vec<u8>>,
+    /// Whether the `begin` message has been written to the secondary JSON
+    /// stream yet.
+    json_tee_began: bool,
     /// Whether to show every match on its own line.
     line_per_match: bool,
     /// Whether to print NUL bytes after a file path instead of new lines
@@ -97,8 +143,23 @@ pub struct Printer<W> {
     colors: ColorSpecs,
     /// The separator to use for file paths. If empty, this is ignored.
     path_separator: Option<u8>,
+    /// The separator to print between the fields (path, line number, column,
+    /// byte offset) of a matching line.
+    field_match_separator: Vec<u8>,
+    /// The separator to print between the fields of a context line.
+    field_context_separator: Vec<u8>,
+    /// A format for terminal hyperlinks wrapped around file paths, with
+    /// {path}, {line} and {column} placeholders. When absent, no hyperlinks
+    /// are emitted.
+    hyperlink_format: Option<String>,
+    /// The working directory used to absolutize relative paths in
+    /// hyperlinks.
+    cwd: Option<PathBuf>,
     /// Restrict lines to this many columns.
     max_columns: Option<usize>,
+    /// When present, annotate each matched line with `git blame`
+    /// information.
+    blame: Option<Blamer>,
 }
 
 impl<W: WriteColor> Printer<W> {
@@ -112,6 +173,9 @@ impl<W: WriteColor> Printer<W> {
             eol: b'\n',
             file_separator: None,
             heading: false,
+            json: false,
+            json_tee: None,
+            json_tee_began: false,
             line_per_match: false,
             null: false,
             only_matching: false,
@@ -119,10 +183,23 @@ impl<W: WriteColor> Printer<W> {
             with_filename: false,
             colors: ColorSpecs::default(),
             path_separator: None,
+            field_match_separator: b":".to_vec(),
+            field_context_separator: b"-".to_vec(),
+            hyperlink_format: None,
+            cwd: env::current_dir().ok(),
             max_columns: None,
+            blame: None,
         }
     }
 
+    /// When enabled, each matched line is annotated with `git blame`
+    /// information (commit, author and age). This has no effect if line
+    /// numbers are disabled.
+    pub fn blame(mut self, yes: bool) -> Printer<W> {
+        self.blame = if yes { Some(Blamer::new()) } else { None };
+        self
+    }
+
     /// Set the color specifications.
     pub fn colors(mut self, colors: ColorSpecs) -> Printer<W> {
         self.colors = colors;
@@ -142,6 +219,29 @@ impl<W: WriteColor> Printer<W> {
         self
     }
 
+    /// Set the separator used between the fields of a matching line. The
+    /// default is `:`.
+    pub fn field_match_separator(mut self, sep: Vec<u8>) -> Printer<W> {
+        self.field_match_separator = sep;
+        self
+    }
+
+    /// Set the separator used between the fields of a context line. The
+    /// default is `-`.
+    pub fn field_context_separator(mut self, sep: Vec<u8>) -> Printer<W> {
+        self.field_context_separator = sep;
+        self
+    }
+
+    /// Set the format used to emit terminal hyperlinks around file paths.
+    /// The format may contain {path}, {line} and {column} placeholders.
+    /// Hyperlinks are disabled by default, and are only emitted when the
+    /// underlying writer supports escape sequences.
+    pub fn hyperlink_format(mut self, format: Option<String>) -> Printer<W> {
+        self.hyperlink_format = format;
+        self
+    }
+
     /// Set the end-of-line terminator. The default is `\n`.
     pub fn eol(mut self, eol: u8) -> Printer<W> {
         self.eol = eol;
@@ -163,6 +263,27 @@ impl<W: WriteColor> Printer<W> {
         self
     }
 
+    /// Whether to emit matches and context as JSON Lines messages instead
+    /// of the standard grep-like output.
+    pub fn json(mut self, yes: bool) -> Printer<W> {
+        self.json = yes;
+        self
+    }
+
+    /// Whether to buffer a secondary copy of the JSON Lines messages for
+    /// everything printed, independently of the regular output. The buffer
+    /// can be drained with `take_json_tee`.
+    pub fn json_tee(mut self, yes: bool) -> Printer<W> {
+        self.json_tee = if yes { Some(vec![]) } else { None };
+        self
+    }
+
+    /// Takes the buffered secondary JSON stream, leaving an empty buffer in
+    /// its place. Returns None if the secondary stream is disabled.
+    pub fn take_json_tee(&mut self) -> Option<Vec<u8>> {
+        self.json_tee.as_mut().map(|buf| mem::replace(buf, vec![]))
+    }
+
     /// Whether to show every match on its own line.
     pub fn line_per_match(mut self, yes: bool) -> Printer<W> {
         self.line_per_match = yes;
@@ -238,20 +359,32 @@ impl<W: WriteColor> Printer<W> {
     /// Prints the given path.
     pub fn path<P: AsRef<Path>>(&mut self, path: P) {
         let path = strip_prefix("./", path.as_ref()).unwrap_or(path.as_ref());
-        self.write_path(path);
+        self.write_hyperlinked_path(path, None, None);
         self.write_path_eol();
     }
 
     /// Prints the given path and a count of the number of matches found.
     pub fn path_count<P: AsRef<Path>>(&mut self, path: P, count: u64) {
         if self.with_filename {
-            self.write_path(path);
-            self.write_path_sep(b':');
+            let sep = self.field_match_separator.clone();
+            self.write_hyperlinked_path(path.as_ref(), None, None);
+            self.write_path_sep(&sep);
         }
         self.write(count.to_string().as_bytes());
         self.write_eol();
     }
 
+    /// Prints a summary line for a binary file containing at least one
+    /// match. This is used in lieu of the matching lines themselves, which
+    /// are never printed for binary files.
+    pub fn binary_file_matches<P: AsRef<Path>>(&mut self, path: P) {
+        let path = strip_prefix("./", path.as_ref()).unwrap_or(path.as_ref());
+        self.write(b"Binary file ");
+        self.write_hyperlinked_path(path, None, None);
+        self.write(b" matches");
+        self.write_eol();
+    }
+
     /// Prints the context separator.
     pub fn context_separate(&mut self) {
         if self.context_separator.is_empty() {
@@ -271,6 +404,13 @@ impl<W: WriteColor> Printer<W> {
         line_number: Option<u64>,
         byte_offset: Option<u64>
     ) {
+        if self.json || self.json_tee.is_some() {
+            self.write_json_match(
+                re, path.as_ref(), buf, start, end, line_number, byte_offset);
+            if self.json {
+                return;
+            }
+        }
         if !self.line_per_match && !self.only_matching {
             let mat =
                 if !self.needs_match() {
@@ -291,6 +431,67 @@ impl<W: WriteColor> Printer<W> {
         }
     }
 
+    /// Emits the `begin` message for the given path if nothing has been
+    /// printed for it yet.
+    fn write_json_begin(&mut self, path: &Path) {
+        if self.has_printed {
+            return;
+        }
+        let msg = json::begin(path);
+        self.write(msg.as_bytes());
+        self.write(b"\n");
+    }
+
+    fn write_json_match(
+        &mut self,
+        re: &Regex,
+        path: &Path,
+        buf: &[u8],
+        start: usize,
+        end: usize,
+        line_number: Option<u64>,
+        byte_offset: Option<u64>,
+    ) {
+        let submatches: Vec<(usize, usize)> = re
+            .find_iter(&buf[start..end])
+            .map(|m| (m.start(), m.end()))
+            .collect();
+        let msg = json::matched(
+            path,
+            &buf[start..end],
+            line_number,
+            byte_offset.map(|o| o + start as u64),
+            &submatches,
+        );
+        self.emit_json(path, &msg);
+    }
+
+    /// Writes a JSON message (preceded by a `begin` message, if necessary)
+    /// to whichever JSON streams are enabled.
+    fn emit_json(&mut self, path: &Path, msg: &str) {
+        if self.json_tee.is_some() {
+            if !self.json_tee_began {
+                self.json_tee_began = true;
+                let begin = json::begin(path);
+                self.tee_json(&begin);
+            }
+            self.tee_json(msg);
+        }
+        if self.json {
+            self.write_json_begin(path);
+            self.write(msg.as_bytes());
+            self.write(b"\n");
+        }
+    }
+
+    /// Appends a JSON message to the secondary JSON stream.
+    fn tee_json(&mut self, msg: &str) {
+        if let Some(ref mut buf) = self.json_tee {
+            buf.extend_from_slice(msg.as_bytes());
+            buf.push(b'\n');
+        }
+    }
+
     fn needs_match(&self) -> bool {
         self.column
         || self.replace.is_some()
@@ -309,26 +510,36 @@ impl<W: WriteColor> Printer<W> {
         match_start: usize,
         match_end: usize,
     ) {
+        let path = path.as_ref();
+        let sep = self.field_match_separator.clone();
         if self.heading && self.with_filename && !self.has_printed {
             self.write_file_sep();
-            self.write_path(path);
+            self.write_hyperlinked_path(path, line_number, None);
             self.write_path_eol();
         } else if !self.heading && self.with_filename {
-            self.write_path(path);
-            self.write_path_sep(b':');
+            let column = Some(match_start as u64 + 1);
+            self.write_hyperlinked_path(path, line_number, column);
+            self.write_path_sep(&sep);
         }
         if let Some(line_number) = line_number {
-            self.line_number(line_number, b':');
+            self.line_number(line_number, &sep);
+            let annotation = self.blame.as_mut().and_then(|blamer| {
+                blamer.blame(path, line_number).map(|info| info.annotation())
+            });
+            if let Some(annotation) = annotation {
+                self.write(annotation.as_bytes());
+                self.separator(&sep);
+            }
         }
         if self.column {
-            self.column_number(match_start as u64 + 1, b':');
+            self.column_number(match_start as u64 + 1, &sep);
         }
         if let Some(byte_offset) = byte_offset {
             if self.only_matching {
                 self.write_byte_offset(
-                    byte_offset + ((start + match_start) as u64), b':');
+                    byte_offset + ((start + match_start) as u64), &sep);
             } else {
-                self.write_byte_offset(byte_offset + (start as u64), b':');
+                self.write_byte_offset(byte_offset + (start as u64), &sep);
             }
         }
         if self.replace.is_some() {
@@ -407,19 +618,35 @@ impl<W: WriteColor> Printer<W> {
         line_number: Option<u64>,
         byte_offset: Option<u64>,
     ) {
+        if self.json || self.json_tee.is_some() {
+            let path = path.as_ref();
+            let msg = json::context(
+                path,
+                &buf[start..end],
+                line_number,
+                byte_offset.map(|o| o + start as u64),
+            );
+            self.emit_json(path, &msg);
+            if self.json {
+                return;
+            }
+        }
         if self.heading && self.with_filename && !self.has_printed {
             self.write_file_sep();
-            self.write_path(path);
+            self.write_hyperlinked_path(path.as_ref(), line_number, None);
             self.write_path_eol();
         } else if !self.heading && self.with_filename {
-            self.write_path(path);
-            self.write_path_sep(b'-');
+            let sep = self.field_context_separator.clone();
+            self.write_hyperlinked_path(path.as_ref(), line_number, None);
+            self.write_path_sep(&sep);
         }
         if let Some(line_number) = line_number {
-            self.line_number(line_number, b'-');
+            let sep = self.field_context_separator.clone();
+            self.line_number(line_number, &sep);
         }
         if let Some(byte_offset) = byte_offset {
-            self.write_byte_offset(byte_offset + (start as u64), b'-');
+            let sep = self.field_context_separator.clone();
+            self.write_byte_offset(byte_offset + (start as u64), &sep);
         }
         if self.max_columns.map_or(false, |m| end - start > m) {
             self.write(b"[Omitted long context line]");
@@ -436,11 +663,61 @@ impl<W: WriteColor> Printer<W> {
         self.write(sep);
     }
 
-    fn write_path_sep(&mut self, sep: u8) {
+    /// Writes the given path, wrapped in a terminal hyperlink (OSC 8) when a
+    /// hyperlink format is configured and the underlying writer supports
+    /// escape sequences.
+    fn write_hyperlinked_path(
+        &mut self,
+        path: &Path,
+        line_number: Option<u64>,
+        column: Option<u64>,
+    ) {
+        match self.hyperlink_url(path, line_number, column) {
+            None => self.write_path(path),
+            Some(url) => {
+                let _ = self.wtr.start_hyperlink(&url);
+                self.write_path(path);
+                let _ = self.wtr.end_hyperlink();
+            }
+        }
+    }
+
+    /// Returns the hyperlink URL for the given path, or `None` if hyperlinks
+    /// are disabled or unsupported. Missing line and column numbers default
+    /// to 1.
+    fn hyperlink_url(
+        &self,
+        path: &Path,
+        line_number: Option<u64>,
+        column: Option<u64>,
+    ) -> Option<String> {
+        let format = match self.hyperlink_format {
+            None => return None,
+            Some(ref format) => format,
+        };
+        if !self.wtr.supports_color() {
+            return None;
+        }
+        let path =
+            if path.is_absolute() {
+                path.to_path_buf()
+            } else {
+                match self.cwd {
+                    None => return None,
+                    Some(ref cwd) => cwd.join(path),
+                }
+            };
+        Some(format
+            .replace("{path}", &path.to_string_lossy())
+            .replace("{line}", &line_number.unwrap_or(1).to_string())
+            .replace("{column}", &column.unwrap_or(1).to_string()))
+    }
+
+    fn write_path_sep(&mut self, sep: &[u8]) {
         if self.null {
             self.write(b"\x00");
         } else {
-            self.separator(&[sep]);
+            self.separator(sep);
         }
     }
 
@@ -481,20 +758,20 @@ impl<W: WriteColor> Printer<W> {
         }
     }
 
-    fn line_number(&mut self, n: u64, sep: u8) {
+    fn line_number(&mut self, n: u64, sep: &[u8]) {
         let line_number = n.to_string();
         self.write_colored(line_number.as_bytes(), |colors| colors.line());
-        self.separator(&[sep]);
+        self.separator(sep);
     }
 
-    fn column_number(&mut self, n: u64, sep: u8) {
+    fn column_number(&mut self, n: u64, sep: &[u8]) {
         self.write_colored(n.to_string().as_bytes(), |colors| colors.column());
-        self.separator(&[sep]);
+        self.separator(sep);
     }
 
-    fn write_byte_offset(&mut self, o: u64, sep: u8) {
+    fn write_byte_offset(&mut self, o: u64, sep: &[u8]) {
         self.write_colored(o.to_string().as_bytes(), |colors| colors.column());
-        self.separator(&[sep]);
+        self.separator(sep);
     }
 
     fn write(&mut self, buf: &[u8]) {
@@ -777,20 +1054,148 @@ impl FromStr for Spec {
                 if pieces.len() < 3 {
                     return Err(Error::InvalidFormat(s.to_string()));
                 }
-                let color: Color = pieces[2].parse()?;
+                let color = parse_color(pieces[2])?;
                 Ok(Spec { ty: otype, value: SpecValue::Fg(color) })
             }
             SpecType::Bg => {
                 if pieces.len() < 3 {
                     return Err(Error::InvalidFormat(s.to_string()));
                 }
-                let color: Color = pieces[2].parse()?;
+                let color = parse_color(pieces[2])?;
                 Ok(Spec { ty: otype, value: SpecValue::Bg(color) })
             }
         }
     }
 }
 
+/// Parses a color value. In addition to the color names and numeric forms
+/// understood by termcolor (including `r,g,b` triples), this accepts
+/// `#rrggbb` hex specs for 24-bit truecolor. Terminals that don't support
+/// truecolor are handled by termcolor itself, which degrades to the nearest
+/// console color where necessary.
+fn parse_color(s: &str) -> Result<Color, Error> {
+    if !s.starts_with("#") {
+        return s.parse().map_err(From::from);
+    }
+    let hex = &s[1..];
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_digit(16)) {
+        return Err(Error::UnrecognizedColor(
+            s.to_string(),
+            format!("Unrecognized color value '{}'. Hex color specs \
+                     must have the form #rrggbb.", s),
+        ));
+    }
+    let r = u8::from_str_radix(&hex[0..2], 16).unwrap();
+    let g = u8::from_str_radix(&hex[2..4], 16).unwrap();
+    let b = u8::from_str_radix(&hex[4..6], 16).unwrap();
+    Ok(Color::Rgb(r, g, b))
+}
+
+/// Parses a color configuration in GNU grep's `GREP_COLORS` format into a
+/// sequence of `Spec`s, e.g., `ms=01;31:fn=35:ln=32`.
+///
+/// Each field is a capability name followed by `=` and a `;` separated list
+/// of SGR parameters. The capabilities map onto ripgrep's output regions as
+/// follows: `mt`, `ms` and `mc` color matches, `fn` colors file paths, `ln`
+/// colors line numbers and `bn` colors column numbers. Supported SGR
+/// parameters are `0` (reset), `1`/`22` (bold on/off), `4`/`24` (underline
+/// on/off), `30-37`/`40-47` (foreground/background colors), `90-97`/
+/// `100-107` (their bright variants), and the extended `38;5;n`, `48;5;n`,
+/// `38;2;r;g;b` and `48;2;r;g;b` forms.
+///
+/// As in GNU grep, each capability's parameter list describes the complete
+/// style for that region, so any previously configured settings for the
+/// region are cleared first. Unrecognized capabilities and parameters are
+/// ignored rather than reported as errors, so that a `GREP_COLORS` value
+/// written for grep works unmodified.
+pub fn parse_grep_colors(config: &str) -> Vec<Spec> {
+    let mut specs = vec![];
+    for field in config.split(':') {
+        let mut pieces = field.splitn(2, '=');
+        let cap = pieces.next().unwrap();
+        let params = match pieces.next() {
+            None => continue,
+            Some(params) => params,
+        };
+        let ty = match cap {
+            "mt" | "ms" | "mc" => OutType::Match,
+            "fn" => OutType::Path,
+            "ln" => OutType::Line,
+            "bn" => OutType::Column,
+            _ => continue,
+        };
+        specs.push(Spec { ty: ty.clone(), value: SpecValue::None });
+        for value in parse_sgr_params(params) {
+            specs.push(Spec { ty: ty.clone(), value: value });
+        }
+    }
+    specs
+}
+
+/// Parses a `;` separated list of SGR parameters into spec values,
+/// silently skipping anything unsupported.
+fn parse_sgr_params(params: &str) -> Vec<SpecValue> {
+    let mut values = vec![];
+    let mut it = params.split(';').map(|p| p.parse::<u8>());
+    while let Some(param) = it.next() {
+        let param = match param {
+            Ok(param) => param,
+            Err(_) => continue,
+        };
+        match param {
+            0 => values.push(SpecValue::None),
+            1 => values.push(SpecValue::Style(Style::Bold)),
+            22 => values.push(SpecValue::Style(Style::NoBold)),
+            4 => values.push(SpecValue::Style(Style::Underline)),
+            24 => values.push(SpecValue::Style(Style::NoUnderline)),
+            30...37 => values.push(SpecValue::Fg(ansi_color(param - 30))),
+            40...47 => values.push(SpecValue::Bg(ansi_color(param - 40))),
+            90...97 => {
+                values.push(SpecValue::Fg(ansi_color(param - 90)));
+                values.push(SpecValue::Style(Style::Intense));
+            }
+            100...107 => {
+                values.push(SpecValue::Bg(ansi_color(param - 100)));
+                values.push(SpecValue::Style(Style::Intense));
+            }
+            38 | 48 => {
+                let color = match (it.next(), it.next()) {
+                    (Some(Ok(5)), Some(Ok(n))) => Color::Ansi256(n),
+                    (Some(Ok(2)), Some(Ok(r))) => {
+                        match (it.next(), it.next()) {
+                            (Some(Ok(g)), Some(Ok(b))) => Color::Rgb(r, g, b),
+                            _ => continue,
+                        }
+                    }
+                    _ => continue,
+                };
+                if param == 38 {
+                    values.push(SpecValue::Fg(color));
+                } else {
+                    values.push(SpecValue::Bg(color));
+                }
+            }
+            _ => continue,
+        }
+    }
+    values
+}
+
+/// Returns the console color for an ANSI color number in `0...7`.
+fn ansi_color(n: u8) -> Color {
+    match n {
+        0 => Color::Black,
+        1 => Color::Red,
+        2 => Color::Green,
+        3 => Color::Yellow,
+        4 => Color::Blue,
+        5 => Color::Magenta,
+        6 => Color::Cyan,
+        7 => Color::White,
+        _ => unreachable!(),
+    }
+}
+
 impl FromStr for OutType {
     type Err = Error;
 
@@ -837,8 +1242,92 @@ impl FromStr for Style {
 
 #[cfg(test)]
 mod tests {
-    use termcolor::{Color, ColorSpec};
-    use super::{ColorSpecs, Error, OutType, Spec, SpecValue, Style};
+    use termcolor::{Ansi, Color, ColorSpec, NoColor};
+    use super::{
+        ColorSpecs, Error, OutType, Spec, SpecValue, Style, WriteHyperlink,
+        parse_grep_colors,
+    };
+
+    #[test]
+    fn hyperlink_escapes() {
+        let mut wtr = Ansi::new(vec![]);
+        wtr.start_hyperlink("file:///tmp/foo").unwrap();
+        wtr.end_hyperlink().unwrap();
+        assert_eq!(
+            wtr.into_inner(),
+            b"\x1b]8;;file:///tmp/foo\x1b\\\x1b]8;;\x1b\\".to_vec());
+    }
+
+    #[test]
+    fn hyperlink_noop_without_escape_support() {
+        let mut wtr = NoColor::new(vec![]);
+        wtr.start_hyperlink("file:///tmp/foo").unwrap();
+        wtr.end_hyperlink().unwrap();
+        assert!(wtr.into_inner().is_empty());
+    }
+
+    #[test]
+    fn grep_colors() {
+        let specs = parse_grep_colors("ms=01;31:fn=35:ln=32:bn=33");
+        assert_eq!(specs, vec![
+            Spec { ty: OutType::Match, value: SpecValue::None },
+            Spec {
+                ty: OutType::Match,
+                value: SpecValue::Style(Style::Bold),
+            },
+            Spec { ty: OutType::Match, value: SpecValue::Fg(Color::Red) },
+            Spec { ty: OutType::Path, value: SpecValue::None },
+            Spec { ty: OutType::Path, value: SpecValue::Fg(Color::Magenta) },
+            Spec { ty: OutType::Line, value: SpecValue::None },
+            Spec { ty: OutType::Line, value: SpecValue::Fg(Color::Green) },
+            Spec { ty: OutType::Column, value: SpecValue::None },
+            Spec {
+                ty: OutType::Column,
+                value: SpecValue::Fg(Color::Yellow),
+            },
+        ]);
+    }
+
+    #[test]
+    fn grep_colors_extended() {
+        let specs = parse_grep_colors("ms=38;5;123:mc=48;2;0;128;255");
+        assert_eq!(specs, vec![
+            Spec { ty: OutType::Match, value: SpecValue::None },
+            Spec {
+                ty: OutType::Match,
+                value: SpecValue::Fg(Color::Ansi256(123)),
+            },
+            Spec { ty: OutType::Match, value: SpecValue::None },
+            Spec {
+                ty: OutType::Match,
+                value: SpecValue::Bg(Color::Rgb(0, 128, 255)),
+            },
+        ]);
+    }
+
+    #[test]
+    fn grep_colors_bright() {
+        let specs = parse_grep_colors("fn=97");
+        assert_eq!(specs, vec![
+            Spec { ty: OutType::Path, value: SpecValue::None },
+            Spec { ty: OutType::Path, value: SpecValue::Fg(Color::White) },
+            Spec {
+                ty: OutType::Path,
+                value: SpecValue::Style(Style::Intense),
+            },
+        ]);
+    }
+
+    #[test]
+    fn grep_colors_lenient() {
+        // Unrecognized capabilities and parameters are skipped, not errors.
+        assert_eq!(parse_grep_colors("se=36:rv:cx=junk"), vec![]);
+        assert_eq!(parse_grep_colors(""), vec![]);
+        // A truncated extended sequence leaves only the leading reset.
+        assert_eq!(parse_grep_colors("ms=38;5"), vec![
+            Spec { ty: OutType::Match, value: SpecValue::None },
+        ]);
+    }
 
     #[test]
     fn merge() {
@@ -895,6 +1384,12 @@ mod tests {
             value: SpecValue::None,
         });
 
+        let spec: Spec = "match:bg:#0080ff".parse().unwrap();
+        assert_eq!(spec, Spec {
+            ty: OutType::Match,
+            value: SpecValue::Bg(Color::Rgb(0, 128, 255)),
+        });
+
         let spec: Spec = "column:bg:green".parse().unwrap();
         assert_eq!(spec, Spec {
             ty: OutType::Column,
@@ -924,5 +1419,17 @@ mod tests {
 
         let err = "foo:fg:brown".parse::<Spec>().unwrap_err();
         assert_eq!(err, Error::UnrecognizedOutType("foo".to_string()));
+
+        let err = "match:fg:#12345".parse::<Spec>().unwrap_err();
+        match err {
+            Error::UnrecognizedColor(name, _) => assert_eq!(name, "#12345"),
+            err => assert!(false, "unexpected error: {:?}", err),
+        }
+
+        let err = "match:fg:#gg0000".parse::<Spec>().unwrap_err();
+        match err {
+            Error::UnrecognizedColor(name, _) => assert_eq!(name, "#gg0000"),
+            err => assert!(false, "unexpected error: {:?}", err),
+        }
     }
 }
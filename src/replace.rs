@@ -0,0 +1,212 @@
+/*!
+The replace module implements the file rewriting behind the --in-place flag.
+The replacement text is applied to every matching line of a file and the new
+contents are written to a temporary file in the same directory, which then
+atomically renames over the original.
+*/
+
+use std::ffi::{OsStr, OsString};
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use memchr::memchr;
+use regex::bytes::Regex;
+
+/// A single line changed by a replacement, used for --dry-run previews.
+struct ChangedLine {
+    /// The 1-based line number of the changed line.
+    line_number: u64,
+    /// The line before the replacement, without its terminator.
+    old: Vec<u8>,
+    /// The line after the replacement, without its terminator.
+    new: Vec<u8>,
+}
+
+/// Applies the replacement to every matching line of the file at `path` and
+/// writes the result back atomically, preserving the original file's
+/// permissions. If `backup_suffix` is given, a copy of the original contents
+/// is kept at the original path with the suffix appended.
+///
+/// Returns the number of lines changed. Files that look binary (i.e.,
+/// contain a NUL byte) are skipped and report zero changed lines, as do
+/// files whose replaced contents are identical to the original.
+pub fn rewrite(
+    re: &Regex,
+    replacement: &[u8],
+    path: &Path,
+    backup_suffix: Option<&OsStr>,
+) -> io::Result<u64> {
+    let contents = match read_contents(path)? {
+        None => return Ok(0),
+        Some(contents) => contents,
+    };
+    let (new_contents, changed) = replace_lines(re, replacement, &contents);
+    if changed.is_empty() {
+        return Ok(0);
+    }
+    let tmp = tmp_path(path);
+    if let Err(err) = write_tmp(&tmp, &new_contents, path) {
+        let _ = fs::remove_file(&tmp);
+        return Err(err);
+    }
+    if let Some(suffix) = backup_suffix {
+        let mut backup = path.as_os_str().to_os_string();
+        backup.push(suffix);
+        if let Err(err) = fs::copy(path, &backup) {
+            let _ = fs::remove_file(&tmp);
+            return Err(err);
+        }
+    }
+    if let Err(err) = fs::rename(&tmp, path) {
+        let _ = fs::remove_file(&tmp);
+        return Err(err);
+    }
+    Ok(changed.len() as u64)
+}
+
+/// Writes a preview of the changes that `rewrite` would make to the file at
+/// `path`, without writing anything back. Each changed line is printed
+/// twice, prefixed with the path and line number: once with '-' for the old
+/// text and once with '+' for the new text.
+///
+/// Returns the number of lines that would change.
+pub fn preview<W: io::Write>(
+    wtr: &mut W,
+    re: &Regex,
+    replacement: &[u8],
+    path: &Path,
+) -> io::Result<u64> {
+    let contents = match read_contents(path)? {
+        None => return Ok(0),
+        Some(contents) => contents,
+    };
+    let (_, changed) = replace_lines(re, replacement, &contents);
+    for line in &changed {
+        write!(wtr, "{}:{}:-", path.display(), line.line_number)?;
+        wtr.write_all(&line.old)?;
+        wtr.write_all(b"\n")?;
+        write!(wtr, "{}:{}:+", path.display(), line.line_number)?;
+        wtr.write_all(&line.new)?;
+        wtr.write_all(b"\n")?;
+    }
+    Ok(changed.len() as u64)
+}
+
+/// Reads the entire file at `path`, returning `None` if it looks binary.
+fn read_contents(path: &Path) -> io::Result<Option<Vec<u8>>> {
+    let mut contents = vec![];
+    File::open(path)?.read_to_end(&mut contents)?;
+    if memchr(0, &contents).is_some() {
+        return Ok(None);
+    }
+    Ok(Some(contents))
+}
+
+/// Applies the replacement to every matching line of `contents`, returning
+/// the new contents along with a record of each changed line. Line
+/// terminators are never part of a match and are carried over unchanged.
+fn replace_lines(
+    re: &Regex,
+    replacement: &[u8],
+    contents: &[u8],
+) -> (Vec<u8>, Vec<ChangedLine>) {
+    let mut new_contents = Vec::with_capacity(contents.len());
+    let mut changed = vec![];
+    let mut line_number = 0;
+    let mut start = 0;
+    while start < contents.len() {
+        line_number += 1;
+        let end = match memchr(b'\n', &contents[start..]) {
+            Some(i) => start + i + 1,
+            None => contents.len(),
+        };
+        let (line, terminator) = split_terminator(&contents[start..end]);
+        let new_line = re.replace_all(line, replacement);
+        if *new_line != *line {
+            changed.push(ChangedLine {
+                line_number: line_number,
+                old: line.to_vec(),
+                new: new_line.to_vec(),
+            });
+        }
+        new_contents.extend_from_slice(&new_line);
+        new_contents.extend_from_slice(terminator);
+        start = end;
+    }
+    (new_contents, changed)
+}
+
+/// Splits a line into its contents and its terminator. The terminator is
+/// `\n`, `\r\n` or empty for a final line without a trailing terminator.
+fn split_terminator(line: &[u8]) -> (&[u8], &[u8]) {
+    if line.ends_with(b"\r\n") {
+        line.split_at(line.len() - 2)
+    } else if line.ends_with(b"\n") {
+        line.split_at(line.len() - 1)
+    } else {
+        (line, b"")
+    }
+}
+
+/// Returns the path of the temporary file used to rewrite `path`, placed in
+/// the same directory so that the final rename stays on one file system.
+fn tmp_path(path: &Path) -> PathBuf {
+    let mut name = OsString::from(".");
+    name.push(path.file_name().unwrap_or_else(|| OsStr::new("rg")));
+    name.push(".rg.tmp");
+    path.with_file_name(name)
+}
+
+/// Writes `contents` to `tmp`, copying the permissions of `original`.
+fn write_tmp(tmp: &Path, contents: &[u8], original: &Path) -> io::Result<()> {
+    let perm = fs::metadata(original)?.permissions();
+    let mut file = File::create(tmp)?;
+    file.write_all(contents)?;
+    file.flush()?;
+    fs::set_permissions(tmp, perm)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use regex::bytes::Regex;
+
+    use super::{replace_lines, split_terminator};
+
+    #[test]
+    fn replaces_matching_lines() {
+        let re = Regex::new("foo").unwrap();
+        let (new, changed) = replace_lines(
+            &re, b"bar", b"foo one\nplain\nfoo two\n");
+        assert_eq!(new, b"bar one\nplain\nbar two\n".to_vec());
+        assert_eq!(changed.len(), 2);
+        assert_eq!(changed[0].line_number, 1);
+        assert_eq!(changed[0].old, b"foo one".to_vec());
+        assert_eq!(changed[0].new, b"bar one".to_vec());
+        assert_eq!(changed[1].line_number, 3);
+    }
+
+    #[test]
+    fn preserves_terminators() {
+        let re = Regex::new("a").unwrap();
+        let (new, changed) = replace_lines(&re, b"b", b"a\r\na\na");
+        assert_eq!(new, b"b\r\nb\nb".to_vec());
+        assert_eq!(changed.len(), 3);
+    }
+
+    #[test]
+    fn identical_replacement_is_not_a_change() {
+        let re = Regex::new("foo").unwrap();
+        let (new, changed) = replace_lines(&re, b"foo", b"foo\n");
+        assert_eq!(new, b"foo\n".to_vec());
+        assert!(changed.is_empty());
+    }
+
+    #[test]
+    fn terminator_split() {
+        assert_eq!(split_terminator(b"ab\r\n"), (&b"ab"[..], &b"\r\n"[..]));
+        assert_eq!(split_terminator(b"ab\n"), (&b"ab"[..], &b"\n"[..]));
+        assert_eq!(split_terminator(b"ab"), (&b"ab"[..], &b""[..]));
+    }
+}
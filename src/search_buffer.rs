@@ -8,12 +8,14 @@ does. Notably, showing contexts.
 */
 use std::cmp;
 use std::path::Path;
+use std::sync::Arc;
 
 use grep::Grep;
 use termcolor::WriteColor;
 
 use printer::Printer;
 use search_stream::{IterLines, Options, count_lines, is_binary};
+use stats::Stats;
 
 pub struct BufferSearcher<'a, W: 'a> {
     opts: Options,
@@ -26,6 +28,7 @@ pub struct BufferSearcher<'a, W: 'a> {
     line_count: Option<u64>,
     byte_offset: Option<u64>,
     last_line: usize,
+    binary_suppress: bool,
 }
 
 impl<'a, W: WriteColor> BufferSearcher<'a, W> {
@@ -46,9 +49,28 @@ impl<'a, W: WriteColor> BufferSearcher<'a, W> {
             line_count: None,
             byte_offset: None,
             last_line: 0,
+            binary_suppress: false,
         }
     }
 
+    /// Additional patterns that a matching line (or, with
+    /// `files_with_all_matches`, the file as a whole) must also match.
+    pub fn and(mut self, and: Vec<Grep>) -> Self {
+        self.opts.and = and;
+        self
+    }
+
+    /// If enabled, binary files are searched instead of skipped, but the
+    /// matching lines themselves are never printed. Instead, a single
+    /// summary line is printed for any binary file that contains at least
+    /// one match.
+    ///
+    /// Disabled by default.
+    pub fn binary(mut self, yes: bool) -> Self {
+        self.opts.binary = yes;
+        self
+    }
+
     /// If enabled, searching will print a 0-based offset of the
     /// matching line (or the actual match if -o is specified) before
     /// printing the line itself.
@@ -84,6 +106,14 @@ impl<'a, W: WriteColor> BufferSearcher<'a, W> {
         self
     }
 
+    /// If enabled, searching will print the path of any file where the
+    /// primary pattern and every `and` pattern match, each somewhere in the
+    /// file, instead of searched lines.
+    pub fn files_with_all_matches(mut self, yes: bool) -> Self {
+        self.opts.files_with_all_matches = yes;
+        self
+    }
+
     /// If enabled, searching will print the path of files that *don't* match
     /// the given pattern.
     ///
@@ -93,6 +123,23 @@ impl<'a, W: WriteColor> BufferSearcher<'a, W> {
         self
     }
 
+    /// A regex that covers the primary pattern and every `and` pattern,
+    /// used instead of the primary pattern when printing matched lines so
+    /// that each contributing match is highlighted.
+    pub fn highlight(mut self, highlight: Option<Grep>) -> Self {
+        self.opts.highlight = highlight;
+        self
+    }
+
+    /// If enabled, counting prints a count of zero for searched files
+    /// without any matches.
+    ///
+    /// Disabled by default.
+    pub fn include_zero(mut self, yes: bool) -> Self {
+        self.opts.include_zero = yes;
+        self
+    }
+
     /// Set the end-of-line byte used by this searcher.
     pub fn eol(mut self, eol: u8) -> Self {
         self.opts.eol = eol;
@@ -128,6 +175,13 @@ impl<'a, W: WriteColor> BufferSearcher<'a, W> {
         self
     }
 
+    /// If given, aggregate statistics (e.g., the total number of individual
+    /// matches and bytes searched) are recorded for this search.
+    pub fn stats(mut self, stats: Option<Arc<Stats>>) -> Self {
+        self.opts.stats = stats;
+        self
+    }
+
     /// If enabled, search binary files as if they were text.
     pub fn text(mut self, yes: bool) -> Self {
         self.opts.text = yes;
@@ -138,7 +192,19 @@ impl<'a, W: WriteColor> BufferSearcher<'a, W> {
     pub fn run(mut self) -> u64 {
         let binary_upto = cmp::min(10_240, self.buf.len());
         if !self.opts.text && is_binary(&self.buf[..binary_upto], true) {
-            return 0;
+            if !self.opts.binary {
+                return 0;
+            }
+            self.binary_suppress = true;
+        }
+
+        if self.opts.files_with_all_matches {
+            // The --and patterns constrain the file as a whole, so settle
+            // them upfront against the full buffer.
+            let all = self.opts.and.iter().all(|g| g.regex().is_match(self.buf));
+            if !all {
+                return 0;
+            }
         }
 
         self.match_line_count = 0;
@@ -146,12 +212,17 @@ impl<'a, W: WriteColor> BufferSearcher<'a, W> {
         // The memory map searcher uses one contiguous block of bytes, so the
         // offsets given the printer are sufficient to compute the byte offset.
         self.byte_offset = if self.opts.byte_offset { Some(0) } else { None };
-        self.match_count = if self.opts.count_matches { Some(0) } else { None };
+        self.match_count =
+            if self.opts.count_matches || self.opts.stats.is_some() {
+                Some(0)
+            } else {
+                None
+            };
         let mut last_end = 0;
         for m in self.grep.iter(self.buf) {
             if self.opts.invert_match {
                 self.print_inverted_matches(last_end, m.start());
-            } else {
+            } else if self.opts.and_line_match(&self.buf[m.start()..m.end()]) {
                 self.print_match(m.start(), m.end());
             }
             last_end = m.end();
@@ -169,13 +240,31 @@ impl<'a, W: WriteColor> BufferSearcher<'a, W> {
             && self.match_count.map_or(false, |c| c > 0)
         {
             self.printer.path_count(self.path, self.match_count.unwrap());
+        } else if self.opts.include_zero
+            && (self.opts.count || self.opts.count_matches)
+            && self.match_line_count == 0
+        {
+            self.printer.path_count(self.path, 0);
         }
         if self.opts.files_with_matches && self.match_line_count > 0 {
             self.printer.path(self.path);
         }
+        if self.opts.files_with_all_matches && self.match_line_count > 0 {
+            self.printer.path(self.path);
+        }
         if self.opts.files_without_matches && self.match_line_count == 0 {
             self.printer.path(self.path);
         }
+        if self.binary_suppress
+            && !self.opts.skip_matches()
+            && self.match_line_count > 0
+        {
+            self.printer.binary_file_matches(self.path);
+        }
+        if let Some(ref stats) = self.opts.stats {
+            stats.add_matches(self.match_count.unwrap_or(0));
+            stats.add_bytes_searched(self.buf.len() as u64);
+        }
         self.match_line_count
     }
 
@@ -192,13 +281,17 @@ impl<'a, W: WriteColor> BufferSearcher<'a, W> {
     pub fn print_match(&mut self, start: usize, end: usize) {
         self.match_line_count += 1;
         self.count_individual_matches(start, end);
-        if self.opts.skip_matches() {
+        if self.opts.skip_matches() || self.binary_suppress {
             return;
         }
         self.count_lines(start);
         self.add_line(end);
+        let re = match self.opts.highlight {
+            None => self.grep.regex(),
+            Some(ref grep) => grep.regex(),
+        };
         self.printer.matched(
-            self.grep.regex(), self.path, self.buf,
+            re, self.path, self.buf,
             start, end, self.line_count, self.byte_offset);
     }
 
@@ -210,7 +303,9 @@ impl<'a, W: WriteColor> BufferSearcher<'a, W> {
             if self.opts.terminate(self.match_line_count) {
                 return;
             }
-            self.print_match(s, e);
+            if self.opts.and_line_match(&self.buf[s..e]) {
+                self.print_match(s, e);
+            }
         }
     }
 
@@ -9,6 +9,7 @@ use std::error::Error as StdError;
 use std::fmt;
 use std::io;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 use bytecount;
 use grep::{Grep, Match};
@@ -16,6 +17,7 @@ use memchr::{memchr, memrchr};
 use termcolor::WriteColor;
 
 use printer::Printer;
+use stats::Stats;
 
 /// The default read size (capacity of input buffer).
 const READ_SIZE: usize = 8 * (1<<10);
@@ -75,23 +77,32 @@ pub struct Searcher<'a, R, W: 'a> {
     last_printed: usize,
     last_line: usize,
     after_context_remaining: usize,
+    printed_gap: bool,
+    and_seen: Vec<bool>,
 }
 
 /// Options for configuring search.
 #[derive(Clone)]
 pub struct Options {
     pub after_context: usize,
+    pub and: Vec<Grep>,
     pub before_context: usize,
+    pub binary: bool,
+    pub context_block: bool,
     pub byte_offset: bool,
     pub count: bool,
     pub count_matches: bool,
+    pub files_with_all_matches: bool,
     pub files_with_matches: bool,
     pub files_without_matches: bool,
+    pub highlight: Option<Grep>,
+    pub include_zero: bool,
     pub eol: u8,
     pub invert_match: bool,
     pub line_number: bool,
     pub max_count: Option<u64>,
     pub quiet: bool,
+    pub stats: Option<Arc<Stats>>,
     pub text: bool,
 }
 
@@ -99,17 +110,24 @@ impl Default for Options {
     fn default() -> Options {
         Options {
             after_context: 0,
+            and: vec![],
             before_context: 0,
+            binary: false,
+            context_block: false,
             byte_offset: false,
             count: false,
             count_matches: false,
+            files_with_all_matches: false,
             files_with_matches: false,
             files_without_matches: false,
+            highlight: None,
+            include_zero: false,
             eol: b'\n',
             invert_match: false,
             line_number: false,
             max_count: None,
             quiet: false,
+            stats: None,
             text: false,
         }
     }
@@ -118,10 +136,19 @@ impl Default for Options {
 
 impl Options {
     /// Several options (--quiet, --count, --count-matches, --files-with-matches,
-    /// --files-without-match) imply that we shouldn't ever display matches.
+    /// --files-with-all-matches, --files-without-match) imply that we
+    /// shouldn't ever display matches.
     pub fn skip_matches(&self) -> bool {
         self.count || self.files_with_matches || self.files_without_matches
-        || self.quiet || self.count_matches
+        || self.files_with_all_matches || self.quiet || self.count_matches
+    }
+
+    /// Returns true if the given matching line satisfies every --and
+    /// pattern. With --files-with-all-matches, the --and patterns constrain
+    /// the file as a whole instead, so every line passes.
+    pub fn and_line_match(&self, line: &[u8]) -> bool {
+        self.files_with_all_matches
+            || self.and.iter().all(|g| g.regex().is_match(line))
     }
 
     /// Some options (--quiet, --files-with-matches, --files-without-match)
@@ -177,6 +204,8 @@ impl<'a, R: io::Read, W: WriteColor> Searcher<'a, R, W> {
             last_printed: 0,
             last_line: 0,
             after_context_remaining: 0,
+            printed_gap: false,
+            and_seen: vec![],
         }
     }
 
@@ -187,6 +216,13 @@ impl<'a, R: io::Read, W: WriteColor> Searcher<'a, R, W> {
         self
     }
 
+    /// Additional patterns that a matching line (or, with
+    /// `files_with_all_matches`, the file as a whole) must also match.
+    pub fn and(mut self, and: Vec<Grep>) -> Self {
+        self.opts.and = and;
+        self
+    }
+
     /// The number of contextual lines to show before each match. The default
     /// is zero.
     pub fn before_context(mut self, count: usize) -> Self {
@@ -194,6 +230,27 @@ impl<'a, R: io::Read, W: WriteColor> Searcher<'a, R, W> {
         self
     }
 
+    /// If enabled, show context around each match up to the nearest blank
+    /// lines before and after it, instead of a fixed number of lines.
+    ///
+    /// Disabled by default.
+    pub fn context_block(mut self, yes: bool) -> Self {
+        self.opts.context_block = yes;
+        self
+    }
+
+    /// If enabled, binary files are searched instead of skipped, but the
+    /// matching lines themselves are never printed. Instead, a single
+    /// summary line is printed for any binary file that contains at least
+    /// one match.
+    ///
+    /// Disabled by default.
+    pub fn binary(mut self, yes: bool) -> Self {
+        self.opts.binary = yes;
+        self.inp.binary(yes);
+        self
+    }
+
     /// If enabled, searching will print a 0-based offset of the
     /// matching line (or the actual match if -o is specified) before
     /// printing the line itself.
@@ -229,6 +286,14 @@ impl<'a, R: io::Read, W: WriteColor> Searcher<'a, R, W> {
         self
     }
 
+    /// If enabled, searching will print the path of any file where the
+    /// primary pattern and every `and` pattern match, each somewhere in the
+    /// file, instead of searched lines.
+    pub fn files_with_all_matches(mut self, yes: bool) -> Self {
+        self.opts.files_with_all_matches = yes;
+        self
+    }
+
     /// If enabled, searching will print the path of files without any matches.
     ///
     /// Disabled by default.
@@ -237,6 +302,23 @@ impl<'a, R: io::Read, W: WriteColor> Searcher<'a, R, W> {
         self
     }
 
+    /// If enabled, counting prints a count of zero for searched files
+    /// without any matches.
+    ///
+    /// Disabled by default.
+    pub fn include_zero(mut self, yes: bool) -> Self {
+        self.opts.include_zero = yes;
+        self
+    }
+
+    /// A regex that covers the primary pattern and every `and` pattern,
+    /// used instead of the primary pattern when printing matched lines so
+    /// that each contributing match is highlighted.
+    pub fn highlight(mut self, highlight: Option<Grep>) -> Self {
+        self.opts.highlight = highlight;
+        self
+    }
+
     /// Set the end-of-line byte used by this searcher.
     pub fn eol(mut self, eol: u8) -> Self {
         self.opts.eol = eol;
@@ -272,6 +354,13 @@ impl<'a, R: io::Read, W: WriteColor> Searcher<'a, R, W> {
         self
     }
 
+    /// If given, aggregate statistics (e.g., the total number of individual
+    /// matches and bytes searched) are recorded for this search.
+    pub fn stats(mut self, stats: Option<Arc<Stats>>) -> Self {
+        self.opts.stats = stats;
+        self
+    }
+
     /// If enabled, search binary files as if they were text.
     pub fn text(mut self, yes: bool) -> Self {
         self.opts.text = yes;
@@ -287,15 +376,23 @@ impl<'a, R: io::Read, W: WriteColor> Searcher<'a, R, W> {
         self.match_line_count = 0;
         self.line_count = if self.opts.line_number { Some(0) } else { None };
         self.byte_offset = if self.opts.byte_offset { Some(0) } else { None };
-        self.match_count = if self.opts.count_matches { Some(0) } else { None };
+        self.match_count =
+            if self.opts.count_matches || self.opts.stats.is_some() {
+                Some(0)
+            } else {
+                None
+            };
         self.last_match = Match::default();
         self.after_context_remaining = 0;
+        self.printed_gap = false;
+        self.and_seen = vec![false; self.opts.and.len()];
         while !self.terminate() {
             let upto = self.inp.lastnl;
             self.print_after_context(upto);
             if !self.fill()? {
                 break;
             }
+            self.record_and_matches();
             while !self.terminate() && self.inp.pos < self.inp.lastnl {
                 let matched = self.grep.read_match(
                     &mut self.last_match,
@@ -317,9 +414,11 @@ impl<'a, R: io::Read, W: WriteColor> Searcher<'a, R, W> {
                 } else if matched {
                     let start = self.last_match.start();
                     let end = self.last_match.end();
-                    self.print_after_context(start);
-                    self.print_before_context(start);
-                    self.print_match(start, end);
+                    if self.opts.and_line_match(&self.inp.buf[start..end]) {
+                        self.print_after_context(start);
+                        self.print_before_context(start);
+                        self.print_match(start, end);
+                    }
                 }
                 if matched {
                     self.inp.pos = self.last_match.end();
@@ -337,6 +436,9 @@ impl<'a, R: io::Read, W: WriteColor> Searcher<'a, R, W> {
                 self.print_after_context(upto);
             }
         }
+        let and_satisfied =
+            !self.opts.files_with_all_matches
+            || self.and_seen.iter().all(|&seen| seen);
         if self.match_line_count > 0 {
             if self.opts.count {
                 self.printer.path_count(self.path, self.match_line_count);
@@ -344,11 +446,23 @@ impl<'a, R: io::Read, W: WriteColor> Searcher<'a, R, W> {
                 self.printer.path_count(self.path, self.match_count.unwrap());
             } else if self.opts.files_with_matches {
                 self.printer.path(self.path);
+            } else if self.opts.files_with_all_matches && and_satisfied {
+                self.printer.path(self.path);
+            } else if self.opts.binary && self.inp.is_binary {
+                self.printer.binary_file_matches(self.path);
             }
+        } else if self.opts.include_zero
+            && (self.opts.count || self.opts.count_matches)
+        {
+            self.printer.path_count(self.path, 0);
         } else if self.opts.files_without_matches {
             self.printer.path(self.path);
         }
-        Ok(self.match_line_count)
+        if let Some(ref stats) = self.opts.stats {
+            stats.add_matches(self.match_count.unwrap_or(0));
+            stats.add_bytes_searched(self.inp.bytes_read);
+        }
+        Ok(if and_satisfied { self.match_line_count } else { 0 })
     }
 
     #[inline(always)]
@@ -356,10 +470,29 @@ impl<'a, R: io::Read, W: WriteColor> Searcher<'a, R, W> {
         self.opts.terminate(self.match_line_count)
     }
 
+    /// Returns true if matched and context lines should not be printed.
+    ///
+    /// This is the case for the various counting/listing modes, and for
+    /// binary files when --binary is enabled, where only a summary line is
+    /// printed at the end of the search.
+    #[inline(always)]
+    fn skip_printing(&self) -> bool {
+        self.opts.skip_matches() || (self.opts.binary && self.inp.is_binary)
+    }
+
     #[inline(always)]
     fn fill(&mut self) -> Result<bool, Error> {
         let keep =
-            if self.opts.before_context > 0 || self.opts.after_context > 0 {
+            if self.opts.context_block {
+                // Keep everything from the start of the paragraph containing
+                // the last complete line, since a match in the next fill may
+                // need to print before-context back to the last blank line.
+                start_of_paragraph(
+                    self.opts.eol,
+                    &self.inp.buf,
+                    self.inp.lastnl.saturating_sub(1))
+            } else if self.opts.before_context > 0
+                || self.opts.after_context > 0 {
                 let lines = 1 + cmp::max(
                     self.opts.before_context, self.opts.after_context);
                 start_of_previous_lines(
@@ -373,6 +506,11 @@ impl<'a, R: io::Read, W: WriteColor> Searcher<'a, R, W> {
         if keep < self.last_printed {
             self.last_printed -= keep;
         } else {
+            // Some unprinted bytes are being discarded, so remember that
+            // the next printed line is not contiguous with the last one.
+            if self.last_printed < keep {
+                self.printed_gap = true;
+            }
             self.last_printed = 0;
         }
         if keep <= self.last_line {
@@ -396,14 +534,35 @@ impl<'a, R: io::Read, W: WriteColor> Searcher<'a, R, W> {
             if self.terminate() {
                 return;
             }
-            self.print_match(start, end);
+            if self.opts.and_line_match(&self.inp.buf[start..end]) {
+                self.print_match(start, end);
+            }
             self.inp.pos = end;
         }
     }
 
+    /// With --files-with-all-matches, records which --and patterns have
+    /// matched somewhere in the input seen so far. Lines never straddle two
+    /// fills, so checking each filled chunk is sufficient.
+    fn record_and_matches(&mut self) {
+        if !self.opts.files_with_all_matches {
+            return;
+        }
+        for i in 0..self.opts.and.len() {
+            if self.and_seen[i] {
+                continue;
+            }
+            let chunk = &self.inp.buf[..self.inp.lastnl];
+            if self.opts.and[i].regex().is_match(chunk) {
+                self.and_seen[i] = true;
+            }
+        }
+    }
+
     #[inline(always)]
     fn print_before_context(&mut self, upto: usize) {
-        if self.opts.skip_matches() || self.opts.before_context == 0 {
+        if self.skip_printing()
+            || (self.opts.before_context == 0 && !self.opts.context_block) {
             return;
         }
         let start = self.last_printed;
@@ -412,11 +571,20 @@ impl<'a, R: io::Read, W: WriteColor> Searcher<'a, R, W> {
             return;
         }
         let before_context_start =
-            start + start_of_previous_lines(
-                self.opts.eol,
-                &self.inp.buf[start..],
-                end - start - 1,
-                self.opts.before_context);
+            if self.opts.context_block {
+                // `end` is the start of the matched line, so this finds the
+                // start of the paragraph that the match belongs to.
+                start + start_of_paragraph(
+                    self.opts.eol,
+                    &self.inp.buf[start..end],
+                    end - start)
+            } else {
+                start + start_of_previous_lines(
+                    self.opts.eol,
+                    &self.inp.buf[start..],
+                    end - start - 1,
+                    self.opts.before_context)
+            };
         let mut it = IterLines::new(self.opts.eol, before_context_start);
         while let Some((s, e)) = it.next(&self.inp.buf[..end]) {
             self.print_separator(s);
@@ -426,17 +594,27 @@ impl<'a, R: io::Read, W: WriteColor> Searcher<'a, R, W> {
 
     #[inline(always)]
     fn print_after_context(&mut self, upto: usize) {
-        if self.opts.skip_matches() || self.after_context_remaining == 0 {
+        if self.skip_printing() || self.after_context_remaining == 0 {
             return;
         }
         let start = self.last_printed;
         let end = upto;
         let mut it = IterLines::new(self.opts.eol, start);
         while let Some((s, e)) = it.next(&self.inp.buf[..end]) {
-            self.print_context(s, e);
-            self.after_context_remaining -= 1;
-            if self.after_context_remaining == 0 {
-                break;
+            if self.opts.context_block {
+                // After-context extends to the next blank line instead of
+                // counting down a fixed number of lines.
+                if is_blank_line(self.opts.eol, &self.inp.buf[s..e]) {
+                    self.after_context_remaining = 0;
+                    break;
+                }
+                self.print_context(s, e);
+            } else {
+                self.print_context(s, e);
+                self.after_context_remaining -= 1;
+                if self.after_context_remaining == 0 {
+                    break;
+                }
             }
         }
     }
@@ -445,17 +623,29 @@ impl<'a, R: io::Read, W: WriteColor> Searcher<'a, R, W> {
     fn print_match(&mut self, start: usize, end: usize) {
         self.match_line_count += 1;
         self.count_individual_matches(start, end);
-        if self.opts.skip_matches() {
+        if self.skip_printing() {
             return;
         }
         self.print_separator(start);
         self.count_lines(start);
         self.add_line(end);
+        let re = match self.opts.highlight {
+            None => self.grep.regex(),
+            Some(ref grep) => grep.regex(),
+        };
         self.printer.matched(
-            self.grep.regex(), self.path,
+            re, self.path,
             &self.inp.buf, start, end, self.line_count, self.byte_offset);
         self.last_printed = end;
-        self.after_context_remaining = self.opts.after_context;
+        self.after_context_remaining =
+            if self.opts.context_block {
+                // A sentinel value; the countdown isn't used in this mode
+                // and any non-zero value keeps after-context printing alive
+                // until a blank line is seen.
+                1
+            } else {
+                self.opts.after_context
+            };
     }
 
     #[inline(always)]
@@ -470,16 +660,19 @@ impl<'a, R: io::Read, W: WriteColor> Searcher<'a, R, W> {
 
     #[inline(always)]
     fn print_separator(&mut self, before: usize) {
-        if self.opts.before_context == 0 && self.opts.after_context == 0 {
+        if self.opts.before_context == 0
+            && self.opts.after_context == 0
+            && !self.opts.context_block {
             return;
         }
         if !self.printer.has_printed() {
             return;
         }
-        if (self.last_printed == 0 && before > 0)
+        if (self.last_printed == 0 && (before > 0 || self.printed_gap))
             || self.last_printed < before {
             self.printer.context_separate();
         }
+        self.printed_gap = false;
     }
 
     #[inline(always)]
@@ -558,8 +751,18 @@ pub struct InputBuffer {
     /// The end position of the buffer. Data after this position is not
     /// specified.
     end: usize,
+    /// The total number of bytes read from the underlying reader since the
+    /// last reset.
+    bytes_read: u64,
     /// Set to true if and only if no reads have occurred yet.
     first: bool,
+    /// If enabled, detecting binary contents does not stop the search.
+    /// Instead, the detection is recorded in is_binary and filling proceeds
+    /// as if the contents were text.
+    binary: bool,
+    /// Set to true if and only if binary contents have been detected since
+    /// the last reset.
+    is_binary: bool,
     /// Set to true if all binary data should be treated as if it were text.
     text: bool,
 }
@@ -588,8 +791,11 @@ impl InputBuffer {
             pos: 0,
             lastnl: 0,
             end: 0,
+            bytes_read: 0,
             first: true,
             text: false,
+            binary: false,
+            is_binary: false,
         }
     }
 
@@ -608,12 +814,22 @@ impl InputBuffer {
         self
     }
 
+    /// If enabled, binary contents do not stop the search. Binary detection
+    /// still runs and its result is recorded, so that the searcher can
+    /// suppress its output accordingly.
+    pub fn binary(&mut self, yes: bool) -> &mut Self {
+        self.binary = yes;
+        self
+    }
+
     /// Resets this buffer so that it may be reused with a new reader.
     fn reset(&mut self) {
         self.pos = 0;
         self.lastnl = 0;
         self.end = 0;
+        self.bytes_read = 0;
         self.first = true;
+        self.is_binary = false;
     }
 
     /// Fill the contents of this buffer with the reader given. The reader
@@ -648,9 +864,13 @@ impl InputBuffer {
             }
             let n = rdr.read(
                 &mut self.buf[self.end..self.end + self.read_size])?;
+            self.bytes_read += n as u64;
             if !self.text {
                 if is_binary(&self.buf[self.end..self.end + n], self.first) {
-                    return Ok(false);
+                    if !self.binary {
+                        return Ok(false);
+                    }
+                    self.is_binary = true;
                 }
             }
             self.first = false;
@@ -825,6 +1045,32 @@ fn start_of_previous_lines(
     end + 2
 }
 
+/// Returns true if and only if the given line is blank, i.e., contains
+/// nothing other than its line terminator (and possibly a carriage return).
+#[inline(always)]
+fn is_blank_line(eol: u8, line: &[u8]) -> bool {
+    line.iter().all(|&b| b == eol || b == b'\r')
+}
+
+/// Returns the starting index of the paragraph containing the position
+/// `upto`, i.e., the start of the first line after the nearest blank line
+/// preceding `upto`, or `0` if there is no such blank line.
+#[inline(always)]
+fn start_of_paragraph(eol: u8, buf: &[u8], upto: usize) -> usize {
+    let mut line_start =
+        memrchr(eol, &buf[..cmp::min(upto, buf.len())]).map_or(0, |i| i + 1);
+    while line_start > 0 {
+        // The previous line ends (exclusive of eol) at line_start - 1.
+        let prev_start =
+            memrchr(eol, &buf[..line_start - 1]).map_or(0, |i| i + 1);
+        if is_blank_line(eol, &buf[prev_start..line_start - 1]) {
+            break;
+        }
+        line_start = prev_start;
+    }
+    line_start
+}
+
 #[cfg(test)]
 mod tests {
     use std::io;
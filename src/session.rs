@@ -0,0 +1,224 @@
+/*!
+The session module provides support for recording a search session to a file
+and replaying it later.
+
+A recorded session captures the exact set of files that were searched, a hash
+of each file's contents and the rendered search results. Replaying a session
+re-renders the recorded results and reports any files whose contents have
+changed since the recording was made, which makes search behavior reported in
+bugs reproducible.
+
+The format is a plain text header followed by the raw recorded output:
+
+```text
+rg-session 1
+count <total number of matching lines>
+file <hash> <path>
+...
+output <number of bytes>
+<raw output bytes>
+```
+*/
+
+use std::collections::hash_map::DefaultHasher;
+use std::fs::File;
+use std::hash::Hasher;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::str;
+use std::sync::Mutex;
+
+use memchr::memchr;
+
+/// The first line of every session file, including a format version.
+const MAGIC: &str = "rg-session 1";
+
+/// Records the files searched and the output printed by a search, and
+/// writes them to a session file.
+///
+/// A recorder can be shared between search workers.
+pub struct SessionRecorder {
+    inner: Mutex<Session>,
+}
+
+impl SessionRecorder {
+    /// Create a new empty session recorder.
+    pub fn new() -> SessionRecorder {
+        SessionRecorder {
+            inner: Mutex::new(Session {
+                files: vec![],
+                output: vec![],
+                count: 0,
+            }),
+        }
+    }
+
+    /// Record one searched file and the output it produced.
+    ///
+    /// `path` should be `None` when stdin was searched. The file's contents
+    /// are hashed at recording time, so this should be called right after
+    /// the file was searched.
+    pub fn record(&self, path: Option<&Path>, output: &[u8]) {
+        let mut inner = self.inner.lock().unwrap();
+        match path {
+            None => inner.files.push((PathBuf::from("-"), 0)),
+            Some(path) => {
+                let hash = hash_file(path).unwrap_or(0);
+                inner.files.push((path.to_path_buf(), hash));
+            }
+        }
+        inner.output.extend_from_slice(output);
+    }
+
+    /// Write the recorded session to the file path given, along with the
+    /// total number of matching lines.
+    pub fn write_to(&self, path: &Path, count: u64) -> io::Result<()> {
+        let inner = self.inner.lock().unwrap();
+        let mut wtr = io::BufWriter::new(File::create(path)?);
+        writeln!(wtr, "{}", MAGIC)?;
+        writeln!(wtr, "count {}", count)?;
+        for &(ref path, hash) in &inner.files {
+            writeln!(wtr, "file {:016x} {}", hash, path.display())?;
+        }
+        writeln!(wtr, "output {}", inner.output.len())?;
+        wtr.write_all(&inner.output)?;
+        wtr.flush()
+    }
+}
+
+/// A search session loaded from a session file.
+pub struct Session {
+    files: Vec<(PathBuf, u64)>,
+    output: Vec<u8>,
+    count: u64,
+}
+
+impl Session {
+    /// Load a session from the file path given.
+    pub fn load(path: &Path) -> io::Result<Session> {
+        let mut contents = vec![];
+        File::open(path)?.read_to_end(&mut contents)?;
+        Session::parse(&contents).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: invalid session file", path.display()),
+            )
+        })
+    }
+
+    /// Parse a session from raw bytes.
+    fn parse(mut bytes: &[u8]) -> Option<Session> {
+        if next_line(&mut bytes)? != MAGIC.as_bytes() {
+            return None;
+        }
+        let mut session = Session {
+            files: vec![],
+            output: vec![],
+            count: 0,
+        };
+        loop {
+            let line = str::from_utf8(next_line(&mut bytes)?).ok()?;
+            if line.starts_with("count ") {
+                session.count = line["count ".len()..].parse().ok()?;
+            } else if line.starts_with("file ") {
+                let rest = &line["file ".len()..];
+                let mut fields = rest.splitn(2, ' ');
+                let hash = u64::from_str_radix(fields.next()?, 16).ok()?;
+                let path = PathBuf::from(fields.next()?);
+                session.files.push((path, hash));
+            } else if line.starts_with("output ") {
+                let len: usize = line["output ".len()..].parse().ok()?;
+                if bytes.len() < len {
+                    return None;
+                }
+                session.output = bytes[..len].to_vec();
+                return Some(session);
+            } else {
+                return None;
+            }
+        }
+    }
+
+    /// The total number of matching lines that the recorded search reported.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// The rendered output of the recorded search.
+    pub fn output(&self) -> &[u8] {
+        &self.output
+    }
+
+    /// Returns the recorded files whose contents have changed (or that no
+    /// longer exist) since the session was recorded.
+    pub fn changed_files(&self) -> Vec<&Path> {
+        let mut changed = vec![];
+        for &(ref path, hash) in &self.files {
+            if path.as_path() == Path::new("-") {
+                continue;
+            }
+            if hash_file(path).ok() != Some(hash) {
+                changed.push(path.as_path());
+            }
+        }
+        changed
+    }
+}
+
+/// Returns the next line (without its terminator) and advances `bytes`
+/// past it.
+fn next_line<'a>(bytes: &mut &'a [u8]) -> Option<&'a [u8]> {
+    let nl = memchr(b'\n', bytes)?;
+    let line = &bytes[..nl];
+    *bytes = &bytes[nl + 1..];
+    Some(line)
+}
+
+/// Returns a hash of the contents of the file path given.
+fn hash_file(path: &Path) -> io::Result<u64> {
+    let mut contents = vec![];
+    File::open(path)?.read_to_end(&mut contents)?;
+    let mut hasher = DefaultHasher::new();
+    hasher.write(&contents);
+    Ok(hasher.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use super::{Session, SessionRecorder, MAGIC};
+
+    #[test]
+    fn roundtrip() {
+        let recorder = SessionRecorder::new();
+        recorder.record(Some(Path::new("a/b")), b"a/b:match\n");
+        recorder.record(None, b"stdin match\n");
+
+        // Serialize by hand so that the test doesn't touch the file system.
+        let inner = recorder.inner.lock().unwrap();
+        let mut raw = format!("{}\ncount 2\n", MAGIC).into_bytes();
+        for &(ref path, hash) in &inner.files {
+            raw.extend_from_slice(
+                format!("file {:016x} {}\n", hash, path.display()).as_bytes(),
+            );
+        }
+        raw.extend_from_slice(
+            format!("output {}\n", inner.output.len()).as_bytes(),
+        );
+        raw.extend_from_slice(&inner.output);
+
+        let session = Session::parse(&raw).unwrap();
+        assert_eq!(2, session.count());
+        assert_eq!(&b"a/b:match\nstdin match\n"[..], session.output());
+        // a/b doesn't exist, so it is reported as changed. stdin isn't.
+        assert_eq!(vec![Path::new("a/b")], session.changed_files());
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert!(Session::parse(b"not a session\n").is_none());
+        assert!(Session::parse(MAGIC.as_bytes()).is_none());
+        let raw = format!("{}\noutput 100\nshort", MAGIC);
+        assert!(Session::parse(raw.as_bytes()).is_none());
+    }
+}
@@ -0,0 +1,86 @@
+/*!
+The stats module defines the aggregate statistics reported by the --stats
+flag. A single `Stats` value is shared by every search worker, so all of its
+counters are atomic.
+*/
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// Aggregate statistics collected across all searches in a single run.
+///
+/// Note that the "matched lines," "files searched" and "files contained
+/// matches" counters are tallied directly in the search driver, since the
+/// driver needs them for other purposes as well. This type only aggregates
+/// the statistics that are observable exclusively inside the searchers.
+#[derive(Debug, Default)]
+pub struct Stats {
+    matches: AtomicUsize,
+    bytes_searched: AtomicUsize,
+    search_time_ns: AtomicUsize,
+    cpu_time_ns: AtomicUsize,
+}
+
+impl Stats {
+    /// Create a new value for tracking aggregate statistics.
+    ///
+    /// All counters start at zero.
+    pub fn new() -> Stats {
+        Stats::default()
+    }
+
+    /// Add to the total number of individual matches.
+    pub fn add_matches(&self, count: u64) {
+        self.matches.fetch_add(count as usize, Ordering::SeqCst);
+    }
+
+    /// Add to the total number of bytes searched.
+    pub fn add_bytes_searched(&self, count: u64) {
+        self.bytes_searched.fetch_add(count as usize, Ordering::SeqCst);
+    }
+
+    /// Add to the total amount of time spent searching.
+    pub fn add_search_time(&self, elapsed: Duration) {
+        let ns =
+            elapsed.as_secs() as usize * 1_000_000_000
+            + elapsed.subsec_nanos() as usize;
+        self.search_time_ns.fetch_add(ns, Ordering::SeqCst);
+    }
+
+    /// Add to the total amount of CPU time spent searching. On platforms
+    /// where thread CPU time cannot be measured, nothing is ever added.
+    pub fn add_cpu_time(&self, elapsed: Duration) {
+        let ns =
+            elapsed.as_secs() as usize * 1_000_000_000
+            + elapsed.subsec_nanos() as usize;
+        self.cpu_time_ns.fetch_add(ns, Ordering::SeqCst);
+    }
+
+    /// Return the total number of individual matches.
+    pub fn matches(&self) -> u64 {
+        self.matches.load(Ordering::SeqCst) as u64
+    }
+
+    /// Return the total number of bytes searched.
+    pub fn bytes_searched(&self) -> u64 {
+        self.bytes_searched.load(Ordering::SeqCst) as u64
+    }
+
+    /// Return the total amount of time spent searching, summed across all
+    /// search workers.
+    ///
+    /// Since workers run in parallel, this can exceed the wall clock time
+    /// of the run.
+    pub fn search_time(&self) -> Duration {
+        let ns = self.search_time_ns.load(Ordering::SeqCst) as u64;
+        Duration::new(ns / 1_000_000_000, (ns % 1_000_000_000) as u32)
+    }
+
+    /// Return the total amount of CPU time spent searching, summed across
+    /// all search workers. Comparing this with `search_time` distinguishes
+    /// real work from time spent blocked on IO.
+    pub fn cpu_time(&self) -> Duration {
+        let ns = self.cpu_time_ns.load(Ordering::SeqCst) as u64;
+        Duration::new(ns / 1_000_000_000, (ns % 1_000_000_000) as u32)
+    }
+}
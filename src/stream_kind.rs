@@ -0,0 +1,138 @@
+/*!
+Detection of what kind of device the standard streams are attached to.
+
+`atty` only answers "is this a terminal?", but several heuristics (such as
+whether to search stdin or the current directory, and whether to line
+buffer or block buffer output) want to distinguish pipes, regular files
+and the null device as well. This module provides that on Unix (via
+`fstat`) and Windows (via `GetFileType`).
+*/
+
+/// The kind of device a standard stream is attached to.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum StreamKind {
+    /// An interactive terminal.
+    Tty,
+    /// A pipe or FIFO. On Unix, this includes sockets.
+    Pipe,
+    /// A regular file, i.e., redirection from or to a file.
+    File,
+    /// The null device (`/dev/null` on Unix, `NUL` on Windows).
+    Null,
+    /// Some other kind of device, or the kind could not be determined.
+    Unknown,
+}
+
+/// Returns the kind of device attached to stdin.
+pub fn stdin_kind() -> StreamKind {
+    imp::stdin_kind()
+}
+
+/// Returns the kind of device attached to stdout.
+#[allow(dead_code)]
+pub fn stdout_kind() -> StreamKind {
+    imp::stdout_kind()
+}
+
+/// Returns the kind of device attached to stderr.
+#[allow(dead_code)]
+pub fn stderr_kind() -> StreamKind {
+    imp::stderr_kind()
+}
+
+#[cfg(unix)]
+mod imp {
+    use std::fs;
+    use std::mem;
+    use std::os::unix::fs::MetadataExt;
+
+    use atty;
+    use libc;
+
+    use super::StreamKind;
+
+    pub fn stdin_kind() -> StreamKind {
+        kind(libc::STDIN_FILENO, atty::Stream::Stdin)
+    }
+
+    pub fn stdout_kind() -> StreamKind {
+        kind(libc::STDOUT_FILENO, atty::Stream::Stdout)
+    }
+
+    pub fn stderr_kind() -> StreamKind {
+        kind(libc::STDERR_FILENO, atty::Stream::Stderr)
+    }
+
+    fn kind(fd: libc::c_int, stream: atty::Stream) -> StreamKind {
+        if atty::is(stream) {
+            return StreamKind::Tty;
+        }
+        let stat = unsafe {
+            let mut stat: libc::stat = mem::zeroed();
+            if libc::fstat(fd, &mut stat) != 0 {
+                return StreamKind::Unknown;
+            }
+            stat
+        };
+        match stat.st_mode as libc::mode_t & libc::S_IFMT {
+            libc::S_IFIFO | libc::S_IFSOCK => StreamKind::Pipe,
+            libc::S_IFREG => StreamKind::File,
+            libc::S_IFCHR => {
+                if is_null_device(&stat) {
+                    StreamKind::Null
+                } else {
+                    StreamKind::Unknown
+                }
+            }
+            _ => StreamKind::Unknown,
+        }
+    }
+
+    fn is_null_device(stat: &libc::stat) -> bool {
+        match fs::metadata("/dev/null") {
+            Ok(md) => md.rdev() == stat.st_rdev as u64,
+            Err(_) => false,
+        }
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use std::io;
+    use std::os::windows::io::{AsRawHandle, RawHandle};
+
+    use atty;
+    use winapi::um::fileapi::GetFileType;
+    use winapi::um::winbase::{
+        FILE_TYPE_CHAR, FILE_TYPE_DISK, FILE_TYPE_PIPE,
+    };
+    use winapi::um::winnt::HANDLE;
+
+    use super::StreamKind;
+
+    pub fn stdin_kind() -> StreamKind {
+        kind(io::stdin().as_raw_handle(), atty::Stream::Stdin)
+    }
+
+    pub fn stdout_kind() -> StreamKind {
+        kind(io::stdout().as_raw_handle(), atty::Stream::Stdout)
+    }
+
+    pub fn stderr_kind() -> StreamKind {
+        kind(io::stderr().as_raw_handle(), atty::Stream::Stderr)
+    }
+
+    fn kind(handle: RawHandle, stream: atty::Stream) -> StreamKind {
+        if atty::is(stream) {
+            return StreamKind::Tty;
+        }
+        match unsafe { GetFileType(handle as HANDLE) } {
+            FILE_TYPE_DISK => StreamKind::File,
+            FILE_TYPE_PIPE => StreamKind::Pipe,
+            // A character device that isn't a console is in all
+            // likelihood the NUL device.
+            FILE_TYPE_CHAR => StreamKind::Null,
+            _ => StreamKind::Unknown,
+        }
+    }
+}
@@ -1,8 +1,13 @@
+use std::fmt;
 use std::fs::File;
 use std::io;
+use std::mem;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use encoding_rs::Encoding;
+use libc;
 use grep::Grep;
 use ignore::DirEntry;
 use memmap::Mmap;
@@ -10,12 +15,14 @@ use termcolor::WriteColor;
 
 // use decoder::DecodeReader;
 use encoding_rs_io::DecodeReaderBytesBuilder;
+use console_stdin;
 use decompressor::{self, DecompressionReader};
-use preprocessor::PreprocessorReader;
+use preprocessor::{PreprocessorGlobs, PreprocessorReader};
 use pathutil::strip_prefix;
 use printer::Printer;
 use search_buffer::BufferSearcher;
 use search_stream::{InputBuffer, Searcher};
+use stats::Stats;
 
 use Result;
 
@@ -34,20 +41,29 @@ struct Options {
     mmap: bool,
     encoding: Option<&'static Encoding>,
     after_context: usize,
+    and: Vec<Grep>,
     before_context: usize,
+    binary: bool,
+    context_block: bool,
     byte_offset: bool,
     count: bool,
     count_matches: bool,
+    files_with_all_matches: bool,
     files_with_matches: bool,
     files_without_matches: bool,
+    highlight: Option<Grep>,
+    include_zero: bool,
     eol: u8,
     invert_match: bool,
     line_number: bool,
+    max_bytes_per_file: Option<u64>,
     max_count: Option<u64>,
     no_messages: bool,
     quiet: bool,
+    stats: Option<Arc<Stats>>,
     text: bool,
     preprocessor: Option<PathBuf>,
+    preprocessor_globs: Option<PreprocessorGlobs>,
     search_zip_files: bool
 }
 
@@ -57,21 +73,96 @@ impl Default for Options {
             mmap: false,
             encoding: None,
             after_context: 0,
+            and: vec![],
             before_context: 0,
+            binary: false,
+            context_block: false,
             byte_offset: false,
             count: false,
             count_matches: false,
+            files_with_all_matches: false,
             files_with_matches: false,
             files_without_matches: false,
+            highlight: None,
+            include_zero: false,
             eol: b'\n',
             invert_match: false,
             line_number: false,
+            max_bytes_per_file: None,
             max_count: None,
             no_messages: false,
             quiet: false,
+            stats: None,
             text: false,
             search_zip_files: false,
             preprocessor: None,
+            preprocessor_globs: None,
+        }
+    }
+}
+
+/// A shared pool of the reusable buffers a worker needs: the line buffer
+/// used for streaming search and the scratch buffer used for transcoding.
+///
+/// Both buffers grow to fit the files they see and keep their allocations
+/// across searches. Workers built from a pool inherit buffers checked in by
+/// previously dropped workers instead of allocating and zeroing fresh ones,
+/// which matters when workers are created frequently, e.g., once per thread
+/// for every parallel search over trees with many small files.
+#[derive(Default)]
+pub struct BufferPool {
+    buffers: Mutex<Vec<Buffers>>,
+}
+
+impl fmt::Debug for BufferPool {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let len = self.buffers.lock().unwrap().len();
+        write!(f, "BufferPool({} idle)", len)
+    }
+}
+
+/// The buffers checked in and out of a `BufferPool`.
+struct Buffers {
+    inpbuf: InputBuffer,
+    decodebuf: Vec<u8>,
+}
+
+impl BufferPool {
+    /// Create a new pool with no buffers in it.
+    ///
+    /// Buffers are allocated lazily, as workers built from this pool find
+    /// it empty.
+    pub fn new() -> BufferPool {
+        BufferPool {
+            buffers: Mutex::new(vec![]),
+        }
+    }
+
+    /// Take a set of buffers out of this pool, allocating fresh ones if the
+    /// pool is empty.
+    fn checkout(&self, eol: u8) -> Buffers {
+        match self.buffers.lock().unwrap().pop() {
+            Some(mut bufs) => {
+                bufs.inpbuf.eol(eol);
+                bufs
+            }
+            None => Buffers::new(eol),
+        }
+    }
+
+    /// Return a set of buffers to this pool for reuse.
+    fn checkin(&self, bufs: Buffers) {
+        self.buffers.lock().unwrap().push(bufs);
+    }
+}
+
+impl Buffers {
+    fn new(eol: u8) -> Buffers {
+        let mut inpbuf = InputBuffer::new();
+        inpbuf.eol(eol);
+        Buffers {
+            inpbuf: inpbuf,
+            decodebuf: vec![0; 8 * (1<<10)],
         }
     }
 }
@@ -88,15 +179,19 @@ impl WorkerBuilder {
         }
     }
 
-    /// Create the worker from this builder.
-    pub fn build(self) -> Worker {
-        let mut inpbuf = InputBuffer::new();
-        inpbuf.eol(self.opts.eol);
+    /// Create the worker from this builder, drawing its buffers from the
+    /// given pool.
+    ///
+    /// When the worker is dropped, its buffers are returned to the pool so
+    /// that the next worker built from it can reuse their allocations.
+    pub fn build_with_pool(self, pool: Arc<BufferPool>) -> Worker {
+        let bufs = pool.checkout(self.opts.eol);
         Worker {
             grep: self.grep,
-            inpbuf: inpbuf,
-            decodebuf: vec![0; 8 * (1<<10)],
+            inpbuf: bufs.inpbuf,
+            decodebuf: bufs.decodebuf,
             opts: self.opts,
+            pool: pool,
         }
     }
 
@@ -114,6 +209,31 @@ impl WorkerBuilder {
         self
     }
 
+    /// If enabled, binary files are searched instead of skipped, but only a
+    /// summary line is printed for a binary file containing a match.
+    ///
+    /// Disabled by default.
+    pub fn binary(mut self, yes: bool) -> Self {
+        self.opts.binary = yes;
+        self
+    }
+
+    /// Additional patterns that a matching line (or, with
+    /// `files_with_all_matches`, the file as a whole) must also match.
+    pub fn and(mut self, and: Vec<Grep>) -> Self {
+        self.opts.and = and;
+        self
+    }
+
+    /// If enabled, show context around each match up to the nearest blank
+    /// lines before and after it, instead of a fixed number of lines.
+    ///
+    /// Disabled by default.
+    pub fn context_block(mut self, yes: bool) -> Self {
+        self.opts.context_block = yes;
+        self
+    }
+
     /// If enabled, searching will print a 0-based offset of the
     /// matching line (or the actual match if -o is specified) before
     /// printing the line itself.
@@ -158,6 +278,13 @@ impl WorkerBuilder {
         self
     }
 
+    /// If enabled, searching will print the path of any file where the
+    /// pattern and every `and` pattern match, each somewhere in the file.
+    pub fn files_with_all_matches(mut self, yes: bool) -> Self {
+        self.opts.files_with_all_matches = yes;
+        self
+    }
+
     /// If enabled, searching will print the path of files without any matches.
     ///
     /// Disabled by default.
@@ -166,6 +293,22 @@ impl WorkerBuilder {
         self
     }
 
+    /// A regex that covers the primary pattern and every `and` pattern, so
+    /// that printing highlights each contributing match.
+    pub fn highlight(mut self, highlight: Option<Grep>) -> Self {
+        self.opts.highlight = highlight;
+        self
+    }
+
+    /// If enabled, counting prints a count of zero for searched files
+    /// without any matches.
+    ///
+    /// Disabled by default.
+    pub fn include_zero(mut self, yes: bool) -> Self {
+        self.opts.include_zero = yes;
+        self
+    }
+
     /// Set the end-of-line byte used by this searcher.
     pub fn eol(mut self, eol: u8) -> Self {
         self.opts.eol = eol;
@@ -186,6 +329,17 @@ impl WorkerBuilder {
         self
     }
 
+    /// Limit the search to the first `count` bytes of each file.
+    ///
+    /// The limit is approximate: the last line searched may straddle the
+    /// boundary, in which case it is truncated at the limit.
+    ///
+    /// The default is None, which corresponds to no limit.
+    pub fn max_bytes_per_file(mut self, count: Option<u64>) -> Self {
+        self.opts.max_bytes_per_file = count;
+        self
+    }
+
     /// Limit the number of matches to the given count.
     ///
     /// The default is None, which corresponds to no limit.
@@ -215,6 +369,14 @@ impl WorkerBuilder {
         self
     }
 
+    /// If given, aggregate statistics (e.g., the total number of individual
+    /// matches, bytes searched and time spent searching) are recorded for
+    /// every search this worker executes.
+    pub fn stats(mut self, stats: Option<Arc<Stats>>) -> Self {
+        self.opts.stats = stats;
+        self
+    }
+
     /// If enabled, search binary files as if they were text.
     pub fn text(mut self, yes: bool) -> Self {
         self.opts.text = yes;
@@ -232,6 +394,16 @@ impl WorkerBuilder {
         self.opts.preprocessor = command;
         self
     }
+
+    /// If non-empty, only run a preprocessor on files matching the given
+    /// globs and search all other files normally.
+    pub fn preprocessor_globs(
+        mut self,
+        globs: Option<PreprocessorGlobs>,
+    ) -> Self {
+        self.opts.preprocessor_globs = globs;
+        self
+    }
 }
 
 /// Worker is responsible for executing searches on file paths, while choosing
@@ -241,6 +413,17 @@ pub struct Worker {
     inpbuf: InputBuffer,
     decodebuf: Vec<u8>,
     opts: Options,
+    pool: Arc<BufferPool>,
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        self.pool.checkin(Buffers {
+            inpbuf: mem::replace(
+                &mut self.inpbuf, InputBuffer::with_capacity(1)),
+            decodebuf: mem::replace(&mut self.decodebuf, vec![]),
+        });
+    }
 }
 
 impl Worker {
@@ -252,15 +435,43 @@ impl Worker {
         printer: &mut Printer<W>,
         work: Work,
     ) -> u64 {
+        let start_time = Instant::now();
+        let start_cpu = thread_cpu_time();
         let result = match work {
             Work::Stdin => {
-                let stdin = io::stdin();
-                let stdin = stdin.lock();
-                self.search(printer, Path::new("<stdin>"), stdin)
+                match console_stdin::console_stdin() {
+                    // An interactive Windows console delivers bytes in the
+                    // ANSI codepage, so read it with the wide APIs instead.
+                    Some(stdin) => {
+                        self.search(printer, Path::new("<stdin>"), stdin)
+                    }
+                    None => {
+                        let stdin = io::stdin();
+                        let stdin = stdin.lock();
+                        self.search(printer, Path::new("<stdin>"), stdin)
+                    }
+                }
             }
             Work::DirEntry(dent) => {
                 let mut path = dent.path();
-                if self.opts.preprocessor.is_some() {
+                if let Some(p) = strip_prefix("./", path) {
+                    path = p;
+                }
+                let pre_reader = match self.opts.preprocessor_globs {
+                    None => None,
+                    Some(ref globs) => globs.reader(path),
+                };
+                if let Some(rdr) = pre_reader {
+                    match rdr {
+                        Ok(reader) => self.search(printer, path, reader),
+                        Err(err) => {
+                            if !self.opts.no_messages {
+                                eprintln!("{}", err);
+                            }
+                            return 0;
+                        }
+                    }
+                } else if self.opts.preprocessor.is_some() {
                     let cmd = self.opts.preprocessor.clone().unwrap();
                     match PreprocessorReader::from_cmd_path(cmd, path) {
                         Ok(reader) => self.search(printer, path, reader),
@@ -290,9 +501,6 @@ impl Worker {
                             return 0;
                         }
                     };
-                    if let Some(p) = strip_prefix("./", path) {
-                        path = p;
-                    }
                     if self.opts.mmap {
                         self.search_mmap(printer, path, &file)
                     } else {
@@ -301,6 +509,14 @@ impl Worker {
                 }
             }
         };
+        if let Some(ref stats) = self.opts.stats {
+            stats.add_search_time(start_time.elapsed());
+            if let (Some(start), Some(end)) = (start_cpu, thread_cpu_time()) {
+                if end > start {
+                    stats.add_cpu_time(end - start);
+                }
+            }
+        }
         match result {
             Ok(count) => {
                 count
@@ -319,6 +535,20 @@ impl Worker {
         printer: &mut Printer<W>,
         path: &Path,
         rdr: R,
+    ) -> Result<u64> {
+        match self.opts.max_bytes_per_file {
+            None => self.search_unlimited(printer, path, rdr),
+            Some(limit) => {
+                self.search_unlimited(printer, path, rdr.take(limit))
+            }
+        }
+    }
+
+    fn search_unlimited<R: io::Read, W: WriteColor>(
+        &mut self,
+        printer: &mut Printer<W>,
+        path: &Path,
+        rdr: R,
     ) -> Result<u64> {
         let rdr = DecodeReaderBytesBuilder::new()
             .encoding(self.opts.encoding)
@@ -328,17 +558,24 @@ impl Worker {
             &mut self.inpbuf, printer, &self.grep, path, rdr);
         searcher
             .after_context(self.opts.after_context)
+            .and(self.opts.and.clone())
             .before_context(self.opts.before_context)
+            .binary(self.opts.binary)
+            .context_block(self.opts.context_block)
             .byte_offset(self.opts.byte_offset)
             .count(self.opts.count)
             .count_matches(self.opts.count_matches)
             .files_with_matches(self.opts.files_with_matches)
+            .files_with_all_matches(self.opts.files_with_all_matches)
             .files_without_matches(self.opts.files_without_matches)
+            .highlight(self.opts.highlight.clone())
+            .include_zero(self.opts.include_zero)
             .eol(self.opts.eol)
             .line_number(self.opts.line_number)
             .invert_match(self.opts.invert_match)
             .max_count(self.opts.max_count)
             .quiet(self.opts.quiet)
+            .stats(self.opts.stats.clone())
             .text(self.opts.text)
             .run()
             .map_err(From::from)
@@ -368,18 +605,30 @@ impl Worker {
             // back to the stream reader, which will do transcoding.
             return self.search(printer, path, file);
         }
+        let buf = match self.opts.max_bytes_per_file {
+            Some(limit) if (buf.len() as u64) > limit => {
+                &buf[..limit as usize]
+            }
+            _ => buf,
+        };
         let searcher = BufferSearcher::new(printer, &self.grep, path, buf);
         Ok(searcher
+            .and(self.opts.and.clone())
+            .binary(self.opts.binary)
             .byte_offset(self.opts.byte_offset)
             .count(self.opts.count)
             .count_matches(self.opts.count_matches)
             .files_with_matches(self.opts.files_with_matches)
+            .files_with_all_matches(self.opts.files_with_all_matches)
             .files_without_matches(self.opts.files_without_matches)
+            .highlight(self.opts.highlight.clone())
+            .include_zero(self.opts.include_zero)
             .eol(self.opts.eol)
             .line_number(self.opts.line_number)
             .invert_match(self.opts.invert_match)
             .max_count(self.opts.max_count)
             .quiet(self.opts.quiet)
+            .stats(self.opts.stats.clone())
             .text(self.opts.text)
             .run())
     }
@@ -411,3 +660,53 @@ impl Worker {
 fn mmap_readonly(file: &File) -> io::Result<Mmap> {
     unsafe { Mmap::map(file) }
 }
+
+/// Returns the CPU time consumed by the calling thread so far, or `None`
+/// on platforms where it cannot be measured.
+#[cfg(unix)]
+fn thread_cpu_time() -> Option<Duration> {
+    let mut ts = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+    let rc = unsafe {
+        libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, &mut ts)
+    };
+    if rc != 0 {
+        return None;
+    }
+    Some(Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32))
+}
+
+/// Returns the CPU time consumed by the calling thread so far, or `None`
+/// on platforms where it cannot be measured.
+#[cfg(not(unix))]
+fn thread_cpu_time() -> Option<Duration> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use grep::GrepBuilder;
+
+    use super::{BufferPool, WorkerBuilder};
+
+    #[test]
+    fn pool_reuses_buffers() {
+        let grep = GrepBuilder::new("x").build().unwrap();
+        let pool = Arc::new(BufferPool::new());
+        assert_eq!(format!("{:?}", pool), "BufferPool(0 idle)");
+
+        // Dropping a worker returns its buffers to the pool.
+        let worker = WorkerBuilder::new(grep.clone())
+            .build_with_pool(pool.clone());
+        assert_eq!(format!("{:?}", pool), "BufferPool(0 idle)");
+        drop(worker);
+        assert_eq!(format!("{:?}", pool), "BufferPool(1 idle)");
+
+        // The next worker takes them back out instead of allocating anew.
+        let worker = WorkerBuilder::new(grep).build_with_pool(pool.clone());
+        assert_eq!(format!("{:?}", pool), "BufferPool(0 idle)");
+        drop(worker);
+        assert_eq!(format!("{:?}", pool), "BufferPool(1 idle)");
+    }
+}
@@ -117,6 +117,74 @@ sherlock!(columns, |wd: WorkDir, mut cmd: Command| {
     assert_eq!(lines, expected);
 });
 
+sherlock!(json, |wd: WorkDir, mut cmd: Command| {
+    cmd.arg("--json").arg("-n");
+    let lines: String = wd.stdout(&mut cmd);
+    let lines: Vec<&str> = lines.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert_eq!(
+        lines[0],
+        r#"{"type":"begin","data":{"path":{"text":"sherlock"}}}"#);
+    assert!(lines[1].starts_with(
+        r#"{"type":"match","data":{"path":{"text":"sherlock"}"#));
+    assert!(lines[1].contains(r#""line_number":1"#));
+    assert!(lines[2].contains(r#""line_number":3"#));
+    assert!(lines[3].starts_with(r#"{"type":"summary""#));
+    assert!(lines[3].contains(
+        r#""stats":{"matched_lines":2,"searches":1,"searches_with_match":1"#));
+    assert!(lines[3].contains(r#""search_time":{"secs":"#));
+    assert!(lines[3].contains(r#""cpu_time":{"secs":"#));
+    assert!(lines[3].contains(r#""human":""#));
+});
+
+sherlock!(json_absolute_offset, |wd: WorkDir, mut cmd: Command| {
+    // The JSON format carries absolute byte offsets even without -b.
+    cmd.arg("--json");
+    let lines: String = wd.stdout(&mut cmd);
+    let lines: Vec<&str> = lines.lines().collect();
+    assert!(lines[1].contains(r#""absolute_offset":0"#));
+    assert!(lines[2].contains(r#""absolute_offset":129"#));
+});
+
+sherlock!(json_file, |wd: WorkDir, mut cmd: Command| {
+    cmd.arg("--json-file").arg("results.jsonl").arg("-n");
+    let lines: String = wd.stdout(&mut cmd);
+    let expected = "\
+1:For the Doctor Watsons of this world, as opposed to the Sherlock
+3:be, to a very large extent, the result of luck. Sherlock Holmes
+";
+    assert_eq!(lines, expected);
+
+    let json = wd.read_file("results.jsonl");
+    let json: Vec<&str> = json.lines().collect();
+    assert_eq!(json.len(), 4);
+    assert_eq!(
+        json[0],
+        r#"{"type":"begin","data":{"path":{"text":"sherlock"}}}"#);
+    assert!(json[1].starts_with(
+        r#"{"type":"match","data":{"path":{"text":"sherlock"}"#));
+    assert!(json[1].contains(r#""line_number":1"#));
+    assert!(json[2].contains(r#""line_number":3"#));
+    assert!(json[3].starts_with(r#"{"type":"summary""#));
+    assert!(json[3].contains(
+        r#""stats":{"matched_lines":2,"searches":1,"searches_with_match":1"#));
+});
+
+sherlock!(json_file_parallel, "Sherlock", ".",
+|wd: WorkDir, mut cmd: Command| {
+    // Exclude the output file itself, since it is written while the
+    // directory is being walked.
+    cmd.arg("--json-file").arg("results.jsonl")
+        .arg("-g").arg("!results.jsonl");
+    let _: String = wd.stdout(&mut cmd);
+
+    let json = wd.read_file("results.jsonl");
+    let json: Vec<&str> = json.lines().collect();
+    assert_eq!(json.len(), 4);
+    assert!(json.last().unwrap().contains(
+        r#""stats":{"matched_lines":2,"searches":1,"searches_with_match":1"#));
+});
+
 sherlock!(with_filename, |wd: WorkDir, mut cmd: Command| {
     cmd.arg("-H");
     let lines: String = wd.stdout(&mut cmd);
@@ -127,6 +195,43 @@ sherlock:be, to a very large extent, the result of luck. Sherlock Holmes
     assert_eq!(lines, expected);
 });
 
+sherlock!(field_match_separator, |wd: WorkDir, mut cmd: Command| {
+    cmd.arg("-H").arg("-n").arg("--field-match-separator").arg("\\t");
+    let lines: String = wd.stdout(&mut cmd);
+    let expected = "\
+sherlock\t1\tFor the Doctor Watsons of this world, as opposed to the Sherlock
+sherlock\t3\tbe, to a very large extent, the result of luck. Sherlock Holmes
+";
+    assert_eq!(lines, expected);
+});
+
+sherlock!(field_context_separator, "Watsons", ".",
+|wd: WorkDir, mut cmd: Command| {
+    cmd.arg("-A1").arg("-n").arg("--field-context-separator").arg("=");
+    let lines: String = wd.stdout(&mut cmd);
+    let expected = "\
+sherlock:1:For the Doctor Watsons of this world, as opposed to the Sherlock
+sherlock=2=Holmeses, success in the province of detective work must always
+";
+    assert_eq!(lines, expected);
+});
+
+sherlock!(hyperlink_format, |wd: WorkDir, mut cmd: Command| {
+    cmd.arg("-H").arg("-n").arg("--color").arg("always");
+    cmd.arg("--hyperlink-format").arg("vscode");
+    let lines: String = wd.stdout(&mut cmd);
+    assert!(lines.contains("\x1b]8;;vscode://file"));
+    assert!(lines.contains("/sherlock:1:"));
+    // Every hyperlink must be closed again.
+    assert_eq!(lines.matches("\x1b]8;;").count(), 4);
+});
+
+sherlock!(hyperlink_format_requires_path, "Sherlock", ".",
+|wd: WorkDir, mut cmd: Command| {
+    cmd.arg("--hyperlink-format").arg("foo://{line}");
+    wd.assert_err(&mut cmd);
+});
+
 sherlock!(with_heading, |wd: WorkDir, mut cmd: Command| {
     // This forces the issue since --with-filename is disabled by default
     // when searching one file.
@@ -270,6 +375,18 @@ but Watson, Doctor has to have it taken out for him and dusted,
     assert_eq!(lines, expected);
 });
 
+sherlock!(replace_named_groups_braces, "(?P<first>[A-Z][a-z]+) (?P<last>[A-Z][a-z]+)",
+|wd: WorkDir, mut cmd: Command| {
+    cmd.arg("-r").arg("${last}_${first}");
+    let lines: String = wd.stdout(&mut cmd);
+    let expected = "\
+For the Watsons_Doctor of this world, as opposed to the Sherlock
+be, to a very large extent, the result of luck. Holmes_Sherlock
+but Watson_Doctor has to have it taken out for him and dusted,
+";
+    assert_eq!(lines, expected);
+});
+
 sherlock!(replace_with_only_matching, "of (\\w+)",
 |wd: WorkDir, mut cmd: Command| {
     cmd.arg("-o").arg("-r").arg("$1");
@@ -417,6 +534,36 @@ sherlock!(count_matches_via_only, "the", ".", |wd: WorkDir, mut cmd: Command| {
     assert_eq!(lines, expected);
 });
 
+sherlock!(count_include_zero, "Sherlock", ".", |wd: WorkDir, mut cmd: Command| {
+    wd.create("foo", "no matches here");
+    cmd.arg("--count").arg("--include-zero").arg("--sort").arg("path");
+    let lines: String = wd.stdout(&mut cmd);
+    let expected = "foo:0\nsherlock:2\n";
+    assert_eq!(lines, expected);
+});
+
+sherlock!(count_matches_include_zero, "the", ".",
+|wd: WorkDir, mut cmd: Command| {
+    wd.create("foo", "no matches here");
+    cmd.arg("--count-matches").arg("--include-zero");
+    cmd.arg("--sort").arg("path");
+    let lines: String = wd.stdout(&mut cmd);
+    let expected = "foo:0\nsherlock:4\n";
+    assert_eq!(lines, expected);
+});
+
+sherlock!(engine_default, "Sherlock", ".", |wd: WorkDir, mut cmd: Command| {
+    cmd.arg("--engine").arg("default").arg("--count");
+    let lines: String = wd.stdout(&mut cmd);
+    assert_eq!(lines, "sherlock:2\n");
+});
+
+sherlock!(engine_pcre2_unavailable, "Sherlock", ".",
+|wd: WorkDir, mut cmd: Command| {
+    cmd.arg("--engine").arg("pcre2");
+    wd.assert_err(&mut cmd);
+});
+
 sherlock!(files_with_matches, "Sherlock", ".", |wd: WorkDir, mut cmd: Command| {
     cmd.arg("--files-with-matches");
     let lines: String = wd.stdout(&mut cmd);
@@ -433,6 +580,48 @@ sherlock!(files_without_matches, "Sherlock", ".",
     assert_eq!(lines, expected);
 });
 
+sherlock!(and_pattern, "Sherlock", |wd: WorkDir, mut cmd: Command| {
+    cmd.arg("--and").arg("luck");
+    let lines: String = wd.stdout(&mut cmd);
+    let expected = "\
+be, to a very large extent, the result of luck. Sherlock Holmes
+";
+    assert_eq!(lines, expected);
+});
+
+sherlock!(and_pattern_multiple, "Watson", |wd: WorkDir, mut cmd: Command| {
+    cmd.arg("--and").arg("dusted").arg("--and").arg("taken");
+    let lines: String = wd.stdout(&mut cmd);
+    let expected = "\
+but Doctor Watson has to have it taken out for him and dusted,
+";
+    assert_eq!(lines, expected);
+});
+
+sherlock!(and_pattern_invert, "Sherlock", |wd: WorkDir, mut cmd: Command| {
+    cmd.arg("-v").arg("--and").arg("Watson");
+    let lines: String = wd.stdout(&mut cmd);
+    let expected = "\
+but Doctor Watson has to have it taken out for him and dusted,
+";
+    assert_eq!(lines, expected);
+});
+
+sherlock!(files_with_all_matches, "Sherlock", ".",
+|wd: WorkDir, mut cmd: Command| {
+    // "Sherlock" and "dusted" never share a line, but both appear in the
+    // file.
+    cmd.arg("--files-with-all-matches").arg("--and").arg("dusted");
+    let lines: String = wd.stdout(&mut cmd);
+    assert_eq!(lines, "sherlock\n");
+
+    let mut cmd = wd.command();
+    cmd.arg("--files-with-all-matches")
+        .arg("--and").arg("dusted").arg("--and").arg("nowhere")
+        .arg("Sherlock").arg(".");
+    wd.assert_err(&mut cmd);
+});
+
 sherlock!(after_context, |wd: WorkDir, mut cmd: Command| {
     cmd.arg("-A").arg("1");
     let lines: String = wd.stdout(&mut cmd);
@@ -506,6 +695,82 @@ sherlock!(context_line_numbers, "world|attached",
     assert_eq!(lines, expected);
 });
 
+sherlock!(color_clicolor_force, "Sherlock",
+|wd: WorkDir, mut cmd: Command| {
+    // With --color auto (the default), CLICOLOR_FORCE turns colors on even
+    // though stdout is not a terminal.
+    cmd.env("CLICOLOR_FORCE", "1");
+    let lines: String = wd.stdout(&mut cmd);
+    assert!(lines.contains("\x1b["));
+});
+
+sherlock!(color_no_color, "Sherlock", |wd: WorkDir, mut cmd: Command| {
+    // NO_COLOR has no effect on an explicit --color always.
+    cmd.env("NO_COLOR", "1").arg("--color").arg("always");
+    let lines: String = wd.stdout(&mut cmd);
+    assert!(lines.contains("\x1b["));
+});
+
+sherlock!(grep_colors_env, "Sherlock", |wd: WorkDir, mut cmd: Command| {
+    // Without --colors, GREP_COLORS is honored using GNU grep's syntax.
+    // The default bold is dropped since `ms` replaces the default style.
+    cmd.env("GREP_COLORS", "ms=35").arg("--color").arg("always");
+    let lines: String = wd.stdout(&mut cmd);
+    assert!(lines.contains("\x1b[35mSherlock\x1b[0m"));
+    assert!(!lines.contains("\x1b[1m"));
+});
+
+sherlock!(grep_colors_env_overridden, "Sherlock",
+|wd: WorkDir, mut cmd: Command| {
+    // An explicit --colors flag causes GREP_COLORS to be ignored.
+    cmd.env("GREP_COLORS", "ms=35")
+       .arg("--colors").arg("match:none")
+       .arg("--colors").arg("match:fg:green")
+       .arg("--color").arg("always");
+    let lines: String = wd.stdout(&mut cmd);
+    assert!(lines.contains("\x1b[32mSherlock\x1b[0m"));
+});
+
+sherlock!(context_separator, "world|attached",
+|wd: WorkDir, mut cmd: Command| {
+    cmd.arg("-C").arg("1").arg("--context-separator").arg("++");
+    let lines: String = wd.stdout(&mut cmd);
+    let expected = "\
+For the Doctor Watsons of this world, as opposed to the Sherlock
+Holmeses, success in the province of detective work must always
+++
+but Doctor Watson has to have it taken out for him and dusted,
+and exhibited clearly, with a label attached.
+";
+    assert_eq!(lines, expected);
+});
+
+sherlock!(context_separator_empty, "world|attached",
+|wd: WorkDir, mut cmd: Command| {
+    cmd.arg("-C").arg("1").arg("--context-separator").arg("");
+    let lines: String = wd.stdout(&mut cmd);
+    let expected = "\
+For the Doctor Watsons of this world, as opposed to the Sherlock
+Holmeses, success in the province of detective work must always
+but Doctor Watson has to have it taken out for him and dusted,
+and exhibited clearly, with a label attached.
+";
+    assert_eq!(lines, expected);
+});
+
+sherlock!(no_context_separator, "world|attached",
+|wd: WorkDir, mut cmd: Command| {
+    cmd.arg("-C").arg("1").arg("--no-context-separator");
+    let lines: String = wd.stdout(&mut cmd);
+    let expected = "\
+For the Doctor Watsons of this world, as opposed to the Sherlock
+Holmeses, success in the province of detective work must always
+but Doctor Watson has to have it taken out for him and dusted,
+and exhibited clearly, with a label attached.
+";
+    assert_eq!(lines, expected);
+});
+
 sherlock!(max_filesize_parse_error_length, "Sherlock", ".",
 |wd: WorkDir, mut cmd: Command| {
     cmd.arg("--max-filesize").arg("44444444444444444444");
@@ -604,6 +869,56 @@ sherlock!(ignore_ripgrep, "Sherlock", ".", |wd: WorkDir, mut cmd: Command| {
     wd.assert_err(&mut cmd);
 });
 
+sherlock!(no_ignore_dot, "Sherlock", ".", |wd: WorkDir, mut cmd: Command| {
+    wd.create(".ignore", "sherlock\n");
+    cmd.arg("--no-ignore-dot");
+    let lines: String = wd.stdout(&mut cmd);
+    let expected = "\
+sherlock:For the Doctor Watsons of this world, as opposed to the Sherlock
+sherlock:be, to a very large extent, the result of luck. Sherlock Holmes
+";
+    assert_eq!(lines, expected);
+});
+
+sherlock!(no_ignore_dot_still_respects_git, "Sherlock", ".",
+|wd: WorkDir, mut cmd: Command| {
+    wd.create(".gitignore", "sherlock\n");
+    cmd.arg("--no-ignore-dot");
+    wd.assert_err(&mut cmd);
+});
+
+sherlock!(no_ignore_exclude, "Sherlock", ".",
+|wd: WorkDir, mut cmd: Command| {
+    wd.create_dir(".git/info");
+    wd.create(".git/info/exclude", "sherlock\n");
+    wd.assert_err(&mut cmd);
+
+    let mut cmd = wd.command();
+    cmd.arg("--no-ignore-exclude").arg("Sherlock").arg(".");
+    let lines: String = wd.stdout(&mut cmd);
+    let expected = "\
+sherlock:For the Doctor Watsons of this world, as opposed to the Sherlock
+sherlock:be, to a very large extent, the result of luck. Sherlock Holmes
+";
+    assert_eq!(lines, expected);
+});
+
+sherlock!(no_ignore_files, "Sherlock", ".", |wd: WorkDir, mut cmd: Command| {
+    wd.create("custom-ignore", "sherlock\n");
+    cmd.arg("--ignore-file").arg("custom-ignore");
+    wd.assert_err(&mut cmd);
+
+    let mut cmd = wd.command();
+    cmd.arg("--ignore-file").arg("custom-ignore").arg("--no-ignore-files");
+    cmd.arg("Sherlock").arg(".");
+    let lines: String = wd.stdout(&mut cmd);
+    let expected = "\
+sherlock:For the Doctor Watsons of this world, as opposed to the Sherlock
+sherlock:be, to a very large extent, the result of luck. Sherlock Holmes
+";
+    assert_eq!(lines, expected);
+});
+
 sherlock!(no_ignore, "Sherlock", ".", |wd: WorkDir, mut cmd: Command| {
     wd.create(".gitignore", "sherlock\n");
     cmd.arg("--no-ignore");
@@ -1690,6 +2005,46 @@ clean!(feature_263_sort_files, "test", ".", |wd: WorkDir, mut cmd: Command| {
     assert_eq!(lines, "abc:test\nbar:test\nfoo:test\nzoo:test\n");
 });
 
+clean!(sort_path, "test", ".", |wd: WorkDir, mut cmd: Command| {
+    wd.create("foo", "test");
+    wd.create("abc", "test");
+    wd.create("zoo", "test");
+    cmd.arg("--sort").arg("path");
+
+    let lines: String = wd.stdout(&mut cmd);
+    assert_eq!(lines, "abc:test\nfoo:test\nzoo:test\n");
+});
+
+clean!(sortr_path, "test", ".", |wd: WorkDir, mut cmd: Command| {
+    wd.create("foo", "test");
+    wd.create("abc", "test");
+    wd.create("zoo", "test");
+    cmd.arg("--sortr").arg("path");
+
+    let lines: String = wd.stdout(&mut cmd);
+    assert_eq!(lines, "zoo:test\nfoo:test\nabc:test\n");
+});
+
+clean!(files_null_sorted, "test", ".", |wd: WorkDir, mut cmd: Command| {
+    wd.create("foo", "test");
+    wd.create("abc", "test");
+    wd.create("zoo", "test");
+    cmd.arg("--files").arg("-0").arg("--sort").arg("path");
+
+    let lines: String = wd.stdout(&mut cmd);
+    assert_eq!(lines, "abc\x00foo\x00zoo\x00");
+});
+
+clean!(sort_size, "test", ".", |wd: WorkDir, mut cmd: Command| {
+    wd.create("small", "test");
+    wd.create("medium", "test test");
+    wd.create("large", "test test test");
+    cmd.arg("--sort").arg("size").arg("-l");
+
+    let lines: String = wd.stdout(&mut cmd);
+    assert_eq!(lines, "small\nmedium\nlarge\n");
+});
+
 // See: https://github.com/BurntSushi/ripgrep/issues/275
 clean!(feature_275_pathsep, "test", ".", |wd: WorkDir, mut cmd: Command| {
     wd.create_dir("foo");
@@ -1760,6 +2115,161 @@ be, to a very large extent, the result of luck. Sherlock Holmes
     assert_eq!(lines, expected);
 }
 
+#[test]
+fn compressed_magic_bytes() {
+    if !cmd_exists("gzip") {
+        return;
+    }
+    let gzip_file = include_bytes!("./data/sherlock.gz");
+
+    let wd = WorkDir::new("feature_search_compressed_magic");
+    // No tell-tale extension, so detection must use the magic bytes.
+    wd.create_bytes("sherlock.log.1", gzip_file);
+
+    let mut cmd = wd.command();
+    cmd.arg("-z").arg("Sherlock").arg("sherlock.log.1");
+    let lines: String = wd.stdout(&mut cmd);
+    let expected = "\
+For the Doctor Watsons of this world, as opposed to the Sherlock
+be, to a very large extent, the result of luck. Sherlock Holmes
+";
+    assert_eq!(lines, expected);
+}
+
+#[test]
+fn preprocessing_glob() {
+    if !cmd_exists("xzcat") {
+        return;
+    }
+    let xz_file = include_bytes!("./data/sherlock.xz");
+
+    let wd = WorkDir::new("feature_preprocessing_glob");
+    wd.create_bytes("sherlock.xz", xz_file);
+    wd.create("plain", "Sherlock Holmes lives at 221B Baker Street.");
+
+    let mut cmd = wd.command();
+    cmd.arg("--pre").arg("xzcat").arg("--pre-glob").arg("*.xz");
+    cmd.arg("--sort-files").arg("Sherlock").arg("./");
+    let lines: String = wd.stdout(&mut cmd);
+    let expected = "\
+plain:Sherlock Holmes lives at 221B Baker Street.
+sherlock.xz:For the Doctor Watsons of this world, as opposed to the Sherlock
+sherlock.xz:be, to a very large extent, the result of luck. Sherlock Holmes
+";
+    assert_eq!(lines, expected);
+}
+
+#[test]
+fn in_place_rewrite() {
+    let wd = WorkDir::new("feature_in_place_rewrite");
+    wd.create("file.txt", "foo one\nplain\nfoo two\n");
+
+    let mut cmd = wd.command();
+    cmd.arg("foo").arg("-r").arg("bar").arg("--in-place").arg("file.txt");
+    wd.assert_exit_code(0, &mut cmd);
+
+    let contents =
+        std::fs::read_to_string(wd.path().join("file.txt")).unwrap();
+    assert_eq!(contents, "bar one\nplain\nbar two\n");
+}
+
+#[test]
+fn in_place_backup_suffix() {
+    let wd = WorkDir::new("feature_in_place_backup_suffix");
+    wd.create("file.txt", "foo one\nplain\nfoo two\n");
+
+    let mut cmd = wd.command();
+    cmd.arg("foo").arg("-r").arg("bar").arg("--in-place");
+    cmd.arg("--backup-suffix").arg(".orig").arg("file.txt");
+    wd.assert_exit_code(0, &mut cmd);
+
+    let contents =
+        std::fs::read_to_string(wd.path().join("file.txt")).unwrap();
+    assert_eq!(contents, "bar one\nplain\nbar two\n");
+    let backup =
+        std::fs::read_to_string(wd.path().join("file.txt.orig")).unwrap();
+    assert_eq!(backup, "foo one\nplain\nfoo two\n");
+}
+
+#[test]
+fn in_place_dry_run() {
+    let wd = WorkDir::new("feature_in_place_dry_run");
+    wd.create("file.txt", "foo one\nplain\nfoo two\n");
+
+    let mut cmd = wd.command();
+    cmd.arg("foo").arg("-r").arg("bar").arg("--in-place");
+    cmd.arg("--dry-run").arg("file.txt");
+    let lines: String = wd.stdout(&mut cmd);
+    let expected = "\
+file.txt:1:-foo one
+file.txt:1:+bar one
+file.txt:3:-foo two
+file.txt:3:+bar two
+";
+    assert_eq!(lines, expected);
+
+    let contents =
+        std::fs::read_to_string(wd.path().join("file.txt")).unwrap();
+    assert_eq!(contents, "foo one\nplain\nfoo two\n");
+}
+
+#[test]
+fn in_place_requires_replace() {
+    let wd = WorkDir::new("feature_in_place_requires_replace");
+    wd.create("file.txt", "foo one\n");
+
+    let mut cmd = wd.command();
+    cmd.arg("foo").arg("--in-place").arg("file.txt");
+    wd.assert_err(&mut cmd);
+}
+
+#[test]
+fn type_add_exclusion() {
+    let wd = WorkDir::new("feature_type_add_exclusion");
+    wd.create("app.js", "Sherlock");
+    wd.create("app.min.js", "Sherlock");
+
+    let mut cmd = wd.command();
+    cmd.arg("--type-add").arg("web:include:js");
+    cmd.arg("--type-add").arg("web:!*.min.js");
+    cmd.arg("-tweb").arg("-l").arg("Sherlock").arg("./");
+    let lines: String = wd.stdout(&mut cmd);
+    assert_eq!(lines, "app.js\n");
+}
+
+#[test]
+fn type_add_file() {
+    let wd = WorkDir::new("feature_type_add_file");
+    wd.create("types.conf", "\
+# Front end sources.
+web:include:html,js
+web:!*.min.js
+");
+    wd.create("index.html", "Sherlock");
+    wd.create("app.js", "Sherlock");
+    wd.create("app.min.js", "Sherlock");
+    wd.create("notes.txt", "Sherlock");
+
+    let mut cmd = wd.command();
+    cmd.arg("--type-add-file").arg("types.conf");
+    cmd.arg("-tweb").arg("-l").arg("--sort").arg("path");
+    cmd.arg("Sherlock").arg("./");
+    let lines: String = wd.stdout(&mut cmd);
+    assert_eq!(lines, "app.js\nindex.html\n");
+}
+
+#[test]
+fn type_add_file_invalid_def() {
+    let wd = WorkDir::new("feature_type_add_file_invalid_def");
+    wd.create("types.conf", "web:include:no-such-type\n");
+    wd.create("index.html", "Sherlock");
+
+    let mut cmd = wd.command();
+    cmd.arg("--type-add-file").arg("types.conf");
+    cmd.arg("-tweb").arg("Sherlock").arg("./");
+    wd.assert_err(&mut cmd);
+}
+
 #[test]
 fn compressed_gzip() {
     if !cmd_exists("gzip") {
@@ -1923,6 +2433,19 @@ fn feature_411_parallel_search_stats() {
     assert_eq!(lines.contains("seconds"), true);
 }
 
+sherlock!(stats_match_and_byte_totals, |wd: WorkDir, mut cmd: Command| {
+    cmd.arg("--stats");
+
+    let lines: String = wd.stdout(&mut cmd);
+    assert_eq!(lines.contains("2 matches"), true);
+    assert_eq!(lines.contains("2 matched lines"), true);
+    assert_eq!(
+        lines.contains(&format!("{} bytes searched", hay::SHERLOCK.len())),
+        true,
+    );
+    assert_eq!(lines.contains("seconds spent searching"), true);
+});
+
 sherlock!(feature_411_ignore_stats_1, |wd: WorkDir, mut cmd: Command| {
     cmd.arg("--files-with-matches");
     cmd.arg("--stats");
@@ -1994,6 +2517,176 @@ fn feature_740_passthru() {
     wd.assert_err(&mut cmd);
 }
 
+#[test]
+fn file_comments() {
+    let wd = WorkDir::new("file_comments");
+    wd.create("patterns", "# secrets rules\n\nfoo\n# more rules\nbar\n");
+    wd.create("file", "foo\nquux\nbar\n# note\n");
+
+    // By default, every line of the pattern file is a pattern, so the
+    // blank line matches everything and the comments match literally.
+    let mut cmd = wd.command();
+    cmd.arg("-N").arg("-f").arg("patterns").arg("file");
+    let lines: String = wd.stdout(&mut cmd);
+    assert_eq!(lines, "foo\nquux\nbar\n# note\n");
+
+    // With --file-comments, blank lines and #-lines are skipped.
+    let mut cmd = wd.command();
+    cmd.arg("-N").arg("--file-comments")
+        .arg("-f").arg("patterns").arg("file");
+    let lines: String = wd.stdout(&mut cmd);
+    assert_eq!(lines, "foo\nbar\n");
+}
+
+#[test]
+fn fuzzy() {
+    let wd = WorkDir::new("fuzzy");
+    wd.create("file", "sherlock\nsherlk\nsehrlock\nwatson\n");
+
+    // Distance 1 permits a single insertion/deletion/substitution.
+    let mut cmd = wd.command();
+    cmd.arg("-F").arg("--fuzzy").arg("1").arg("sherlock").arg("file");
+    let lines: String = wd.stdout(&mut cmd);
+    assert_eq!(lines, "sherlock\n");
+
+    // A transposition counts as two edits.
+    let mut cmd = wd.command();
+    cmd.arg("-F").arg("--fuzzy").arg("2").arg("sherlock").arg("file");
+    let lines: String = wd.stdout(&mut cmd);
+    assert_eq!(lines, "sherlock\nsherlk\nsehrlock\n");
+
+    // --fuzzy requires -F.
+    let mut cmd = wd.command();
+    cmd.arg("--fuzzy").arg("1").arg("sherlock").arg("file");
+    wd.assert_err(&mut cmd);
+}
+
+#[test]
+fn wordlist() {
+    let wd = WorkDir::new("wordlist");
+    wd.create("words", "foo.bar\nquux\n");
+    wd.create("file", "foobbar\nfoo.bar\nbaz\nquux\n");
+
+    // Every wordlist entry is a literal, so `foo.bar` must not match
+    // `foobbar`.
+    let mut cmd = wd.command();
+    cmd.arg("--wordlist").arg("words").arg("file");
+    let lines: String = wd.stdout(&mut cmd);
+    assert_eq!(lines, "foo.bar\nquux\n");
+
+    // Wordlist entries combine with -e patterns.
+    let mut cmd = wd.command();
+    cmd.arg("--wordlist").arg("words").arg("-e").arg("baz").arg("file");
+    let lines: String = wd.stdout(&mut cmd);
+    assert_eq!(lines, "foo.bar\nbaz\nquux\n");
+}
+
+#[test]
+fn context_block() {
+    let wd = WorkDir::new("context_block");
+    wd.create("file", "\
+a1 foo
+a2
+
+b1
+b2
+
+c1 foo
+c2
+");
+
+    // Context expands to the surrounding blank lines, and non-matching
+    // paragraphs are skipped entirely.
+    let mut cmd = wd.command();
+    cmd.arg("--context-block").arg("foo").arg("file");
+    let lines: String = wd.stdout(&mut cmd);
+    assert_eq!(lines, "a1 foo\na2\n--\nc1 foo\nc2\n");
+}
+
+#[test]
+fn dedup_paths() {
+    let wd = WorkDir::new("dedup_paths");
+    wd.create("foo", "match\n");
+
+    // Overlapping paths are deduplicated, so the file is searched once.
+    let mut cmd = wd.command();
+    cmd.arg("match").arg("./").arg("foo");
+    let lines: String = wd.stdout(&mut cmd);
+    assert_eq!(lines.matches("match").count(), 1);
+
+    // The same goes for repeated paths.
+    let mut cmd = wd.command();
+    cmd.arg("match").arg("foo").arg("foo");
+    let lines: String = wd.stdout(&mut cmd);
+    assert_eq!(lines.matches("match").count(), 1);
+
+    // ... unless deduplication is disabled.
+    let mut cmd = wd.command();
+    cmd.arg("--no-dedup-paths").arg("match").arg("./").arg("foo");
+    let lines: String = wd.stdout(&mut cmd);
+    assert_eq!(lines.matches("match").count(), 2);
+}
+
+#[test]
+fn record_replay() {
+    let wd = WorkDir::new("record_replay");
+    wd.create("input", "Sherlock\nfoo\nSherlock\n");
+
+    let mut cmd = wd.command();
+    cmd.arg("--record").arg("session.rg").arg("Sherlock").arg("input");
+    let recorded: String = wd.stdout(&mut cmd);
+    assert_eq!("Sherlock\nSherlock\n", recorded);
+
+    // Replaying re-renders the recorded results without searching.
+    let mut cmd = wd.command();
+    cmd.arg("--replay").arg("session.rg");
+    let replayed: String = wd.stdout(&mut cmd);
+    assert_eq!(recorded, replayed);
+
+    // Changing a recorded file makes the replay warn about it.
+    wd.create("input", "changed\n");
+    let mut cmd = wd.command();
+    cmd.arg("--replay").arg("session.rg");
+    let output = cmd.output().unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(recorded, String::from_utf8_lossy(&output.stdout));
+    assert!(stderr.contains("changed since the session was recorded"));
+}
+
+#[test]
+fn max_bytes_per_file() {
+    let wd = WorkDir::new("max_bytes_per_file");
+    wd.create("file", "header match\nbody match\n");
+
+    let mut cmd = wd.command();
+    cmd.arg("--max-bytes-per-file").arg("13").arg("match").arg("file");
+    let lines: String = wd.stdout(&mut cmd);
+    assert_eq!("header match\n", lines);
+}
+
+#[test]
+#[cfg(unix)]
+fn pattern_transcoding() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let wd = WorkDir::new("pattern_transcoding");
+    wd.create("file", "caffé\n");
+
+    // A windows-1252 encoded pattern is transcoded to UTF-8 with a warning.
+    let mut cmd = wd.command();
+    cmd.arg(OsStr::from_bytes(b"caff\xe9")).arg("file");
+    let output = cmd.output().unwrap();
+    assert_eq!("caffé\n", String::from_utf8_lossy(&output.stdout));
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("assuming windows-1252"));
+
+    // Patterns that look like UTF-16 get a specific diagnostic.
+    let mut cmd = wd.command();
+    cmd.arg(OsStr::from_bytes(b"\xff\xfecaff\xe9")).arg("file");
+    wd.assert_err(&mut cmd);
+}
+
 #[test]
 fn binary_nosearch() {
     let wd = WorkDir::new("binary_nosearch");
@@ -2240,6 +2933,106 @@ fn type_list() {
     assert!(!lines.is_empty());
 }
 
+#[test]
+fn generate_man() {
+    let wd = WorkDir::new("generate_man");
+
+    let mut cmd = wd.command();
+    cmd.arg("--generate").arg("man");
+    let lines: String = wd.stdout(&mut cmd);
+    assert!(lines.starts_with(".TH RG 1"));
+    assert!(lines.contains("\\fB\\-i\\fP, \\fB\\-\\-ignore\\-case\\fP"));
+}
+
+#[test]
+fn generate_complete_bash() {
+    let wd = WorkDir::new("generate_complete_bash");
+
+    let mut cmd = wd.command();
+    cmd.arg("--generate").arg("complete-bash");
+    let lines: String = wd.stdout(&mut cmd);
+    assert!(lines.contains("_rg()"));
+}
+
+#[test]
+fn generate_complete_zsh() {
+    let wd = WorkDir::new("generate_complete_zsh");
+
+    let mut cmd = wd.command();
+    cmd.arg("--generate").arg("complete-zsh");
+    let lines: String = wd.stdout(&mut cmd);
+    assert!(lines.starts_with("#compdef rg"));
+}
+
+#[test]
+fn generate_unknown_kind() {
+    let wd = WorkDir::new("generate_unknown_kind");
+
+    let mut cmd = wd.command();
+    cmd.arg("--generate").arg("html");
+    wd.assert_err(&mut cmd);
+}
+
+clean!(glob_case_insensitive, "foo", ".", |wd: WorkDir, mut cmd: Command| {
+    wd.create("file.HTML", "foo");
+    cmd.arg("--glob-case-insensitive").arg("-g").arg("*.html");
+
+    let lines: String = wd.stdout(&mut cmd);
+    assert_eq!(lines, "file.HTML:foo\n");
+});
+
+clean!(glob_case_sensitive_by_default, "foo", ".",
+|wd: WorkDir, mut cmd: Command| {
+    wd.create("file.HTML", "foo");
+    cmd.arg("-g").arg("*.html");
+    wd.assert_err(&mut cmd);
+});
+
+sherlock!(line_buffered, |wd: WorkDir, mut cmd: Command| {
+    cmd.arg("--line-buffered");
+
+    let lines: String = wd.stdout(&mut cmd);
+    let expected = "\
+For the Doctor Watsons of this world, as opposed to the Sherlock
+be, to a very large extent, the result of luck. Sherlock Holmes
+";
+    assert_eq!(lines, expected);
+});
+
+sherlock!(block_buffered, |wd: WorkDir, mut cmd: Command| {
+    cmd.arg("--block-buffered");
+
+    let lines: String = wd.stdout(&mut cmd);
+    let expected = "\
+For the Doctor Watsons of this world, as opposed to the Sherlock
+be, to a very large extent, the result of luck. Sherlock Holmes
+";
+    assert_eq!(lines, expected);
+});
+
+clean!(binary_flag_summary, "foo", ".", |wd: WorkDir, mut cmd: Command| {
+    wd.create_bytes("hay.bin", b"foo one\x00\nfoo two\n");
+    cmd.arg("--binary");
+
+    let lines: String = wd.stdout(&mut cmd);
+    assert_eq!(lines, "Binary file hay.bin matches\n");
+});
+
+clean!(binary_skipped_by_default, "foo", ".",
+|wd: WorkDir, mut cmd: Command| {
+    wd.create_bytes("hay.bin", b"foo one\x00\nfoo two\n");
+    wd.assert_err(&mut cmd);
+});
+
+clean!(binary_flag_count, "foo", "hay.bin",
+|wd: WorkDir, mut cmd: Command| {
+    wd.create_bytes("hay.bin", b"foo one\x00\nfoo two\n");
+    cmd.arg("--binary").arg("-c");
+
+    let lines: String = wd.stdout(&mut cmd);
+    assert_eq!(lines, "2\n");
+});
+
 // See: https://github.com/BurntSushi/ripgrep/issues/948
 sherlock!(
     exit_code_match_success,
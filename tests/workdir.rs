@@ -76,6 +76,18 @@ impl WorkDir {
         file.flush()
     }
 
+    /// Read the contents of the file with the given name in this directory,
+    /// or panic on error.
+    pub fn read_file<P: AsRef<Path>>(&self, name: P) -> String {
+        use std::io::Read;
+
+        let path = self.dir.join(name);
+        let mut file = nice_err(&path, File::open(&path));
+        let mut contents = String::new();
+        nice_err(&path, file.read_to_string(&mut contents));
+        contents
+    }
+
     /// Remove a file with the given name from this directory.
     pub fn remove<P: AsRef<Path>>(&self, name: P) {
         let path = self.dir.join(name);